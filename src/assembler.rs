@@ -1,33 +1,142 @@
-use crate::{tokens::*,parser::*,generator::*};
+use crate::{tokens::*,parser::*,generator::*,c_backend,x86_backend,profiler::Profiler,coverage::Coverage,debugger::Debugger};
 
 pub struct Basm;
 
+/// Languages `bytecode build --emit` can translate a program into.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum EmitTarget {
+    C,
+    /// x86-64 GNU assembler for Linux.
+    Asm,
+}
+
+impl EmitTarget {
+    pub fn from_name(name:&str) -> Option<Self> {
+        match name {
+            "c" => Some(Self::C),
+            "asm" => Some(Self::Asm),
+            _ => None
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::C => "c",
+            Self::Asm => "s",
+        }
+    }
+}
+
 impl Basm {
-    pub fn run_string(input:String) {
-        let tokens = Tokenizer::new(input).tokenize();
-        let mut parsed = Parser::new(tokens);
+    /// `file_name` is only used to tell where instructions come from.
+    pub fn run_string(file_name:&str,input:String) {
+        let mut generator = Basm::generate_string(file_name,input);
+        Basm::run_raw_string(generator.vm.get_raw_byte_code(),generator.source_map);
+    }
+
+    fn generate_string(file_name:&str,input:String) -> Generator {
+        let mut tokenizer = Tokenizer::new(input);
+        let tokens = tokenizer.tokenize();
+        let mut parsed = Parser::new(tokens,tokenizer.token_lines);
         parsed.parse();
-        let mut generator = Generator::new(parsed.builtins,parsed.labels,parsed.instructions);
+        let mut generator = Generator::new(file_name.to_string(),parsed.builtins,parsed.labels,parsed.instructions,parsed.instruction_lines);
         generator.generate(false);
-        let bc = generator.vm.get_raw_byte_code();
-        Basm::run_raw_string(bc); 
+        generator
     }
 
     pub fn run_file(file_name:String) {
-       let s = match std::fs::read_to_string(file_name) {
+        let s = Basm::read_file(&file_name);
+        Basm::run_string(&file_name,s);
+    }
+
+    /// Translates `file_name` into `target` and writes it to `output`, or next to `file_name`
+    /// with the target's extension if no output is given.
+    pub fn build_file(file_name:String, target:EmitTarget, output:Option<String>) {
+        let s = Basm::read_file(&file_name);
+        let mut generator = Basm::generate_string(&file_name,s);
+        let vm = crate::vm::VM::from_raw_instructions(generator.vm.get_raw_byte_code());
+        let translated = match target {
+            EmitTarget::C => c_backend::translate(vm.generated_instructions(),vm.header(),&generator.source_map),
+            EmitTarget::Asm => x86_backend::translate(vm.generated_instructions(),vm.header(),&generator.source_map),
+        };
+        let translated = match translated {
+            Ok(t) => t,
+            Err(e) => {
+                println!("Build Error: {}",e);
+                std::process::exit(1);
+            }
+        };
+        let output = output.unwrap_or_else(|| {
+            std::path::Path::new(&file_name).with_extension(target.file_extension()).to_string_lossy().to_string()
+        });
+        if let Err(e) = std::fs::write(&output,translated) {
+            println!("Error in writing file {:?}: {:?}",output,e);
+            std::process::exit(1);
+        }
+    }
+
+    /// Runs `file_name` while profiling it. When the program halts, the text report is written to
+    /// `<file_name>.profile` and the folded call stacks to `<file_name>.folded`.
+    pub fn profile_file(file_name:String) {
+        let s = Basm::read_file(&file_name);
+        let mut generator = Basm::generate_string(&file_name,s);
+        let mut profiler = Profiler::new(generator.vm.labels());
+        let mut vm = crate::vm::VM::from_raw_instructions(generator.vm.get_raw_byte_code());
+        vm.set_source_map(generator.source_map.clone());
+        vm.eval_traced(&mut profiler);
+
+        let report = format!("Profile of {}\n{}",file_name,profiler.report(vm.generated_instructions()));
+        for (extension,contents) in [("profile",report),("folded",profiler.folded_stacks())] {
+            let output = format!("{}.{}",file_name,extension);
+            if let Err(e) = std::fs::write(&output,contents) {
+                println!("Error in writing file {:?}: {:?}",output,e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Runs `file_name` while collecting coverage. When the program halts, the coverage of every
+    /// basm file it uses is written to `<file_name>.lcov` and summarized on the terminal.
+    pub fn coverage_file(file_name:String) {
+        let s = Basm::read_file(&file_name);
+        let mut generator = Basm::generate_string(&file_name,s);
+        let mut vm = crate::vm::VM::from_raw_instructions(generator.vm.get_raw_byte_code());
+        vm.set_source_map(generator.source_map.clone());
+        let mut coverage = Coverage::new(generator.source_map,generator.source_lines,vm.generated_instructions());
+        vm.eval_traced(&mut coverage);
+
+        let output = format!("{}.lcov",file_name);
+        if let Err(e) = std::fs::write(&output,coverage.lcov()) {
+            println!("Error in writing file {:?}: {:?}",output,e);
+            std::process::exit(1);
+        }
+        println!("\nCoverage (written to {}):\n{}",output,coverage.summary());
+    }
+
+    /// Runs `file_name` with the debugger attached, which pauses at every `brk`.
+    pub fn debug_file(file_name:String) {
+        let s = Basm::read_file(&file_name);
+        let mut generator = Basm::generate_string(&file_name,s);
+        let mut vm = crate::vm::VM::from_raw_instructions(generator.vm.get_raw_byte_code());
+        vm.set_source_map(generator.source_map.clone());
+        vm.eval_traced(&mut Debugger::new());
+    }
+
+    fn read_file(file_name:&String) -> String {
+        match std::fs::read_to_string(file_name) {
             Err(err) => {
                 println!("Error in reading file: {:?}",err);
                 std::process::exit(1);
             }
             Ok(s)=> s
-        };
-        Basm::run_string(s);
+        }
     }
 
-
         
-    pub fn run_raw_string(s:String) {
+    /// `source_map` gives the source line of every instruction for runtime error messages.
+    pub fn run_raw_string(s:String,source_map:Vec<Option<SourceLocation>>) {
         let mut vm = crate::vm::VM::from_raw_instructions(s);
+        vm.set_source_map(source_map);
         vm.eval_raw();
     }
 }
@@ -0,0 +1,1568 @@
+use crate::{
+    instruction::{Instruction,StringNumberUnion,ProgramHeader,condition_flags,assert_message},
+    constants_and_types::*,
+    parser::SourceLocation,
+    vm::{describe_location,describe_frame_slot},
+    float_math,
+};
+
+use std::collections::BTreeSet;
+
+/// Translates generated instructions into a standalone C program.
+///
+/// Every instruction becomes a call into a small runtime (see `C_RUNTIME`) that mirrors what
+/// `VM::run_instruction` does, and every instruction that can be jumped to gets a `L<n>:` label
+/// so jumps become plain `goto`s. Returning from a call goes through a `switch` over all the
+/// places a call can return to, and jumps through a register through one over the addresses `lea` loads.
+///
+/// `instructions` must already have its labels resolved to instruction numbers, which is the case
+/// for instructions read back with `VM::from_raw_instructions`. `source_map` is the generator's
+/// and is used for the lines in runtime error messages. Registers are as wide as `header` asks for.
+pub fn translate(instructions:&[Instruction],header:ProgramHeader,source_map:&[Option<SourceLocation>]) -> Result<String,String> {
+    use Instruction::*;
+
+    let mut jump_targets: BTreeSet<usize> = BTreeSet::new();
+    let mut return_targets: BTreeSet<usize> = BTreeSet::new();
+    let mut address_targets: BTreeSet<usize> = BTreeSet::new();
+    for (i,inst) in instructions.iter().enumerate() {
+        if let Some(target) = get_jump_destination(inst)? {
+            jump_targets.insert(target);
+        }
+        if let Call(_) | CallRegister(_) = inst {
+            return_targets.insert(i+1);
+        }
+        if let LoadAddress(..) = inst {
+            address_targets.insert(get_jump_destination(inst)?.unwrap());
+        }
+        if let JumpTable(_,count) = inst {
+            jump_targets.extend(i+1..=i+1+*count as usize);
+        }
+    }
+    // Jumping past the generated code lands on the `Halt`s the VM fills its instruction memory with.
+    let instruction_count = jump_targets.iter().chain(return_targets.iter())
+        .map(|t| t+1)
+        .fold(instructions.len(),usize::max);
+
+    let mut body = String::new();
+    for i in 0..instruction_count {
+        if jump_targets.contains(&i) || return_targets.contains(&i) {
+            body.push_str(&format!("L{}:\n",i));
+        }
+        let inst = instructions.get(i).unwrap_or(&Halt);
+        let location = format!("{:?}",describe_location(source_map,i));
+        let line = match inst {
+            Halt => "bc_halt();".to_string(),
+            Mov(a,b) => format!("bc_mov({},{});",a,b),
+            Add(a,b) => format!("bc_add({},registers[{}],{});",a,b,location),
+            Sub(a,b) => format!("bc_sub({},registers[{}],{});",a,b,location),
+            Mul(a,b) => format!("bc_mul({},reg_int({}),{});",a,b,location),
+            Div(a,b) => format!("bc_div({},reg_int({}),{});",a,b,location),
+            Mod(a,b) => format!("bc_mod({},reg_int({}),{});",a,b,location),
+            Display(a) => format!("bc_display({});",a),
+            Push(a) => format!("bc_push_value((bc_word)(bc_sword)({}));",a),
+            MovWide(a,b) => format!("registers[{}] = (bc_word){}ull;",a,*b as u64),
+            PushWide(a) => format!("bc_push_value((bc_word){}ull);",*a as u64),
+            PushRegister(a) => format!("bc_push_value(registers[{}]);",a),
+            Pop(a) => format!("registers[{}] = bc_pop_value();",a),
+
+            Jump(_) => format!("goto L{};",get_jump_destination(inst)?.unwrap()),
+            Call(_) => format!("bc_push_return_address({}); goto L{};",i,get_jump_destination(inst)?.unwrap()),
+            TailCall(_) => format!("bc_drop_handlers(return_addresses_len); goto L{};",get_jump_destination(inst)?.unwrap()),
+            Loop(a,_) => format!("if (--registers[{}] != 0) goto L{};",a,get_jump_destination(inst)?.unwrap()),
+            JumpIfZero(_) => format!("if (flags[{}] != 0) goto L{};",ZERO_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfNotZero(_) => format!("if (flags[{}] == 0) goto L{};",ZERO_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfEqual(_) => format!("if (flags[{}] != 0) goto L{};",EQUAL_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfNotEqual(_) => format!("if (flags[{}] != 1) goto L{};",EQUAL_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfGreater(_) => format!("if (flags[{}] != 0) goto L{};",GREATER_THAN_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfLess(_) => format!("if (flags[{}] != 0) goto L{};",LESS_THAN_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfOverflow(_) => format!("if (flags[{}] != 0) goto L{};",OVERFLOW_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfNotOverflow(_) => format!("if (flags[{}] == 0) goto L{};",OVERFLOW_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfCarry(_) => format!("if (flags[{}] != 0) goto L{};",CARRY_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfNotCarry(_) => format!("if (flags[{}] == 0) goto L{};",CARRY_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfGreaterOrEqual(_) => format!("if (flags[{}] != 0 || flags[{}] != 0) goto L{};",GREATER_THAN_FLAG,EQUAL_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfLessOrEqual(_) => format!("if (flags[{}] != 0 || flags[{}] != 0) goto L{};",LESS_THAN_FLAG,EQUAL_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfAbove(_) => format!("if (flags[{}] != 0) goto L{};",ABOVE_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfBelow(_) => format!("if (flags[{}] != 0) goto L{};",BELOW_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfAboveOrEqual(_) => format!("if (flags[{}] != 0 || flags[{}] != 0) goto L{};",ABOVE_FLAG,EQUAL_FLAG,get_jump_destination(inst)?.unwrap()),
+            JumpIfBelowOrEqual(_) => format!("if (flags[{}] != 0 || flags[{}] != 0) goto L{};",BELOW_FLAG,EQUAL_FLAG,get_jump_destination(inst)?.unwrap()),
+            Return => format!("return_to = bc_return({}) + 1; goto dispatch;",location),
+            JumpTable(a,count) => {
+                let mut line = format!("switch (registers[{}]) {{",a);
+                for case in 0..*count as usize {
+                    line.push_str(&format!(" case {}: goto L{};",case,i+1+case));
+                }
+                line.push_str(&format!(" default: goto L{}; }}",i+1+*count as usize));
+                line
+            }
+            LoadAddress(a,_) => format!("registers[{}] = (bc_word){}u;",a,get_jump_destination(inst)?.unwrap()),
+            JumpRegister(a) => format!("indirect_to = registers[{}]; indirect_location = {}; goto indirect;",a,location),
+            CallRegister(a) => format!("bc_push_return_address({}); indirect_to = registers[{}]; indirect_location = {}; goto indirect;",i,a,location),
+
+            Compare(a,b) => format!("bc_compare(reg_int({}),reg_int({}));",a,b),
+            GetFromStack(a,b) => format!("bc_get_from_stack({},{},{});",a,b,location),
+            GetFromStackPointer(a,b) => format!("bc_get_from_stack_pointer({},{},{});",a,b,location),
+            SetStack(a,b) => format!("bc_set_stack({},{},{});",a,b,location),
+            SetFromStackPointer(a,b) => format!("bc_set_from_stack_pointer({},{},{});",a,b,location),
+            ExtendStack(a,b) => format!("bc_extend_stack({},{});",a,b),
+            TruncateStack(a) => format!("bc_truncate_stack({},{});",a,location),
+            TruncateStackRange(a,b) => format!("bc_truncate_stack_range({},{});",a,b),
+            GetStackPointer(a) => format!("registers[{}] = (bc_word)sp;",a),
+            Enter(a) => format!("bc_enter({}u);",a),
+            Leave => format!("bc_leave({});",location),
+            GetFromBasePointer(a,b) => format!("registers[{}] = (bc_word)stack[bc_frame_slot({},{:?},{})];",a,b,describe_frame_slot(*b),location),
+            SetFromBasePointer(a,b) => format!("stack[bc_frame_slot({},{:?},{})] = bc_slot(registers[{}]);",b,describe_frame_slot(*b),location,a),
+            StackCopyBackSp(a,b,c) => format!("bc_stack_copy_back_sp({},{},{});",a,b,c),
+
+            Malloc(a) => format!("bc_malloc({});",a),
+            Free(a) => format!("bc_free({},{});",a,location),
+            GetMemory(a,b,c) => format!("bc_get_memory({},{},{},{});",a,b,c,location),
+            SetMemory(a,b,c) => format!("bc_set_memory({},{},{},{});",a,b,c,location),
+            GetMemory8(a,b,c) => format!("bc_load_memory({},{},{},1,0,{});",a,b,c,location),
+            GetMemory8Signed(a,b,c) => format!("bc_load_memory({},{},{},1,1,{});",a,b,c,location),
+            GetMemory16(a,b,c) => format!("bc_load_memory({},{},{},2,0,{});",a,b,c,location),
+            GetMemory16Signed(a,b,c) => format!("bc_load_memory({},{},{},2,1,{});",a,b,c,location),
+            GetMemory32(a,b,c) => format!("bc_load_memory({},{},{},4,0,{});",a,b,c,location),
+            GetMemory32Signed(a,b,c) => format!("bc_load_memory({},{},{},4,1,{});",a,b,c,location),
+            SetMemory8(a,b,c) => format!("bc_store_memory({},{},{},1,{});",a,b,c,location),
+            SetMemory16(a,b,c) => format!("bc_store_memory({},{},{},2,{});",a,b,c,location),
+            SetMemory32(a,b,c) => format!("bc_store_memory({},{},{},4,{});",a,b,c,location),
+
+            Or(a,b) => format!("bc_logic({},registers[{}] | registers[{}]);",a,a,b),
+            And(a,b) => format!("bc_logic({},registers[{}] & registers[{}]);",a,a,b),
+            Xor(a,b) => format!("bc_logic({},registers[{}] ^ registers[{}]);",a,a,b),
+            Not(a) => format!("bc_logic({},~registers[{}]);",a,a),
+
+            // Immediates are written as their two's complement so `INT32_MIN` needs no special casing,
+            // and are sign extended when registers are wider.
+            AddImmediate(a,b) => format!("bc_add({},(bc_word)(int32_t){}u,{});",a,*b as u32,location),
+            SubImmediate(a,b) => format!("bc_sub({},(bc_word)(int32_t){}u,{});",a,*b as u32,location),
+            MulImmediate(a,b) => format!("bc_mul({},(int32_t){}u,{});",a,*b as u32,location),
+            DivImmediate(a,b) => format!("bc_div({},(int32_t){}u,{});",a,*b as u32,location),
+            ModImmediate(a,b) => format!("bc_mod({},(int32_t){}u,{});",a,*b as u32,location),
+            AndImmediate(a,b) => format!("bc_logic({},registers[{}] & (bc_word)(int32_t){}u);",a,a,*b as u32),
+            OrImmediate(a,b) => format!("bc_logic({},registers[{}] | (bc_word)(int32_t){}u);",a,a,*b as u32),
+            XorImmediate(a,b) => format!("bc_logic({},registers[{}] ^ (bc_word)(int32_t){}u);",a,a,*b as u32),
+            CompareImmediate(a,b) => format!("bc_compare(reg_int({}),(int32_t){}u);",a,*b as u32),
+            CompareUnsigned(a,b) => format!("bc_compare_unsigned(registers[{}],registers[{}]);",a,b),
+            DivUnsigned(a,b) => format!("bc_divu({},registers[{}],{});",a,b,location),
+            ModUnsigned(a,b) => format!("bc_modu({},registers[{}],{});",a,b,location),
+            CompareUnsignedImmediate(a,b) => format!("bc_compare_unsigned(registers[{}],(bc_word)(int32_t){}u);",a,*b as u32),
+            DivUnsignedImmediate(a,b) => format!("bc_divu({},(bc_word)(int32_t){}u,{});",a,*b as u32,location),
+            ModUnsignedImmediate(a,b) => format!("bc_modu({},(bc_word)(int32_t){}u,{});",a,*b as u32,location),
+            SetArithmeticMode(a) => format!("trap_overflow = {};",(*a == ARITHMETIC_TRAPPING) as u8),
+
+            Shl(a,b) => format!("bc_logic({},bc_shl(registers[{}],registers[{}]));",a,a,b),
+            Shr(a,b) => format!("bc_logic({},bc_shr(registers[{}],registers[{}]));",a,a,b),
+            Sar(a,b) => format!("bc_logic({},bc_sar(registers[{}],registers[{}]));",a,a,b),
+            Rol(a,b) => format!("bc_logic({},bc_rol(registers[{}],registers[{}]));",a,a,b),
+            Ror(a,b) => format!("bc_logic({},bc_ror(registers[{}],registers[{}]));",a,a,b),
+            ShlImmediate(a,b) => format!("bc_logic({},bc_shl(registers[{}],{}u));",a,a,*b as u32),
+            ShrImmediate(a,b) => format!("bc_logic({},bc_shr(registers[{}],{}u));",a,a,*b as u32),
+            SarImmediate(a,b) => format!("bc_logic({},bc_sar(registers[{}],{}u));",a,a,*b as u32),
+            RolImmediate(a,b) => format!("bc_logic({},bc_rol(registers[{}],{}u));",a,a,*b as u32),
+            RorImmediate(a,b) => format!("bc_logic({},bc_ror(registers[{}],{}u));",a,a,*b as u32),
+
+            Movf(a,b) if header.double_floats => format!("float_registers[{}] = bc_float_from_bits({}ull);",a,b.to_bits()),
+            Movf(a,b) => format!("float_registers[{}] = bc_float_from_bits({}u);",a,(*b as f32).to_bits()),
+            MovRegister(a,b) => format!("registers[{}] = registers[{}];",a,b),
+            MovfRegister(a,b) => format!("float_registers[{}] = float_registers[{}];",a,b),
+            MovIntToFloat(a,b) => format!("float_registers[{}] = (bc_float)reg_int({});",a,b),
+            MovFloatToInt(a,b) => format!("registers[{}] = (bc_word)bc_float_to_int(float_registers[{}]);",a,b),
+            // `rint` rounds ties to even in the default rounding mode.
+            MovFloatToIntRounded(a,b,mode) => {
+                let round = match *mode {
+                    ROUND_NEAREST => "rint",
+                    ROUND_DOWN => "floor",
+                    ROUND_UP => "ceil",
+                    ROUND_ZERO => "trunc",
+                    mode => return Err(format!("Rounding mode {} does not exist.",mode)),
+                };
+                format!("registers[{}] = (bc_word)bc_float_to_int((bc_float){}(float_registers[{}]));",a,round,b)
+            }
+            MovFloatBitsToInt(a,b) => format!("registers[{}] = (bc_word)bc_float_to_bits(float_registers[{}]);",a,b),
+            MovIntBitsToFloat(a,b) => format!("float_registers[{}] = bc_float_from_bits((bc_float_bits)registers[{}]);",a,b),
+            // Computed in double like the VM, single precision rounds once when storing.
+            FloatMath(a,b,function) => {
+                let name = c_math_function(*function)?;
+                if float_math::is_binary(*function) {
+                    format!("float_registers[{}] = (bc_float){}((double)float_registers[{}],(double)float_registers[{}]);",a,name,a,b)
+                }else {
+                    format!("float_registers[{}] = (bc_float){}((double)float_registers[{}]);",a,name,b)
+                }
+            }
+            CompareFloat(a,b) => format!("bc_compare_float(float_registers[{}],float_registers[{}]);",a,b),
+            Addf(a,b) => format!("float_registers[{}] += float_registers[{}];",a,b),
+            Subf(a,b) => format!("float_registers[{}] -= float_registers[{}];",a,b),
+            Mulf(a,b) => format!("float_registers[{}] *= float_registers[{}];",a,b),
+            Divf(a,b) => format!("float_registers[{}] /= float_registers[{}];",a,b),
+            Displayf(a) => format!("bc_display_float(float_registers[{}]);",a),
+            // Single precision floats go on the stack like integers, doubles fill the whole slot.
+            PushFloatRegister(a) if header.double_floats => format!("bc_push_slot(bc_float_to_bits(float_registers[{}]));",a),
+            PushFloatRegister(a) => format!("bc_push_value(bc_float_to_bits(float_registers[{}]));",a),
+            PopFloat(a) => format!("float_registers[{}] = bc_float_from_bits((bc_float_bits)bc_pop_slot());",a),
+
+            DisplayChar(a) => format!("bc_display_char({});",a),
+            GetFlag(a,b) => format!("bc_get_flag({},{});",a,b),
+            Try(_) => format!("bc_try({}u);",get_jump_destination(inst)?.unwrap()),
+            EndTry => format!("bc_end_try({});",location),
+            Throw(a) => format!("bc_throw(registers[{}],{});",a,location),
+            Breakpoint => format!("bc_breakpoint({});",location),
+            AssertFailed(_) => {
+                let message = assert_message(instructions,i).ok_or(format!("Instruction {} is not preceded by the message it asserts with.",i))?;
+                format!("bc_trap(BC_ERROR_ASSERT,{:?},{});",format!("assertion failed: {}",message),location)
+            }
+            ConditionalMove(a,b,condition) => format!("if ({}) registers[{}] = registers[{}];",c_condition(*condition)?,a,b),
+            SetIf(a,condition) => format!("registers[{}] = ({}) ? 1 : 0;",a,c_condition(*condition)?),
+            Write(a,b) => format!("bc_write({},{});",a,b),
+
+            // The interpreter does not implement these either.
+            Nand(..) | Modf(..) => format!("bc_panic(\"not implemented: {:?}\");",inst),
+        };
+        body.push_str("    ");
+        body.push_str(&line);
+        body.push('\n');
+    }
+
+    body.push_str("    bc_halt();\n");
+
+    // `ret` jumps back through here as C cannot `goto` a computed label.
+    let has_return = instructions.contains(&Return);
+    let mut dispatch = String::new();
+    if has_return {
+        dispatch.push_str("dispatch:\n    switch (return_to) {\n");
+        for target in return_targets.iter() {
+            dispatch.push_str(&format!("        case {}: goto L{};\n",target,target));
+        }
+        dispatch.push_str("        default: bc_panic(\"Could not return to an address that no call returns to\");\n    }\n");
+    }
+    // Indirect jumps and calls go through here to any address a `lea` loads.
+    let has_indirect = instructions.iter().any(|inst| matches!(inst,JumpRegister(_) | CallRegister(_)));
+    if has_indirect {
+        dispatch.push_str("indirect:\n    switch (indirect_to) {\n");
+        for target in address_targets.iter() {
+            dispatch.push_str(&format!("        case {}: goto L{};\n",target,target));
+        }
+        dispatch.push_str("        default: bc_bad_indirect_target(indirect_to, indirect_location);\n    }\n");
+    }
+    // A runtime error after a `@try` longjmps back here and goes on at the handler.
+    let has_try = instructions.iter().any(|inst| matches!(inst,Try(_)));
+    if has_try {
+        dispatch.push_str("catch_handler:\n    switch (catch_to) {\n");
+        for inst in instructions.iter().filter(|inst| matches!(inst,Try(_))) {
+            let target = get_jump_destination(inst)?.unwrap();
+            dispatch.push_str(&format!("        case {}: goto L{};\n",target,target));
+        }
+        dispatch.push_str("        default: bc_panic(\"Could not go to a handler that no @try sets\");\n    }\n");
+    }
+    let mut locals = String::from("    (void)float_registers;\n");
+    if has_return {
+        locals.push_str("    size_t return_to = 0;\n");
+    }
+    if has_indirect {
+        locals.push_str("    bc_word indirect_to = 0;\n    const char *indirect_location = \"\";\n");
+    }
+    if has_try {
+        locals.push_str("    size_t catch_to = 0;\n    if (setjmp(bc_catch_point)) {\n        catch_to = bc_catch();\n        goto catch_handler;\n    }\n");
+    }
+
+    let word_types = if header.wide_integers { C_WIDE_INTEGERS } else { C_INTEGERS };
+    let float_types = if header.double_floats { C_DOUBLE_FLOATS } else { C_FLOATS };
+    let float_types = format!("{}#define BC_REGISTER_COUNT {}\n#define BC_FLOAT_REGISTER_COUNT {}\n#define BC_REGBP {}\n",float_types,REGISTER_COUNT,FLOAT_REGISTER_COUNT,REGBP);
+    let float_types = format!("{}#define BC_ERROR_DIVISION_BY_ZERO {}\n#define BC_ERROR_OVERFLOW {}\n#define BC_ERROR_STACK {}\n#define BC_ERROR_MEMORY {}\n#define BC_ERROR_BAD_ADDRESS {}\n#define BC_ERROR_BAD_INSTRUCTION {}\n#define BC_ERROR_ASSERT {}\n",
+        float_types,ERROR_DIVISION_BY_ZERO,ERROR_OVERFLOW,ERROR_STACK,ERROR_MEMORY,ERROR_BAD_ADDRESS,ERROR_BAD_INSTRUCTION,ERROR_ASSERT);
+    let math = if instructions.iter().any(|inst| matches!(inst,FloatMath(..))) { C_MATH } else { "" };
+    let breakpoint = if instructions.contains(&Breakpoint) { C_BREAKPOINT } else { "" };
+    Ok(format!("{}{}{}{}{}{}\nint main(void) {{\n{}{}{}    return 0;\n}}\n",C_INCLUDES,word_types,float_types,C_RUNTIME,math,breakpoint,locals,body,dispatch))
+}
+
+/// C function that computes the `MATH_` function `function`, all of them take and return doubles.
+fn c_math_function(function:InstructionParamType) -> Result<&'static str,String> {
+    Ok(match function {
+        MATH_SQRT => "sqrt",
+        MATH_ABS => "fabs",
+        MATH_FLOOR => "floor",
+        MATH_CEIL => "ceil",
+        MATH_ROUND => "round",
+        MATH_EXP => "bc_exp",
+        MATH_LN => "bc_ln",
+        MATH_SIN => "bc_sin",
+        MATH_COS => "bc_cos",
+        MATH_TAN => "bc_tan",
+        MATH_POW => "bc_pow",
+        MATH_ATAN2 => "bc_atan2",
+        MATH_MIN => "bc_min",
+        MATH_MAX => "bc_max",
+        function => return Err(format!("Math function {} does not exist.",function)),
+    })
+}
+
+/// C expression that is true when the condition of `ConditionalMove` or `SetIf` holds.
+fn c_condition(condition:InstructionParamType) -> Result<String,String> {
+    let (flags,negated) = condition_flags(condition).ok_or(format!("Condition {} does not exist.",condition))?;
+    let any = flags.iter().map(|flag| format!("flags[{}] != 0",flag)).collect::<Vec<String>>().join(" || ");
+    Ok(if negated { format!("!({})",any) } else { any })
+}
+
+fn get_jump_destination(inst:&Instruction) -> Result<Option<usize>,String> {
+    use Instruction::*;
+    match inst {
+        Jump(s) | Call(s) | TailCall(s) | Loop(_,s) | Try(s) |
+            JumpIfZero(s) | JumpIfNotZero(s) |
+            JumpIfEqual(s) | JumpIfNotEqual(s) |
+            JumpIfGreater(s) | JumpIfLess(s) |
+            JumpIfGreaterOrEqual(s) | JumpIfLessOrEqual(s) |
+            JumpIfAbove(s) | JumpIfBelow(s) | JumpIfAboveOrEqual(s) | JumpIfBelowOrEqual(s) |
+            JumpIfOverflow(s) | JumpIfNotOverflow(s) | JumpIfCarry(s) | JumpIfNotCarry(s) |
+            LoadAddress(_,s) => {
+                match s {
+                    StringNumberUnion::Num(n) => Ok(Some(*n as usize)),
+                    StringNumberUnion::String(s) => Err(format!("Label {:?} has not been resolved to an instruction number.",s)),
+                }
+            }
+        _ => Ok(None)
+    }
+}
+
+const C_INCLUDES: &str = r#"/* Generated by bytecode. Compile with: cc -O2 <file>.c -o <program> -lm */
+#include <math.h>
+#include <setjmp.h>
+#include <signal.h>
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+"#;
+
+/// Integer registers, stack slots and memory cells hold a `bc_word`.
+const C_INTEGERS: &str = r#"typedef uint32_t bc_word;
+typedef int32_t bc_sword;
+#define BC_WORD_BITS 32
+#define BC_SWORD_MIN INT32_MIN
+#define BC_SWORD_MAX INT32_MAX
+"#;
+
+const C_WIDE_INTEGERS: &str = r#"typedef uint64_t bc_word;
+typedef int64_t bc_sword;
+#define BC_WORD_BITS 64
+#define BC_SWORD_MIN INT64_MIN
+#define BC_SWORD_MAX INT64_MAX
+"#;
+
+/// Float registers hold a `bc_float`. `BC_FLOAT_DIGITS` is the most significant digits needed to
+/// print one so that it reads back the same.
+const C_FLOATS: &str = r#"typedef float bc_float;
+typedef uint32_t bc_float_bits;
+#define BC_FLOAT_DIGITS 9
+#define bc_strtof strtof
+"#;
+
+const C_DOUBLE_FLOATS: &str = r#"typedef double bc_float;
+typedef uint64_t bc_float_bits;
+#define BC_FLOAT_DIGITS 17
+#define bc_strtof strtod
+"#;
+
+/// Only added to programs that use `Breakpoint`. Prints the state the way `VM::dump_state` does.
+const C_BREAKPOINT: &str = r#"
+static int bc_debugger_attached(void) {
+    FILE *status = fopen("/proc/self/status", "r");
+    if (!status) return 0;
+    char line[256];
+    int attached = 0;
+    while (fgets(line, sizeof line, status)) {
+        if (strncmp(line, "TracerPid:", 10) == 0) {
+            attached = atoi(line + 10) != 0;
+            break;
+        }
+    }
+    fclose(status);
+    return attached;
+}
+
+/* Stops in the debugger when one is attached, prints the state otherwise. */
+static void bc_breakpoint(const char *location) {
+    static const char *const register_names[] = {"rax", "rbx", "rcx", "rdx", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15"};
+    static const char *const flag_names[] = {"zf", "eqf", "gf", "lf", "bf", "af", "of", "cf", "sf"};
+    static const int flag_numbers[] = {0, 1, 3, 2, 4, 5, 6, 7, 8};
+    fflush(stdout);
+    if (bc_debugger_attached()) {
+        raise(SIGTRAP);
+        return;
+    }
+    fprintf(stderr, "Breakpoint %s\n ", location);
+    for (int i = 0; i < 12; i++) fprintf(stderr, " %s=%lld", register_names[i], (long long)(bc_sword)registers[i]);
+    fprintf(stderr, " bp=%lld\n ", (long long)(bc_sword)registers[BC_REGBP]);
+    for (int i = 0; i < 9; i++) fprintf(stderr, " %s=%d", flag_names[i], flags[flag_numbers[i]]);
+    size_t shown = stack_len < 8 ? stack_len : 8;
+    fprintf(stderr, "\n  stack of %zu (top last):%s", stack_len, shown < stack_len ? " ..." : "");
+    for (size_t i = stack_len - shown; i < stack_len; i++) fprintf(stderr, " %lld", (long long)(int64_t)stack[i]);
+    fprintf(stderr, "\n");
+}
+"#;
+
+/// Copy of float_math.rs, only added to programs that use `FloatMath`. Contracting `a * b + c`
+/// into a fused multiply-add would round differently from the VM, so it is turned off.
+const C_MATH: &str = r#"
+#if defined(__clang__)
+#pragma clang fp contract(off)
+#elif defined(__GNUC__)
+#pragma GCC optimize("fp-contract=off")
+#endif
+
+static inline uint64_t bc_f64_bits(double x) { uint64_t bits; memcpy(&bits, &x, sizeof bits); return bits; }
+static inline double bc_f64_from_bits(uint64_t bits) { double x; memcpy(&x, &bits, sizeof x); return x; }
+
+static inline void bc_exact_mul(double a, double b, double *hi, double *lo) {
+    const double split = 134217729.0;
+    double c = split * a, ah = c - (c - a), al = a - ah;
+    c = split * b;
+    double bh = c - (c - b), bl = b - bh;
+    *hi = a * b;
+    *lo = ((ah * bh - *hi) + ah * bl + al * bh) + al * bl;
+}
+
+static inline void bc_exact_add(double a, double b, double *hi, double *lo) {
+    double s = a + b, bb = s - a;
+    *hi = s;
+    *lo = (a - (s - bb)) + (b - bb);
+}
+
+static inline void bc_extended_mul(double a_hi, double a_lo, double b_hi, double b_lo, double *hi, double *lo) {
+    double p, e;
+    bc_exact_mul(a_hi, b_hi, &p, &e);
+    e = e + (a_hi * b_lo + a_lo * b_hi);
+    *hi = p + e;
+    *lo = e - (*hi - p);
+}
+
+static inline double bc_min(double a, double b) {
+    if (isnan(a)) return b;
+    if (isnan(b)) return a;
+    return (a < b || (a == b && signbit(a))) ? a : b;
+}
+
+static inline double bc_max(double a, double b) {
+    if (isnan(a)) return b;
+    if (isnan(b)) return a;
+    return (a > b || (a == b && !signbit(a))) ? a : b;
+}
+
+static inline double bc_scale(double x, int n) {
+    double y = x;
+    if (n > 1023) {
+        y *= bc_f64_from_bits(0x7fe0000000000000ull);
+        n -= 1023;
+        if (n > 1023) {
+            y *= bc_f64_from_bits(0x7fe0000000000000ull);
+            n -= 1023;
+            if (n > 1023) n = 1023;
+        }
+    } else if (n < -1022) {
+        y *= bc_f64_from_bits(0x0360000000000000ull);
+        n += 1022 - 53;
+        if (n < -1022) {
+            y *= bc_f64_from_bits(0x0360000000000000ull);
+            n += 1022 - 53;
+            if (n < -1022) n = -1022;
+        }
+    }
+    return y * bc_f64_from_bits((uint64_t)(0x3ff + n) << 52);
+}
+
+#define BC_LN2_HI 6.93147180369123816490e-01
+#define BC_LN2_LO 1.90821492927058770002e-10
+
+static inline double bc_exp(double x) {
+    const double inv_ln2 = 1.44269504088896338700e+00;
+    const double p1 = 1.66666666666666019037e-01, p2 = -2.77777777770155933842e-03,
+        p3 = 6.61375632143793436117e-05, p4 = -1.65339022054652515390e-06,
+        p5 = 4.13813679705723846039e-08;
+    const double half_ln2 = 0.5 * 0.693147180559945309417232121458176568;
+    int k;
+    double hi, lo;
+
+    if (isnan(x)) return x;
+    if (x > 709.782712893383973096) return INFINITY;
+    if (x < -745.13321910194110842) return 0.0;
+
+    double abs = fabs(x);
+    if (abs > half_ln2) {
+        if (abs < 3.0 * half_ln2) k = x < 0.0 ? -1 : 1;
+        else k = (int)(inv_ln2 * x + (x < 0.0 ? -0.5 : 0.5));
+        hi = x - k * BC_LN2_HI;
+        lo = k * BC_LN2_LO;
+    } else if (abs > bc_f64_from_bits(0x3e30000000000000ull)) {
+        k = 0;
+        hi = x;
+        lo = 0.0;
+    } else {
+        return 1.0 + x;
+    }
+    double r = hi - lo;
+    double rr = r * r;
+    double c = r - rr * (p1 + rr * (p2 + rr * (p3 + rr * (p4 + rr * p5))));
+    double y = 1.0 + (r * c / (2.0 - c) - lo + hi);
+    return k == 0 ? y : bc_scale(y, k);
+}
+
+static inline double bc_ln(double x) {
+    const double lg1 = 6.666666666666735130e-01, lg2 = 3.999999999940941908e-01,
+        lg3 = 2.857142874366239149e-01, lg4 = 2.222219843214978396e-01,
+        lg5 = 1.818357216161805012e-01, lg6 = 1.531383769920937332e-01,
+        lg7 = 1.479819860511658591e-01;
+    int k = 0;
+    uint32_t hx = (uint32_t)(bc_f64_bits(x) >> 32);
+    if (hx < 0x00100000 || hx >> 31 != 0) {
+        if (x == 0.0) return -INFINITY;
+        if (x < 0.0 || isnan(x)) return NAN;
+        k -= 54;
+        x *= bc_f64_from_bits(0x4350000000000000ull);
+        hx = (uint32_t)(bc_f64_bits(x) >> 32);
+    } else if (hx >= 0x7ff00000) {
+        return x;
+    } else if (x == 1.0) {
+        return 0.0;
+    }
+
+    hx += 0x3ff00000 - 0x3fe6a09e;
+    k += (int)(hx >> 20) - 0x3ff;
+    hx = (hx & 0x000fffff) + 0x3fe6a09e;
+    x = bc_f64_from_bits((uint64_t)hx << 32 | (bc_f64_bits(x) & 0xffffffffull));
+
+    double f = x - 1.0;
+    double hfsq = 0.5 * f * f;
+    double s = f / (2.0 + f);
+    double z = s * s;
+    double w = z * z;
+    double t1 = w * (lg2 + w * (lg4 + w * lg6));
+    double t2 = z * (lg1 + w * (lg3 + w * (lg5 + w * lg7)));
+    double r = t2 + t1;
+    double dk = k;
+    return s * (hfsq + r) + dk * BC_LN2_LO - hfsq + f + dk * BC_LN2_HI;
+}
+
+static inline double bc_sin_kernel(double x, double y) {
+    const double s1 = -1.66666666666666324348e-01, s2 = 8.33333333332248946124e-03,
+        s3 = -1.98412698298579493134e-04, s4 = 2.75573137070700676789e-06,
+        s5 = -2.50507602534068634195e-08, s6 = 1.58969099521155010221e-10;
+    double z = x * x;
+    double w = z * z;
+    double r = s2 + z * (s3 + z * s4) + z * w * (s5 + z * s6);
+    double v = z * x;
+    return x - ((z * (0.5 * y - v * r) - y) - v * s1);
+}
+
+static inline double bc_cos_kernel(double x, double y) {
+    const double c1 = 4.16666666666666019037e-02, c2 = -1.38888888888741095749e-03,
+        c3 = 2.48015872894767294178e-05, c4 = -2.75573143513906633035e-07,
+        c5 = 2.08757232129817482790e-09, c6 = -1.13596475577881948265e-11;
+    double z = x * x;
+    double w = z * z;
+    double r = z * (c1 + z * (c2 + z * c3)) + w * w * (c4 + z * (c5 + z * c6));
+    double hz = 0.5 * z;
+    w = 1.0 - hz;
+    return w + (((1.0 - w) - hz) + (z * r - x * y));
+}
+
+static inline int bc_exponent(double x) { return (int)((bc_f64_bits(x) >> 52) & 0x7ff); }
+
+#define BC_PIO2_HI 1.57079632679489655800e+00
+#define BC_PIO2_LO 6.12323399573676603587e-17
+
+static const uint32_t bc_two_over_pi[40] = {
+    0xa2f9836e, 0x4e441529, 0xfc2757d1, 0xf534ddc0, 0xdb629599, 0x3c439041, 0xfe5163ab, 0xdebbc561,
+    0xb7246e3a, 0x424dd2e0, 0x06492eea, 0x09d1921c, 0xfe1deb1c, 0xb129a73e, 0xe88235f5, 0x2ebb4484,
+    0xe99c7026, 0xb45f7e41, 0x3991d639, 0x835339f4, 0x9c845f8b, 0xbdf9283b, 0x1ff897ff, 0xde05980f,
+    0xef2f118b, 0x5a0a6d1f, 0x6d367ecf, 0x27cb09b7, 0x4f463f66, 0x9e5fea2d, 0x7527bac7, 0xebe5f17b,
+    0x3d0739f7, 0x8a5292ea, 0x6bfb5fb1, 0x1f8d5d08, 0x56033046, 0xfc7b6bab, 0xf0cfbc20, 0x9af4361d,
+};
+
+static inline uint64_t bc_two_over_pi_word(int i) { return i < 2 ? 0 : bc_two_over_pi[i - 2]; }
+
+static inline uint64_t bc_product_word(const uint64_t *product, int i) { return i < 0 ? 0 : product[i]; }
+
+static inline int bc_reduce_large(double x, double *y0, double *y1) {
+    uint64_t bits = bc_f64_bits(fabs(x));
+    uint64_t mantissa = (bits & 0x000fffffffffffffull) | 0x0010000000000000ull;
+    int e = (int)((bits >> 52) & 0x7ff) - 1075;
+
+    int first = e - 2 + 64;
+    uint64_t window[6];
+    for (int i = 0; i < 6; i++) {
+        int bit = first + 32 * (5 - i);
+        int index = bit / 32, shift = bit % 32;
+        window[i] = ((bc_two_over_pi_word(index) << 32 | bc_two_over_pi_word(index + 1)) >> (32 - shift)) & 0xffffffffu;
+    }
+
+    uint64_t product[6] = {0};
+    uint64_t m[2] = {mantissa & 0xffffffffu, mantissa >> 32};
+    for (int j = 0; j < 2; j++) {
+        uint64_t carry = 0;
+        for (int i = 0; i < 6 - j; i++) {
+            uint64_t t = window[i] * m[j] + product[i + j] + carry;
+            product[i + j] = t & 0xffffffffu;
+            carry = t >> 32;
+        }
+    }
+    uint64_t n = (product[5] >> 30) + ((product[5] >> 29) & 1);
+    product[5] = (product[5] - (n << 30)) & 0xffffffffu;
+    int negative = product[5] >> 31 == 1;
+    if (negative) {
+        uint64_t carry = 1;
+        for (int i = 0; i < 6; i++) {
+            uint64_t t = (~product[i] & 0xffffffffu) + carry;
+            product[i] = t & 0xffffffffu;
+            carry = t >> 32;
+        }
+    }
+
+    int top = 5;
+    while (top >= 0 && product[top] == 0) top--;
+    if (top < 0) {
+        *y0 = *y1 = 0.0;
+        return (int)(n & 3);
+    }
+    int zeros = 0;
+    while (!(product[top] << zeros & 0x80000000u)) zeros++;
+    uint64_t high = ((product[top] << 32 | bc_product_word(product, top - 1)) << zeros) | (bc_product_word(product, top - 2) << zeros >> 32);
+    uint64_t low = ((bc_product_word(product, top - 2) << zeros) | (bc_product_word(product, top - 3) << zeros >> 32)) & 0xffffffffu;
+    int exponent = 32 * (top - 2) - zeros - 190;
+    double t_hi = (double)(high >> 11) * bc_f64_from_bits((uint64_t)(0x3ff + 43 + exponent) << 52);
+    double t_lo = (double)((high & 0x7ff) << 32 | low) * bc_f64_from_bits((uint64_t)(0x3ff + exponent) << 52);
+
+    double p, err;
+    bc_exact_mul(t_hi, BC_PIO2_HI, &p, &err);
+    err = err + (t_hi * BC_PIO2_LO + t_lo * BC_PIO2_HI);
+    *y0 = p + err;
+    *y1 = err - (*y0 - p);
+    int quadrant = (int)n;
+    if (negative != (x < 0.0)) {
+        *y0 = -*y0;
+        *y1 = -*y1;
+    }
+    if (x < 0.0) quadrant = -quadrant;
+    return quadrant & 3;
+}
+
+static inline int bc_reduce(double x, double *y0, double *y1) {
+    const double to_int = 1.5 / 2.22044604925031308085e-16;
+    const double inv_pio2 = 6.36619772367581382433e-01;
+    const double pio2_1 = 1.57079632673412561417e+00, pio2_1t = 6.07710050650619224932e-11;
+    const double pio2_2 = 6.07710050630396597660e-11, pio2_2t = 2.02226624879595063154e-21;
+    const double pio2_3 = 2.02226624871116645580e-21, pio2_3t = 8.47842766036889956997e-32;
+
+    if (fabs(x) <= 0.785398163397448309615660845819875721) {
+        *y0 = x;
+        *y1 = 0.0;
+        return 0;
+    }
+    if (fabs(x) > 1647099.0) return bc_reduce_large(x, y0, y1);
+    double n = x * inv_pio2 + to_int - to_int;
+    double r = x - n * pio2_1;
+    double w = n * pio2_1t;
+    *y0 = r - w;
+    if (bc_exponent(x) - bc_exponent(*y0) > 16) {
+        double t = r;
+        w = n * pio2_2;
+        r = t - w;
+        w = n * pio2_2t - ((t - r) - w);
+        *y0 = r - w;
+        if (bc_exponent(x) - bc_exponent(*y0) > 49) {
+            t = r;
+            w = n * pio2_3;
+            r = t - w;
+            w = n * pio2_3t - ((t - r) - w);
+            *y0 = r - w;
+        }
+    }
+    *y1 = (r - *y0) - w;
+    return (int)n & 3;
+}
+
+static inline double bc_sin(double x) {
+    double y0, y1;
+    if (!isfinite(x)) return NAN;
+    if (fabs(x) < bc_f64_from_bits(0x3e50000000000000ull)) return x;
+    switch (bc_reduce(x, &y0, &y1)) {
+        case 0: return bc_sin_kernel(y0, y1);
+        case 1: return bc_cos_kernel(y0, y1);
+        case 2: return -bc_sin_kernel(y0, y1);
+        default: return -bc_cos_kernel(y0, y1);
+    }
+}
+
+static inline double bc_cos(double x) {
+    double y0, y1;
+    if (!isfinite(x)) return NAN;
+    if (fabs(x) < bc_f64_from_bits(0x3e46a09e667f3bcdull)) return 1.0;
+    switch (bc_reduce(x, &y0, &y1)) {
+        case 0: return bc_cos_kernel(y0, y1);
+        case 1: return -bc_sin_kernel(y0, y1);
+        case 2: return -bc_cos_kernel(y0, y1);
+        default: return bc_sin_kernel(y0, y1);
+    }
+}
+
+static inline double bc_tan(double x) {
+    double y0, y1;
+    if (!isfinite(x)) return NAN;
+    if (fabs(x) < bc_f64_from_bits(0x3e40000000000000ull)) return x;
+    int n = bc_reduce(x, &y0, &y1);
+    double s = bc_sin_kernel(y0, y1), c = bc_cos_kernel(y0, y1);
+    return (n & 1) == 0 ? s / c : -c / s;
+}
+
+static inline double bc_atan(double x) {
+    static const double atan_hi[4] = {4.63647609000806093515e-01, 7.85398163397448278999e-01, 9.82793723247329054082e-01, 1.57079632679489655800e+00};
+    static const double atan_lo[4] = {2.26987774529616870924e-17, 3.06161699786838301793e-17, 1.39033110312309984516e-17, 6.12323399573676603587e-17};
+    static const double at[11] = {
+        3.33333333333329318027e-01, -1.99999999998764832476e-01, 1.42857142725034663711e-01,
+        -1.11111104054623557880e-01, 9.09088713343650656196e-02, -7.69187620504482999495e-02,
+        6.66107313738753120669e-02, -5.83357013379057348645e-02, 4.97687799461593236017e-02,
+        -3.65315727442169155270e-02, 1.62858201153657823623e-02,
+    };
+    int id;
+
+    if (isnan(x)) return x;
+    int negative = x < 0.0;
+    double ax = fabs(x);
+    if (ax >= bc_f64_from_bits(0x4410000000000000ull)) {
+        double z = atan_hi[3] + atan_lo[3];
+        return negative ? -z : z;
+    }
+    if (ax < bc_f64_from_bits(0x3e40000000000000ull)) return x;
+    if (ax < 0.4375) {
+        id = -1;
+    } else if (ax < 0.6875) {
+        ax = (2.0 * ax - 1.0) / (2.0 + ax);
+        id = 0;
+    } else if (ax < 1.1875) {
+        ax = (ax - 1.0) / (ax + 1.0);
+        id = 1;
+    } else if (ax < 2.4375) {
+        ax = (ax - 1.5) / (1.0 + 1.5 * ax);
+        id = 2;
+    } else {
+        ax = -1.0 / ax;
+        id = 3;
+    }
+    x = id < 0 && negative ? -ax : ax;
+    double z = x * x;
+    double w = z * z;
+    double s1 = z * (at[0] + w * (at[2] + w * (at[4] + w * (at[6] + w * (at[8] + w * at[10])))));
+    double s2 = w * (at[1] + w * (at[3] + w * (at[5] + w * (at[7] + w * at[9]))));
+    if (id < 0) return x - x * (s1 + s2);
+    z = atan_hi[id] - ((x * (s1 + s2) - atan_lo[id]) - x);
+    return negative ? -z : z;
+}
+
+static inline double bc_atan2(double y, double x) {
+    const double pi = 3.14159265358979323846264338327950288;
+    const double pi_2 = 1.57079632679489661923132169163975144;
+    const double pi_4 = 0.785398163397448309615660845819875721;
+    const double pi_lo = 1.2246467991473531772e-16;
+
+    if (isnan(x) || isnan(y)) return x + y;
+    if (x == 1.0) return bc_atan(y);
+    int m = (signbit(y) ? 1 : 0) | (signbit(x) ? 2 : 0);
+    if (y == 0.0) {
+        switch (m) {
+            case 0: case 1: return y;
+            case 2: return pi;
+            default: return -pi;
+        }
+    }
+    if (x == 0.0) return m & 1 ? -pi_2 : pi_2;
+    if (isinf(x)) {
+        if (isinf(y)) {
+            switch (m) {
+                case 0: return pi_4;
+                case 1: return -pi_4;
+                case 2: return 3.0 * pi_4;
+                default: return -3.0 * pi_4;
+            }
+        }
+        switch (m) {
+            case 0: return 0.0;
+            case 1: return -0.0;
+            case 2: return pi;
+            default: return -pi;
+        }
+    }
+    double ratio = fabs(y / x);
+    if (isinf(y) || ratio > bc_f64_from_bits(0x43f0000000000000ull)) return m & 1 ? -pi_2 : pi_2;
+    double z = (m & 2) && ratio < bc_f64_from_bits(0x3bf0000000000000ull) ? 0.0 : bc_atan(ratio);
+    switch (m) {
+        case 0: return z;
+        case 1: return -z;
+        case 2: return pi - (z - pi_lo);
+        default: return (z - pi_lo) - pi;
+    }
+}
+
+#define BC_TWO_THIRDS_HI 6.66666666666666629659e-01
+#define BC_TWO_THIRDS_LO 3.70074341541718833847e-17
+
+static inline void bc_ln_extended(double x, double *hi, double *lo) {
+    int k = 0;
+    if (x < 2.2250738585072014e-308) {
+        k -= 54;
+        x *= bc_f64_from_bits(0x4350000000000000ull);
+    }
+    uint32_t hx = (uint32_t)(bc_f64_bits(x) >> 32);
+    hx += 0x3ff00000 - 0x3fe6a09e;
+    k += (int)(hx >> 20) - 0x3ff;
+    hx = (hx & 0x000fffff) + 0x3fe6a09e;
+    double m = bc_f64_from_bits((uint64_t)hx << 32 | (bc_f64_bits(x) & 0xffffffffull));
+
+    double u = m - 1.0;
+    double v_hi, v_lo, p, e;
+    bc_exact_add(1.0, m, &v_hi, &v_lo);
+    double s_hi = u / v_hi;
+    bc_exact_mul(s_hi, v_hi, &p, &e);
+    double s_lo = (((u - p) - e) - s_hi * v_lo) / v_hi;
+    double z, z_lo, cube_hi, cube_lo, tail_hi, tail_lo;
+    bc_exact_mul(s_hi, s_hi, &z, &z_lo);
+    bc_extended_mul(s_hi, 0.0, z, z_lo, &cube_hi, &cube_lo);
+    double series = 2.0 / 25.0;
+    for (int d = 23; d >= 5; d -= 2) series = 2.0 / d + z * series;
+    bc_extended_mul(cube_hi, cube_lo, BC_TWO_THIRDS_HI, BC_TWO_THIRDS_LO + z * series, &tail_hi, &tail_lo);
+
+    double dk = k, sum, lo1, lo2;
+    bc_exact_add(2.0 * s_hi, tail_hi, &sum, &lo1);
+    bc_exact_add(dk * BC_LN2_HI, sum, &sum, &lo2);
+    double rest = lo1 + lo2 + (tail_lo + 2.0 * s_lo / (1.0 - z) + dk * BC_LN2_LO);
+    *hi = sum + rest;
+    *lo = rest - (*hi - sum);
+}
+
+static inline double bc_powi(double x, uint64_t n, int negative) {
+    double base_hi = x, base_lo = 0.0, hi = 1.0, lo = 0.0;
+    while (n > 0) {
+        if (n & 1) bc_extended_mul(hi, lo, base_hi, base_lo, &hi, &lo);
+        n >>= 1;
+        if (n > 0) bc_extended_mul(base_hi, base_lo, base_hi, base_lo, &base_hi, &base_lo);
+    }
+    if (!isfinite(hi) || hi < bc_f64_from_bits(0x06b0000000000000ull)) return NAN;
+    if (!negative) return hi;
+    double q = 1.0 / hi, p, e;
+    bc_exact_mul(q, hi, &p, &e);
+    return q + (((1.0 - p) - e) - lo * q) * q;
+}
+
+static inline double bc_pow(double x, double y) {
+    if (y == 0.0 || x == 1.0) return 1.0;
+    if (isnan(x) || isnan(y)) return NAN;
+    int y_integer = floor(y) == y;
+    int y_odd = y_integer && fabs(y) < 9007199254740992.0 && floor(y * 0.5) != y * 0.5;
+    if (isinf(y)) {
+        if (x == -1.0) return 1.0;
+        return (fabs(x) < 1.0) == (y < 0.0) ? INFINITY : 0.0;
+    }
+    if (x == 0.0 || isinf(x)) {
+        double magnitude = (x == 0.0) == (y < 0.0) ? INFINITY : 0.0;
+        return signbit(x) && y_odd ? -magnitude : magnitude;
+    }
+    if (x < 0.0 && !y_integer) return NAN;
+    double ax = fabs(x);
+    double result = NAN;
+    if (y_integer && fabs(y) <= 1024.0) result = bc_powi(ax, (uint64_t)fabs(y), y < 0.0);
+    if (isnan(result)) {
+        double l_hi, l_lo, p, e;
+        bc_ln_extended(ax, &l_hi, &l_lo);
+        p = y * l_hi;
+        if (p > 710.0) return x < 0.0 && y_odd ? -INFINITY : INFINITY;
+        if (p < -746.0) return x < 0.0 && y_odd ? -0.0 : 0.0;
+        bc_exact_mul(y, l_hi, &p, &e);
+        e = e + y * l_lo;
+        double w_hi = p + e;
+        double w_lo = e - (w_hi - p);
+        result = bc_exp(w_hi);
+        if (isfinite(result)) result += result * w_lo;
+    }
+    return x < 0.0 && y_odd ? -result : result;
+}
+"#;
+
+/// Runtime shared by every translated program. Error messages follow the ones the VM prints.
+const C_RUNTIME: &str = r#"
+static bc_word registers[BC_REGISTER_COUNT];
+static bc_float float_registers[BC_FLOAT_REGISTER_COUNT];
+static uint8_t flags[16];
+static int trap_overflow;
+
+/* The VM keeps the stack contents and the stack pointer separately and they can
+   disagree (e.g. after stkcpybacksp), so both are tracked here as well. Slots are
+   64 bits like the VM's so they can hold a double in any integer mode. */
+static uint64_t *stack;
+static size_t stack_len;
+static size_t stack_cap;
+static size_t sp;
+
+static size_t *return_addresses;
+static size_t return_addresses_len;
+static size_t return_addresses_cap;
+
+typedef struct {
+    size_t id;
+    size_t size;
+    bc_word *cells;
+    uint8_t *is_set;
+} memory_unit;
+
+static memory_unit *memory_units;
+static size_t memory_units_len;
+static size_t memory_units_cap;
+static size_t memory_ids_count;
+
+static inline void bc_halt(void) {
+    fflush(stdout);
+    exit(0);
+}
+
+static inline void bc_panic(const char *msg) {
+    fflush(stdout);
+    fprintf(stderr, "Runtime Error: %s\n", msg);
+    exit(101);
+}
+
+static inline void bc_fail(const char *msg) {
+    printf("%s\n", msg);
+    exit(1);
+}
+
+/* State at a `@try`, which a runtime error goes back to before running `address`. */
+typedef struct {
+    size_t address;
+    size_t return_addresses_len;
+    /* A copy of the whole stack, as the protected code can pop or overwrite what was there. */
+    uint64_t *stack;
+    size_t stack_len;
+    size_t sp;
+    bc_word bp;
+} bc_handler;
+
+static bc_handler *handlers;
+static size_t handlers_len;
+static size_t handlers_cap;
+static jmp_buf bc_catch_point;
+static bc_word bc_caught;
+
+/* Goes to the handler of the last `@try` if there is one, returns otherwise. */
+static inline void bc_catch_fault(bc_word code) {
+    if (handlers_len == 0) return;
+    bc_caught = code;
+    longjmp(bc_catch_point, 1);
+}
+
+static inline void *bc_grow(void *ptr, size_t *cap, size_t needed, size_t elem_size) {
+    if (needed <= *cap) return ptr;
+    size_t new_cap = *cap ? *cap * 2 : 64;
+    while (new_cap < needed) new_cap *= 2;
+    ptr = realloc(ptr, new_cap * elem_size);
+    if (!ptr) bc_panic("out of memory");
+    *cap = new_cap;
+    return ptr;
+}
+
+static inline bc_sword reg_int(uint32_t reg) {
+    return (bc_sword)registers[reg];
+}
+
+/* Integers are sign extended into stack slots, as in the VM. */
+static inline uint64_t bc_slot(bc_word value) {
+    return (uint64_t)(int64_t)(bc_sword)value;
+}
+
+static inline bc_float_bits bc_float_to_bits(bc_float f) {
+    bc_float_bits bits;
+    memcpy(&bits, &f, sizeof bits);
+    return bits;
+}
+
+/* Converts like Rust's `as`: rounds towards zero, saturates and turns NaN into 0. */
+static inline bc_sword bc_float_to_int(bc_float f) {
+    if (isnan(f)) return 0;
+    if (f >= -(bc_float)BC_SWORD_MIN) return BC_SWORD_MAX;
+    if (f <= (bc_float)BC_SWORD_MIN) return BC_SWORD_MIN;
+    return (bc_sword)f;
+}
+
+static inline bc_float bc_float_from_bits(bc_float_bits bits) {
+    bc_float f;
+    memcpy(&f, &bits, sizeof f);
+    return f;
+}
+
+static inline void bc_push_slot(uint64_t slot) {
+    stack = bc_grow(stack, &stack_cap, stack_len + 1, sizeof *stack);
+    stack[stack_len++] = slot;
+    sp += 1;
+}
+
+static inline uint64_t bc_pop_slot(void) {
+    if (stack_len == 0) {
+        bc_catch_fault(BC_ERROR_STACK);
+        bc_fail("Runtime Error: Stack cannot be popped from as stack is empty.");
+    }
+    if (sp == 0) bc_panic("stack pointer underflow");
+    sp -= 1;
+    return stack[--stack_len];
+}
+
+static inline void bc_push_value(bc_word value) {
+    bc_push_slot(bc_slot(value));
+}
+
+static inline bc_word bc_pop_value(void) {
+    return (bc_word)bc_pop_slot();
+}
+
+static inline void bc_push_return_address(size_t address) {
+    return_addresses = bc_grow(return_addresses, &return_addresses_cap, return_addresses_len + 1, sizeof *return_addresses);
+    return_addresses[return_addresses_len++] = address;
+}
+
+
+static inline void bc_mov(uint32_t reg, int32_t value) {
+    registers[reg] = (bc_word)(bc_sword)value;
+}
+
+/* Runtime errors caused by an instruction, `location` tells where it comes from. */
+static inline void bc_trapf(bc_word code, const char *location, const char *format, ...) {
+    bc_catch_fault(code);
+    va_list args;
+    va_start(args, format);
+    printf("Runtime Error: ");
+    vprintf(format, args);
+    printf(" %s\n", location);
+    va_end(args);
+    exit(1);
+}
+
+static inline void bc_trap(bc_word code, const char *message, const char *location) {
+    bc_trapf(code, location, "%s", message);
+}
+
+static inline void bc_try(size_t address) {
+    uint64_t *copy = malloc((stack_len ? stack_len : 1) * sizeof *stack);
+    if (!copy) bc_panic("out of memory");
+    if (stack_len) memcpy(copy, stack, stack_len * sizeof *stack);
+    handlers = bc_grow(handlers, &handlers_cap, handlers_len + 1, sizeof *handlers);
+    handlers[handlers_len++] = (bc_handler){ address, return_addresses_len, copy, stack_len, sp, registers[BC_REGBP] };
+}
+
+static inline bc_handler bc_pop_handler(void) {
+    bc_handler handler = handlers[--handlers_len];
+    free(handler.stack);
+    handler.stack = NULL;
+    return handler;
+}
+
+/* Forgets the handlers of `@try`s run `depth` calls deep or deeper, as their frame is gone. */
+static inline void bc_drop_handlers(size_t depth) {
+    while (handlers_len > 0 && handlers[handlers_len - 1].return_addresses_len >= depth) bc_pop_handler();
+}
+
+static inline size_t bc_return(const char *location) {
+    if (return_addresses_len == 0) bc_trap(BC_ERROR_STACK, "ret without a matching call", location);
+    bc_drop_handlers(return_addresses_len);
+    return return_addresses[--return_addresses_len];
+}
+
+static inline void bc_end_try(const char *location) {
+    if (handlers_len == 0) bc_trap(BC_ERROR_BAD_INSTRUCTION, "@endtry without a matching @try", location);
+    bc_pop_handler();
+}
+
+static inline void bc_throw(bc_word code, const char *location) {
+    bc_trapf(code, location, "uncaught error %lld", (long long)(bc_sword)code);
+}
+
+/* Puts the state back to how it was at the last `@try` and returns its handler. */
+static inline size_t bc_catch(void) {
+    bc_handler handler = handlers[handlers_len - 1];
+    if (return_addresses_len > handler.return_addresses_len) return_addresses_len = handler.return_addresses_len;
+    stack = bc_grow(stack, &stack_cap, handler.stack_len, sizeof *stack);
+    if (handler.stack_len) memcpy(stack, handler.stack, handler.stack_len * sizeof *stack);
+    stack_len = handler.stack_len;
+    bc_pop_handler();
+    sp = handler.sp;
+    registers[BC_REGBP] = handler.bp;
+    registers[0] = bc_caught;
+    return handler.address;
+}
+
+/* `jmp` or `call` through a register holding an address that no `lea` loads. */
+static inline void bc_bad_indirect_target(bc_word address, const char *location) {
+    bc_catch_fault(BC_ERROR_BAD_ADDRESS);
+    printf("Runtime Error: cannot jump to address %lld as no lea loads it %s\n", (long long)(bc_sword)address, location);
+    exit(1);
+}
+
+/* Zero and sign flags of an arithmetic or logic result. */
+static inline void bc_set_result_flags(bc_word result) {
+    flags[0] = result == 0;
+    flags[8] = result >> (BC_WORD_BITS - 1);
+}
+
+/* Stores the result of a logic, shift or rotate instruction, which never overflows or carries. */
+static inline void bc_logic(uint32_t a, bc_word result) {
+    bc_set_result_flags(result);
+    flags[6] = 0;
+    flags[7] = 0;
+    registers[a] = result;
+}
+
+static inline void bc_set_arithmetic_flags(int overflow, int carry, const char *location) {
+    flags[6] = overflow;
+    flags[7] = carry;
+    if (overflow && trap_overflow) bc_trap(BC_ERROR_OVERFLOW, "integer overflow", location);
+}
+
+static inline void bc_add(uint32_t a, bc_word b, const char *location) {
+    bc_word result = registers[a] + b;
+    bc_set_arithmetic_flags((int)(((registers[a] ^ result) & (b ^ result)) >> (BC_WORD_BITS - 1)), result < b, location);
+    bc_set_result_flags(result);
+    registers[a] = result;
+}
+
+static inline void bc_sub(uint32_t a, bc_word b, const char *location) {
+    bc_word result = registers[a] - b;
+    bc_set_arithmetic_flags((int)(((registers[a] ^ b) & (registers[a] ^ result)) >> (BC_WORD_BITS - 1)), registers[a] < b, location);
+    bc_set_result_flags(result);
+    registers[a] = result;
+}
+
+static inline void bc_mul(uint32_t a, bc_sword b, const char *location) {
+    bc_sword product;
+    int overflow = __builtin_mul_overflow(reg_int(a), b, &product);
+    bc_set_arithmetic_flags(overflow, overflow, location);
+    bc_set_result_flags((bc_word)product);
+    registers[a] = (bc_word)product;
+}
+
+/* Dividing BC_SWORD_MIN by -1 wraps to BC_SWORD_MIN with a remainder of 0. */
+static inline void bc_div(uint32_t a, bc_sword b, const char *location) {
+    if (b == 0) bc_trap(BC_ERROR_DIVISION_BY_ZERO, "division by zero", location);
+    int overflow = reg_int(a) == BC_SWORD_MIN && b == -1;
+    bc_set_arithmetic_flags(overflow, overflow, location);
+    if (!overflow) registers[a] = (bc_word)(reg_int(a) / b);
+    bc_set_result_flags(registers[a]);
+}
+
+static inline void bc_mod(uint32_t a, bc_sword b, const char *location) {
+    if (b == 0) bc_trap(BC_ERROR_DIVISION_BY_ZERO, "division by zero", location);
+    int overflow = reg_int(a) == BC_SWORD_MIN && b == -1;
+    bc_set_arithmetic_flags(overflow, overflow, location);
+    registers[a] = overflow ? 0 : (bc_word)(reg_int(a) % b);
+    bc_set_result_flags(registers[a]);
+}
+
+static inline void bc_divu(uint32_t a, bc_word b, const char *location) {
+    if (b == 0) bc_trap(BC_ERROR_DIVISION_BY_ZERO, "division by zero", location);
+    bc_logic(a, registers[a] / b);
+}
+
+static inline void bc_modu(uint32_t a, bc_word b, const char *location) {
+    if (b == 0) bc_trap(BC_ERROR_DIVISION_BY_ZERO, "division by zero", location);
+    bc_logic(a, registers[a] % b);
+}
+
+/* C leaves shifting by the register width or more undefined, the VM moves every bit out. */
+static inline bc_word bc_shl(bc_word value, bc_word count) {
+    return count >= BC_WORD_BITS ? 0 : value << count;
+}
+
+static inline bc_word bc_shr(bc_word value, bc_word count) {
+    return count >= BC_WORD_BITS ? 0 : value >> count;
+}
+
+static inline bc_word bc_sar(bc_word value, bc_word count) {
+    if (count >= BC_WORD_BITS) count = BC_WORD_BITS - 1;
+    return (bc_word)((bc_sword)value >> count);
+}
+
+static inline bc_word bc_rol(bc_word value, bc_word count) {
+    count %= BC_WORD_BITS;
+    return count == 0 ? value : (value << count) | (value >> (BC_WORD_BITS - count));
+}
+
+static inline bc_word bc_ror(bc_word value, bc_word count) {
+    count %= BC_WORD_BITS;
+    return count == 0 ? value : (value >> count) | (value << (BC_WORD_BITS - count));
+}
+
+static inline void bc_display(uint32_t reg) {
+    printf("%lld\n", (long long)reg_int(reg));
+}
+
+/* Prints a float the way Rust's `{:?}` does: the shortest digits that read back as the
+   same float, in scientific notation only for very small or very large numbers. */
+static inline void bc_display_float(bc_float f) {
+    char buf[64];
+    char digits[32];
+    int digit_count = 0;
+    int exponent;
+    int precision;
+    int negative;
+    char *p;
+
+    if (isnan(f)) { printf("NaN\n"); return; }
+    if (isinf(f)) { printf(f < 0 ? "-inf\n" : "inf\n"); return; }
+    if (f == 0.0f) { printf(signbit(f) ? "-0.0\n" : "0.0\n"); return; }
+
+    for (precision = 0; precision < BC_FLOAT_DIGITS; precision++) {
+        snprintf(buf, sizeof buf, "%.*e", precision, (double)f);
+        if (bc_strtof(buf, NULL) == f) break;
+    }
+    negative = buf[0] == '-';
+    for (p = buf + negative; *p && *p != 'e'; p++) {
+        if (*p >= '0' && *p <= '9') digits[digit_count++] = *p;
+    }
+    exponent = atoi(p + 1);
+    while (digit_count > 1 && digits[digit_count - 1] == '0') digit_count--;
+
+    if (negative) putchar('-');
+    if (exponent < -4 || exponent >= 16) {
+        putchar(digits[0]);
+        if (digit_count > 1) {
+            putchar('.');
+            fwrite(digits + 1, 1, digit_count - 1, stdout);
+        }
+        printf("e%d\n", exponent);
+    } else if (exponent < 0) {
+        printf("0.");
+        for (int i = 0; i < -exponent - 1; i++) putchar('0');
+        fwrite(digits, 1, digit_count, stdout);
+        putchar('\n');
+    } else {
+        for (int i = 0; i <= exponent; i++) putchar(i < digit_count ? digits[i] : '0');
+        putchar('.');
+        if (digit_count > exponent + 1) fwrite(digits + exponent + 1, 1, digit_count - exponent - 1, stdout);
+        else putchar('0');
+        putchar('\n');
+    }
+}
+
+static inline void bc_put_utf8(uint32_t c) {
+    if (c < 0x80) {
+        putchar((int)c);
+    } else if (c < 0x800) {
+        putchar((int)(0xC0 | (c >> 6)));
+        putchar((int)(0x80 | (c & 0x3F)));
+    } else if (c < 0x10000) {
+        putchar((int)(0xE0 | (c >> 12)));
+        putchar((int)(0x80 | ((c >> 6) & 0x3F)));
+        putchar((int)(0x80 | (c & 0x3F)));
+    } else {
+        putchar((int)(0xF0 | (c >> 18)));
+        putchar((int)(0x80 | ((c >> 12) & 0x3F)));
+        putchar((int)(0x80 | ((c >> 6) & 0x3F)));
+        putchar((int)(0x80 | (c & 0x3F)));
+    }
+}
+
+static inline void bc_display_char(uint32_t reg) {
+    bc_sword c = reg_int(reg);
+    if (c < 0) bc_panic("character code cannot be negative");
+    if (c > 0x10FFFF || (c >= 0xD800 && c <= 0xDFFF)) {
+        printf("Run Time Error: Cannot get character from number %lld\n", (long long)c);
+        exit(1);
+    }
+    bc_put_utf8((uint32_t)c);
+}
+
+/* Sets the flags of `a - b` without keeping it, plus the comparison flags. */
+static inline void bc_compare(bc_sword a, bc_sword b) {
+    bc_word result = (bc_word)a - (bc_word)b;
+    bc_set_result_flags(result);
+    flags[6] = (((bc_word)a ^ (bc_word)b) & ((bc_word)a ^ result)) >> (BC_WORD_BITS - 1);
+    flags[7] = (bc_word)a < (bc_word)b;
+    flags[1] = a == b;
+    flags[3] = a > b;
+    flags[2] = a < b;
+    flags[4] = (bc_word)a < (bc_word)b;
+    flags[5] = (bc_word)a > (bc_word)b;
+}
+
+static inline void bc_compare_unsigned(bc_word a, bc_word b) {
+    bc_compare((bc_sword)a, (bc_sword)b);
+    flags[3] = a > b;
+    flags[2] = a < b;
+}
+
+/* Every comparison with NaN is false, only the overflow flag tells it happened. */
+static inline void bc_compare_float(bc_float a, bc_float b) {
+    flags[0] = flags[1] = a == b;
+    flags[3] = flags[5] = a > b;
+    flags[2] = flags[4] = flags[8] = flags[7] = a < b;
+    flags[6] = isnan(a) || isnan(b);
+}
+
+static inline void bc_get_flag(uint32_t dest, uint32_t flag_reg) {
+    bc_sword flag = reg_int(flag_reg);
+    if (flag < 0 || flag >= 16) {
+        printf("Runtime Error: Could not get flag number %lld as it does not exist.\n", (long long)flag);
+        exit(1);
+    }
+    registers[dest] = flags[flag];
+}
+
+/* Index `index` of the stack, which `action` ("get" or "set") needs to be on it. */
+static inline size_t bc_stack_index(int64_t index, const char *action, const char *location) {
+    if (index < 0 || (uint64_t)index >= stack_len) bc_trapf(BC_ERROR_STACK, location, "cannot %s element %lld of the stack", action, (long long)index);
+    return (size_t)index;
+}
+
+static inline void bc_get_from_stack(uint32_t index_reg, uint32_t dest, const char *location) {
+    registers[dest] = stack[bc_stack_index((int64_t)reg_int(index_reg), "get", location)];
+}
+
+static inline void bc_get_from_stack_pointer(uint32_t offset_reg, uint32_t dest, const char *location) {
+    registers[dest] = stack[bc_stack_index((int64_t)sp - (int64_t)reg_int(offset_reg), "get", location)];
+}
+
+static inline void bc_set_stack(uint32_t index_reg, uint32_t src, const char *location) {
+    stack[bc_stack_index((int64_t)reg_int(index_reg), "set", location)] = bc_slot(registers[src]);
+}
+
+static inline void bc_set_from_stack_pointer(uint32_t offset_reg, uint32_t src, const char *location) {
+    stack[bc_stack_index((int64_t)sp - (int64_t)reg_int(offset_reg), "set", location)] = bc_slot(registers[src]);
+}
+
+static inline void bc_enter(uint32_t locals) {
+    bc_push_value(registers[BC_REGBP]);
+    registers[BC_REGBP] = (bc_word)stack_len;
+    stack = bc_grow(stack, &stack_cap, stack_len + locals, sizeof *stack);
+    memset(stack + stack_len, 0, locals * sizeof *stack);
+    stack_len += locals;
+    sp += locals;
+}
+
+static inline void bc_leave(const char *location) {
+    bc_sword bp = reg_int(BC_REGBP);
+    if (bp < 1 || (size_t)bp > stack_len) bc_trap(BC_ERROR_STACK, "leave without a matching enter", location);
+    sp -= stack_len - (size_t)bp + 1;
+    stack_len = (size_t)bp - 1;
+    registers[BC_REGBP] = (bc_word)stack[stack_len];
+}
+
+/* Index of the stack slot `bp + offset`, `slot` is how the program wrote it. */
+static inline size_t bc_frame_slot(int32_t offset, const char *slot, const char *location) {
+    int64_t index = (int64_t)reg_int(BC_REGBP) + offset;
+    if (index < 0 || (uint64_t)index >= stack_len) {
+        bc_catch_fault(BC_ERROR_STACK);
+        printf("Runtime Error: %s is outside the stack %s\n", slot, location);
+        exit(1);
+    }
+    return (size_t)index;
+}
+
+static inline void bc_extend_stack(uint32_t extend_by_reg, uint32_t default_reg) {
+    bc_sword extend_by = reg_int(extend_by_reg);
+    if (extend_by < 0) bc_panic("Cannot extend stack by negative number");
+    stack = bc_grow(stack, &stack_cap, stack_len + (size_t)extend_by, sizeof *stack);
+    for (bc_sword i = 0; i < extend_by; i++) stack[stack_len++] = bc_slot(registers[default_reg]);
+    sp += (size_t)extend_by;
+}
+
+static inline void bc_truncate_stack(uint32_t reg, const char *location) {
+    bc_sword count = reg_int(reg);
+    if (count < 0 || (uint64_t)count > sp) bc_trapf(BC_ERROR_STACK, location, "cannot truncate %lld elements of the stack", (long long)count);
+    for (bc_sword i = 0; i < count; i++) {
+        if (stack_len > 0) stack_len--;
+    }
+    sp -= (size_t)count;
+}
+
+static inline void bc_truncate_stack_range(uint32_t min_reg, uint32_t max_reg) {
+    size_t min = (size_t)(int64_t)reg_int(min_reg);
+    size_t max = (size_t)(int64_t)reg_int(max_reg);
+    if (min > max || max > stack_len) bc_panic("range out of bounds of the stack");
+    memmove(stack + min, stack + max, (stack_len - max) * sizeof *stack);
+    stack_len -= max - min;
+    if (max - min > sp) bc_panic("stack pointer underflow");
+    sp -= max - min;
+}
+
+static inline void bc_stack_copy_back_sp(uint32_t start_reg, uint32_t end_reg, uint32_t dest_reg) {
+    size_t start_rel = (size_t)(int64_t)reg_int(start_reg);
+    size_t end_rel = (size_t)(int64_t)reg_int(end_reg);
+    size_t dest_rel = (size_t)(int64_t)reg_int(dest_reg);
+    if (sp == 0) bc_panic("Stack is empty");
+    if (start_rel > sp || end_rel > sp || dest_rel > sp || sp > stack_len) bc_panic("stack location out of bounds");
+    size_t start_loc = sp - start_rel;
+    size_t end_loc = sp - end_rel;
+    if (end_loc < start_loc) bc_panic("stack location out of bounds");
+    size_t data_size = end_loc - start_loc;
+    long needed = (long)data_size - (long)(stack_len - sp) - 1;
+    if (end_loc > stack_len) bc_panic("stack location out of bounds");
+    uint64_t *data = malloc((data_size ? data_size : 1) * sizeof *data);
+    if (!data) bc_panic("out of memory");
+    memcpy(data, stack + start_loc, data_size * sizeof *data);
+    if (needed > 0) {
+        stack = bc_grow(stack, &stack_cap, stack_len + (size_t)needed, sizeof *stack);
+        memset(stack + stack_len, 0, (size_t)needed * sizeof *stack);
+        stack_len += (size_t)needed;
+    }
+    if (sp - dest_rel + data_size > stack_len) bc_panic("stack location out of bounds");
+    memcpy(stack + sp - dest_rel, data, data_size * sizeof *data);
+    free(data);
+}
+
+static inline memory_unit *bc_get_memory_unit(size_t id) {
+    for (size_t i = 0; i < memory_units_len; i++) {
+        if (memory_units[i].id == id) return &memory_units[i];
+    }
+    return NULL;
+}
+
+static inline void bc_malloc(uint32_t size_reg) {
+    size_t size = (size_t)(int64_t)reg_int(size_reg);
+    memory_units = bc_grow(memory_units, &memory_units_cap, memory_units_len + 1, sizeof *memory_units);
+    memory_unit *unit = &memory_units[memory_units_len++];
+    unit->id = memory_ids_count++;
+    unit->size = size;
+    unit->cells = calloc(size ? size : 1, sizeof *unit->cells);
+    unit->is_set = calloc(size ? size : 1, 1);
+    if (!unit->cells || !unit->is_set) bc_panic("out of memory");
+    bc_push_value((bc_word)unit->id);
+}
+
+static inline void bc_free(uint32_t id_reg, const char *location) {
+    long long id = reg_int(id_reg);
+    memory_unit *unit = bc_get_memory_unit((size_t)(int64_t)id);
+    if (!unit) bc_trapf(BC_ERROR_MEMORY, location, "cannot free memory unit %lld as it does not exist", id);
+    free(unit->cells);
+    free(unit->is_set);
+    size_t index = (size_t)(unit - memory_units);
+    memmove(memory_units + index, memory_units + index + 1, (memory_units_len - index - 1) * sizeof *memory_units);
+    memory_units_len--;
+}
+
+static inline void bc_get_memory(uint32_t id_reg, uint32_t dest, uint32_t offset_reg, const char *location) {
+    long long id = reg_int(id_reg);
+    long long offset = reg_int(offset_reg);
+    memory_unit *unit = bc_get_memory_unit((size_t)(int64_t)id);
+    if (!unit) bc_trapf(BC_ERROR_MEMORY, location, "memory unit %lld does not exist", id);
+    if (offset < 0 || (size_t)offset >= unit->size || !unit->is_set[offset]) bc_trapf(BC_ERROR_MEMORY, location, "location %lld of memory unit %lld is not set", offset, id);
+    registers[dest] = unit->cells[offset];
+}
+
+static inline void bc_set_memory(uint32_t id_reg, uint32_t src, uint32_t offset_reg, const char *location) {
+    long long id = reg_int(id_reg);
+    long long offset = reg_int(offset_reg);
+    memory_unit *unit = bc_get_memory_unit((size_t)(int64_t)id);
+    if (!unit) bc_trapf(BC_ERROR_MEMORY, location, "memory unit %lld does not exist", id);
+    if (offset < 0 || (size_t)offset >= unit->size) bc_trapf(BC_ERROR_MEMORY, location, "location %lld of memory unit %lld does not exist", offset, id);
+    unit->cells[offset] = registers[src];
+    unit->is_set[offset] = 1;
+}
+
+/* Byte `n` of a unit is byte `n % sizeof(bc_word)` of cell `n / sizeof(bc_word)`, lowest first.
+   Checks that the `width` bytes from `offset` are in the unit. */
+static inline memory_unit *bc_memory_bytes(long long id, long long offset, size_t width, const char *location) {
+    memory_unit *unit = bc_get_memory_unit((size_t)(int64_t)id);
+    if (!unit) bc_trapf(BC_ERROR_MEMORY, location, "memory unit %lld does not exist", id);
+    long long size = (long long)(unit->size * sizeof(bc_word));
+    if (offset < 0 || offset + (long long)width > size) bc_trapf(BC_ERROR_MEMORY, location, "byte %lld of memory unit %lld does not exist", offset < 0 || offset > size ? offset : size, id);
+    return unit;
+}
+
+static inline void bc_load_memory(uint32_t id_reg, uint32_t dest, uint32_t offset_reg, size_t width, int is_signed, const char *location) {
+    long long id = reg_int(id_reg);
+    long long offset = reg_int(offset_reg);
+    memory_unit *unit = bc_memory_bytes(id, offset, width, location);
+    uint64_t value = 0;
+    for (size_t i = 0; i < width; i++) {
+        size_t byte = (size_t)offset + i;
+        if (!unit->is_set[byte / sizeof(bc_word)]) bc_trapf(BC_ERROR_MEMORY, location, "byte %zu of memory unit %lld is not set", byte, id);
+        value |= (uint64_t)((unit->cells[byte / sizeof(bc_word)] >> (byte % sizeof(bc_word) * 8)) & 0xff) << (8 * i);
+    }
+    if (is_signed && width < 8) {
+        uint64_t sign = 1ull << (8 * width - 1);
+        value = (value ^ sign) - sign;
+    }
+    registers[dest] = (bc_word)value;
+}
+
+static inline void bc_store_memory(uint32_t id_reg, uint32_t src, uint32_t offset_reg, size_t width, const char *location) {
+    long long id = reg_int(id_reg);
+    long long offset = reg_int(offset_reg);
+    memory_unit *unit = bc_memory_bytes(id, offset, width, location);
+    for (size_t i = 0; i < width; i++) {
+        size_t byte = (size_t)offset + i, cell = byte / sizeof(bc_word), shift = byte % sizeof(bc_word) * 8;
+        bc_word old = unit->is_set[cell] ? unit->cells[cell] : 0;
+        unit->cells[cell] = (old & ~((bc_word)0xff << shift)) | (((registers[src] >> (8 * i)) & 0xff) << shift);
+        unit->is_set[cell] = 1;
+    }
+}
+
+static inline void bc_write(uint32_t len_reg, uint32_t loc_reg) {
+    size_t len = (size_t)(int64_t)reg_int(len_reg);
+    size_t loc = (size_t)(int64_t)reg_int(loc_reg);
+    if (len > loc) bc_panic("attempt to subtract with overflow");
+    if (loc <= stack_len) {
+        for (size_t i = loc - len; i < loc; i++) {
+            bc_sword c = (bc_sword)stack[i];
+            bc_put_utf8(c >= 0 && c <= 255 ? (uint32_t)c : 0);
+        }
+    }
+    fflush(stdout);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs every `MATH_` function of `C_MATH` on pairs of `float_math::test_inputs` and checks the
+    /// bits of each result against `float_math`.
+    #[test]
+    fn c_math_matches_float_math() {
+        let inputs = float_math::test_inputs();
+        let mut program = format!("{}{}\nstatic const uint64_t inputs[] = {{",C_INCLUDES,C_MATH);
+        for input in inputs.iter() {
+            program.push_str(&format!("0x{:016x}ull,",input.to_bits()));
+        }
+        program.push_str("};\nstatic void print(double x) { printf(\"%016llx\\n\", (unsigned long long)bc_f64_bits(x)); }\n");
+        program.push_str("int main(void) {\n    size_t count = sizeof inputs / sizeof *inputs;\n");
+        let mut expected = Vec::new();
+        for function in MATH_SQRT..=MATH_MAX {
+            let name = c_math_function(function).unwrap();
+            if float_math::is_binary(function) {
+                program.push_str(&format!("    for (size_t i = 0; i < count; i++) for (size_t j = 0; j < count; j++) print({}(bc_f64_from_bits(inputs[i]), bc_f64_from_bits(inputs[j])));\n",name));
+                for a in inputs.iter() {
+                    for b in inputs.iter() {
+                        expected.push((function,*a,*b,float_math::apply(function,*a,*b)));
+                    }
+                }
+            }else {
+                program.push_str(&format!("    for (size_t i = 0; i < count; i++) print({}(bc_f64_from_bits(inputs[i])));\n",name));
+                for b in inputs.iter() {
+                    expected.push((function,0.0,*b,float_math::apply(function,0.0,*b)));
+                }
+            }
+        }
+        program.push_str("    return 0;\n}\n");
+
+        let dir = std::env::temp_dir().join(format!("bytecode-c-math-{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source,binary) = (dir.join("math.c"),dir.join("math"));
+        std::fs::write(&source,program).unwrap();
+        let compile = std::process::Command::new("cc").args(["-O2","-Wall"]).arg(&source).arg("-o").arg(&binary).arg("-lm").output().unwrap();
+        assert!(compile.status.success(),"{}",String::from_utf8_lossy(&compile.stderr));
+        let output = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let lines = String::from_utf8(output.stdout).unwrap();
+        let lines = lines.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(),expected.len());
+        for (line,(function,a,b,result)) in lines.iter().zip(expected.iter()) {
+            assert_eq!(*line,format!("{:016x}",result.to_bits()),"MATH_ function {} of {:e} and {:e}",function,a,b);
+        }
+    }
+}
@@ -1,36 +1,129 @@
-pub const STACK_DATA_SIZE:usize = 32;
+pub const STACK_DATA_SIZE:usize = 64;
 
-pub type RegisterDataType = u32;
-pub type iRegisterDataType = i32;
+// Registers, stack slots and memory cells are 64 bits wide. Programs in 32-bit mode (the
+// default, see `ProgramHeader`) keep every value sign extended from its low 32 bits.
+pub type RegisterDataType = u64;
+pub type iRegisterDataType = i64;
 
 pub type InstructionParamType = u32;
 pub type iInstructionParamType = i32;
-pub type FloatInstructionParamType = f32;
+pub type FloatInstructionParamType = f64;
+
+// Number of instructions a program can have, including the jump to main.
+pub const INSTRUCTION_MEMORY_SIZE:usize = 1000;
 
 pub type InstructionNameBinaryType = u32;
 pub const INSTRUCTION_NAME_SIZE:usize = 32; // bits
 
+// The bytecode starts with a header holding settings for the whole program.
+pub type HeaderBinaryType = u32;
+pub const HEADER_SIZE:usize = 32; // bits
+pub const HEADER_WIDE_INTEGERS:HeaderBinaryType = 1;
+pub const HEADER_DOUBLE_FLOATS:HeaderBinaryType = 2;
+
 
 pub const ZERO_FLAG:usize = 0;
 pub const EQUAL_FLAG:usize = 1;
 pub const LESS_THAN_FLAG:usize = 2;
 pub const GREATER_THAN_FLAG:usize = 3;
+// Same as less and greater, but comparing the registers as unsigned numbers.
+pub const BELOW_FLAG:usize = 4;
+pub const ABOVE_FLAG:usize = 5;
+// Set by arithmetic instructions and cmp, cleared by logic ones. Overflow: the signed result did not fit. Carry: the unsigned one did not.
+pub const OVERFLOW_FLAG:usize = 6;
+pub const CARRY_FLAG:usize = 7;
+// Set from the highest bit of the result.
+pub const SIGN_FLAG:usize = 8;
+
+// Arithmetic modes set with `SetArithmeticMode`.
+// Wrapping keeps the low bits of results that do not fit a register, trapping stops with a runtime error.
+pub const ARITHMETIC_WRAPPING:InstructionParamType = 0;
+pub const ARITHMETIC_TRAPPING:InstructionParamType = 1;
+
+// Rounding modes of `MovFloatToIntRounded`. Nearest rounds ties to even.
+pub const ROUND_NEAREST:InstructionParamType = 0;
+pub const ROUND_DOWN:InstructionParamType = 1;
+pub const ROUND_UP:InstructionParamType = 2;
+pub const ROUND_ZERO:InstructionParamType = 3;
+
+// Functions of `FloatMath`. Round rounds ties away from zero, min and max ignore NaN.
+pub const MATH_SQRT:InstructionParamType = 0;
+pub const MATH_ABS:InstructionParamType = 1;
+pub const MATH_FLOOR:InstructionParamType = 2;
+pub const MATH_CEIL:InstructionParamType = 3;
+pub const MATH_ROUND:InstructionParamType = 4;
+pub const MATH_EXP:InstructionParamType = 5;
+pub const MATH_LN:InstructionParamType = 6;
+pub const MATH_SIN:InstructionParamType = 7;
+pub const MATH_COS:InstructionParamType = 8;
+pub const MATH_TAN:InstructionParamType = 9;
+pub const MATH_POW:InstructionParamType = 10;
+pub const MATH_ATAN2:InstructionParamType = 11;
+pub const MATH_MIN:InstructionParamType = 12;
+pub const MATH_MAX:InstructionParamType = 13;
+
+// Conditions of `ConditionalMove` and `SetIf`, one for every conditional jump. See `instruction::condition_flags`.
+pub const CONDITION_ZERO:InstructionParamType = 0;
+pub const CONDITION_NOT_ZERO:InstructionParamType = 1;
+pub const CONDITION_EQUAL:InstructionParamType = 2;
+pub const CONDITION_NOT_EQUAL:InstructionParamType = 3;
+pub const CONDITION_GREATER:InstructionParamType = 4;
+pub const CONDITION_LESS:InstructionParamType = 5;
+pub const CONDITION_GREATER_OR_EQUAL:InstructionParamType = 6;
+pub const CONDITION_LESS_OR_EQUAL:InstructionParamType = 7;
+pub const CONDITION_ABOVE:InstructionParamType = 8;
+pub const CONDITION_BELOW:InstructionParamType = 9;
+pub const CONDITION_ABOVE_OR_EQUAL:InstructionParamType = 10;
+pub const CONDITION_BELOW_OR_EQUAL:InstructionParamType = 11;
+pub const CONDITION_OVERFLOW:InstructionParamType = 12;
+pub const CONDITION_NOT_OVERFLOW:InstructionParamType = 13;
+pub const CONDITION_CARRY:InstructionParamType = 14;
+pub const CONDITION_NOT_CARRY:InstructionParamType = 15;
+
+// Error codes a `@try` handler finds in rax when it catches a runtime error. `throw` can use any
+// other number.
+pub const ERROR_DIVISION_BY_ZERO:RegisterDataType = 1;
+pub const ERROR_OVERFLOW:RegisterDataType = 2;
+// Popping an empty stack, a `[bp + k]` slot outside of it or `leave` without `enter`.
+pub const ERROR_STACK:RegisterDataType = 3;
+// A memory unit or a location in one that does not exist or is not set.
+pub const ERROR_MEMORY:RegisterDataType = 4;
+// `jmp` or `call` through a register to an address that no `lea` loads.
+pub const ERROR_BAD_ADDRESS:RegisterDataType = 5;
+// Instructions that cannot run as they are, such as `@endtry` without `@try`.
+pub const ERROR_BAD_INSTRUCTION:RegisterDataType = 6;
+// An `assert` whose condition does not hold.
+pub const ERROR_ASSERT:RegisterDataType = 7;
 
 pub const REGA:InstructionParamType = 0;
 pub const REGB:InstructionParamType = 1;
 pub const REGC:InstructionParamType = 2;
 pub const REGD:InstructionParamType = 3;
-pub const RESERVEREGISTER1:InstructionParamType = 4;
-pub const RESERVEREGISTER2:InstructionParamType = 5;
-pub const RESERVEREGISTER3:InstructionParamType = 6;
+// Programs can name `rax`-`rdx` and `r8`-`r15`, numbered 0 to 11 in the order of `tokens::REGISTERS`.
+// The reserve registers come after them and only the generator uses them, so expanding an
+// instruction never changes a register the program can see.
+pub const USER_REGISTER_COUNT:usize = 12;
+pub const RESERVEREGISTER1:InstructionParamType = 12;
+pub const RESERVEREGISTER2:InstructionParamType = 13;
+pub const RESERVEREGISTER3:InstructionParamType = 14;
+// `bp`, the base of the current stack frame (see `Enter`). Programs can read it with `mov` and
+// use it in `[bp + k]`, but only `enter`, `leave` and catching an error change it.
+pub const REGBP:InstructionParamType = 15;
+pub const REGISTER_COUNT:usize = 16;
+// Likewise `fa`-`fd` and `f8`-`f15`, followed by one reserve float register.
+pub const USER_FLOAT_REGISTER_COUNT:usize = 12;
+pub const RESERVEFLOATREGISTER:InstructionParamType = 12;
+pub const FLOAT_REGISTER_COUNT:usize = 13;
 
 
 
 // These are floating point register 
-// They store floating point only data
-pub type FloatRegisterDataType = f32;
+// They store floating point only data. Programs in single precision mode (the default) round
+// every value to the nearest f32.
+pub type FloatRegisterDataType = f64;
 
 pub const INT_PARAM_SIZE:usize = 32; // bits 
-pub const FLOAT_PARAM_SIZE:usize = 32;
+pub const WIDE_INT_PARAM_SIZE:usize = 64;
+pub const FLOAT_PARAM_SIZE:usize = 64;
 pub const REGISTER_PARAM_SIZE:usize = 32;
 pub const JUMP_DESTINATION_PARAM_SIZE:usize = 32;
@@ -1,20 +1,19 @@
-use crate::constants_and_types::*;
-
 #[macro_export]
 macro_rules! to_binary_slice {
     ($t:ty, $num:expr) => {
         {
        let to_binary = {
-         let max_power = (<$t>::MAX as f64).log2() as u64 as usize + 1;
+         let max_power = <$t>::BITS as usize;
+         let num = $num;
                  
                 let mut fin = Vec::new();
                 
                 for i in 0..max_power {
-                if $num == 0 { fin.push (0); continue };
+                if num == 0 { fin.push (0); continue };
                 let ok = 1u64.checked_shl(max_power as u32-1-i as u32);
 
                 if ok.is_none() { continue }; 
-                if $num & (1 << (max_power-1 - i)) != 0 {
+                if num & (1 << (max_power-1 - i)) != 0 {
                     fin.push(1);
                 }else {
                         fin.push(0);
@@ -36,9 +35,9 @@ macro_rules!  binary_slice_to_number {
                 if $slice[i] == 0 {
                     continue;
                 }
-                let n = 1u32.checked_shl($slice.len() as u32 -1-i as u32);
+                let n = (1 as $t).checked_shl($slice.len() as u32 -1-i as u32);
                 if n.is_none() { continue; }
-                num += 1 << ($slice.len() - 1 - i);
+                num += n.unwrap();
             }
             num
         }
@@ -46,42 +45,13 @@ macro_rules!  binary_slice_to_number {
 }
 
 
-#[macro_export]
-macro_rules! ones_complement {
-    ($t:ty,$num:expr) => {
-        {
-            let (n,sign) = {
-                if $num < 0 {
-                    ($num*-1,1)
-                }else {
-                    ($num,0)
-                }
-            };
-            let mut slice = to_binary_slice!($t,n);
-            slice[0] = sign;
-            for i in  0..slice.len() {
-                if i == 0 {
-                    continue;
-
-                }
-                slice[i] = {
-                    if slice[i] == 0 {
-                        1
-                    }else {
-                        0
-                    }
-                }
-            }
-            binary_slice_to_number!($t,slice)
-        }
-    }
-}
-
 #[macro_export]
 macro_rules!  twos_complement {
     ($t:ty, $num:expr) => {
         {
-            ones_complement!($t,$num)+1
+            // Registers, the stack and memory all store integers in two's complement, so the
+            // bits of the signed number are simply reinterpreted as the unsigned type.
+            ($num) as $t
         }
     }
 }
@@ -92,23 +62,7 @@ macro_rules!  twos_complement {
 macro_rules! integer_from_twos_complement {
     ($itype:ty,$utype:ty,$twos_comp:expr) => {
         {
-            let twos_comp_slice = to_binary_slice!($utype,$twos_comp);
-            let sign = if twos_comp_slice[0] == 1 {
-                -1
-            }else {
-                1
-            };
-            let new_slice = &twos_comp_slice[1..];
-            let num = binary_slice_to_number!($utype,new_slice);
-            let ones_comp =  {
-                if num != 0 {
-                    num-1
-                }else {
-                   ones_complement!($utype,0 as $itype)
-                }
-            };
-            sign as $itype  * ones_complement!($utype,ones_comp as $itype) as $itype
-            
+            (($twos_comp) as $utype) as $itype
         }
     }
 }  
@@ -129,30 +83,30 @@ macro_rules! to_float_repr {
 macro_rules! binary_to_float {
     ($ftype:ty,$utype:ty,$num:expr) => {
         {
-            let binary_slice = to_binary_slice!($utype,$num);
-            let sign = binary_slice[0];
-
-            let exponent = &binary_slice[1..9];
-            let mantissa = &binary_slice[9..];
+            <$ftype>::from_bits($num as $utype)
+        }
+    }
+}
 
-            let sign = if sign == 0 { 1.0 } else { -1.0 };
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn twos_complement_round_trips() {
+        for n in [0i64,1,-1,5,-5,i32::MIN as i64,i64::MIN,i64::MAX] {
+            let bits = twos_complement!(u64,n);
+            assert_eq!(bits,n as u64);
+            assert_eq!(integer_from_twos_complement!(i64,u64,bits),n);
+        }
+    }
 
-            let twos_power = {
-                let num = binary_slice_to_number!($utype,exponent) as i32;
-            
-                2_f32.powi(num-127)
-            };
-            let mantissa_number = {
-                let mut  fract_sum = 1.0; 
-                
-                    for i in 0..mantissa.len() {
-                        if mantissa[i] == 0 { continue; }
-                        fract_sum += 1.0/(2_f32.powf(i as f32+1.));   
-                    }
-                fract_sum
-            };
-            sign * twos_power * mantissa_number 
+    #[test]
+    fn float_bits_round_trip() {
+        // Zeros, a subnormal and infinity keep their exact bits.
+        for f in [0.0f32,-0.0,1.5,-0.1,1e-40,f32::INFINITY,f32::MIN_POSITIVE] {
+            let bits = to_float_repr!(f32,u32,f);
+            assert_eq!(binary_to_float!(f32,u32,bits).to_bits(),f.to_bits());
         }
+        assert!(binary_to_float!(f32,u32,f32::NAN.to_bits()).is_nan());
+        assert_eq!(binary_to_float!(f64,u64,(-2.5f64).to_bits()),-2.5);
     }
 }
-
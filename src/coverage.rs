@@ -0,0 +1,228 @@
+use crate::{
+    instruction::Instruction,
+    parser::SourceLocation,
+    vm::Tracer,
+};
+
+use std::collections::BTreeMap;
+
+/// Records which instructions and conditional jumps run while `VM::eval_traced` runs a program
+/// and maps them back to the basm lines they were generated from.
+pub struct Coverage {
+    source_map: Vec<Option<SourceLocation>>,
+    source_lines: Vec<SourceLocation>,
+    instruction_counts: Vec<u64>,
+    /// Conditional jumps, loops and jump tables by position, with how often each way out was taken.
+    branches: BTreeMap<usize,BranchCounts>,
+}
+
+#[derive(Default,Clone)]
+struct BranchCounts {
+    /// Where each way out continues. A conditional jump or loop has its destination first and
+    /// the next instruction second, a jump table has one entry per case and the default.
+    targets: Vec<usize>,
+    counts: Vec<u64>,
+}
+
+impl BranchCounts {
+    fn new(targets:Vec<usize>) -> Self {
+        let counts = vec![0;targets.len()];
+        Self { targets, counts }
+    }
+
+    fn ran(&self) -> bool {
+        self.counts.iter().any(|c| *c > 0)
+    }
+
+    fn hit(&self) -> usize {
+        self.counts.iter().filter(|c| **c > 0).count()
+    }
+}
+
+#[derive(Default)]
+struct FileCoverage {
+    /// Times each line ran. Lines made of several instructions count their busiest one.
+    lines: BTreeMap<usize,u64>,
+    /// (line, position of the jump, counts)
+    branches: Vec<(usize,usize,BranchCounts)>,
+}
+
+impl FileCoverage {
+    fn branches_found(&self) -> usize {
+        self.branches.iter().map(|(_,_,counts)| counts.targets.len()).sum()
+    }
+
+    fn branches_hit(&self) -> usize {
+        self.branches.iter().map(|(_,_,counts)| counts.hit()).sum()
+    }
+}
+
+impl Coverage {
+    /// `source_map` and `source_lines` come from the `Generator` that generated `instructions`.
+    pub fn new(source_map:Vec<Option<SourceLocation>>,source_lines:Vec<SourceLocation>,instructions:&[Instruction]) -> Self {
+        use Instruction::*;
+        use crate::instruction::StringNumberUnion::Num;
+        let mut branches = BTreeMap::new();
+        for (pc,inst) in instructions.iter().enumerate() {
+            match inst {
+                JumpIfZero(Num(n)) | JumpIfNotZero(Num(n)) |
+                    JumpIfEqual(Num(n)) | JumpIfNotEqual(Num(n)) |
+                    JumpIfGreater(Num(n)) | JumpIfLess(Num(n)) |
+                    JumpIfGreaterOrEqual(Num(n)) | JumpIfLessOrEqual(Num(n)) |
+                    JumpIfAbove(Num(n)) | JumpIfBelow(Num(n)) |
+                    JumpIfAboveOrEqual(Num(n)) | JumpIfBelowOrEqual(Num(n)) |
+                    JumpIfOverflow(Num(n)) | JumpIfNotOverflow(Num(n)) |
+                    JumpIfCarry(Num(n)) | JumpIfNotCarry(Num(n)) |
+                    Loop(_,Num(n)) => {
+                        // `assert` jumps over its `AssertFailed`, which is not a branch of the program.
+                        if crate::instruction::assert_message(instructions,(*n as usize).wrapping_sub(1)).is_some() {
+                            continue;
+                        }
+                        branches.insert(pc,BranchCounts::new(vec![*n as usize,pc+1]));
+                    }
+                JumpTable(_,cases) => {
+                    branches.insert(pc,BranchCounts::new((pc+1..=pc+1+*cases as usize).collect()));
+                }
+                _ => {}
+            }
+        }
+        Self {
+            source_map,
+            source_lines,
+            instruction_counts: Vec::new(),
+            branches,
+        }
+    }
+
+    fn record_instruction(&mut self,pc:usize) {
+        if pc >= self.instruction_counts.len() {
+            self.instruction_counts.resize(pc+1,0);
+        }
+        self.instruction_counts[pc] += 1;
+    }
+
+    fn files(&self) -> BTreeMap<&str,FileCoverage> {
+        let mut files: BTreeMap<&str,FileCoverage> = BTreeMap::new();
+        for location in self.source_lines.iter() {
+            files.entry(location.file.as_str()).or_default().lines.entry(location.line).or_insert(0);
+        }
+        for (pc,location) in self.source_map.iter().enumerate() {
+            let location = match location {
+                Some(l) => l,
+                None => continue,
+            };
+            let file = files.entry(location.file.as_str()).or_default();
+            let count = self.instruction_counts.get(pc).copied().unwrap_or(0);
+            let line = file.lines.entry(location.line).or_insert(0);
+            *line = (*line).max(count);
+            if let Some(branch) = self.branches.get(&pc) {
+                file.branches.push((location.line,pc,branch.clone()));
+            }
+        }
+        files
+    }
+
+    /// Coverage in the lcov tracefile format, one record per basm file.
+    pub fn lcov(&self) -> String {
+        let mut out = String::new();
+        for (file_name,file) in self.files().iter() {
+            out.push_str(&format!("TN:\nSF:{}\n",file_name));
+            for (line,pc,counts) in file.branches.iter() {
+                for (branch,count) in counts.counts.iter().enumerate() {
+                    if counts.ran() {
+                        out.push_str(&format!("BRDA:{},{},{},{}\n",line,pc,branch,count));
+                    }else {
+                        out.push_str(&format!("BRDA:{},{},{},-\n",line,pc,branch));
+                    }
+                }
+            }
+            out.push_str(&format!("BRF:{}\nBRH:{}\n",file.branches_found(),file.branches_hit()));
+            for (line,count) in file.lines.iter() {
+                out.push_str(&format!("DA:{},{}\n",line,count));
+            }
+            let lines_hit = file.lines.values().filter(|c| **c > 0).count();
+            out.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n",file.lines.len(),lines_hit));
+        }
+        out
+    }
+
+    /// Table with the line and branch coverage of each file.
+    pub fn summary(&self) -> String {
+        let files = self.files();
+        let width = files.keys().map(|f| f.len()).max().unwrap_or(0).max(4);
+        let mut out = format!("{:<width$}  {:>22}  {:>22}\n","File","Lines","Branches",width=width);
+        for (file_name,file) in files.iter() {
+            let lines_hit = file.lines.values().filter(|c| **c > 0).count();
+            out.push_str(&format!("{:<width$}  {:>22}  {:>22}\n",
+                    file_name,
+                    coverage_ratio(lines_hit,file.lines.len()),
+                    coverage_ratio(file.branches_hit(),file.branches_found()),
+                    width=width));
+        }
+        out
+    }
+}
+
+fn coverage_ratio(hit:usize,found:usize) -> String {
+    if found == 0 {
+        return "-".to_string();
+    }
+    format!("{}/{} ({:.2}%)",hit,found,hit as f64 * 100.0 / found as f64)
+}
+
+impl Tracer for Coverage {
+    fn executed(&mut self,pc:usize,next_pc:usize,_depth:usize) {
+        self.record_instruction(pc);
+        if let Some(branch) = self.branches.get_mut(&pc) {
+            if let Some(way) = branch.targets.iter().position(|t| *t == next_pc) {
+                branch.counts[way] += 1;
+            }
+        }
+    }
+
+    fn halted(&mut self,pc:usize) {
+        self.record_instruction(pc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants_and_types::iInstructionParamType,instruction::StringNumberUnion::Num};
+
+    /// Coverage of `instructions` with instruction `i` on line `i + 1` of `t.basm`.
+    fn coverage(instructions:&[Instruction]) -> Coverage {
+        let lines = (0..instructions.len())
+            .map(|i| SourceLocation { file: "t.basm".to_string(), line: i+1 })
+            .collect::<Vec<SourceLocation>>();
+        Coverage::new(lines.iter().cloned().map(Some).collect(),lines,instructions)
+    }
+
+    fn branch_lines(lcov:&str) -> Vec<&str> {
+        lcov.lines().filter(|l| l.starts_with("BR")).collect()
+    }
+
+    #[test]
+    fn loop_has_a_taken_and_a_not_taken_branch() {
+        let mut coverage = coverage(&[Instruction::Mov(0,2),Instruction::Loop(0,Num(0)),Instruction::Halt]);
+        coverage.executed(1,0,0);
+        coverage.executed(1,2,0);
+        coverage.executed(1,2,0);
+        assert_eq!(branch_lines(&coverage.lcov()),["BRDA:2,1,0,1","BRDA:2,1,1,2","BRF:2","BRH:2"]);
+    }
+
+    #[test]
+    fn jump_table_has_a_branch_per_case_and_the_default() {
+        let mut coverage = coverage(&[Instruction::JumpTable(0,2),Instruction::Jump(Num(4)),Instruction::Jump(Num(4)),Instruction::Jump(Num(4)),Instruction::Halt]);
+        coverage.executed(0,1,0);
+        coverage.executed(0,3,0);
+        assert_eq!(branch_lines(&coverage.lcov()),["BRDA:1,0,0,1","BRDA:1,0,1,0","BRDA:1,0,2,1","BRF:3","BRH:2"]);
+    }
+
+    #[test]
+    fn assert_is_not_a_branch() {
+        let coverage = coverage(&[Instruction::JumpIfEqual(Num(4)),Instruction::Push('n' as iInstructionParamType),
+            Instruction::Push('o' as iInstructionParamType),Instruction::AssertFailed(2),Instruction::Halt]);
+        assert_eq!(branch_lines(&coverage.lcov()),["BRF:0","BRH:0"]);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{instruction::Instruction,vm::{Tracer,VM}};
+
+use std::io::{BufRead,Write};
+
+/// Pauses a program that `VM::eval_traced` runs at every `brk` and reads what to do next from
+/// the terminal. The program's own output stays on stdout, the debugger talks on stderr.
+pub struct Debugger {
+    /// Pause before every instruction instead of only at breakpoints.
+    stepping: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self { stepping: false }
+    }
+
+    /// Shows where the program is and waits for a command.
+    fn pause(&mut self, vm:&VM, pc:usize, reason:&str) {
+        let next = vm.generated_instructions().get(pc).cloned().unwrap_or(Instruction::Halt);
+        eprintln!("{} {}\n  next: {:?}\n{}",reason,vm.location(pc),next,vm.dump_state());
+        let stdin = std::io::stdin();
+        loop {
+            eprint!("(basm) ");
+            let _ = std::io::stderr().flush();
+            let mut line = String::new();
+            // Nothing more to read, so there is no one to stop for either.
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                self.stepping = false;
+                return;
+            }
+            match line.trim() {
+                "c" | "continue" => {
+                    self.stepping = false;
+                    return;
+                }
+                "s" | "step" => {
+                    self.stepping = true;
+                    return;
+                }
+                "p" | "print" => eprintln!("{}",vm.dump_state()),
+                "q" | "quit" => std::process::exit(0),
+                _ => eprintln!("Commands: c(ontinue) to the next brk, s(tep) one instruction, p(rint) the state, q(uit)"),
+            }
+        }
+    }
+}
+
+impl Tracer for Debugger {
+    fn before(&mut self, vm:&VM, pc:usize) {
+        if self.stepping {
+            self.pause(vm,pc,"Step");
+        }
+    }
+
+    fn breakpoint(&mut self, vm:&VM, pc:usize) -> bool {
+        // Stepping onto a `brk` already paused before it.
+        if !self.stepping {
+            self.pause(vm,pc,"Breakpoint");
+        }
+        true
+    }
+
+    fn executed(&mut self, _pc:usize, _next_pc:usize, _depth:usize) {}
+
+    fn halted(&mut self, _pc:usize) {
+        if self.stepping {
+            eprintln!("Program halted.");
+        }
+    }
+}
@@ -0,0 +1,588 @@
+//! Float functions for `FloatMath`.
+//!
+//! The platform's libm gives slightly different results on different systems, so the functions
+//! that are not exactly rounded by IEEE 754 are implemented here with only `+`, `-`, `*`, `/` and
+//! bit operations, following fdlibm. The C and x86-64 backends carry a copy of the same code, so
+//! programs print the same digits everywhere. All functions work on f64, single precision programs
+//! round the result once. Constants that have no exact short decimal form are written as their bits.
+
+use crate::constants_and_types::*;
+
+/// Applies the `MATH_` function `function` to `a` and `b`. One operand functions use `b`, two
+/// operand ones compute `f(a,b)`.
+pub fn apply(function:InstructionParamType, a:f64, b:f64) -> f64 {
+    match function {
+        MATH_SQRT => b.sqrt(),
+        MATH_ABS => b.abs(),
+        MATH_FLOOR => b.floor(),
+        MATH_CEIL => b.ceil(),
+        MATH_ROUND => b.round(),
+        MATH_EXP => exp(b),
+        MATH_LN => ln(b),
+        MATH_SIN => sin(b),
+        MATH_COS => cos(b),
+        MATH_TAN => tan(b),
+        MATH_POW => pow(a,b),
+        MATH_ATAN2 => atan2(a,b),
+        MATH_MIN => min(a,b),
+        MATH_MAX => max(a,b),
+        function => panic!("Math function {} does not exist.",function),
+    }
+}
+
+/// Whether `function` takes two operands.
+pub fn is_binary(function:InstructionParamType) -> bool {
+    matches!(function,MATH_POW | MATH_ATAN2 | MATH_MIN | MATH_MAX)
+}
+
+/// NaN is ignored if the other operand is a number and -0.0 is less than 0.0.
+pub fn min(a:f64, b:f64) -> f64 {
+    if a.is_nan() { return b; }
+    if b.is_nan() { return a; }
+    if a < b || (a == b && a.is_sign_negative()) { a } else { b }
+}
+
+pub fn max(a:f64, b:f64) -> f64 {
+    if a.is_nan() { return b; }
+    if b.is_nan() { return a; }
+    if a > b || (a == b && a.is_sign_positive()) { a } else { b }
+}
+
+/// `x * 2^n` without rounding twice.
+fn scale(x:f64, mut n:i32) -> f64 {
+    let mut y = x;
+    if n > 1023 {
+        y *= f64::from_bits(0x7fe0000000000000);
+        n -= 1023;
+        if n > 1023 {
+            y *= f64::from_bits(0x7fe0000000000000);
+            n -= 1023;
+            if n > 1023 { n = 1023; }
+        }
+    }else if n < -1022 {
+        // 2^-1022 * 2^53, so the result is rounded only once when it ends up subnormal.
+        y *= f64::from_bits(0x0360000000000000);
+        n += 1022 - 53;
+        if n < -1022 {
+            y *= f64::from_bits(0x0360000000000000);
+            n += 1022 - 53;
+            if n < -1022 { n = -1022; }
+        }
+    }
+    y * f64::from_bits(((0x3ff + n) as u64) << 52)
+}
+
+const LN2_HI:f64 = f64::from_bits(0x3fe62e42fee00000);
+const LN2_LO:f64 = f64::from_bits(0x3dea39ef35793c76);
+
+pub fn exp(x:f64) -> f64 {
+    const INV_LN2:f64 = std::f64::consts::LOG2_E;
+    const P1:f64 = f64::from_bits(0x3fc555555555553e);
+    const P2:f64 = f64::from_bits(0xbf66c16c16bebd93);
+    const P3:f64 = f64::from_bits(0x3f11566aaf25de2c);
+    const P4:f64 = f64::from_bits(0xbebbbd41c5d26bf1);
+    const P5:f64 = f64::from_bits(0x3e66376972bea4d0);
+
+    if x.is_nan() { return x; }
+    // ln(f64::MAX) and ln of half the smallest subnormal.
+    if x > f64::from_bits(0x40862e42fefa39ef) { return f64::INFINITY; }
+    if x < f64::from_bits(0xc0874910d52d3051) { return 0.0; }
+
+    // x = k*ln2 + r with |r| <= ln2/2.
+    let abs = x.abs();
+    let (k,hi,lo) = if abs > 0.5 * std::f64::consts::LN_2 {
+        let k = if abs < 1.5 * std::f64::consts::LN_2 {
+            if x < 0.0 { -1 } else { 1 }
+        }else {
+            (INV_LN2 * x + 0.5f64.copysign(x)) as i32
+        };
+        (k,x - k as f64 * LN2_HI,k as f64 * LN2_LO)
+    }else if abs > f64::from_bits(0x3e30000000000000) {
+        (0,x,0.0)
+    }else {
+        return 1.0 + x;
+    };
+    let r = hi - lo;
+    let rr = r * r;
+    let c = r - rr * (P1 + rr * (P2 + rr * (P3 + rr * (P4 + rr * P5))));
+    let y = 1.0 + (r * c / (2.0 - c) - lo + hi);
+    if k == 0 { y } else { scale(y,k) }
+}
+
+pub fn ln(x:f64) -> f64 {
+    const LG1:f64 = f64::from_bits(0x3fe5555555555593);
+    const LG2:f64 = f64::from_bits(0x3fd999999997fa04);
+    const LG3:f64 = f64::from_bits(0x3fd2492494229359);
+    const LG4:f64 = f64::from_bits(0x3fcc71c51d8e78af);
+    const LG5:f64 = f64::from_bits(0x3fc7466496cb03de);
+    const LG6:f64 = f64::from_bits(0x3fc39a09d078c69f);
+    const LG7:f64 = f64::from_bits(0x3fc2f112df3e5244);
+
+    let mut x = x;
+    let mut k:i32 = 0;
+    let mut hx = (x.to_bits() >> 32) as u32;
+    if hx < 0x00100000 || hx >> 31 != 0 {
+        if x == 0.0 { return f64::NEG_INFINITY; }
+        if x < 0.0 || x.is_nan() { return f64::NAN; }
+        // Subnormal, scale it up.
+        k -= 54;
+        x *= f64::from_bits(0x4350000000000000);
+        hx = (x.to_bits() >> 32) as u32;
+    }else if hx >= 0x7ff00000 {
+        return x;
+    }else if x == 1.0 {
+        return 0.0;
+    }
+
+    // x = 2^k * m with sqrt(2)/2 < m < sqrt(2).
+    hx = hx.wrapping_add(0x3ff00000 - 0x3fe6a09e);
+    k += (hx >> 20) as i32 - 0x3ff;
+    hx = (hx & 0x000fffff) + 0x3fe6a09e;
+    x = f64::from_bits((hx as u64) << 32 | (x.to_bits() & 0xffffffff));
+
+    let f = x - 1.0;
+    let hfsq = 0.5 * f * f;
+    let s = f / (2.0 + f);
+    let z = s * s;
+    let w = z * z;
+    let t1 = w * (LG2 + w * (LG4 + w * LG6));
+    let t2 = z * (LG1 + w * (LG3 + w * (LG5 + w * LG7)));
+    let r = t2 + t1;
+    let dk = k as f64;
+    s * (hfsq + r) + dk * LN2_LO - hfsq + f + dk * LN2_HI
+}
+
+/// sin on [-pi/4, pi/4], `x + y` being the argument with `y` the tail of a reduced one.
+fn sin_kernel(x:f64, y:f64) -> f64 {
+    const S1:f64 = f64::from_bits(0xbfc5555555555549);
+    const S2:f64 = f64::from_bits(0x3f8111111110f8a6);
+    const S3:f64 = f64::from_bits(0xbf2a01a019c161d5);
+    const S4:f64 = f64::from_bits(0x3ec71de357b1fe7d);
+    const S5:f64 = f64::from_bits(0xbe5ae5e68a2b9ceb);
+    const S6:f64 = f64::from_bits(0x3de5d93a5acfd57c);
+
+    let z = x * x;
+    let w = z * z;
+    let r = S2 + z * (S3 + z * S4) + z * w * (S5 + z * S6);
+    let v = z * x;
+    x - ((z * (0.5 * y - v * r) - y) - v * S1)
+}
+
+fn cos_kernel(x:f64, y:f64) -> f64 {
+    const C1:f64 = f64::from_bits(0x3fa555555555554c);
+    const C2:f64 = f64::from_bits(0xbf56c16c16c15177);
+    const C3:f64 = f64::from_bits(0x3efa01a019cb1590);
+    const C4:f64 = f64::from_bits(0xbe927e4f809c52ad);
+    const C5:f64 = f64::from_bits(0x3e21ee9ebdb4b1c4);
+    const C6:f64 = f64::from_bits(0xbda8fae9be8838d4);
+
+    let z = x * x;
+    let w = z * z;
+    let r = z * (C1 + z * (C2 + z * C3)) + w * w * (C4 + z * (C5 + z * C6));
+    let hz = 0.5 * z;
+    let w = 1.0 - hz;
+    w + (((1.0 - w) - hz) + (z * r - x * y))
+}
+
+/// `a * b` as a sum of two floats without rounding, by splitting both into halves.
+fn exact_mul(a:f64, b:f64) -> (f64,f64) {
+    const SPLIT:f64 = 134217729.0;
+    let split = |v:f64| {
+        let c = SPLIT * v;
+        let high = c - (c - v);
+        (high,v - high)
+    };
+    let (ah,al) = split(a);
+    let (bh,bl) = split(b);
+    let p = a * b;
+    (p,((ah * bh - p) + ah * bl + al * bh) + al * bl)
+}
+
+/// `a + b` as a sum of two floats without rounding.
+fn exact_add(a:f64, b:f64) -> (f64,f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s,(a - (s - bb)) + (b - bb))
+}
+
+const PIO2_HI:f64 = std::f64::consts::FRAC_PI_2;
+const PIO2_LO:f64 = f64::from_bits(0x3c91a62633145c07);
+
+/// The bits of 2/pi after the point, enough for the largest f64.
+const TWO_OVER_PI:[u32;40] = [
+    0xa2f9836e,0x4e441529,0xfc2757d1,0xf534ddc0,0xdb629599,0x3c439041,0xfe5163ab,0xdebbc561,
+    0xb7246e3a,0x424dd2e0,0x06492eea,0x09d1921c,0xfe1deb1c,0xb129a73e,0xe88235f5,0x2ebb4484,
+    0xe99c7026,0xb45f7e41,0x3991d639,0x835339f4,0x9c845f8b,0xbdf9283b,0x1ff897ff,0xde05980f,
+    0xef2f118b,0x5a0a6d1f,0x6d367ecf,0x27cb09b7,0x4f463f66,0x9e5fea2d,0x7527bac7,0xebe5f17b,
+    0x3d0739f7,0x8a5292ea,0x6bfb5fb1,0x1f8d5d08,0x56033046,0xfc7b6bab,0xf0cfbc20,0x9af4361d,
+];
+
+/// `reduce` for arguments where n*pi/2 needs more bits of pi than a few floats hold.
+/// x*2/pi mod 4 is computed exactly with 32 bit pieces of the mantissa and of TWO_OVER_PI,
+/// bits of 2/pi that only add multiples of 4 are skipped.
+fn reduce_large(x:f64) -> (i32,f64,f64) {
+    let bits = x.abs().to_bits();
+    let mantissa = (bits & 0x000fffffffffffff) | 0x0010000000000000;
+    let e = ((bits >> 52) & 0x7ff) as i32 - 1075;
+
+    // 192 bits of 2/pi starting with the one that makes the product 2^1, two zero words in front.
+    let table = |i:i32| if i < 2 { 0 } else { TWO_OVER_PI[(i - 2) as usize] as u64 };
+    let first = e - 2 + 64;
+    let mut window = [0u64;6];
+    for (i,word) in window.iter_mut().enumerate() {
+        let bit = first + 32 * (5 - i as i32);
+        let (index,shift) = (bit / 32,bit % 32);
+        *word = ((table(index) << 32 | table(index + 1)) >> (32 - shift)) & 0xffffffff;
+    }
+
+    // product mod 2^192, the top two bits are x*2/pi mod 4 and the rest its fraction.
+    let mut product = [0u64;6];
+    for (j,m) in [mantissa & 0xffffffff,mantissa >> 32].iter().enumerate() {
+        let mut carry = 0;
+        for i in 0..6 - j {
+            let t = window[i] * m + product[i + j] + carry;
+            product[i + j] = t & 0xffffffff;
+            carry = t >> 32;
+        }
+    }
+    let n = (product[5] >> 30) + ((product[5] >> 29) & 1);
+    product[5] = product[5].wrapping_sub(n << 30) & 0xffffffff;
+    let negative = product[5] >> 31 == 1;
+    if negative {
+        let mut carry = 1;
+        for word in product.iter_mut() {
+            let t = (!*word & 0xffffffff) + carry;
+            *word = t & 0xffffffff;
+            carry = t >> 32;
+        }
+    }
+
+    // The fraction to two floats, from its highest set bit on.
+    let top = match product.iter().rposition(|&word| word != 0) {
+        Some(top) => top as i32,
+        None => return ((n & 3) as i32,0.0,0.0),
+    };
+    let word = |i:i32| if i < 0 { 0 } else { product[i as usize] };
+    let zeros = word(top).leading_zeros() as i32 - 32;
+    let high = ((word(top) << 32 | word(top - 1)) << zeros) | (word(top - 2) << zeros >> 32);
+    let low = ((word(top - 2) << zeros) | (word(top - 3) << zeros >> 32)) & 0xffffffff;
+    let exponent = 32 * (top - 2) - zeros - 190;
+    let t_hi = (high >> 11) as f64 * f64::from_bits(((0x3ff + 43 + exponent) as u64) << 52);
+    let t_lo = ((high & 0x7ff) << 32 | low) as f64 * f64::from_bits(((0x3ff + exponent) as u64) << 52);
+
+    let (p,e) = exact_mul(t_hi,PIO2_HI);
+    let e = e + (t_hi * PIO2_LO + t_lo * PIO2_HI);
+    let mut y0 = p + e;
+    let mut y1 = e - (y0 - p);
+    let mut n = n as i32;
+    if negative != (x < 0.0) {
+        y0 = -y0;
+        y1 = -y1;
+    }
+    if x < 0.0 {
+        n = -n;
+    }
+    (n & 3,y0,y1)
+}
+
+/// Returns `n` and `y0 + y1` with x = n*pi/2 + y0 + y1 and |y0 + y1| <= pi/4.
+fn reduce(x:f64) -> (i32,f64,f64) {
+    const TO_INT:f64 = 1.5 / f64::EPSILON;
+    const INV_PIO2:f64 = std::f64::consts::FRAC_2_PI;
+    const PIO2_1:f64 = f64::from_bits(0x3ff921fb54400000);
+    const PIO2_1T:f64 = f64::from_bits(0x3dd0b4611a626331);
+    const PIO2_2:f64 = f64::from_bits(0x3dd0b4611a600000);
+    const PIO2_2T:f64 = f64::from_bits(0x3ba3198a2e037073);
+    const PIO2_3:f64 = f64::from_bits(0x3ba3198a2e000000);
+    const PIO2_3T:f64 = f64::from_bits(0x397b839a252049c1);
+
+    if x.abs() <= std::f64::consts::FRAC_PI_4 {
+        return (0,x,0.0);
+    }
+    // Past 2^20*pi/2 the three parts of pi/2 below are not enough.
+    if x.abs() > 1647099.0 {
+        return reduce_large(x);
+    }
+    let exponent = |v:f64| ((v.to_bits() >> 52) & 0x7ff) as i32;
+    let n = x * INV_PIO2 + TO_INT - TO_INT;
+    let mut r = x - n * PIO2_1;
+    let mut w = n * PIO2_1T;
+    let mut y0 = r - w;
+    // Take more bits of pi/2 when the first subtraction cancelled a lot.
+    if exponent(x) - exponent(y0) > 16 {
+        let t = r;
+        w = n * PIO2_2;
+        r = t - w;
+        w = n * PIO2_2T - ((t - r) - w);
+        y0 = r - w;
+        if exponent(x) - exponent(y0) > 49 {
+            let t = r;
+            w = n * PIO2_3;
+            r = t - w;
+            w = n * PIO2_3T - ((t - r) - w);
+            y0 = r - w;
+        }
+    }
+    let y1 = (r - y0) - w;
+    (n as i32 & 3,y0,y1)
+}
+
+pub fn sin(x:f64) -> f64 {
+    if !x.is_finite() { return f64::NAN; }
+    if x.abs() < f64::from_bits(0x3e50000000000000) { return x; }
+    let (n,y0,y1) = reduce(x);
+    match n {
+        0 => sin_kernel(y0,y1),
+        1 => cos_kernel(y0,y1),
+        2 => -sin_kernel(y0,y1),
+        _ => -cos_kernel(y0,y1),
+    }
+}
+
+pub fn cos(x:f64) -> f64 {
+    if !x.is_finite() { return f64::NAN; }
+    if x.abs() < f64::from_bits(0x3e46a09e667f3bcd) { return 1.0; }
+    let (n,y0,y1) = reduce(x);
+    match n {
+        0 => cos_kernel(y0,y1),
+        1 => -sin_kernel(y0,y1),
+        2 => -cos_kernel(y0,y1),
+        _ => sin_kernel(y0,y1),
+    }
+}
+
+pub fn tan(x:f64) -> f64 {
+    if !x.is_finite() { return f64::NAN; }
+    if x.abs() < f64::from_bits(0x3e40000000000000) { return x; }
+    let (n,y0,y1) = reduce(x);
+    let (s,c) = (sin_kernel(y0,y1),cos_kernel(y0,y1));
+    if n & 1 == 0 { s / c } else { -c / s }
+}
+
+fn atan(x:f64) -> f64 {
+    const ATAN_HI:[f64;4] = [f64::from_bits(0x3fddac670561bb4f),std::f64::consts::FRAC_PI_4,f64::from_bits(0x3fef730bd281f69b),std::f64::consts::FRAC_PI_2];
+    const ATAN_LO:[f64;4] = [f64::from_bits(0x3c7a2b7f222f65e2),f64::from_bits(0x3c81a62633145c07),f64::from_bits(0x3c7007887af0cbbd),f64::from_bits(0x3c91a62633145c07)];
+    const AT:[f64;11] = [
+        f64::from_bits(0x3fd555555555550d),f64::from_bits(0xbfc999999998ebc4),f64::from_bits(0x3fc24924920083ff),
+        f64::from_bits(0xbfbc71c6fe231671),f64::from_bits(0x3fb745cdc54c206e),f64::from_bits(0xbfb3b0f2af749a6d),
+        f64::from_bits(0x3fb10d66a0d03d51),f64::from_bits(0xbfadde2d52defd9a),f64::from_bits(0x3fa97b4b24760deb),
+        f64::from_bits(0xbfa2b4442c6a6c2f),f64::from_bits(0x3f90ad3ae322da11),
+    ];
+
+    if x.is_nan() { return x; }
+    let negative = x < 0.0;
+    let mut ax = x.abs();
+    if ax >= f64::from_bits(0x4410000000000000) {
+        let z = ATAN_HI[3] + ATAN_LO[3];
+        return if negative { -z } else { z };
+    }
+    if ax < f64::from_bits(0x3e40000000000000) {
+        return x;
+    }
+    // Moves the argument near one of the points in ATAN_HI.
+    let id:i32 = if ax < 0.4375 {
+        -1
+    }else if ax < 0.6875 {
+        ax = (2.0 * ax - 1.0) / (2.0 + ax);
+        0
+    }else if ax < 1.1875 {
+        ax = (ax - 1.0) / (ax + 1.0);
+        1
+    }else if ax < 2.4375 {
+        ax = (ax - 1.5) / (1.0 + 1.5 * ax);
+        2
+    }else {
+        ax = -1.0 / ax;
+        3
+    };
+    let x = if id < 0 && negative { -ax } else { ax };
+    let z = x * x;
+    let w = z * z;
+    let s1 = z * (AT[0] + w * (AT[2] + w * (AT[4] + w * (AT[6] + w * (AT[8] + w * AT[10])))));
+    let s2 = w * (AT[1] + w * (AT[3] + w * (AT[5] + w * (AT[7] + w * AT[9]))));
+    if id < 0 {
+        return x - x * (s1 + s2);
+    }
+    let z = ATAN_HI[id as usize] - ((x * (s1 + s2) - ATAN_LO[id as usize]) - x);
+    if negative { -z } else { z }
+}
+
+/// Angle of the point (`x`, `y`), the arguments are in the same order as in C.
+pub fn atan2(y:f64, x:f64) -> f64 {
+    use std::f64::consts::{PI,FRAC_PI_2,FRAC_PI_4};
+    const PI_LO:f64 = f64::from_bits(0x3ca1a62633145c07);
+
+    if x.is_nan() || y.is_nan() { return x + y; }
+    if x == 1.0 { return atan(y); }
+    let m = (y.is_sign_negative() as u8) | ((x.is_sign_negative() as u8) << 1);
+    if y == 0.0 {
+        return match m {
+            0 | 1 => y,
+            2 => PI,
+            _ => -PI,
+        };
+    }
+    if x == 0.0 {
+        return if m & 1 == 1 { -FRAC_PI_2 } else { FRAC_PI_2 };
+    }
+    if x.is_infinite() {
+        if y.is_infinite() {
+            return match m {
+                0 => FRAC_PI_4,
+                1 => -FRAC_PI_4,
+                2 => 3.0 * FRAC_PI_4,
+                _ => -3.0 * FRAC_PI_4,
+            };
+        }
+        return match m {
+            0 => 0.0,
+            1 => -0.0,
+            2 => PI,
+            _ => -PI,
+        };
+    }
+    let ratio = (y / x).abs();
+    if y.is_infinite() || ratio > f64::from_bits(0x43f0000000000000) {
+        return if m & 1 == 1 { -FRAC_PI_2 } else { FRAC_PI_2 };
+    }
+    let z = if m & 2 != 0 && ratio < f64::from_bits(0x3bf0000000000000) { 0.0 } else { atan(ratio) };
+    match m {
+        0 => z,
+        1 => -z,
+        2 => PI - (z - PI_LO),
+        _ => (z - PI_LO) - PI,
+    }
+}
+
+const TWO_THIRDS_HI:f64 = f64::from_bits(0x3fe5555555555555);
+const TWO_THIRDS_LO:f64 = f64::from_bits(0x3c85555555555555);
+
+/// ln(x) for a positive finite `x` as the sum of two floats, good for about 70 bits, which `pow`
+/// needs as it multiplies the error by y.
+fn ln_extended(x:f64) -> (f64,f64) {
+    let mut x = x;
+    let mut k:i32 = 0;
+    if x < f64::MIN_POSITIVE {
+        k -= 54;
+        x *= f64::from_bits(0x4350000000000000);
+    }
+    let mut hx = (x.to_bits() >> 32) as u32;
+    hx = hx.wrapping_add(0x3ff00000 - 0x3fe6a09e);
+    k += (hx >> 20) as i32 - 0x3ff;
+    hx = (hx & 0x000fffff) + 0x3fe6a09e;
+    let m = f64::from_bits((hx as u64) << 32 | (x.to_bits() & 0xffffffff));
+
+    // ln(m) = 2*atanh(s) = 2s + 2s^3/3 + 2s^5/5 + ... with s = (m-1)/(m+1), |s| < 0.172.
+    let u = m - 1.0;
+    let (v_hi,v_lo) = exact_add(1.0,m);
+    let s_hi = u / v_hi;
+    let (p,e) = exact_mul(s_hi,v_hi);
+    let s_lo = (((u - p) - e) - s_hi * v_lo) / v_hi;
+    // The s^3 term is still large enough to need the extra bits.
+    let (z,z_lo) = exact_mul(s_hi,s_hi);
+    let cube = extended_mul((s_hi,0.0),(z,z_lo));
+    let mut series = 2.0 / 25.0;
+    for d in [23.0,21.0,19.0,17.0,15.0,13.0,11.0,9.0,7.0,5.0] {
+        series = 2.0 / d + z * series;
+    }
+    let (tail_hi,tail_lo) = extended_mul(cube,(TWO_THIRDS_HI,TWO_THIRDS_LO + z * series));
+
+    let dk = k as f64;
+    let (hi,lo1) = exact_add(2.0 * s_hi,tail_hi);
+    let (hi,lo2) = exact_add(dk * LN2_HI,hi);
+    let lo = lo1 + lo2 + (tail_lo + 2.0 * s_lo / (1.0 - z) + dk * LN2_LO);
+    let sum = hi + lo;
+    (sum,lo - (sum - hi))
+}
+
+/// Product of two numbers that are each the sum of two floats.
+fn extended_mul((a_hi,a_lo):(f64,f64), (b_hi,b_lo):(f64,f64)) -> (f64,f64) {
+    let (p,e) = exact_mul(a_hi,b_hi);
+    let e = e + (a_hi * b_lo + a_lo * b_hi);
+    let sum = p + e;
+    (sum,e - (sum - p))
+}
+
+/// `x` to the power of an integer by squaring, carrying the rounding errors along so exact
+/// results stay exact. NaN if anything on the way overflows or gets so small that the low halves
+/// lose bits.
+fn powi(x:f64, n:u64, negative:bool) -> f64 {
+    let (mut base,mut n,mut result) = ((x,0.0),n,(1.0,0.0));
+    while n > 0 {
+        if n & 1 == 1 { result = extended_mul(result,base); }
+        n >>= 1;
+        if n > 0 { base = extended_mul(base,base); }
+    }
+    if !result.0.is_finite() || result.0 < f64::from_bits(0x06b0000000000000) {
+        return f64::NAN;
+    }
+    if !negative {
+        return result.0;
+    }
+    let q = 1.0 / result.0;
+    let (p,e) = exact_mul(q,result.0);
+    q + (((1.0 - p) - e) - result.1 * q) * q
+}
+
+pub fn pow(x:f64, y:f64) -> f64 {
+    if y == 0.0 || x == 1.0 { return 1.0; }
+    if x.is_nan() || y.is_nan() { return f64::NAN; }
+    let y_integer = y.floor() == y;
+    let y_odd = y_integer && y.abs() < 9007199254740992.0 && (y * 0.5).floor() != y * 0.5;
+    if y.is_infinite() {
+        if x == -1.0 { return 1.0; }
+        return if (x.abs() < 1.0) == (y < 0.0) { f64::INFINITY } else { 0.0 };
+    }
+    if x == 0.0 || x.is_infinite() {
+        let magnitude = if (x == 0.0) == (y < 0.0) { f64::INFINITY } else { 0.0 };
+        return if x.is_sign_negative() && y_odd { -magnitude } else { magnitude };
+    }
+    if x < 0.0 && !y_integer {
+        return f64::NAN;
+    }
+    let ax = x.abs();
+    let mut result = f64::NAN;
+    if y_integer && y.abs() <= 1024.0 {
+        result = powi(ax,y.abs() as u64,y < 0.0);
+    }
+    if result.is_nan() {
+        let (l_hi,l_lo) = ln_extended(ax);
+        let p = y * l_hi;
+        if p > 710.0 { return if x < 0.0 && y_odd { f64::NEG_INFINITY } else { f64::INFINITY }; }
+        if p < -746.0 { return if x < 0.0 && y_odd { -0.0 } else { 0.0 }; }
+        // exp(w_hi + w_lo) = exp(w_hi) * (1 + w_lo) as w_lo is tiny.
+        let (p,e) = exact_mul(y,l_hi);
+        let e = e + y * l_lo;
+        let w_hi = p + e;
+        let w_lo = e - (w_hi - p);
+        result = exp(w_hi);
+        if result.is_finite() {
+            result += result * w_lo;
+        }
+    }
+    if x < 0.0 && y_odd { -result } else { result }
+}
+
+/// Special values, the edges of the ranges the functions reduce or overflow at, and a spread
+/// of values over every exponent, for the tests of the copies of these functions in the backends.
+#[cfg(test)]
+pub fn test_inputs() -> Vec<f64> {
+    let mut inputs = vec![
+        0.0,-0.0,f64::INFINITY,f64::NEG_INFINITY,f64::NAN,1.0,-1.0,0.5,-0.5,2.0,-2.0,3.0,-3.0,2.5,-2.5,
+        10.0,0.1,1e-300,-1e-300,f64::MIN_POSITIVE,f64::from_bits(1),f64::MAX,f64::MIN,1e22,1e300,
+        709.78,709.79,-745.13,-745.14,710.0,-746.0,1023.5,-1074.5,0.999999,1.000001,
+        std::f64::consts::FRAC_PI_4,std::f64::consts::FRAC_PI_2,std::f64::consts::PI,std::f64::consts::TAU,
+        3.0*std::f64::consts::FRAC_PI_4,5.0*std::f64::consts::PI,1e6,-1e6,1e15,9007199254740993.0,
+        std::f64::consts::E,std::f64::consts::LN_2,0.7,-0.7,7.0,-7.0,0.25,
+    ];
+    // A fixed sequence so every run checks the same numbers.
+    let mut state:u64 = 0x243f6a8885a308d3;
+    for _ in 0..150 {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let exponent = (state >> 40) % 120;
+        let mantissa = state & 0x000f_ffff_ffff_ffff;
+        let sign = (state >> 63) << 63;
+        inputs.push(f64::from_bits(sign | ((exponent + 1023 - 60) << 52) | mantissa));
+    }
+    inputs
+}
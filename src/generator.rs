@@ -1,56 +1,83 @@
 use crate::{
-    parser::{NodeExpr,NodeInstruction,NodeBuiltin,Parser},
-    instruction::Instruction,
+    parser::{MemoryWidth,NodeExpr,NodeInstruction,NodeBuiltin,Parser,SourceLocation},
+    instruction::{Instruction,ProgramHeader},
     constants_and_types::*, 
     tokens::*,
     vm::VM,
+    optimizer,
 };
 
 use std::collections::HashMap;
 
+/// Builds an instruction that takes two registers, or a register and a signed immediate.
+type RegisterInstruction = fn(InstructionParamType,InstructionParamType) -> Instruction;
+type ImmediateInstruction = fn(InstructionParamType,iInstructionParamType) -> Instruction;
+
+/// An imported instruction with the labels (and their positions) that point at it.
+type ImportedInstruction = (Vec<(String,usize)>,NodeInstruction,SourceLocation);
+
 pub struct Generator {
+    file_name: String,
     node_instructions: Vec<NodeInstruction>,
+    instruction_lines: Vec<usize>,
     labels: HashMap<String,usize>,
     builtins: Vec<NodeBuiltin>,
     pub vm:VM,
+    /// Source line each generated instruction comes from, indexed like the VM's instructions.
+    /// The jump to main that starts the program has no source line.
+    pub source_map: Vec<Option<SourceLocation>>,
+    /// Every line that contains an instruction, including instructions removed as unreachable.
+    pub source_lines: Vec<SourceLocation>,
 }
 
 impl Generator {
-    pub fn new(builtins:Vec<NodeBuiltin>,labels:HashMap<String,usize>,node_instructions:Vec<NodeInstruction>) -> Self {
+    /// `instruction_lines` are the source lines of `node_instructions` in the file `file_name`.
+    pub fn new(file_name:String,builtins:Vec<NodeBuiltin>,labels:HashMap<String,usize>,node_instructions:Vec<NodeInstruction>,instruction_lines:Vec<usize>) -> Self {
         Self {
+            file_name,
             labels,
             vm:VM::new(),
             builtins,
             node_instructions,
+            instruction_lines,
+            source_map: Vec::new(),
+            source_lines: Vec::new(),
         }
     }
 
     // NOTE: Make this code better lol.
     pub fn generate(&mut self,no_main:bool) {
-        let external_labels: Vec<(Vec<(String,usize)>,NodeInstruction)> = self.generate_imports();
-        let mut new_instructions: Vec<(Vec<String>,NodeInstruction)> = Vec::new();
+        self.vm.set_header(self.header());
+        let external_labels: Vec<ImportedInstruction> = self.generate_imports();
+        let mut new_instructions: Vec<(Vec<String>,NodeInstruction,SourceLocation)> = Vec::new();
         // We first push the external instructions into new_instructions and attach any label
         // identifier they have.
-        for i in 0..external_labels.len() {
-            let cur = external_labels[i].clone();
+        for cur in external_labels {
             if cur.0.is_empty() {
-                new_instructions.push((Vec::new(),cur.1));
+                new_instructions.push((Vec::new(),cur.1,cur.2));
             }else {
                 let v = cur.0.iter().map(|(x,_)| x.as_str().to_string()).collect::<Vec<String>>(); 
-                new_instructions.push((v,cur.1));
+                new_instructions.push((v,cur.1,cur.2));
             }
         }
 
         let l =new_instructions.len();
         
-        for inst in self.node_instructions.iter() {
-            new_instructions.push((Vec::new(),inst.clone()));
+        for (inst,line) in self.node_instructions.iter().zip(self.instruction_lines.iter()) {
+            let location = SourceLocation { file: self.file_name.clone(), line: *line };
+            new_instructions.push((Vec::new(),inst.clone(),location));
         }
         // We search if there are any labels in our main file then attach them onto the
         // new_instructions 
         for (name,start) in self.labels.iter() {
             new_instructions[*start+l-1].0.push(name.clone());
         }
+        check_proc_calls(&new_instructions);
+        self.source_lines = new_instructions.iter().map(|(_,_,location)| location.clone()).collect();
+        // Without a main label there is no entry point to search from, so everything is kept.
+        if !no_main {
+            new_instructions = optimizer::eliminate_dead_code(new_instructions,"main");
+        }
 
         self.generate_instructions(new_instructions);
         if !no_main {
@@ -60,7 +87,87 @@ impl Generator {
     }
 
 
-    pub fn generate_imports(&mut self) -> Vec<(Vec<(String,usize)>,NodeInstruction)> {
+    /// Reads the program header from the builtins of the main file, `@bits(64)` asks for 64 bit
+    /// integer registers and `@floatbits(64)` for 64 bit float registers.
+    pub fn header(&self) -> ProgramHeader {
+        let mut header = ProgramHeader::default();
+        for builtin in self.builtins.iter() {
+            if let NodeBuiltin::NodeBuiltinBits { value:NodeExpr::NodeExprIntLit { value }, float } = builtin {
+                let wide = match value.value.clone().unwrap().as_str() {
+                    "32" => false,
+                    "64" => true,
+                    bits => {
+                        let name = if *float { "floatbits" } else { "bits" };
+                        println!("Builtin function error: @{}({}) is not supported, registers can be 32 or 64 bits.",name,bits);
+                        std::process::exit(1);
+                    }
+                };
+                if *float {
+                    header.double_floats = wide;
+                }else {
+                    header.wide_integers = wide;
+                }
+            }
+        }
+        header
+    }
+
+    /// Parses an integer literal that goes into a register. In 32 bit mode it wraps to 32 bits.
+    fn literal(&self,value:&Token) -> iRegisterDataType {
+        let int = parse_int(value);
+        if self.vm.header().wide_integers {
+            return int
+        }
+        if int < i32::MIN as iRegisterDataType || int > u32::MAX as iRegisterDataType {
+            println!("Number {} does not fit in 32 bits. Use @bits(64) for 64 bit registers.",value.value.clone().unwrap());
+            std::process::exit(1);
+        }
+        int as i32 as iRegisterDataType
+    }
+
+    fn mov_literal(&mut self,reg:InstructionParamType,value:&Token) {
+        let int = self.literal(value);
+        match iInstructionParamType::try_from(int) {
+            Ok(int) => self.vm.add_instruction(Instruction::Mov(reg,int)),
+            Err(_) => self.vm.add_instruction(Instruction::MovWide(reg,int)),
+        }
+    }
+
+    /// `cmp lhs, rhs`, a number on the left goes through a reserved register.
+    fn compare(&mut self, lhs:&NodeExpr, rhs:&NodeExpr) {
+        let lreg = match lhs {
+            NodeExpr::NodeExprRegister { value:_value } => {
+                get_register(lhs)
+            }
+            NodeExpr::NodeExprIntLit { value } => {
+                let val =  immediate(value);
+                self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,val));
+                RESERVEREGISTER1
+            },
+            _ => unreachable!()
+        }; 
+
+        match rhs {
+            NodeExpr::NodeExprRegister { value: _} => {
+                self.vm.add_instruction(Instruction::Compare(lreg,get_register(rhs)));
+            }
+            NodeExpr::NodeExprIntLit { value } => {
+                let val = immediate(value);
+                self.vm.add_instruction(Instruction::CompareImmediate(lreg,val));
+            }
+            _ => unreachable!()
+        };
+    }
+
+    fn push_literal(&mut self,value:&Token) {
+        let int = self.literal(value);
+        match iInstructionParamType::try_from(int) {
+            Ok(int) => self.vm.add_instruction(Instruction::Push(int)),
+            Err(_) => self.vm.add_instruction(Instruction::PushWide(int)),
+        }
+    }
+
+    pub fn generate_imports(&mut self) -> Vec<ImportedInstruction> {
         let mut out = Vec::new();
         for builtin in self.builtins.iter() {
             match builtin {
@@ -78,39 +185,47 @@ impl Generator {
                             std::process::exit(1);
                         }
                     };
-                    let tokens = Tokenizer::new(file).tokenize();
-                    let mut parsed = Parser::new(tokens);
+                    let mut tokenizer = Tokenizer::new(file);
+                    let tokens = tokenizer.tokenize();
+                    let mut parsed = Parser::new(tokens,tokenizer.token_lines);
                     parsed.parse();
                     'outer: for i in 1..parsed.instructions.len()+1 {
+                        let location = SourceLocation { file: file_loc.clone(), line: parsed.instruction_lines[i-1] };
                         let mut cur_labels = Vec::new();
                         for (label_name,start) in parsed.labels.iter() {
-                            if *start == i && *label_name !="main".to_string(){
+                            if *start == i && *label_name !="main"{
                                 cur_labels.push((label_name.clone(),*start));
                               // out.push((Some((label_name.clone(),*start)),parsed.instructions[i-1].clone()));
                                
                             }
-                            if cur_labels.len() != 0 {
-                                out.push((cur_labels,parsed.instructions[i-1].clone()));
+                            if !cur_labels.is_empty() {
+                                out.push((cur_labels,parsed.instructions[i-1].clone(),location));
                                 continue 'outer;
                             } 
                         }
-                        out.push((Vec::new(),parsed.instructions[i-1].clone()));
+                        out.push((Vec::new(),parsed.instructions[i-1].clone(),location));
                     }
 
                 }
                 NodeBuiltin::NodeBuiltinLoadString { value:_, load_len:_} => {
                    dbg!("alrady done while parsing"); 
                 }
+                NodeBuiltin::NodeBuiltinBits { value:_, float:_ } => {}
+                // Expanded into instructions while parsing.
+                NodeBuiltin::NodeBuiltinSwitch { .. } => {}
+                NodeBuiltin::NodeBuiltinProc { .. } | NodeBuiltin::NodeBuiltinEndProc => {}
+                NodeBuiltin::NodeBuiltinTry { .. } | NodeBuiltin::NodeBuiltinEndTry => {}
             }
         }
         out
     }
 
 
-    pub fn generate_instructions(&mut self,insts:Vec<(Vec<String>,NodeInstruction)>){
+    pub fn generate_instructions(&mut self,insts:Vec<(Vec<String>,NodeInstruction,SourceLocation)>){
 
         use NodeInstruction::*;
-        for (label_vec,node) in insts.iter() {
+        for (label_vec,node,location) in insts.iter() {
+            let first_generated = self.vm.last_command();
             if !label_vec.is_empty() {
                 for label_name in label_vec.iter() {
                     self.vm.create_label(self.vm.last_command(),&label_name.clone());
@@ -118,14 +233,28 @@ impl Generator {
             }
             match node {
                 NodeInstructionHalt => self.vm.add_instruction(Instruction::Halt),
+                NodeInstructionMov {lhs:NodeExpr::NodeExprFrameSlot{value},rhs} => {
+                    let reg = match rhs {
+                        NodeExpr::NodeExprIntLit{value} => {
+                            self.mov_literal(RESERVEREGISTER1,value);
+                            RESERVEREGISTER1
+                        }
+                        _ => get_register(rhs),
+                    };
+                    self.vm.add_instruction(Instruction::SetFromBasePointer(reg,immediate(value)));
+                }
+                NodeInstructionMov {lhs,rhs:NodeExpr::NodeExprFrameSlot{value}} => {
+                    self.vm.add_instruction(Instruction::GetFromBasePointer(get_register(lhs),immediate(value)));
+                }
                 NodeInstructionMov {lhs,rhs} => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
-                        NodeExpr::NodeExprIntLit{value} => self.vm.add_instruction(Instruction::Mov(reg as InstructionParamType,value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap())),
+                        NodeExpr::NodeExprIntLit{value} => self.mov_literal(reg,value),
+                        NodeExpr::NodeExprRegister{value} if value.token_type == TokenType::FloatRegister => {
+                            self.vm.add_instruction(Instruction::MovFloatToInt(reg,get_fregister(rhs)));
+                        }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2 = get_register(&rhs);
-                            self.vm.add_instruction(Instruction::PushRegister(reg2));
-                            self.vm.add_instruction(Instruction::Pop(reg));
+                            self.vm.add_instruction(Instruction::MovRegister(reg,get_register(rhs)));
                         } 
                         _ => unreachable!()
                     }
@@ -140,7 +269,7 @@ impl Generator {
                             }
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap()));
+                            self.mov_literal(RESERVEREGISTER1,value);
                             self.vm.add_instruction(Instruction::Display(RESERVEREGISTER1)); 
                         },
                         _ => unreachable!()
@@ -157,10 +286,8 @@ NodeInstructionDisplayf { value } => {
                         }
                         NodeExpr::NodeExprFloat { value } => {
                             let val = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushFloatRegister(0));
-                            self.vm.add_instruction(Instruction::Movf(0,val));
-                            self.vm.add_instruction(Instruction::Displayf(0));
-                            self.vm.add_instruction(Instruction::PopFloat(0));
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,val));
+                            self.vm.add_instruction(Instruction::Displayf(RESERVEFLOATREGISTER));
                         },
                         _ => unreachable!()
                     }
@@ -176,11 +303,9 @@ NodeInstructionDisplayChar { value } => {
                             }
                         }
                         NodeExpr::NodeExprFloat { value } => {
-                            let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(0));
-                            self.vm.add_instruction(Instruction::Mov(0,val));
-                            self.vm.add_instruction(Instruction::DisplayChar(0));
-                            self.vm.add_instruction(Instruction::Pop(0));
+                            let val = immediate(value);
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,val));
+                            self.vm.add_instruction(Instruction::DisplayChar(RESERVEREGISTER1));
                         },
                         _ => unreachable!()
                     }
@@ -189,34 +314,28 @@ NodeInstructionDisplayChar { value } => {
 
 
                 NodeInstructionAdd { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Add(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::AddImmediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::Add(reg,get_register(&rhs))); 
+                            self.vm.add_instruction(Instruction::Add(reg,get_register(rhs))); 
                         }
                         _ => unreachable!()
                     }
                 }
                 NodeInstructionSub { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
 
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Sub(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::SubImmediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2= get_register(&rhs); 
+                            let reg2= get_register(rhs); 
                             self.vm.add_instruction(Instruction::Sub(reg,reg2));
 
                         }
@@ -225,17 +344,14 @@ NodeInstructionDisplayChar { value } => {
                 }
 
                 NodeInstructionMod { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Mod(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::ModImmediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2= get_register(&rhs); 
+                            let reg2= get_register(rhs); 
                             self.vm.add_instruction(Instruction::Mod(reg,reg2));
 
                         }
@@ -246,16 +362,26 @@ NodeInstructionDisplayChar { value } => {
                 NodeInstructionPush{value}=>{ 
                     match value {
                         NodeExpr::NodeExprIntLit { value } => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::Push(int));
+                            self.push_literal(value);
+                        }
+                        NodeExpr::NodeExprRegister { value:register } if register.token_type == TokenType::FloatRegister => {
+                            self.vm.add_instruction(Instruction::PushFloatRegister(get_fregister(value)));
                         }
                         NodeExpr::NodeExprRegister { value:_value } => {
                             let reg = get_register(value);
                             self.vm.add_instruction(Instruction::PushRegister(reg));
                         }
+                        NodeExpr::NodeExprFrameSlot { value } => {
+                            self.vm.add_instruction(Instruction::GetFromBasePointer(RESERVEREGISTER1,immediate(value)));
+                            self.vm.add_instruction(Instruction::PushRegister(RESERVEREGISTER1));
+                        }
                         _ => unreachable!()
                     }
                 },
+                NodeInstructionPop{value:NodeExpr::NodeExprRegister { value:register }} if register.token_type == TokenType::FloatRegister => {
+                    let reg = get_fregister(&NodeExpr::NodeExprRegister { value:register.clone() });
+                    self.vm.add_instruction(Instruction::PopFloat(reg));
+                },
                 NodeInstructionPop{value}=>{
                     let reg = get_register(value);
                     self.vm.add_instruction(Instruction::Pop(reg));
@@ -349,69 +475,179 @@ NodeInstructionDisplayChar { value } => {
                     }              
                 },
 
-                NodeInstructionCompare{lhs, rhs} => {
-                    let mut lreg_store = false;
-                    let mut rreg_store = false;
-                    let lreg = match lhs {
-                        NodeExpr::NodeExprRegister { value:_value } => {
-                            get_register(&lhs)
+                NodeInstructionJumpIfGreaterOrEqual{value} |
+                    NodeInstructionJumpIfLessOrEqual{value} |
+                    NodeInstructionJumpIfAbove{value} |
+                    NodeInstructionJumpIfBelow{value} |
+                    NodeInstructionJumpIfAboveOrEqual{value} |
+                    NodeInstructionJumpIfBelowOrEqual{value} |
+                    NodeInstructionJumpIfOverflow{value} |
+                    NodeInstructionJumpIfNotOverflow{value} |
+                    NodeInstructionJumpIfCarry{value} |
+                    NodeInstructionJumpIfNotCarry{value} => {
+                    let jump: fn(crate::instruction::StringNumberUnion) -> Instruction = match node {
+                        NodeInstructionJumpIfGreaterOrEqual {..} => Instruction::JumpIfGreaterOrEqual,
+                        NodeInstructionJumpIfLessOrEqual {..} => Instruction::JumpIfLessOrEqual,
+                        NodeInstructionJumpIfAbove {..} => Instruction::JumpIfAbove,
+                        NodeInstructionJumpIfBelow {..} => Instruction::JumpIfBelow,
+                        NodeInstructionJumpIfAboveOrEqual {..} => Instruction::JumpIfAboveOrEqual,
+                        NodeInstructionJumpIfBelowOrEqual {..} => Instruction::JumpIfBelowOrEqual,
+                        NodeInstructionJumpIfOverflow {..} => Instruction::JumpIfOverflow,
+                        NodeInstructionJumpIfNotOverflow {..} => Instruction::JumpIfNotOverflow,
+                        NodeInstructionJumpIfCarry {..} => Instruction::JumpIfCarry,
+                        _ => Instruction::JumpIfNotCarry,
+                    };
+                    match value {
+                        NodeExpr::NodeExprIntLit{value} => {
+                            let inst_address = value.value.as_ref().unwrap().parse::<u32>().unwrap();
+                            self.vm.add_instruction(jump(crate::instruction::StringNumberUnion::Num(inst_address)));
+                        }
+                        NodeExpr::NodeExprLabelName{value:_v} => {
+                            let label_name=  get_jump_label(value.clone()).unwrap();
+                            self.vm.add_instruction(jump(crate::instruction::StringNumberUnion::String(label_name)));
                         }
-                        NodeExpr::NodeExprIntLit { value } => {
-                            let val =  value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(REGC));
-                            self.vm.add_instruction(Instruction::Mov(REGC,val));
-                            lreg_store = true;
-                            REGC
-                        },
                         _ => unreachable!()
-                    }; 
+                    }
+                },
 
-                    let rreg = match rhs {
-                        NodeExpr::NodeExprRegister { value: _} => {
-                            get_register(&rhs)
+                NodeInstructionSetArithmeticMode { value } => {
+                    let mode = match get_jump_label(value.clone()).unwrap().as_str() {
+                        "trap" => ARITHMETIC_TRAPPING,
+                        _ => ARITHMETIC_WRAPPING,
+                    };
+                    self.vm.add_instruction(Instruction::SetArithmeticMode(mode));
+                }
+
+                NodeInstructionCompareFloat { lhs, rhs } => {
+                    let reg = get_fregister(lhs);
+                    match rhs {
+                        NodeExpr::NodeExprFloat{value} => {
+                            let val = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,val));
+                            self.vm.add_instruction(Instruction::CompareFloat(reg,RESERVEFLOATREGISTER));
                         }
-                        NodeExpr::NodeExprIntLit { value } => {
-                            let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(REGD));
-                            self.vm.add_instruction(Instruction::Mov(REGD,val));
-                            rreg_store = true;
-                            REGD
+                        NodeExpr::NodeExprRegister{value:_} => {
+                            self.vm.add_instruction(Instruction::CompareFloat(reg,get_fregister(rhs)));
                         }
                         _ => unreachable!()
+                    }
+                }
+                NodeInstructionConvertFloat { lhs, rhs, mode } => {
+                    let mode = match get_jump_label(mode.clone()).unwrap().as_str() {
+                        "nearest" => ROUND_NEAREST,
+                        "down" => ROUND_DOWN,
+                        "up" => ROUND_UP,
+                        _ => ROUND_ZERO,
+                    };
+                    self.vm.add_instruction(Instruction::MovFloatToIntRounded(get_register(lhs),get_fregister(rhs),mode));
+                }
+                // 64 bit floats do not fit in 32 bit integer registers, so the bits would not come back.
+                NodeInstructionMovBits { .. } if self.vm.header().double_floats && !self.vm.header().wide_integers => {
+                    println!("Generation Error: movbits with @floatbits(64) needs @bits(64) at {}:{}",location.file,location.line);
+                    std::process::exit(1);
+                }
+                NodeInstructionMovBits { lhs:NodeExpr::NodeExprRegister { value }, rhs } if value.token_type == TokenType::FloatRegister => {
+                    let reg = get_fregister(&NodeExpr::NodeExprRegister { value:value.clone() });
+                    self.vm.add_instruction(Instruction::MovIntBitsToFloat(reg,get_register(rhs)));
+                }
+                NodeInstructionMovBits { lhs, rhs } => {
+                    self.vm.add_instruction(Instruction::MovFloatBitsToInt(get_register(lhs),get_fregister(rhs)));
+                }
+                NodeInstructionFloatMath { function, lhs, rhs } => {
+                    let function = match get_jump_label(function.clone()).unwrap().as_str() {
+                        "sqrtf" => MATH_SQRT,
+                        "absf" => MATH_ABS,
+                        "floorf" => MATH_FLOOR,
+                        "ceilf" => MATH_CEIL,
+                        "roundf" => MATH_ROUND,
+                        "expf" => MATH_EXP,
+                        "lnf" => MATH_LN,
+                        "sinf" => MATH_SIN,
+                        "cosf" => MATH_COS,
+                        "tanf" => MATH_TAN,
+                        "powf" => MATH_POW,
+                        "atan2f" => MATH_ATAN2,
+                        "minf" => MATH_MIN,
+                        _ => MATH_MAX,
                     };
-                    self.vm.add_instruction(Instruction::Compare(lreg,rreg));
-                    if rreg_store {
-                        self.vm.add_instruction(Instruction::Pop(REGD));
+                    let reg = get_fregister(lhs);
+                    match rhs {
+                        NodeExpr::NodeExprFloat{value} => {
+                            let val = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,val));
+                            self.vm.add_instruction(Instruction::FloatMath(reg,RESERVEFLOATREGISTER,function));
+                        }
+                        NodeExpr::NodeExprRegister{value:_} => {
+                            self.vm.add_instruction(Instruction::FloatMath(reg,get_fregister(rhs),function));
+                        }
+                        _ => unreachable!()
                     }
-                    if lreg_store {
-                        self.vm.add_instruction(Instruction::Pop(REGC));
+                }
+
+                NodeInstructionCompareUnsigned { lhs, rhs } => {
+                    let reg = get_register(lhs);
+                    match rhs {
+                        NodeExpr::NodeExprIntLit{value} => {
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::CompareUnsignedImmediate(reg,int));
+                        }
+                        NodeExpr::NodeExprRegister{value:_} => {
+                            self.vm.add_instruction(Instruction::CompareUnsigned(reg,get_register(rhs)));
+                        }
+                        _ => unreachable!()
                     }
-                },
+                }
+                NodeInstructionDivUnsigned { lhs, rhs } => {
+                    let reg = get_register(lhs);
+                    match rhs {
+                        NodeExpr::NodeExprIntLit{value} => {
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::DivUnsignedImmediate(reg,int));
+                        }
+                        NodeExpr::NodeExprRegister{value:_} => {
+                            self.vm.add_instruction(Instruction::DivUnsigned(reg,get_register(rhs)));
+                        }
+                        _ => unreachable!()
+                    }
+                }
+                NodeInstructionModUnsigned { lhs, rhs } => {
+                    let reg = get_register(lhs);
+                    match rhs {
+                        NodeExpr::NodeExprIntLit{value} => {
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::ModUnsignedImmediate(reg,int));
+                        }
+                        NodeExpr::NodeExprRegister{value:_} => {
+                            self.vm.add_instruction(Instruction::ModUnsigned(reg,get_register(rhs)));
+                        }
+                        _ => unreachable!()
+                    }
+                }
+
+                NodeInstructionCompare{lhs, rhs} => self.compare(lhs,rhs),
 
                 NodeInstructionGetFromStack{lhs, rhs} => {
-                    let dest = get_register(&rhs);
+                    let dest = get_register(rhs);
                     match lhs {
                         NodeExpr::NodeExprRegister { value: _} => {
-                            self.vm.add_instruction(Instruction::GetFromStack(get_register(&lhs),dest));
+                            self.vm.add_instruction(Instruction::GetFromStack(get_register(lhs),dest));
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                            let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(dest+1));
-                            self.vm.add_instruction(Instruction::Mov(dest+1,val));
-                            self.vm.add_instruction(Instruction::GetFromStack(dest+1,dest));
-                            self.vm.add_instruction(Instruction::Pop(dest+1));
+                            let val = immediate(value);
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,val));
+                            self.vm.add_instruction(Instruction::GetFromStack(RESERVEREGISTER1,dest));
                         }
                         _ => unreachable!()
                     };
                 },
                 NodeInstructionGetFromStackPointer{lhs, rhs} => {
-                    let dest = get_register(&rhs);
+                    let dest = get_register(rhs);
                     match lhs {
                         NodeExpr::NodeExprRegister { value: _} => {
-                            self.vm.add_instruction(Instruction::GetFromStackPointer(get_register(&lhs),dest));
+                            self.vm.add_instruction(Instruction::GetFromStackPointer(get_register(lhs),dest));
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                            let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                            let val = immediate(value);
                             self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,val));
                             self.vm.add_instruction(Instruction::GetFromStackPointer(RESERVEREGISTER1,dest));
                         }
@@ -420,17 +656,15 @@ NodeInstructionDisplayChar { value } => {
                 },
                 
                 NodeInstructionSetStack { lhs, rhs } => {
-                    let dest = get_register(&rhs);
+                    let dest = get_register(rhs);
                     match lhs {
                         NodeExpr::NodeExprRegister { value: _} => {
-                            self.vm.add_instruction(Instruction::SetStack(get_register(&lhs),dest));
+                            self.vm.add_instruction(Instruction::SetStack(get_register(lhs),dest));
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();                  
-                            self.vm.add_instruction(Instruction::PushRegister(dest+1));
-                            self.vm.add_instruction(Instruction::Mov(dest+1,int));
-                            self.vm.add_instruction(Instruction::SetStack(dest+1,dest));
-                            self.vm.add_instruction(Instruction::Pop(dest+1));
+                            let int = immediate(value);                  
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int));
+                            self.vm.add_instruction(Instruction::SetStack(RESERVEREGISTER1,dest));
                         }
                         _ => unreachable!()
                     };
@@ -438,17 +672,15 @@ NodeInstructionDisplayChar { value } => {
                 }
 
                 NodeInstructionSetFromStackPointer { lhs, rhs } => {
-                    let dest = get_register(&rhs);
+                    let dest = get_register(rhs);
                     match lhs {
                         NodeExpr::NodeExprRegister { value: _} => {
-                            self.vm.add_instruction(Instruction::SetFromStackPointer(get_register(&lhs),dest));
+                            self.vm.add_instruction(Instruction::SetFromStackPointer(get_register(lhs),dest));
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();                  
-                            self.vm.add_instruction(Instruction::PushRegister(dest+1));
-                            self.vm.add_instruction(Instruction::Mov(dest+1,int+1));
-                            self.vm.add_instruction(Instruction::SetFromStackPointer(dest+1,dest));
-                            self.vm.add_instruction(Instruction::Pop(dest+1));
+                            let int = immediate(value);                  
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int));
+                            self.vm.add_instruction(Instruction::SetFromStackPointer(RESERVEREGISTER1,dest));
                         }
                         _ => unreachable!()
                     };
@@ -458,7 +690,7 @@ NodeInstructionDisplayChar { value } => {
                 NodeInstructionTruncateStack { value } => {
                     match value {
                         NodeExpr::NodeExprIntLit { value } => {
-                            let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                            let val = immediate(value);
                             self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,val));
                             self.vm.add_instruction(Instruction::TruncateStack(RESERVEREGISTER1));
                         }
@@ -479,7 +711,7 @@ NodeInstructionDisplayChar { value } => {
                             }
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                            let int = immediate(value);
                             self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int));
                             self.vm.add_instruction(Instruction::Malloc(RESERVEREGISTER1));
                         },
@@ -497,7 +729,7 @@ NodeInstructionDisplayChar { value } => {
                             }
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                            let int = immediate(value);
                             self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int));
                             self.vm.add_instruction(Instruction::Free(RESERVEREGISTER1));
                         },
@@ -505,16 +737,16 @@ NodeInstructionDisplayChar { value } => {
                     }
 
                 }
-                NodeInstructionGetMemory{lhs, rhs,offset} => {
-                    let lreg = get_register(&lhs);
+                NodeInstructionGetMemory{lhs, rhs,offset,width} => {
+                    let lreg = get_register(lhs);
                     let rreg = { 
                         match rhs {
                             NodeExpr::NodeExprRegister { value: _} => {
-                                get_register(&rhs)
+                                get_register(rhs)
                                 //self.vm.add_instruction(Instruction::GetMemory(lreg,get_register(&rhs)));
                             }
                             NodeExpr::NodeExprIntLit { value } => {
-                                let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                                let val = immediate(value);
                                 self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,val));
                                 //self.vm.add_instruction(Instruction::GetMemory(lreg,lreg+1));
                                 //self.vm.add_instruction(Instruction::Pop(lreg+1));
@@ -525,26 +757,26 @@ NodeInstructionDisplayChar { value } => {
                     };
                     match offset {
                         NodeExpr::NodeExprRegister { value: _ } => {
-                            self.vm.add_instruction(Instruction::GetMemory(lreg,rreg,get_register(&offset)));
+                            self.vm.add_instruction(memory_instruction(*width,false,lreg,rreg,get_register(offset)));
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                                let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                                let val = immediate(value);
                                 self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER2,val));
-                                self.vm.add_instruction(Instruction::GetMemory(lreg,rreg,RESERVEREGISTER2)); 
+                                self.vm.add_instruction(memory_instruction(*width,false,lreg,rreg,RESERVEREGISTER2));
                         }
                         _ => unreachable!()
                     }
                 },
-                NodeInstructionSetMemory{lhs, rhs,offset} => {
-                    let lreg = get_register(&lhs);
+                NodeInstructionSetMemory{lhs, rhs,offset,width} => {
+                    let lreg = get_register(lhs);
                     let rreg = { 
                         match rhs {
                             NodeExpr::NodeExprRegister { value: _} => {
-                                get_register(&rhs)
+                                get_register(rhs)
                                 //self.vm.add_instruction(Instruction::GetMemory(lreg,get_register(&rhs)));
                             }
                             NodeExpr::NodeExprIntLit { value } => {
-                                let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                                let val = immediate(value);
                                 self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,val));
                                 //self.vm.add_instruction(Instruction::SetMemory(lreg,lreg+1));
                                 //self.vm.add_instruction(Instruction::Pop(lreg+1));
@@ -555,12 +787,12 @@ NodeInstructionDisplayChar { value } => {
                     };
                     match offset {
                         NodeExpr::NodeExprRegister { value: _ } => {
-                            self.vm.add_instruction(Instruction::SetMemory(lreg,rreg,get_register(&offset)));
+                            self.vm.add_instruction(memory_instruction(*width,true,lreg,rreg,get_register(offset)));
                         }
                         NodeExpr::NodeExprIntLit { value } => {
-                                let val = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                                let val = immediate(value);
                                 self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER2,val));
-                                self.vm.add_instruction(Instruction::SetMemory(lreg,rreg,RESERVEREGISTER2)); 
+                                self.vm.add_instruction(memory_instruction(*width,true,lreg,rreg,RESERVEREGISTER2));
                         }
                         _ => unreachable!()
                     }                },
@@ -568,33 +800,27 @@ NodeInstructionDisplayChar { value } => {
                 NodeInstructionReturn=>{ self.vm.add_instruction(Instruction::Return); },
 
                 NodeInstructionMul { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Mul(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::MulImmediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::Mul(reg,get_register(&rhs))); 
+                            self.vm.add_instruction(Instruction::Mul(reg,get_register(rhs))); 
                         }
                         _ => unreachable!()
                     }
                 }
                 NodeInstructionDiv { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Div(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::DivImmediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2= get_register(&rhs); 
+                            let reg2= get_register(rhs); 
                             self.vm.add_instruction(Instruction::Div(reg,reg2));
 
                         }
@@ -607,33 +833,27 @@ NodeInstructionDisplayChar { value } => {
                     self.vm.add_instruction(Instruction::Not(reg));
                 }
                 NodeInstructionAnd { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::And(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::AndImmediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::And(reg,get_register(&rhs))); 
+                            self.vm.add_instruction(Instruction::And(reg,get_register(rhs))); 
                         }
                         _ => unreachable!()
                     }
                 }
                 NodeInstructionOr { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Or(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::OrImmediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2= get_register(&rhs); 
+                            let reg2= get_register(rhs); 
                             self.vm.add_instruction(Instruction::Or(reg,reg2));
 
                         }
@@ -641,33 +861,49 @@ NodeInstructionDisplayChar { value } => {
                     }
                 }
                 NodeInstructionXor { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
+                    match rhs  {
+                        NodeExpr::NodeExprIntLit{value} => {
+                            let int = immediate(value);
+                            self.vm.add_instruction(Instruction::XorImmediate(reg,int));
+                        }
+                        NodeExpr::NodeExprRegister{value:_} => {
+                            self.vm.add_instruction(Instruction::Xor(reg,get_register(rhs))); 
+                        }
+                        _ => unreachable!()
+                    }
+                }
+                NodeInstructionShl { lhs, rhs } | NodeInstructionShr { lhs, rhs } | NodeInstructionSar { lhs, rhs } |
+                    NodeInstructionRol { lhs, rhs } | NodeInstructionRor { lhs, rhs } => {
+                    let reg = get_register(lhs);
+                    let (by_register,by_immediate): (RegisterInstruction,ImmediateInstruction) = match node {
+                        NodeInstructionShl {..} => (Instruction::Shl,Instruction::ShlImmediate),
+                        NodeInstructionShr {..} => (Instruction::Shr,Instruction::ShrImmediate),
+                        NodeInstructionSar {..} => (Instruction::Sar,Instruction::SarImmediate),
+                        NodeInstructionRol {..} => (Instruction::Rol,Instruction::RolImmediate),
+                        _ => (Instruction::Ror,Instruction::RorImmediate),
+                    };
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Xor(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);
+                            self.vm.add_instruction(by_immediate(reg,int));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::Xor(reg,get_register(&rhs))); 
+                            self.vm.add_instruction(by_register(reg,get_register(rhs)));
                         }
                         _ => unreachable!()
                     }
                 }
                 NodeInstructionNand { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();             
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int));
-                            self.vm.add_instruction(Instruction::Nand(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1));
+                            let int = immediate(value);             
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int));
+                            self.vm.add_instruction(Instruction::Nand(reg,RESERVEREGISTER1));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2= get_register(&rhs); 
+                            let reg2= get_register(rhs); 
                             self.vm.add_instruction(Instruction::Nand(reg,reg2));
 
                         }
@@ -679,13 +915,14 @@ NodeInstructionDisplayChar { value } => {
 
 
                 NodeInstructionMovf {lhs,rhs} => {
-                    let reg = get_fregister(&lhs);
+                    let reg = get_fregister(lhs);
                     match rhs  {
                         NodeExpr::NodeExprFloat{value} => self.vm.add_instruction(Instruction::Movf(reg as InstructionParamType,value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap())),
+                        NodeExpr::NodeExprRegister{value} if value.token_type == TokenType::Register => {
+                            self.vm.add_instruction(Instruction::MovIntToFloat(reg,get_register(rhs)));
+                        }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2 = get_fregister(&rhs);
-                            self.vm.add_instruction(Instruction::PushFloatRegister(reg2));
-                            self.vm.add_instruction(Instruction::PopFloat(reg));
+                            self.vm.add_instruction(Instruction::MovfRegister(reg,get_fregister(rhs)));
                         } 
                         _ => unreachable!()
                     }
@@ -693,34 +930,30 @@ NodeInstructionDisplayChar { value } => {
 
 
                 NodeInstructionAddf { lhs, rhs } => {
-                    let reg = get_fregister(&lhs);
+                    let reg = get_fregister(lhs);
                     match rhs  {
                         NodeExpr::NodeExprFloat{value} => {
                             let int = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushFloatRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Movf(reg+1,int));
-                            self.vm.add_instruction(Instruction::Addf(reg,reg+1));
-                            self.vm.add_instruction(Instruction::PopFloat(reg+1));
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,int));
+                            self.vm.add_instruction(Instruction::Addf(reg,RESERVEFLOATREGISTER));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::Add(reg,get_fregister(&rhs))); 
+                            self.vm.add_instruction(Instruction::Addf(reg,get_fregister(rhs))); 
                         }
                         _ => unreachable!()
                     }
                 }
                 NodeInstructionSubf { lhs, rhs } => {
-                    let reg = get_fregister(&lhs);
+                    let reg = get_fregister(lhs);
                     match rhs  {
 
                         NodeExpr::NodeExprFloat{value} => {
                             let int = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushFloatRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Movf(reg+1,int));
-                            self.vm.add_instruction(Instruction::Subf(reg,reg+1));
-                            self.vm.add_instruction(Instruction::PopFloat(reg+1));
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,int));
+                            self.vm.add_instruction(Instruction::Subf(reg,RESERVEFLOATREGISTER));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2= get_fregister(&rhs); 
+                            let reg2= get_fregister(rhs); 
                             self.vm.add_instruction(Instruction::Subf(reg,reg2));
 
                         }
@@ -728,50 +961,44 @@ NodeInstructionDisplayChar { value } => {
                     }
                 }
                 NodeInstructionMulf { lhs, rhs } => {
-                    let reg = get_fregister(&lhs);
+                    let reg = get_fregister(lhs);
                     match rhs  {
                         NodeExpr::NodeExprFloat{value} => {
                             let int = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushFloatRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Movf(reg+1,int));
-                            self.vm.add_instruction(Instruction::Mulf(reg,reg+1));
-                            self.vm.add_instruction(Instruction::PopFloat(reg+1));
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,int));
+                            self.vm.add_instruction(Instruction::Mulf(reg,RESERVEFLOATREGISTER));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::Mulf(reg,get_fregister(&rhs))); 
+                            self.vm.add_instruction(Instruction::Mulf(reg,get_fregister(rhs))); 
                         }
                         _ => unreachable!()
                     }
                 }
                 NodeInstructionDivf { lhs, rhs } => {
-                   let reg = get_fregister(&lhs);
+                   let reg = get_fregister(lhs);
                     match rhs  {
                         NodeExpr::NodeExprFloat{value} => {
                             let int = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushFloatRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Movf(reg+1,int));
-                            self.vm.add_instruction(Instruction::Divf(reg,reg+1));
-                            self.vm.add_instruction(Instruction::PopFloat(reg+1));
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,int));
+                            self.vm.add_instruction(Instruction::Divf(reg,RESERVEFLOATREGISTER));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::Divf(reg,get_fregister(&rhs))); 
+                            self.vm.add_instruction(Instruction::Divf(reg,get_fregister(rhs))); 
                         }
                         _ => unreachable!()
                     }
                 }
 
                 NodeInstructionModf { lhs, rhs } => {
-                    let reg = get_fregister(&lhs);
+                    let reg = get_fregister(lhs);
                     match rhs  {
                         NodeExpr::NodeExprFloat{value} => {
                             let int = value.value.clone().unwrap().parse::<FloatInstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushFloatRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Movf(reg+1,int));
-                            self.vm.add_instruction(Instruction::Modf(reg,reg+1));
-                            self.vm.add_instruction(Instruction::PopFloat(reg+1));
+                            self.vm.add_instruction(Instruction::Movf(RESERVEFLOATREGISTER,int));
+                            self.vm.add_instruction(Instruction::Modf(reg,RESERVEFLOATREGISTER));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            let reg2= get_fregister(&rhs); 
+                            let reg2= get_fregister(rhs); 
                             self.vm.add_instruction(Instruction::Modf(reg,reg2));
 
                         }
@@ -779,59 +1006,55 @@ NodeInstructionDisplayChar { value } => {
                     }
                 }
                 NodeInstructionGetFlag { lhs, rhs} => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs {
                         NodeExpr::NodeExprIntLit { value } => {
                             let int = value.value.clone().unwrap().parse::<InstructionParamType>().unwrap();
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int as iInstructionParamType));
-                            self.vm.add_instruction(Instruction::GetFlag(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1))
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int as iInstructionParamType));
+                            self.vm.add_instruction(Instruction::GetFlag(reg,RESERVEREGISTER1));
                         }
                         NodeExpr::NodeExprFlag { value } => {
                             let int = get_flag(value);
-                            self.vm.add_instruction(Instruction::PushRegister(reg+1));
-                            self.vm.add_instruction(Instruction::Mov(reg+1,int as iInstructionParamType));
-                            self.vm.add_instruction(Instruction::GetFlag(reg,reg+1));
-                            self.vm.add_instruction(Instruction::Pop(reg+1))
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int as iInstructionParamType));
+                            self.vm.add_instruction(Instruction::GetFlag(reg,RESERVEREGISTER1));
                         }
                         NodeExpr::NodeExprRegister { value:_ } => {
-                            self.vm.add_instruction(Instruction::GetFlag(reg,get_register(&rhs)));
+                            self.vm.add_instruction(Instruction::GetFlag(reg,get_register(rhs)));
                         } 
                         _ => unreachable!()
                     }
                 }
 
                 NodeInstructionGetStackPointer { lhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     self.vm.add_instruction(Instruction::GetStackPointer(reg)); 
                 }
 
                 NodeInstructionTruncateStackRange { lhs, rhs } => {
-                    let reg = get_register(&lhs);
+                    let reg = get_register(lhs);
                     match rhs  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                            let int = immediate(value);
                             self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int));
                             self.vm.add_instruction(Instruction::TruncateStackRange(reg,RESERVEREGISTER1));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::TruncateStackRange(reg,get_register(&rhs))); 
+                            self.vm.add_instruction(Instruction::TruncateStackRange(reg,get_register(rhs))); 
                         }
                         _ => unreachable!()
                     }
 
                 }
                 NodeInstructionExtendStack { extend_by, default_value } => {
-                    let reg = get_register(&extend_by);
+                    let reg = get_register(extend_by);
                     match default_value  {
                         NodeExpr::NodeExprIntLit{value} => {
-                            let int = value.value.clone().unwrap().parse::<iInstructionParamType>().unwrap();
+                            let int = immediate(value);
                             self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,int));
                             self.vm.add_instruction(Instruction::ExtendStack(reg,RESERVEREGISTER1));
                         }
                         NodeExpr::NodeExprRegister{value:_} => {
-                            self.vm.add_instruction(Instruction::ExtendStack(reg,get_register(&default_value))); 
+                            self.vm.add_instruction(Instruction::ExtendStack(reg,get_register(default_value))); 
                         }
                         _ => unreachable!()
                     }
@@ -853,6 +1076,118 @@ NodeInstructionDisplayChar { value } => {
                     }
                 }
 
+                NodeInstructionJumpRegister { value } => {
+                    self.vm.add_instruction(Instruction::JumpRegister(get_register(value)));
+                }
+                NodeInstructionCallRegister { value } => {
+                    self.vm.add_instruction(Instruction::CallRegister(get_register(value)));
+                }
+                NodeInstructionEnter { value } => {
+                    let locals = match value {
+                        NodeExpr::NodeExprIntLit{value} => value.value.as_ref().unwrap().parse::<InstructionParamType>().unwrap(),
+                        _ => unreachable!()
+                    };
+                    self.vm.add_instruction(Instruction::Enter(locals));
+                }
+                NodeInstructionLeave => self.vm.add_instruction(Instruction::Leave),
+                NodeInstructionProcStart { params:_, locals } => {
+                    self.vm.add_instruction(Instruction::Enter(*locals as InstructionParamType));
+                }
+                // The caller pushed the arguments, so they are dropped here after the frame.
+                NodeInstructionProcReturn { params } => {
+                    self.vm.add_instruction(Instruction::Leave);
+                    if *params > 0 {
+                        self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,*params as iInstructionParamType));
+                        self.vm.add_instruction(Instruction::TruncateStack(RESERVEREGISTER1));
+                    }
+                    self.vm.add_instruction(Instruction::Return);
+                }
+                NodeInstructionTailCall { value, proc_params } => {
+                    if let Some(params) = proc_params {
+                        self.vm.add_instruction(Instruction::Leave);
+                        if *params > 0 {
+                            self.vm.add_instruction(Instruction::Mov(RESERVEREGISTER1,*params as iInstructionParamType));
+                            self.vm.add_instruction(Instruction::TruncateStack(RESERVEREGISTER1));
+                        }
+                    }
+                    let label_name = get_jump_label(value.clone()).unwrap();
+                    self.vm.add_instruction(Instruction::TailCall(crate::instruction::StringNumberUnion::String(label_name)));
+                }
+                NodeInstructionTry { value } => {
+                    let label_name = get_jump_label(value.clone()).unwrap();
+                    self.vm.add_instruction(Instruction::Try(crate::instruction::StringNumberUnion::String(label_name)));
+                }
+                NodeInstructionEndTry => self.vm.add_instruction(Instruction::EndTry),
+                NodeInstructionBreakpoint => self.vm.add_instruction(Instruction::Breakpoint),
+                NodeInstructionAssert { condition, compare, message } => {
+                    if let Some((lhs,rhs)) = compare {
+                        self.compare(lhs,rhs);
+                    }
+                    // The message is pushed for `AssertFailed`, which is jumped over when the
+                    // condition holds.
+                    let message = match message {
+                        NodeExpr::NodeExprStringLit { value } => value.value.clone().unwrap(),
+                        _ => unreachable!()
+                    };
+                    let len = message.chars().count();
+                    let past = (self.vm.last_command() + 1 + len + 1) as u32;
+                    if past as usize > INSTRUCTION_MEMORY_SIZE {
+                        println!("Generation Error: assert message of {} characters does not fit in the {} instructions the VM has at {}:{}",len,INSTRUCTION_MEMORY_SIZE,location.file,location.line);
+                        std::process::exit(1);
+                    }
+                    self.vm.add_instruction(crate::instruction::jump_if(get_condition(condition),crate::instruction::StringNumberUnion::Num(past)).unwrap());
+                    for ch in message.chars() {
+                        self.vm.add_instruction(Instruction::Push(ch as iInstructionParamType));
+                    }
+                    self.vm.add_instruction(Instruction::AssertFailed(len as InstructionParamType));
+                }
+                NodeInstructionThrow { value } => {
+                    let reg = match value {
+                        NodeExpr::NodeExprIntLit{value} => {
+                            self.mov_literal(RESERVEREGISTER1,value);
+                            RESERVEREGISTER1
+                        }
+                        _ => get_register(value),
+                    };
+                    self.vm.add_instruction(Instruction::Throw(reg));
+                }
+                NodeInstructionConditionalMove { condition, lhs, rhs } => {
+                    let condition = get_condition(condition);
+                    self.vm.add_instruction(Instruction::ConditionalMove(get_register(lhs),get_register(rhs),condition));
+                }
+                NodeInstructionSetIf { condition, value } => {
+                    let condition = get_condition(condition);
+                    self.vm.add_instruction(Instruction::SetIf(get_register(value),condition));
+                }
+                NodeInstructionLoop { lhs, rhs } => {
+                    let label_name = get_jump_label(rhs.clone()).unwrap();
+                    self.vm.add_instruction(Instruction::Loop(get_register(lhs),crate::instruction::StringNumberUnion::String(label_name)));
+                }
+                NodeInstructionProcCall { value, args } => {
+                    for arg in args.iter() {
+                        match arg {
+                            NodeExpr::NodeExprIntLit { value } => self.push_literal(value),
+                            NodeExpr::NodeExprFrameSlot { value } => {
+                                self.vm.add_instruction(Instruction::GetFromBasePointer(RESERVEREGISTER1,immediate(value)));
+                                self.vm.add_instruction(Instruction::PushRegister(RESERVEREGISTER1));
+                            }
+                            _ => self.vm.add_instruction(Instruction::PushRegister(get_register(arg))),
+                        }
+                    }
+                    let label_name = get_jump_label(value.clone()).unwrap();
+                    self.vm.add_instruction(Instruction::Call(crate::instruction::StringNumberUnion::String(label_name)));
+                }
+                NodeInstructionJumpTable { value, count } => {
+                    self.vm.add_instruction(Instruction::JumpTable(get_register(value),*count as InstructionParamType));
+                }
+                NodeInstructionLoadAddress { lhs, rhs } => {
+                    let address = match rhs {
+                        NodeExpr::NodeExprIntLit{value} => crate::instruction::StringNumberUnion::Num(value.value.as_ref().unwrap().parse::<u32>().unwrap()),
+                        _ => crate::instruction::StringNumberUnion::String(get_jump_label(rhs.clone()).unwrap()),
+                    };
+                    self.vm.add_instruction(Instruction::LoadAddress(get_register(lhs),address));
+                }
+
                 NodeInstructionWrite { len, str_loc } => {
                    self.get_int_value_as_reserve_register1(len.clone());
                     self.get_int_value_as_reserve_register2(str_loc.clone());
@@ -866,6 +1201,8 @@ NodeInstructionDisplayChar { value } => {
      		}	
 
             }
+            self.source_map.resize(first_generated,None);
+            self.source_map.resize(self.vm.last_command(),Some(location.clone()));
         }
     }
     fn get_int_value_as_register(&mut self, register_dest:InstructionParamType,expr:NodeExpr) {
@@ -879,8 +1216,7 @@ NodeInstructionDisplayChar { value } => {
                     println!("Generation Error: Invalid register {}",value.value.clone().unwrap_or("".to_string()));
                     std::process::exit(1);
                 }
-                self.vm.add_instruction(Instruction::PushRegister(reg.unwrap()));
-                self.vm.add_instruction(Instruction::Pop(register_dest));
+                self.vm.add_instruction(Instruction::MovRegister(register_dest,reg.unwrap()));
             }
             _ => {
                 // Should not come to this
@@ -904,17 +1240,40 @@ NodeInstructionDisplayChar { value } => {
 }
 
 
+/// Checks that every `call name(...)` of a `@proc` passes as many arguments as it has parameters.
+/// Done on the whole program so calls into imported files are checked too.
+fn check_proc_calls(insts:&[(Vec<String>,NodeInstruction,SourceLocation)]) {
+    let mut params = HashMap::new();
+    for (labels,inst,_) in insts.iter() {
+        if let NodeInstruction::NodeInstructionProcStart { params:count, .. } = inst {
+            for label in labels.iter() {
+                params.insert(label.clone(),*count);
+            }
+        }
+    }
+    for (_,inst,location) in insts.iter() {
+        if let NodeInstruction::NodeInstructionProcCall { value, args } = inst {
+            let name = get_jump_label(value.clone()).unwrap();
+            match params.get(&name) {
+                Some(count) if *count != args.len() => {
+                    println!("Generation Error: {} takes {} arguments but {} were given at {}:{}",name,count,args.len(),location.file,location.line);
+                    std::process::exit(1);
+                }
+                Some(_) => {}
+                None => {
+                    println!("Generation Error: {} is not a @proc at {}:{}",name,location.file,location.line);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
 pub fn get_register_value(reg:Token) -> Option<InstructionParamType> {
     if reg.token_type != TokenType::Register { return None };
+    if reg.value.as_deref() == Some("bp") { return Some(REGBP) };
     if let Some(val) = reg.value {
-        let val = match val.as_str() {
-            "rax" => REGA,
-            "rbx" => REGB,
-            "rcx" => REGC,
-            "rdx" => REGD,
-            _ => unreachable!()
-        };
-        return Some(val)
+        return Some(REGISTERS.iter().position(|name| *name == val).unwrap() as InstructionParamType)
     }
     None
 }
@@ -924,14 +1283,7 @@ pub fn get_fregister_value(reg:Token) -> Option<InstructionParamType> {
     if reg.token_type != TokenType::FloatRegister { return None };
     
     if let Some(val) = reg.value {
-        let val = match val.as_str() {
-            "fa" => REGA,
-            "fb" => REGB,
-            "fc" => REGC,
-            "fd" => REGD,
-            _ => unreachable!()
-        };
-        return Some(val)
+        return Some(FLOAT_REGISTERS.iter().position(|name| *name == val).unwrap() as InstructionParamType)
     }
     None
 }
@@ -961,6 +1313,26 @@ pub fn get_register(value:&NodeExpr) -> InstructionParamType {
     };
 }
 
+/// Integer literals are checked to fit 64 bits by the tokenizer, the ones above `i64::MAX` wrap.
+pub fn parse_int(value:&Token) -> iRegisterDataType {
+    let text = value.value.clone().unwrap();
+    match text.parse::<iRegisterDataType>() {
+        Ok(int) => int,
+        Err(_) => text.parse::<RegisterDataType>().unwrap() as iRegisterDataType,
+    }
+}
+
+/// Parses an integer literal used as the 32 bit immediate of an instruction.
+pub fn immediate(value:&Token) -> iInstructionParamType {
+    match iInstructionParamType::try_from(parse_int(value)) {
+        Ok(int) => int,
+        Err(_) => {
+            println!("Number {} does not fit in a 32 bit immediate. Move it into a register first.",value.value.clone().unwrap());
+            std::process::exit(1);
+        }
+    }
+}
+
 pub fn get_fregister(value:&NodeExpr) -> InstructionParamType {
     match value  {
         NodeExpr::NodeExprRegister { value } => {
@@ -977,6 +1349,45 @@ pub fn get_fregister(value:&NodeExpr) -> InstructionParamType {
     };
 }
 
+/// `getmem` or `setmem` accessing `width`.
+pub fn memory_instruction(width:MemoryWidth, set:bool, id:InstructionParamType, reg:InstructionParamType, offset:InstructionParamType) -> Instruction {
+    match (width,set) {
+        (MemoryWidth::Cell,false) => Instruction::GetMemory(id,reg,offset),
+        (MemoryWidth::Bits8 { signed: false },false) => Instruction::GetMemory8(id,reg,offset),
+        (MemoryWidth::Bits8 { signed: true },false) => Instruction::GetMemory8Signed(id,reg,offset),
+        (MemoryWidth::Bits16 { signed: false },false) => Instruction::GetMemory16(id,reg,offset),
+        (MemoryWidth::Bits16 { signed: true },false) => Instruction::GetMemory16Signed(id,reg,offset),
+        (MemoryWidth::Bits32 { signed: false },false) => Instruction::GetMemory32(id,reg,offset),
+        (MemoryWidth::Bits32 { signed: true },false) => Instruction::GetMemory32Signed(id,reg,offset),
+        (MemoryWidth::Cell,true) => Instruction::SetMemory(id,reg,offset),
+        (MemoryWidth::Bits8 {..},true) => Instruction::SetMemory8(id,reg,offset),
+        (MemoryWidth::Bits16 {..},true) => Instruction::SetMemory16(id,reg,offset),
+        (MemoryWidth::Bits32 {..},true) => Instruction::SetMemory32(id,reg,offset),
+    }
+}
+
+/// The `CONDITION_` constant of a `cmov` or `set` token.
+pub fn get_condition(value:&NodeExpr) -> InstructionParamType {
+    match get_jump_label(value.clone()).unwrap().as_str() {
+        "z" => CONDITION_ZERO,
+        "nz" => CONDITION_NOT_ZERO,
+        "e" => CONDITION_EQUAL,
+        "ne" => CONDITION_NOT_EQUAL,
+        "g" => CONDITION_GREATER,
+        "l" => CONDITION_LESS,
+        "ge" => CONDITION_GREATER_OR_EQUAL,
+        "le" => CONDITION_LESS_OR_EQUAL,
+        "a" => CONDITION_ABOVE,
+        "b" => CONDITION_BELOW,
+        "ae" => CONDITION_ABOVE_OR_EQUAL,
+        "be" => CONDITION_BELOW_OR_EQUAL,
+        "o" => CONDITION_OVERFLOW,
+        "no" => CONDITION_NOT_OVERFLOW,
+        "c" => CONDITION_CARRY,
+        _ => CONDITION_NOT_CARRY,
+    }
+}
+
 pub fn get_flag(value:&Token) -> InstructionParamType {
     let v = match value.value.clone().unwrap().as_str() {
         "zf" => ZERO_FLAG ,
@@ -984,6 +1395,11 @@ pub fn get_flag(value:&Token) -> InstructionParamType {
         "gf" => GREATER_THAN_FLAG,
 
         "lf" => LESS_THAN_FLAG,
+        "bf" => BELOW_FLAG,
+        "af" => ABOVE_FLAG,
+        "of" => OVERFLOW_FLAG,
+        "cf" => CARRY_FLAG,
+        "sf" => SIGN_FLAG,
          _ => {
             println!("Internal Error. Flag {:?} does note exist.",value);
             std::process::exit(1);
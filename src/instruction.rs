@@ -1,5 +1,5 @@
 use crate::constants_and_types::*;
-use crate::{to_binary_slice,binary_slice_to_number,ones_complement,twos_complement,to_float_repr};
+use crate::{to_binary_slice,twos_complement,to_float_repr};
 
 #[derive(Debug,Clone,PartialEq)]
 pub enum StringNumberUnion {
@@ -13,6 +13,35 @@ impl Default for StringNumberUnion {
     }
 }
 
+/// Settings for a whole program, stored in the first `HEADER_SIZE` bits of the bytecode.
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct ProgramHeader {
+    /// Integer registers, stack slots and memory cells hold 64 bit numbers instead of 32 bit ones.
+    pub wide_integers: bool,
+    /// Float registers hold f64 instead of f32.
+    pub double_floats: bool,
+}
+
+impl ProgramHeader {
+    pub fn to_binary(self) -> Vec<u8> {
+        let mut header:HeaderBinaryType = 0;
+        if self.wide_integers {
+            header |= HEADER_WIDE_INTEGERS;
+        }
+        if self.double_floats {
+            header |= HEADER_DOUBLE_FLOATS;
+        }
+        to_binary_slice!(HeaderBinaryType,header).to_vec()
+    }
+
+    pub fn from_number(header:HeaderBinaryType) -> Self {
+        Self {
+            wide_integers: header & HEADER_WIDE_INTEGERS != 0,
+            double_floats: header & HEADER_DOUBLE_FLOATS != 0,
+        }
+    }
+}
+
 #[derive(Debug,Clone,PartialEq)]
 pub enum Instruction {
     Halt,
@@ -52,6 +81,20 @@ pub enum Instruction {
     GetMemory(InstructionParamType,InstructionParamType,InstructionParamType),
     //       Memory unit id        src                 offset 
     SetMemory(InstructionParamType,InstructionParamType,InstructionParamType),
+    /// Same arguments as `GetMemory`, but the offset counts bytes and only that many bits are
+    /// loaded, with zero or sign extension. Byte `n` of a unit is byte `n % w` of cell `n / w`,
+    /// lowest first, where `w` is the number of bytes in a register.
+    GetMemory8(InstructionParamType,InstructionParamType,InstructionParamType),
+    GetMemory8Signed(InstructionParamType,InstructionParamType,InstructionParamType),
+    GetMemory16(InstructionParamType,InstructionParamType,InstructionParamType),
+    GetMemory16Signed(InstructionParamType,InstructionParamType,InstructionParamType),
+    GetMemory32(InstructionParamType,InstructionParamType,InstructionParamType),
+    GetMemory32Signed(InstructionParamType,InstructionParamType,InstructionParamType),
+    /// Same arguments as `SetMemory`, but the offset counts bytes like for `GetMemory8` and only
+    /// the low bits of the register are stored. The rest of a cell that was not set becomes 0.
+    SetMemory8(InstructionParamType,InstructionParamType,InstructionParamType),
+    SetMemory16(InstructionParamType,InstructionParamType,InstructionParamType),
+    SetMemory32(InstructionParamType,InstructionParamType,InstructionParamType),
 
 
     Or(InstructionParamType,InstructionParamType),
@@ -92,6 +135,140 @@ pub enum Instruction {
     /// arg 3: start location of the new location to copy the data. value is relative to the sp 
     StackCopyBackSp(InstructionParamType,InstructionParamType,InstructionParamType),
 
+    // Same as the instructions without `Immediate`, but the second operand is a number
+    // encoded in the instruction instead of a register.
+    AddImmediate(InstructionParamType,iInstructionParamType),
+    SubImmediate(InstructionParamType,iInstructionParamType),
+    MulImmediate(InstructionParamType,iInstructionParamType),
+    DivImmediate(InstructionParamType,iInstructionParamType),
+    ModImmediate(InstructionParamType,iInstructionParamType),
+    AndImmediate(InstructionParamType,iInstructionParamType),
+    OrImmediate(InstructionParamType,iInstructionParamType),
+    XorImmediate(InstructionParamType,iInstructionParamType),
+    CompareImmediate(InstructionParamType,iInstructionParamType),
+
+    /// Copies the second register into the first.
+    MovRegister(InstructionParamType,InstructionParamType),
+    /// Copies the second float register into the first.
+    MovfRegister(InstructionParamType,InstructionParamType),
+    /// arg 1: float register. arg 2: register whose signed value is converted to a float.
+    MovIntToFloat(InstructionParamType,InstructionParamType),
+    /// arg 1: register. arg 2: float register whose value is converted to an integer,
+    /// rounding towards zero and saturating at the integer limits. NaN becomes 0.
+    MovFloatToInt(InstructionParamType,InstructionParamType),
+
+    // Shifts and rotates of the first register by the count in the second register.
+    // The count is read as unsigned. Shifting by the register width or more gives 0 for `Shl` and
+    // `Shr` and fills the register with the sign bit for `Sar`. Rotates use the count modulo the width.
+    Shl(InstructionParamType,InstructionParamType),
+    Shr(InstructionParamType,InstructionParamType),
+    Sar(InstructionParamType,InstructionParamType),
+    Rol(InstructionParamType,InstructionParamType),
+    Ror(InstructionParamType,InstructionParamType),
+    // Same as above with the count encoded in the instruction.
+    ShlImmediate(InstructionParamType,iInstructionParamType),
+    ShrImmediate(InstructionParamType,iInstructionParamType),
+    SarImmediate(InstructionParamType,iInstructionParamType),
+    RolImmediate(InstructionParamType,iInstructionParamType),
+    RorImmediate(InstructionParamType,iInstructionParamType),
+
+    // Same as `Compare`, `Div` and `Mod` but reading the registers as unsigned numbers.
+    // `CompareUnsigned` sets the less and greater flags from the unsigned order too.
+    CompareUnsigned(InstructionParamType,InstructionParamType),
+    DivUnsigned(InstructionParamType,InstructionParamType),
+    ModUnsigned(InstructionParamType,InstructionParamType),
+    CompareUnsignedImmediate(InstructionParamType,iInstructionParamType),
+    DivUnsignedImmediate(InstructionParamType,iInstructionParamType),
+    ModUnsignedImmediate(InstructionParamType,iInstructionParamType),
+
+    JumpIfGreaterOrEqual(StringNumberUnion),
+    JumpIfLessOrEqual(StringNumberUnion),
+    JumpIfAbove(StringNumberUnion),
+    JumpIfBelow(StringNumberUnion),
+    JumpIfAboveOrEqual(StringNumberUnion),
+    JumpIfBelowOrEqual(StringNumberUnion),
+    JumpIfOverflow(StringNumberUnion),
+    JumpIfNotOverflow(StringNumberUnion),
+    JumpIfCarry(StringNumberUnion),
+    JumpIfNotCarry(StringNumberUnion),
+
+    /// arg 1: `ARITHMETIC_WRAPPING` or `ARITHMETIC_TRAPPING`, how add, sub, mul, div and mod
+    /// handle results that do not fit.
+    SetArithmeticMode(InstructionParamType),
+
+    // Same as `Mov` and `Push` with a 64 bit number, for programs with 64 bit integers.
+    MovWide(InstructionParamType,iRegisterDataType),
+    PushWide(iRegisterDataType),
+
+    /// Compares two float registers. Sets the equal, greater and less flags and the zero flag
+    /// when they are equal, the sign and carry flags when the first is less, and the above and
+    /// below flags like greater and less. If either is NaN all of these are cleared and the
+    /// overflow flag is set instead.
+    CompareFloat(InstructionParamType,InstructionParamType),
+    /// arg 1: register. arg 2: float register. arg 3: rounding mode, one of the `ROUND_` constants.
+    /// Saturates and turns NaN into 0 like `MovFloatToInt`.
+    MovFloatToIntRounded(InstructionParamType,InstructionParamType,InstructionParamType),
+    // Copy the bits of a float register into an integer register and back without converting.
+    // Only as many bits as both registers hold are copied, the rest are zero.
+    MovFloatBitsToInt(InstructionParamType,InstructionParamType),
+    MovIntBitsToFloat(InstructionParamType,InstructionParamType),
+    /// arg 1: destination float register. arg 2: float register. arg 3: one of the `MATH_`
+    /// constants. One operand functions compute f(arg 2), two operand ones f(arg 1, arg 2).
+    /// The results are the same on every platform, see float_math.rs.
+    FloatMath(InstructionParamType,InstructionParamType,InstructionParamType),
+
+    // Same as `Jump` and `Call` with the address taken from a register. The address must be
+    // one that a `LoadAddress` in the program loads.
+    JumpRegister(InstructionParamType),
+    CallRegister(InstructionParamType),
+    /// arg 1: register. arg 2: label or instruction number, stored in the register as an
+    /// address for `JumpRegister` and `CallRegister`.
+    LoadAddress(InstructionParamType,StringNumberUnion),
+    /// arg 1: register. arg 2: number of cases. Jumps to the instruction as many places after
+    /// the next one as the register holds, read as unsigned, or as many as there are cases when
+    /// it holds more. The generator follows it with a `Jump` for every case and one for the default.
+    JumpTable(InstructionParamType,InstructionParamType),
+
+    /// arg 1: number of locals. Pushes `bp`, points `bp` at the top of the stack and pushes that
+    /// many zeros, so the locals are at `bp`, `bp + 1` and so on and the values pushed before at
+    /// `bp - 2`, `bp - 3` and so on. `bp - 1` holds the `bp` of the caller.
+    Enter(InstructionParamType),
+    /// Drops everything from `bp` up and pops `bp` back, undoing `Enter`.
+    Leave,
+    // Load and store the stack slot `bp + arg 2`. arg 1: register.
+    GetFromBasePointer(InstructionParamType,iInstructionParamType),
+    SetFromBasePointer(InstructionParamType,iInstructionParamType),
+
+    /// Same as `Jump`. Used in place of a `Call` followed by a `Return`, the routine it jumps to
+    /// returns to the caller of this one so the return addresses do not grow.
+    TailCall(StringNumberUnion),
+    /// arg 1: register. arg 2: label or instruction number. Subtracts 1 from the register and
+    /// jumps while it is not 0. The flags are left as they are.
+    Loop(InstructionParamType,StringNumberUnion),
+
+    /// arg 1: destination register. arg 2: register. arg 3: one of the `CONDITION_` constants.
+    /// Copies arg 2 into arg 1 when the condition holds, the flags are left as they are.
+    ConditionalMove(InstructionParamType,InstructionParamType,InstructionParamType),
+    /// arg 1: register. arg 2: one of the `CONDITION_` constants. Sets the register to 1 when
+    /// the condition holds and to 0 otherwise.
+    SetIf(InstructionParamType,InstructionParamType),
+
+    /// arg 1: label or instruction number of a handler. Until the matching `EndTry`, a runtime
+    /// error or `Throw` goes to the handler instead of stopping the program, with the stack,
+    /// `bp` and the return addresses as they were here and the error code in rax.
+    Try(StringNumberUnion),
+    /// Removes the handler of the last `Try`.
+    EndTry,
+    /// Raises the error code in the register, which goes to the handler of the last `Try`.
+    Throw(InstructionParamType),
+
+    /// Hands control to an attached debugger, or prints the registers, flags and top of the
+    /// stack when there is none and goes on.
+    Breakpoint,
+    /// arg 1: length of the message. Raises `ERROR_ASSERT` with the message, which is the
+    /// characters of the last `Push`es before it, one per push.
+    AssertFailed(InstructionParamType),
+
 }
 
 impl Instruction {
@@ -110,6 +287,19 @@ impl Instruction {
                 instr_binary.append(&mut b_binary);
                 return instr_binary
             }
+            AddImmediate(a,b) | SubImmediate(a,b) | MulImmediate(a,b) | DivImmediate(a,b) | ModImmediate(a,b) | AndImmediate(a,b) | OrImmediate(a,b) | XorImmediate(a,b) | CompareImmediate(a,b) |
+            ShlImmediate(a,b) | ShrImmediate(a,b) | SarImmediate(a,b) | RolImmediate(a,b) | RorImmediate(a,b) |
+            CompareUnsignedImmediate(a,b) | DivUnsignedImmediate(a,b) | ModUnsignedImmediate(a,b) |
+            GetFromBasePointer(a,b) | SetFromBasePointer(a,b) => {
+                let mut a_binary = to_binary_slice!(InstructionParamType,*a).to_vec();
+                let b = twos_complement!(InstructionParamType,*b);
+                let mut b_binary = to_binary_slice!(InstructionParamType,b).to_vec();
+                let mut instr_binary = to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec();
+
+                instr_binary.append(&mut a_binary);
+                instr_binary.append(&mut b_binary);
+                return instr_binary
+            }
             Add(a,b) | Sub(a,b) | Div(a,b) | Mul(a,b) | Mod(a,b) |
             Addf(a,b) | Subf(a,b) | Divf(a,b) | Mulf(a,b) | Modf(a,b) | 
             Compare(a,b) |
@@ -120,7 +310,13 @@ impl Instruction {
 
             TruncateStackRange(a,b) | 
             ExtendStack(a,b) |
-            Write(a,b)
+            Write(a,b) |
+            MovRegister(a,b) | MovfRegister(a,b) | MovIntToFloat(a,b) | MovFloatToInt(a,b) |
+            Shl(a,b) | Shr(a,b) | Sar(a,b) | Rol(a,b) | Ror(a,b) |
+            CompareUnsigned(a,b) | DivUnsigned(a,b) | ModUnsigned(a,b) |
+            CompareFloat(a,b) | MovFloatBitsToInt(a,b) | MovIntBitsToFloat(a,b) |
+            JumpTable(a,b) |
+            SetIf(a,b)
             => {
                 
                 let mut a_binary = to_binary_slice!(InstructionParamType,*a).to_vec();
@@ -142,7 +338,13 @@ impl Instruction {
                 Not(a)| 
                 GetStackPointer(a)|
                 Malloc(a) | 
-                Free(a)
+                Free(a) |
+                SetArithmeticMode(a) |
+                JumpRegister(a) |
+                CallRegister(a) |
+                Enter(a) |
+                Throw(a) |
+                AssertFailed(a)
                 => {
                     let mut a_binary = to_binary_slice!(InstructionParamType,*a).to_vec();
                     let mut instr_binary = to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec();
@@ -152,7 +354,7 @@ impl Instruction {
 
             Movf(a,b) => {
                 let mut a_binary = to_binary_slice!(InstructionParamType,*a).to_vec();
-                let mut b_binary = to_binary_slice!(InstructionParamType,to_float_repr!(FloatInstructionParamType,InstructionParamType,*b)).to_vec();
+                let mut b_binary = to_binary_slice!(u64,to_float_repr!(FloatInstructionParamType,u64,*b)).to_vec();
  
                 let mut instr_binary = to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec();
 
@@ -161,7 +363,7 @@ impl Instruction {
                 return instr_binary               
             }
 
-            Return => return to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec(),
+            Return | Leave | EndTry | Breakpoint => return to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec(),
             Jump(s) |  
                 JumpIfZero(s)|
                 JumpIfNotZero(s)|
@@ -169,6 +371,18 @@ impl Instruction {
                 JumpIfNotEqual(s)|
                 JumpIfGreater(s)|
                 JumpIfLess(s)|
+                JumpIfGreaterOrEqual(s)|
+                JumpIfLessOrEqual(s)|
+                JumpIfAbove(s)|
+                JumpIfBelow(s)|
+                JumpIfAboveOrEqual(s)|
+                JumpIfBelowOrEqual(s)|
+                JumpIfOverflow(s)|
+                JumpIfNotOverflow(s)|
+                JumpIfCarry(s)|
+                JumpIfNotCarry(s)|
+                TailCall(s)|
+                Try(s)|
                 Call(s) => {
                     use StringNumberUnion::*;
                 match s {
@@ -183,6 +397,38 @@ impl Instruction {
                 }
             }
 
+            LoadAddress(a,s) | Loop(a,s) => {
+                match s {
+                    StringNumberUnion::Num(b) => {
+                        let mut a_binary = to_binary_slice!(InstructionParamType,*a).to_vec();
+                        let mut b_binary = to_binary_slice!(InstructionParamType,*b).to_vec();
+                        let mut instr_binary = to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec();
+
+                        instr_binary.append(&mut a_binary);
+                        instr_binary.append(&mut b_binary);
+                        return instr_binary
+                    }
+                    StringNumberUnion::String(s) => unreachable!("unresolved label {s}")
+                }
+            }
+
+            MovWide(a,b) => {
+                let mut a_binary = to_binary_slice!(InstructionParamType,*a).to_vec();
+                let mut b_binary = to_binary_slice!(RegisterDataType,twos_complement!(RegisterDataType,*b)).to_vec();
+                let mut instr_binary = to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec();
+
+                instr_binary.append(&mut a_binary);
+                instr_binary.append(&mut b_binary);
+                return instr_binary
+            }
+            PushWide(a) => {
+                let mut a_binary = to_binary_slice!(RegisterDataType,twos_complement!(RegisterDataType,*a)).to_vec();
+                let mut instr_binary = to_binary_slice!(InstructionNameBinaryType,self.get_instruction_number()).to_vec();
+
+                instr_binary.append(&mut a_binary);
+                return instr_binary
+            }
+
             Push(a) => {
                 let a = twos_complement!(InstructionParamType,*a);
                 let mut a_binary = to_binary_slice!(InstructionParamType,a).to_vec();
@@ -194,7 +440,14 @@ impl Instruction {
 
             GetMemory(a,b,c) |
             SetMemory(a,b,c) |  
-	    StackCopyBackSp(a,b,c) => {
+            GetMemory8(a,b,c) | GetMemory8Signed(a,b,c) |
+            GetMemory16(a,b,c) | GetMemory16Signed(a,b,c) |
+            GetMemory32(a,b,c) | GetMemory32Signed(a,b,c) |
+            SetMemory8(a,b,c) | SetMemory16(a,b,c) | SetMemory32(a,b,c) |
+	    StackCopyBackSp(a,b,c) |
+            MovFloatToIntRounded(a,b,c) |
+            FloatMath(a,b,c) |
+            ConditionalMove(a,b,c) => {
 
                 let mut a_binary = to_binary_slice!(InstructionParamType,*a).to_vec();
                 let mut b_binary = to_binary_slice!(InstructionParamType, *b).to_vec();
@@ -207,11 +460,6 @@ impl Instruction {
                 return instr_binary 
                
             }
-
-
-            _ => {
-                panic!("Unimplemented: {:?}",self);
-            }
         }
     }
 
@@ -270,6 +518,79 @@ impl Instruction {
             Write(..) => 48,
             Free(..) => 49,
 	    StackCopyBackSp(..) => 50,
+            AddImmediate(..) => 51,
+            SubImmediate(..) => 52,
+            MulImmediate(..) => 53,
+            DivImmediate(..) => 54,
+            ModImmediate(..) => 55,
+            AndImmediate(..) => 56,
+            OrImmediate(..) => 57,
+            XorImmediate(..) => 58,
+            CompareImmediate(..) => 59,
+            MovRegister(..) => 60,
+            MovfRegister(..) => 61,
+            MovIntToFloat(..) => 62,
+            MovFloatToInt(..) => 63,
+            Shl(..) => 64,
+            Shr(..) => 65,
+            Sar(..) => 66,
+            Rol(..) => 67,
+            Ror(..) => 68,
+            ShlImmediate(..) => 69,
+            ShrImmediate(..) => 70,
+            SarImmediate(..) => 71,
+            RolImmediate(..) => 72,
+            RorImmediate(..) => 73,
+            CompareUnsigned(..) => 74,
+            DivUnsigned(..) => 75,
+            ModUnsigned(..) => 76,
+            CompareUnsignedImmediate(..) => 77,
+            DivUnsignedImmediate(..) => 78,
+            ModUnsignedImmediate(..) => 79,
+            JumpIfGreaterOrEqual(..) => 80,
+            JumpIfLessOrEqual(..) => 81,
+            JumpIfAbove(..) => 82,
+            JumpIfBelow(..) => 83,
+            JumpIfAboveOrEqual(..) => 84,
+            JumpIfBelowOrEqual(..) => 85,
+            JumpIfOverflow(..) => 86,
+            JumpIfNotOverflow(..) => 87,
+            JumpIfCarry(..) => 88,
+            JumpIfNotCarry(..) => 89,
+            SetArithmeticMode(..) => 90,
+            MovWide(..) => 91,
+            PushWide(..) => 92,
+            CompareFloat(..) => 93,
+            MovFloatToIntRounded(..) => 94,
+            MovFloatBitsToInt(..) => 95,
+            MovIntBitsToFloat(..) => 96,
+            FloatMath(..) => 97,
+            JumpRegister(..) => 98,
+            CallRegister(..) => 99,
+            LoadAddress(..) => 100,
+            JumpTable(..) => 101,
+            Enter(..) => 102,
+            Leave => 103,
+            GetFromBasePointer(..) => 104,
+            SetFromBasePointer(..) => 105,
+            TailCall(..) => 106,
+            Loop(..) => 107,
+            ConditionalMove(..) => 108,
+            SetIf(..) => 109,
+            Try(..) => 110,
+            EndTry => 111,
+            Throw(..) => 112,
+            Breakpoint => 113,
+            AssertFailed(..) => 114,
+            GetMemory8(..) => 115,
+            GetMemory8Signed(..) => 116,
+            GetMemory16(..) => 117,
+            GetMemory16Signed(..) => 118,
+            GetMemory32(..) => 119,
+            GetMemory32Signed(..) => 120,
+            SetMemory8(..) => 121,
+            SetMemory16(..) => 122,
+            SetMemory32(..) => 123,
 	    
         }
     }
@@ -330,6 +651,79 @@ impl Instruction {
             48 => Some(Write(InstructionParamType::default(),InstructionParamType::default())),
             49 => Some(Free(InstructionParamType::default())),
             50 => Some(StackCopyBackSp(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            51 => Some(AddImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            52 => Some(SubImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            53 => Some(MulImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            54 => Some(DivImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            55 => Some(ModImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            56 => Some(AndImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            57 => Some(OrImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            58 => Some(XorImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            59 => Some(CompareImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            60 => Some(MovRegister(InstructionParamType::default(), InstructionParamType::default())),
+            61 => Some(MovfRegister(InstructionParamType::default(), InstructionParamType::default())),
+            62 => Some(MovIntToFloat(InstructionParamType::default(), InstructionParamType::default())),
+            63 => Some(MovFloatToInt(InstructionParamType::default(), InstructionParamType::default())),
+            64 => Some(Shl(InstructionParamType::default(), InstructionParamType::default())),
+            65 => Some(Shr(InstructionParamType::default(), InstructionParamType::default())),
+            66 => Some(Sar(InstructionParamType::default(), InstructionParamType::default())),
+            67 => Some(Rol(InstructionParamType::default(), InstructionParamType::default())),
+            68 => Some(Ror(InstructionParamType::default(), InstructionParamType::default())),
+            69 => Some(ShlImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            70 => Some(ShrImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            71 => Some(SarImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            72 => Some(RolImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            73 => Some(RorImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            74 => Some(CompareUnsigned(InstructionParamType::default(), InstructionParamType::default())),
+            75 => Some(DivUnsigned(InstructionParamType::default(), InstructionParamType::default())),
+            76 => Some(ModUnsigned(InstructionParamType::default(), InstructionParamType::default())),
+            77 => Some(CompareUnsignedImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            78 => Some(DivUnsignedImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            79 => Some(ModUnsignedImmediate(InstructionParamType::default(), iInstructionParamType::default())),
+            80 => Some(JumpIfGreaterOrEqual(StringNumberUnion::default())),
+            81 => Some(JumpIfLessOrEqual(StringNumberUnion::default())),
+            82 => Some(JumpIfAbove(StringNumberUnion::default())),
+            83 => Some(JumpIfBelow(StringNumberUnion::default())),
+            84 => Some(JumpIfAboveOrEqual(StringNumberUnion::default())),
+            85 => Some(JumpIfBelowOrEqual(StringNumberUnion::default())),
+            86 => Some(JumpIfOverflow(StringNumberUnion::default())),
+            87 => Some(JumpIfNotOverflow(StringNumberUnion::default())),
+            88 => Some(JumpIfCarry(StringNumberUnion::default())),
+            89 => Some(JumpIfNotCarry(StringNumberUnion::default())),
+            90 => Some(SetArithmeticMode(InstructionParamType::default())),
+            91 => Some(MovWide(InstructionParamType::default(), iRegisterDataType::default())),
+            92 => Some(PushWide(iRegisterDataType::default())),
+            93 => Some(CompareFloat(InstructionParamType::default(), InstructionParamType::default())),
+            94 => Some(MovFloatToIntRounded(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            95 => Some(MovFloatBitsToInt(InstructionParamType::default(), InstructionParamType::default())),
+            96 => Some(MovIntBitsToFloat(InstructionParamType::default(), InstructionParamType::default())),
+            97 => Some(FloatMath(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            98 => Some(JumpRegister(InstructionParamType::default())),
+            99 => Some(CallRegister(InstructionParamType::default())),
+            100 => Some(LoadAddress(InstructionParamType::default(), StringNumberUnion::default())),
+            101 => Some(JumpTable(InstructionParamType::default(), InstructionParamType::default())),
+            102 => Some(Enter(InstructionParamType::default())),
+            103 => Some(Leave),
+            104 => Some(GetFromBasePointer(InstructionParamType::default(), iInstructionParamType::default())),
+            105 => Some(SetFromBasePointer(InstructionParamType::default(), iInstructionParamType::default())),
+            106 => Some(TailCall(StringNumberUnion::default())),
+            107 => Some(Loop(InstructionParamType::default(), StringNumberUnion::default())),
+            108 => Some(ConditionalMove(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            109 => Some(SetIf(InstructionParamType::default(), InstructionParamType::default())),
+            110 => Some(Try(StringNumberUnion::default())),
+            111 => Some(EndTry),
+            112 => Some(Throw(InstructionParamType::default())),
+            113 => Some(Breakpoint),
+            114 => Some(AssertFailed(InstructionParamType::default())),
+            115 => Some(GetMemory8(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            116 => Some(GetMemory8Signed(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            117 => Some(GetMemory16(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            118 => Some(GetMemory16Signed(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            119 => Some(GetMemory32(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            120 => Some(GetMemory32Signed(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            121 => Some(SetMemory8(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            122 => Some(SetMemory16(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
+            123 => Some(SetMemory32(InstructionParamType::default(), InstructionParamType::default(), InstructionParamType::default())),
             _ => unimplemented!(),
         }
     }
@@ -338,7 +732,11 @@ impl Instruction {
         use Instruction::*;
         match self {
             Halt => { (None,None,None) },
-            Mov(_,_) => { (Some(REGISTER_PARAM_SIZE),Some(INT_PARAM_SIZE),None)  }
+            Mov(_,_) |
+                AddImmediate(..) | SubImmediate(..) | MulImmediate(..) | DivImmediate(..) | ModImmediate(..) | AndImmediate(..) | OrImmediate(..) | XorImmediate(..) | CompareImmediate(..) |
+                ShlImmediate(..) | ShrImmediate(..) | SarImmediate(..) | RolImmediate(..) | RorImmediate(..) |
+                CompareUnsignedImmediate(..) | DivUnsignedImmediate(..) | ModUnsignedImmediate(..) |
+                GetFromBasePointer(..) | SetFromBasePointer(..) => { (Some(REGISTER_PARAM_SIZE),Some(INT_PARAM_SIZE),None)  }
             Add(_,_) | Sub(_,_) | Div(_,_) | Mul(_,_) | Mod(_,_) |
                 Addf(_,_) | Subf(_,_) | Divf(_,_) | Mulf(_,_) | Modf(_,_) | 
                 Compare(_,_) |
@@ -348,7 +746,11 @@ impl Instruction {
                 GetFlag(_,_) |
                 TruncateStackRange(_,_) | 
                 ExtendStack(..) |
-                Write(_,_)
+                Write(_,_) |
+                MovRegister(_,_) | MovfRegister(_,_) | MovIntToFloat(_,_) | MovFloatToInt(_,_) |
+                Shl(_,_) | Shr(_,_) | Sar(_,_) | Rol(_,_) | Ror(_,_) |
+                CompareUnsigned(_,_) | DivUnsigned(_,_) | ModUnsigned(_,_) |
+                CompareFloat(_,_) | MovFloatBitsToInt(_,_) | MovIntBitsToFloat(_,_) | SetIf(_,_)
                 => {
                     (Some(REGISTER_PARAM_SIZE),Some(REGISTER_PARAM_SIZE),None)
                 }
@@ -363,7 +765,13 @@ impl Instruction {
                 Not(_) |
                 GetStackPointer(_) |
                 Malloc(..) |
-                Free(..)
+                Free(..) |
+                SetArithmeticMode(..) |
+                JumpRegister(..) |
+                CallRegister(..) |
+                Enter(..) |
+                Throw(..) |
+                AssertFailed(..)
                 => {
                     (Some(REGISTER_PARAM_SIZE),None,None)
                 }
@@ -372,7 +780,12 @@ impl Instruction {
                 (Some(REGISTER_PARAM_SIZE),Some(FLOAT_PARAM_SIZE),None)
             }
 
-            Return => { (None,None,None) } 
+            MovWide(_,_) => (Some(REGISTER_PARAM_SIZE),Some(WIDE_INT_PARAM_SIZE),None),
+            PushWide(_) => (Some(WIDE_INT_PARAM_SIZE),None,None),
+            LoadAddress(_,_) | Loop(_,_) => (Some(REGISTER_PARAM_SIZE),Some(JUMP_DESTINATION_PARAM_SIZE),None),
+            JumpTable(_,_) => (Some(REGISTER_PARAM_SIZE),Some(INT_PARAM_SIZE),None),
+
+            Return | Leave | EndTry | Breakpoint => { (None,None,None) } 
 
 
             Jump(_) |
@@ -382,20 +795,99 @@ impl Instruction {
                 JumpIfNotEqual(_) |
                 JumpIfGreater(_) |
                 JumpIfLess(_) |
+                JumpIfGreaterOrEqual(_) |
+                JumpIfLessOrEqual(_) |
+                JumpIfAbove(_) |
+                JumpIfBelow(_) |
+                JumpIfAboveOrEqual(_) |
+                JumpIfBelowOrEqual(_) |
+                JumpIfOverflow(_) |
+                JumpIfNotOverflow(_) |
+                JumpIfCarry(_) |
+                JumpIfNotCarry(_) |
+                TailCall(_) |
+                Try(_) |
                 Call(_) => {
                     (Some(JUMP_DESTINATION_PARAM_SIZE),None,None)
                 }
 
               GetMemory(..) |
                 SetMemory(..) |
-		StackCopyBackSp(..) => {
+                GetMemory8(..) | GetMemory8Signed(..) |
+                GetMemory16(..) | GetMemory16Signed(..) |
+                GetMemory32(..) | GetMemory32Signed(..) |
+                SetMemory8(..) | SetMemory16(..) | SetMemory32(..) |
+		StackCopyBackSp(..) |
+                MovFloatToIntRounded(..) |
+                FloatMath(..) |
+                ConditionalMove(..) => {
                     (Some(REGISTER_PARAM_SIZE),Some(REGISTER_PARAM_SIZE),Some(REGISTER_PARAM_SIZE))
-                }               
-            _ => {
-                (None,None,None)
-            }
+                }
         }
     }
 
 }
 
+/// The flags a condition of `ConditionalMove` and `SetIf` tests, and whether it is negated. The
+/// condition holds when any of the flags is set, or when none is if it is negated, the same as
+/// the matching jump.
+pub fn condition_flags(condition:InstructionParamType) -> Option<(&'static [usize],bool)> {
+    Some(match condition {
+        CONDITION_ZERO => (&[ZERO_FLAG],false),
+        CONDITION_NOT_ZERO => (&[ZERO_FLAG],true),
+        CONDITION_EQUAL => (&[EQUAL_FLAG],false),
+        CONDITION_NOT_EQUAL => (&[EQUAL_FLAG],true),
+        CONDITION_GREATER => (&[GREATER_THAN_FLAG],false),
+        CONDITION_LESS => (&[LESS_THAN_FLAG],false),
+        CONDITION_GREATER_OR_EQUAL => (&[GREATER_THAN_FLAG,EQUAL_FLAG],false),
+        CONDITION_LESS_OR_EQUAL => (&[LESS_THAN_FLAG,EQUAL_FLAG],false),
+        CONDITION_ABOVE => (&[ABOVE_FLAG],false),
+        CONDITION_BELOW => (&[BELOW_FLAG],false),
+        CONDITION_ABOVE_OR_EQUAL => (&[ABOVE_FLAG,EQUAL_FLAG],false),
+        CONDITION_BELOW_OR_EQUAL => (&[BELOW_FLAG,EQUAL_FLAG],false),
+        CONDITION_OVERFLOW => (&[OVERFLOW_FLAG],false),
+        CONDITION_NOT_OVERFLOW => (&[OVERFLOW_FLAG],true),
+        CONDITION_CARRY => (&[CARRY_FLAG],false),
+        CONDITION_NOT_CARRY => (&[CARRY_FLAG],true),
+        _ => return None,
+    })
+}
+
+/// The jump to `destination` that is taken when `condition` holds.
+pub fn jump_if(condition:InstructionParamType, destination:StringNumberUnion) -> Option<Instruction> {
+    use Instruction::*;
+    Some(match condition {
+        CONDITION_ZERO => JumpIfZero(destination),
+        CONDITION_NOT_ZERO => JumpIfNotZero(destination),
+        CONDITION_EQUAL => JumpIfEqual(destination),
+        CONDITION_NOT_EQUAL => JumpIfNotEqual(destination),
+        CONDITION_GREATER => JumpIfGreater(destination),
+        CONDITION_LESS => JumpIfLess(destination),
+        CONDITION_GREATER_OR_EQUAL => JumpIfGreaterOrEqual(destination),
+        CONDITION_LESS_OR_EQUAL => JumpIfLessOrEqual(destination),
+        CONDITION_ABOVE => JumpIfAbove(destination),
+        CONDITION_BELOW => JumpIfBelow(destination),
+        CONDITION_ABOVE_OR_EQUAL => JumpIfAboveOrEqual(destination),
+        CONDITION_BELOW_OR_EQUAL => JumpIfBelowOrEqual(destination),
+        CONDITION_OVERFLOW => JumpIfOverflow(destination),
+        CONDITION_NOT_OVERFLOW => JumpIfNotOverflow(destination),
+        CONDITION_CARRY => JumpIfCarry(destination),
+        CONDITION_NOT_CARRY => JumpIfNotCarry(destination),
+        _ => return None,
+    })
+}
+
+/// The message of the `AssertFailed` at `at`, read back from the pushes the generator puts
+/// before it. `None` when they are not there.
+pub fn assert_message(instructions:&[Instruction], at:usize) -> Option<String> {
+    let len = match instructions.get(at)? {
+        Instruction::AssertFailed(len) => *len as usize,
+        _ => return None,
+    };
+    instructions.get(at.checked_sub(len)?..at)?.iter()
+        .map(|inst| match inst {
+            Instruction::Push(ch) => char::from_u32(*ch as u32),
+            _ => None,
+        })
+        .collect()
+}
@@ -1,4 +1,7 @@
 #![recursion_limit = "10000"]
+// The node and token enums prefix their variants with the enum's name, `iRegisterDataType` and
+// `iInstructionParamType` name the signed types like `i64`, and routines end in explicit `return`s.
+#![allow(clippy::enum_variant_names,clippy::needless_return,non_camel_case_types)]
 mod instruction;
 mod conversions;
 mod util_macros;
@@ -9,18 +12,74 @@ mod parser;
 mod generator;
 mod assembler;
 mod memory;
+mod optimizer;
+mod c_backend;
+mod x86_backend;
+mod profiler;
+mod coverage;
+mod debugger;
+mod float_math;
 
 fn main() {
-    use assembler::Basm;
+    use assembler::{Basm,EmitTarget};
     use std::env;
 
     let args = env::args().collect::<Vec<String>>();
     if args.len() < 2 {
-        println!("Incorrect Usage.\nCorrect usage:\n\tbytecode <file-name>.basm");
+        print_usage();
         std::process::exit(1);
     }
+
+    if args[1] == "build" {
+        let mut target = None;
+        let mut output = None;
+        let mut filename = None;
+        let mut i = 2;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--emit" => {
+                    i += 1;
+                    target = args.get(i).and_then(|t| EmitTarget::from_name(t));
+                    if target.is_none() {
+                        println!("Expected a language to emit after --emit, found {:?}. Supported: c, asm",args.get(i));
+                        std::process::exit(1);
+                    }
+                }
+                "-o" => {
+                    i += 1;
+                    output = args.get(i).cloned();
+                }
+                f => filename = Some(f.to_string()),
+            }
+            i += 1;
+        }
+        match (target,filename) {
+            (Some(target),Some(filename)) => Basm::build_file(filename,target,output),
+            _ => {
+                print_usage();
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
     
+    if args[1] == "--profile" || args[1] == "--coverage" || args[1] == "--debug" {
+        match args.get(2) {
+            Some(filename) if args[1] == "--profile" => Basm::profile_file(filename.clone()),
+            Some(filename) if args[1] == "--debug" => Basm::debug_file(filename.clone()),
+            Some(filename) => Basm::coverage_file(filename.clone()),
+            None => {
+                print_usage();
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let filename = &args[1];
    Basm::run_file(filename.clone()); 
 }
 
+fn print_usage() {
+    println!("Incorrect Usage.\nCorrect usage:\n\tbytecode <file-name>.basm\n\tbytecode --profile <file-name>.basm\n\tbytecode --coverage <file-name>.basm\n\tbytecode --debug <file-name>.basm\n\tbytecode build --emit <c|asm> <file-name>.basm [-o <output>]");
+}
@@ -77,10 +77,16 @@ impl Memory {
         }
     }
 
+    #[allow(dead_code)]
     pub fn id(&self) -> usize {
         return self.id 
     }
 
+    /// Number of cells in the unit.
+    pub fn size(&self) -> usize {
+        self.contents.len()
+    }
+
     pub fn get(&self, offset:usize) -> Option<RegisterDataType> {
         *self.contents.get(offset).unwrap_or(&None)
     }
@@ -0,0 +1,217 @@
+use crate::parser::{NodeExpr,NodeInstruction};
+
+use std::collections::{HashMap,HashSet};
+
+/// Removes every instruction that cannot be reached from the `entry` label by falling through,
+/// jumping or calling, and every label that is no longer the target of a jump or call.
+/// This is mostly useful for `@import`, which pulls in every label of the imported file.
+///
+/// If the program jumps to a raw instruction number anywhere, nothing is removed as removing
+/// instructions would change the meaning of that number.
+///
+/// Each instruction can carry extra data (`T`), which is kept with the instructions that remain.
+pub fn eliminate_dead_code<T>(insts:Vec<(Vec<String>,NodeInstruction,T)>,entry:&str) -> Vec<(Vec<String>,NodeInstruction,T)> {
+    let mut label_locations: HashMap<&str,usize> = HashMap::new();
+    for (i,(labels,_,_)) in insts.iter().enumerate() {
+        for label in labels.iter() {
+            label_locations.insert(label.as_str(),i);
+        }
+    }
+    let entry_location = match label_locations.get(entry) {
+        Some(l) => *l,
+        None => return insts,
+    };
+    let uses_raw_address = insts.iter().any(|(_,inst,_)| match get_jump_target(inst) {
+        Some(NodeExpr::NodeExprLabelName { .. }) | None => false,
+        Some(_) => true,
+    });
+    if uses_raw_address {
+        return insts;
+    }
+
+    let mut reachable = vec![false;insts.len()];
+    let mut referenced_labels: HashSet<String> = HashSet::new();
+    referenced_labels.insert(entry.to_string());
+    let mut to_visit = vec![entry_location];
+
+    while let Some(i) = to_visit.pop() {
+        if i >= insts.len() || reachable[i] { continue; }
+        reachable[i] = true;
+        let inst = &insts[i].1;
+
+        if let Some(NodeExpr::NodeExprLabelName { value }) = get_jump_target(inst) {
+            let name = value.value.clone().unwrap();
+            match label_locations.get(name.as_str()) {
+                Some(l) => to_visit.push(*l),
+                None => {
+                    println!("Generation Error: Cannot jump to label {:?} as it does not exist.",name);
+                    std::process::exit(1);
+                }
+            }
+            referenced_labels.insert(name);
+        }
+        if let NodeInstruction::NodeInstructionJumpTable { count, .. } = inst {
+            to_visit.extend(i+1..=i+1+count);
+        }
+        if !is_unconditional_exit(inst) {
+            to_visit.push(i+1);
+        }
+    }
+
+    insts.into_iter()
+        .zip(reachable)
+        .filter(|(_,is_reachable)| *is_reachable)
+        .map(|((labels,inst,data),_)| {
+            let labels = labels.into_iter().filter(|l| referenced_labels.contains(l)).collect::<Vec<String>>();
+            (labels,inst,data)
+        })
+        .collect()
+}
+
+/// Returns the destination of instructions which can transfer control somewhere other than the next instruction.
+/// `lea` counts too, as `jmp` and `call` through a register can only go to addresses it loads.
+fn get_jump_target(inst:&NodeInstruction) -> Option<&NodeExpr> {
+    use NodeInstruction::*;
+    match inst {
+        NodeInstructionJump { value } |
+            NodeInstructionCall { value } |
+            NodeInstructionProcCall { value, .. } |
+            NodeInstructionTailCall { value, .. } |
+            NodeInstructionTry { value } |
+            NodeInstructionJumpIfZero { value } |
+            NodeInstructionJumpIfNotZero { value } |
+            NodeInstructionJumpIfEqual { value } |
+            NodeInstructionJumpIfNotEqual { value } |
+            NodeInstructionJumpIfGreater { value } |
+            NodeInstructionJumpIfLess { value } |
+            NodeInstructionJumpIfGreaterOrEqual { value } |
+            NodeInstructionJumpIfLessOrEqual { value } |
+            NodeInstructionJumpIfAbove { value } |
+            NodeInstructionJumpIfBelow { value } |
+            NodeInstructionJumpIfAboveOrEqual { value } |
+            NodeInstructionJumpIfBelowOrEqual { value } |
+            NodeInstructionJumpIfOverflow { value } |
+            NodeInstructionJumpIfNotOverflow { value } |
+            NodeInstructionJumpIfCarry { value } |
+            NodeInstructionJumpIfNotCarry { value } => Some(value),
+        NodeInstructionLoadAddress { rhs, .. } | NodeInstructionLoop { rhs, .. } => Some(rhs),
+        _ => None
+    }
+}
+
+/// Instructions after which execution never continues with the next instruction.
+fn is_unconditional_exit(inst:&NodeInstruction) -> bool {
+    use NodeInstruction::*;
+    matches!(inst, NodeInstructionJump { .. } | NodeInstructionJumpRegister { .. } | NodeInstructionReturn | NodeInstructionProcReturn { .. } | NodeInstructionTailCall { .. } | NodeInstructionThrow { .. } | NodeInstructionHalt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::{Token,TokenType};
+    use NodeInstruction::*;
+
+    fn token(token_type:TokenType, value:&str) -> Token {
+        Token { token_type, value: Some(value.to_string()) }
+    }
+
+    fn label(name:&str) -> NodeExpr {
+        NodeExpr::NodeExprLabelName { value: token(TokenType::Ident,name) }
+    }
+
+    fn rax() -> NodeExpr {
+        NodeExpr::NodeExprRegister { value: token(TokenType::Register,"rax") }
+    }
+
+    /// Runs the pass from `main` over `program` and returns the positions of the instructions
+    /// that are left, with the labels they keep.
+    fn kept(program:Vec<(&[&str],NodeInstruction)>) -> Vec<(usize,Vec<String>)> {
+        let insts = program.into_iter()
+            .enumerate()
+            .map(|(i,(labels,inst))| (labels.iter().map(|l| l.to_string()).collect(),inst,i))
+            .collect();
+        eliminate_dead_code(insts,"main").into_iter().map(|(labels,_,i)| (i,labels)).collect()
+    }
+
+    fn positions(program:Vec<(&[&str],NodeInstruction)>) -> Vec<usize> {
+        kept(program).into_iter().map(|(i,_)| i).collect()
+    }
+
+    #[test]
+    fn code_after_jmp_ret_halt_and_tailcall_is_removed() {
+        let program: Vec<(&[&str],NodeInstruction)> = vec![
+            (&["main"],NodeInstructionCall { value: label("f") }),
+            (&[],NodeInstructionHalt),
+            (&[],NodeInstructionDisplay { value: rax() }),
+            (&["f"],NodeInstructionJump { value: label("g") }),
+            (&[],NodeInstructionDisplay { value: rax() }),
+            (&["g"],NodeInstructionTailCall { value: label("h"), proc_params: None }),
+            (&[],NodeInstructionDisplay { value: rax() }),
+            (&["h"],NodeInstructionReturn),
+            (&[],NodeInstructionDisplay { value: rax() }),
+        ];
+        assert_eq!(positions(program),[0,1,3,5,7]);
+    }
+
+    #[test]
+    fn labels_nothing_uses_are_removed() {
+        let program: Vec<(&[&str],NodeInstruction)> = vec![
+            (&["main"],NodeInstructionHalt),
+            (&["imported"],NodeInstructionDisplay { value: rax() }),
+            (&[],NodeInstructionReturn),
+        ];
+        assert_eq!(kept(program),[(0,vec!["main".to_string()])]);
+    }
+
+    #[test]
+    fn falling_through_keeps_the_next_label_but_not_its_name() {
+        let program: Vec<(&[&str],NodeInstruction)> = vec![
+            (&["main"],NodeInstructionDisplay { value: rax() }),
+            (&["next"],NodeInstructionHalt),
+        ];
+        assert_eq!(kept(program),[(0,vec!["main".to_string()]),(1,Vec::new())]);
+    }
+
+    #[test]
+    fn lea_loop_and_try_targets_are_kept() {
+        let program: Vec<(&[&str],NodeInstruction)> = vec![
+            (&["main"],NodeInstructionTry { value: label("handler") }),
+            (&[],NodeInstructionLoadAddress { lhs: rax(), rhs: label("callback") }),
+            (&["again"],NodeInstructionLoop { lhs: rax(), rhs: label("again") }),
+            (&[],NodeInstructionHalt),
+            (&["handler"],NodeInstructionHalt),
+            (&["callback"],NodeInstructionReturn),
+            (&["unused"],NodeInstructionReturn),
+        ];
+        let kept = kept(program);
+        assert_eq!(kept.iter().map(|(i,_)| *i).collect::<Vec<usize>>(),[0,1,2,3,4,5]);
+        assert_eq!(kept[4].1,["handler"]);
+        assert_eq!(kept[5].1,["callback"]);
+    }
+
+    #[test]
+    fn every_jump_table_target_is_kept() {
+        let program: Vec<(&[&str],NodeInstruction)> = vec![
+            (&["main"],NodeInstructionJumpTable { value: rax(), count: 2 }),
+            (&[],NodeInstructionJump { value: label("a") }),
+            (&[],NodeInstructionJump { value: label("b") }),
+            (&[],NodeInstructionJump { value: label("c") }),
+            (&["a"],NodeInstructionHalt),
+            (&["b"],NodeInstructionHalt),
+            (&["c"],NodeInstructionHalt),
+            (&[],NodeInstructionDisplay { value: rax() }),
+        ];
+        assert_eq!(positions(program),[0,1,2,3,4,5,6]);
+    }
+
+    #[test]
+    fn a_raw_instruction_number_keeps_everything() {
+        let program: Vec<(&[&str],NodeInstruction)> = vec![
+            (&["main"],NodeInstructionJump { value: NodeExpr::NodeExprIntLit { value: token(TokenType::IntLit,"3") } }),
+            (&[],NodeInstructionDisplay { value: rax() }),
+            (&[],NodeInstructionHalt),
+            (&["unused"],NodeInstructionReturn),
+        ];
+        assert_eq!(kept(program),[(0,vec!["main".to_string()]),(1,Vec::new()),(2,Vec::new()),(3,vec!["unused".to_string()])]);
+    }
+}
@@ -6,11 +6,31 @@ use std::collections::HashMap;
 
 pub struct Parser {
     tokens:Vec<Token>,
+    token_lines:Vec<usize>,
     index: usize,
     instruction_counter:usize,
     pub  labels: HashMap<String,usize>,
     pub builtins: Vec<NodeBuiltin>,
     pub instructions: Vec<NodeInstruction>,
+    /// Source line of each instruction in `instructions`.
+    pub instruction_lines: Vec<usize>,
+    /// The `@proc` being parsed, if any.
+    proc: Option<Proc>,
+    /// Number of `@try`s waiting for their `@endtry`.
+    try_depth: usize,
+}
+
+/// Parameters and locals of a `@proc`, by name.
+struct Proc {
+    params: usize,
+    slots: HashMap<String,iInstructionParamType>,
+}
+
+/// Line of a basm file an instruction was written on.
+#[derive(Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
 }
 
 
@@ -22,12 +42,46 @@ pub enum NodeExpr {
     NodeExprLabelName {value: Token},
     NodeExprStringLit {value:Token},
     NodeExprFlag     {value:Token},
+    /// `[bp - 3]`, the value is the offset from `bp`.
+    NodeExprFrameSlot {value:Token},
 }
 
 #[derive(Debug,Clone)]
 pub enum NodeBuiltin {
     NodeBuiltinImport {value:NodeExpr},
-    NodeBuiltinLoadString { value: NodeExpr , load_len:bool}
+    NodeBuiltinLoadString { value: NodeExpr , load_len:bool},
+    NodeBuiltinBits { value: NodeExpr, float:bool },
+    NodeBuiltinSwitch { value: NodeExpr, cases: Vec<NodeExpr>, default: NodeExpr },
+    NodeBuiltinProc { name: NodeExpr, params: Vec<Token>, locals: Vec<Token> },
+    NodeBuiltinEndProc,
+    NodeBuiltinTry { value: NodeExpr },
+    NodeBuiltinEndTry,
+}
+
+/// What `getmem` and `setmem` access: a whole cell, or the bytes named by the suffix of
+/// `getmem8s` and the like. Stores ignore `signed`.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum MemoryWidth {
+    Cell,
+    Bits8 { signed: bool },
+    Bits16 { signed: bool },
+    Bits32 { signed: bool },
+}
+
+impl MemoryWidth {
+    /// The width of a `GetMemory` or `SetMemory` token from its suffix.
+    fn from_suffix(suffix:Option<&str>) -> Self {
+        match suffix {
+            None => MemoryWidth::Cell,
+            Some("8") => MemoryWidth::Bits8 { signed: false },
+            Some("8s") => MemoryWidth::Bits8 { signed: true },
+            Some("16") => MemoryWidth::Bits16 { signed: false },
+            Some("16s") => MemoryWidth::Bits16 { signed: true },
+            Some("32") => MemoryWidth::Bits32 { signed: false },
+            Some("32s") => MemoryWidth::Bits32 { signed: true },
+            Some(other) => unreachable!("memory width suffix {other}"),
+        }
+    }
 }
 
 #[derive(Debug,Clone)]
@@ -42,12 +96,52 @@ pub enum NodeInstruction  {
 
     NodeInstructionCall           {value:NodeExpr},
     NodeInstructionJump           {value:NodeExpr},
+    /// `jmp` and `call` through the register in `value`.
+    NodeInstructionCallRegister   {value:NodeExpr},
+    NodeInstructionJumpRegister   {value:NodeExpr},
+    /// `rhs` is the label or instruction number whose address is loaded.
+    NodeInstructionLoadAddress    {lhs:NodeExpr, rhs:NodeExpr},
+    /// Always followed by `count` jumps for the cases and one for the default, see `@switch`.
+    NodeInstructionJumpTable      {value:NodeExpr, count:usize},
+    NodeInstructionEnter          {value:NodeExpr},
+    NodeInstructionLeave,
+    /// Start of a `@proc`, which sets up its frame. `params` is only there to check calls.
+    NodeInstructionProcStart      {params:usize, locals:usize},
+    /// `ret` in a `@proc`, which also drops the `params` arguments.
+    NodeInstructionProcReturn     {params:usize},
+    /// `call name(rax, 5)`, which pushes the arguments and calls.
+    NodeInstructionProcCall       {value:NodeExpr, args:Vec<NodeExpr>},
+    /// In a `@proc`, `proc_params` is its number of parameters and its frame is left first like `ret`.
+    NodeInstructionTailCall       {value:NodeExpr, proc_params:Option<usize>},
+    NodeInstructionLoop           {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionTry            {value:NodeExpr},
+    NodeInstructionEndTry,
+    NodeInstructionThrow          {value:NodeExpr},
+    NodeInstructionBreakpoint,
+    /// `compare` is what `cmp` compares first, if anything. `message` is a string literal.
+    NodeInstructionAssert         {condition:NodeExpr, compare:Option<(NodeExpr,NodeExpr)>, message:NodeExpr},
+    /// `condition` is the `ConditionalMove` token, which holds the condition.
+    NodeInstructionConditionalMove {condition:NodeExpr, lhs:NodeExpr, rhs:NodeExpr},
+    /// `condition` is the `SetIf` token, which holds the condition.
+    NodeInstructionSetIf          {condition:NodeExpr, value:NodeExpr},
     NodeInstructionJumpIfZero     {value:NodeExpr},
     NodeInstructionJumpIfNotZero  {value:NodeExpr},
     NodeInstructionJumpIfEqual    {value:NodeExpr},
     NodeInstructionJumpIfNotEqual {value:NodeExpr},
     NodeInstructionJumpIfGreater  {value:NodeExpr},
     NodeInstructionJumpIfLess     {value:NodeExpr},
+    NodeInstructionJumpIfGreaterOrEqual {value:NodeExpr},
+    NodeInstructionJumpIfLessOrEqual {value:NodeExpr},
+    NodeInstructionJumpIfAbove {value:NodeExpr},
+    NodeInstructionJumpIfBelow {value:NodeExpr},
+    NodeInstructionJumpIfAboveOrEqual {value:NodeExpr},
+    NodeInstructionJumpIfBelowOrEqual {value:NodeExpr},
+    NodeInstructionJumpIfOverflow {value:NodeExpr},
+    NodeInstructionJumpIfNotOverflow {value:NodeExpr},
+    NodeInstructionJumpIfCarry {value:NodeExpr},
+    NodeInstructionJumpIfNotCarry {value:NodeExpr},
+    /// `arith wrap` or `arith trap`, value is the mode's name.
+    NodeInstructionSetArithmeticMode {value:NodeExpr},
 
     NodeInstructionCompare  {lhs:NodeExpr, rhs:NodeExpr},
 
@@ -58,8 +152,8 @@ pub enum NodeInstruction  {
 
     NodeInstructionMalloc    {value:NodeExpr},
     NodeInstructionFree      {value:NodeExpr},
-    NodeInstructionGetMemory {lhs:NodeExpr, rhs:NodeExpr,offset:NodeExpr},
-    NodeInstructionSetMemory {lhs:NodeExpr, rhs:NodeExpr, offset:NodeExpr},
+    NodeInstructionGetMemory {lhs:NodeExpr, rhs:NodeExpr,offset:NodeExpr, width:MemoryWidth},
+    NodeInstructionSetMemory {lhs:NodeExpr, rhs:NodeExpr, offset:NodeExpr, width:MemoryWidth},
 
     NodeInstructionReturn,
 
@@ -73,6 +167,16 @@ pub enum NodeInstruction  {
     NodeInstructionXor  {lhs:NodeExpr, rhs:NodeExpr},
     NodeInstructionNand {lhs:NodeExpr, rhs:NodeExpr},
 
+    // Same as `cmp`, `div` and `mod` but treating the registers as unsigned.
+    NodeInstructionCompareUnsigned {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionDivUnsigned {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionModUnsigned {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionShl  {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionShr  {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionSar  {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionRol  {lhs:NodeExpr, rhs:NodeExpr},
+    NodeInstructionRor  {lhs:NodeExpr, rhs:NodeExpr},
+
     NodeInstructionTruncateStack {value:NodeExpr},
     NodeInstructionExtendStack { extend_by:NodeExpr, default_value:NodeExpr},
 
@@ -83,6 +187,12 @@ pub enum NodeInstruction  {
     NodeInstructionDivf {lhs:NodeExpr, rhs:NodeExpr},
     NodeInstructionModf {lhs:NodeExpr, rhs:NodeExpr},
     NodeInstructionDisplayf {value:NodeExpr},
+    NodeInstructionCompareFloat {lhs:NodeExpr, rhs:NodeExpr},
+    /// `mode` is the name of the rounding mode.
+    NodeInstructionConvertFloat {lhs:NodeExpr, rhs:NodeExpr, mode:NodeExpr},
+    NodeInstructionMovBits {lhs:NodeExpr, rhs:NodeExpr},
+    /// `function` is the `FloatMath` token, which holds the name.
+    NodeInstructionFloatMath {function:NodeExpr, lhs:NodeExpr, rhs:NodeExpr},
     NodeInstructionDisplayChar {value: NodeExpr},
     NodeInstructionGetFlag { lhs: NodeExpr, rhs:NodeExpr},
     NodeInstructionGetStackPointer {lhs:NodeExpr},
@@ -94,14 +204,19 @@ pub enum NodeInstruction  {
 
 
 impl  Parser {
-    pub fn new(tokens:Vec<Token>) -> Self {
+    /// `token_lines` holds the line of each token, as collected by `Tokenizer::tokenize`.
+    pub fn new(tokens:Vec<Token>,token_lines:Vec<usize>) -> Self {
         Self {
             tokens,
+            token_lines,
             index:0,
             labels:HashMap::new(),
             builtins:Vec::new(),
             instructions:Vec::new(),
+            instruction_lines:Vec::new(),
             instruction_counter:1,
+            proc:None,
+            try_depth:0,
         }
     }
 
@@ -118,7 +233,12 @@ impl  Parser {
         if let Some(_mov_tok) = self.try_consume(TokenType::Mov) {
             let mut lhs:Option<NodeExpr> = None;
 
-            if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            if self.try_consume(TokenType::BasePointer).is_some() {
+                println!("bp can only be changed by enter and leave.");
+                std::process::exit(1);
+            }else if let Some(slot) = self.parse_frame_slot().or_else(|| self.parse_named_slot()) {
+                lhs = Some(slot);
+            }else if let Some(register_tok) = self.try_consume(TokenType::Register) {
                 lhs = Some(NodeExpr::NodeExprRegister{value:register_tok});
             }else if let Some(int_tok) = self.try_consume(TokenType::IntLit) {
                 let register_tok = get_register_from_number(int_tok.value.clone().unwrap().parse::<i32>().unwrap());
@@ -148,6 +268,23 @@ impl  Parser {
                     rhs:NodeExpr::NodeExprRegister{value:register2_tok}
                 });
 
+            }else if matches!(lhs,NodeExpr::NodeExprRegister{..}) && self.try_consume(TokenType::BasePointer).is_some() {
+                // The generator knows `bp` as a register, it is only the programs that cannot write it.
+                return Some(NodeInstruction::NodeInstructionMov {
+                    lhs,
+                    rhs:NodeExpr::NodeExprRegister{value:Token { token_type: TokenType::Register, value: Some("bp".to_string()) }}
+                });
+            }else if let NodeExpr::NodeExprFrameSlot{..} = lhs {
+                println!("Expected register or number to store in a stack slot, found {:?}",self.peek_token());
+                std::process::exit(1);
+            }else if let Some(slot) = self.parse_frame_slot().or_else(|| self.parse_named_slot()) {
+                return Some(NodeInstruction::NodeInstructionMov { lhs, rhs:slot });
+            }else if let Some(fregister_tok) = self.try_consume(TokenType::FloatRegister) {
+                return Some(NodeInstruction::NodeInstructionMov {
+                    lhs,
+                    rhs:NodeExpr::NodeExprRegister{value:fregister_tok}
+                });
+
             }else {
                 println!("Expected either register or number value to move.");
                 std::process::exit(1);
@@ -318,10 +455,9 @@ impl  Parser {
 
 
     pub fn parse_logical(&mut self) -> Option<NodeInstruction> {
-        let tok = self.peek_token();
-        if tok.is_none() { return None; }
-        use TokenType::{Not,Or,And,Nand,Xor};
-        match tok.unwrap().token_type {
+        let tok = self.peek_token()?;
+        use TokenType::{Not,Or,And,Nand,Xor,Shl,Shr,Sar,Rol,Ror};
+        match tok.token_type {
             Not => {
                 self.consume_token();
                 if let Some(register_tok) = self.try_consume(TokenType::Register) {
@@ -446,6 +582,35 @@ impl  Parser {
 
                 }
             }
+            Shl | Shr | Sar | Rol | Ror => {
+                let op = self.consume_token().unwrap().token_type;
+                let name = format!("{:?}",op).to_lowercase();
+                let lhs = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+                    NodeExpr::NodeExprRegister{value:register_tok}
+                }else {
+                    println!("Expected register to perform {} operation.",name);
+                    std::process::exit(1);
+                };
+                if self.try_consume(TokenType::Comma).is_none() {
+                    println!("Expected Comma, found:{:?}",self.peek_token());
+                    std::process::exit(1);
+                }
+                let rhs = if let Some(int_lit) = self.try_consume(TokenType::IntLit) {
+                    NodeExpr::NodeExprIntLit{value:int_lit}
+                }else if let Some(register2_tok) = self.try_consume(TokenType::Register) {
+                    NodeExpr::NodeExprRegister{value:register2_tok}
+                }else {
+                    println!("Expected either register or number value as the count of the {} operation.",name);
+                    std::process::exit(1);
+                };
+                match op {
+                    Shl => Some(NodeInstruction::NodeInstructionShl { lhs, rhs }),
+                    Shr => Some(NodeInstruction::NodeInstructionShr { lhs, rhs }),
+                    Sar => Some(NodeInstruction::NodeInstructionSar { lhs, rhs }),
+                    Rol => Some(NodeInstruction::NodeInstructionRol { lhs, rhs }),
+                    _ => Some(NodeInstruction::NodeInstructionRor { lhs, rhs }),
+                }
+            }
             _ => None
         }
 
@@ -513,11 +678,27 @@ impl  Parser {
                 return Some(NodeInstruction::NodeInstructionPush {
                     value:NodeExpr::NodeExprRegister{value:reg}
                 });
+            }else if let Some(reg) = self.try_consume(TokenType::FloatRegister) {
+                return Some(NodeInstruction::NodeInstructionPush {
+                    value:NodeExpr::NodeExprRegister{value:reg}
+                });
+            }else if let Some(slot) = self.parse_frame_slot().or_else(|| self.parse_named_slot()) {
+                return Some(NodeInstruction::NodeInstructionPush { value:slot });
             }else {
                 println!("Expected register or number to push.");
                 std::process::exit(1);
             }
         }
+        if let Some(_push_tok) = self.try_consume(TokenType::PushFloatRegister) {
+            if let Some(reg) = self.try_consume(TokenType::FloatRegister) {
+                return Some(NodeInstruction::NodeInstructionPush {
+                    value:NodeExpr::NodeExprRegister{value:reg}
+                });
+            }else {
+                println!("Expected float register to push, found {:?}",self.peek_token());
+                std::process::exit(1);
+            }
+        }
         None
     }
 
@@ -527,12 +708,26 @@ impl  Parser {
                 return Some(NodeInstruction::NodeInstructionPop {
                     value:NodeExpr::NodeExprRegister{value:reg}
                 });
-            }      
+            }else if let Some(reg) = self.try_consume(TokenType::FloatRegister) {
+                return Some(NodeInstruction::NodeInstructionPop {
+                    value:NodeExpr::NodeExprRegister{value:reg}
+                });
+            }
             else {
                 println!("Expected register to pop into, found {:?}",self.peek_token());
                 std::process::exit(1);
             }
         }
+        if let Some(_pop_tok) = self.try_consume(TokenType::PopFloat) {
+            if let Some(reg) = self.try_consume(TokenType::FloatRegister) {
+                return Some(NodeInstruction::NodeInstructionPop {
+                    value:NodeExpr::NodeExprRegister{value:reg}
+                });
+            }else {
+                println!("Expected float register to pop into, found {:?}",self.peek_token());
+                std::process::exit(1);
+            }
+        }
         None
     }
 
@@ -540,9 +735,36 @@ impl  Parser {
         if let Some(_call_tok) = self.try_consume(TokenType::Call) {
 
             if let Some(reg) = self.try_consume(TokenType::Ident) {
+                if self.try_consume(TokenType::LParen).is_some() {
+                    let mut args = Vec::new();
+                    while self.try_consume(TokenType::RParen).is_none() {
+                        if !args.is_empty() && self.try_consume(TokenType::Comma).is_none() {
+                            println!("Expected , or ) in the arguments of {}, found {:?}",reg.value.unwrap(),self.peek_token());
+                            std::process::exit(1);
+                        }
+                        if let Some(int_lit) = self.try_consume(TokenType::IntLit) {
+                            args.push(NodeExpr::NodeExprIntLit{value:int_lit});
+                        }else if let Some(register) = self.try_consume(TokenType::Register) {
+                            args.push(NodeExpr::NodeExprRegister{value:register});
+                        }else if let Some(slot) = self.parse_named_slot() {
+                            args.push(slot);
+                        }else {
+                            println!("Expected register, number or parameter as an argument of {}, found {:?}",reg.value.unwrap(),self.peek_token());
+                            std::process::exit(1);
+                        }
+                    }
+                    return Some(NodeInstruction::NodeInstructionProcCall {
+                        value:NodeExpr::NodeExprLabelName{value:reg},
+                        args
+                    });
+                }
                 return Some(NodeInstruction::NodeInstructionCall {
                     value:NodeExpr::NodeExprLabelName{value:reg}
                 });
+            }else if let Some(reg) = self.try_consume(TokenType::Register) {
+                return Some(NodeInstruction::NodeInstructionCallRegister {
+                    value:NodeExpr::NodeExprRegister{value:reg}
+                });
             }else if let Some(ad) = self.try_consume(TokenType::IntLit) {
                 let int = ad.value.as_ref().unwrap().parse::<i32>().unwrap();
                 if int < 0 {
@@ -571,6 +793,10 @@ impl  Parser {
                 return Some(NodeInstruction::NodeInstructionJump {
                     value:NodeExpr::NodeExprLabelName{value:reg}
                 });
+            }else if let Some(reg) = self.try_consume(TokenType::Register) {
+                return Some(NodeInstruction::NodeInstructionJumpRegister {
+                    value:NodeExpr::NodeExprRegister{value:reg}
+                });
             }else if let Some(ad) = self.try_consume(TokenType::IntLit) {
                 let int = ad.value.as_ref().unwrap().parse::<i32>().unwrap();
                 if int < 0 {
@@ -670,6 +896,151 @@ impl  Parser {
         None   
     }
 
+    pub fn parse_jump_greater_equal(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfGreaterOrEqual;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfGreaterOrEqual {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_less_equal(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfLessOrEqual;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfLessOrEqual {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_above(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfAbove;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfAbove {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_below(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfBelow;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfBelow {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_above_equal(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfAboveOrEqual;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfAboveOrEqual {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_below_equal(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfBelowOrEqual;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfBelowOrEqual {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_overflow(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfOverflow;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfOverflow {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_not_overflow(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfNotOverflow;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfNotOverflow {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_carry(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfCarry;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfCarry {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_jump_not_carry(&mut self) -> Option<NodeInstruction> {
+        let jmp_token_type=  TokenType::JumpIfNotCarry;
+        let mut jmp_node_inst = NodeInstruction::NodeInstructionJumpIfNotCarry {
+            value:NodeExpr::NodeExprLabelName{value:Token {token_type:TokenType::IntLit,value:Some("0".to_string())}}
+        };
+        let mut try_consume = |t| {self.try_consume(t)};
+        let res = parse_jump!(jmp_token_type,jmp_node_inst,try_consume); 
+        if res.is_ok(){
+            return Some(jmp_node_inst);
+        }
+        None   
+    }
+
+    pub fn parse_arithmetic_mode(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::ArithmeticMode)?;
+        match self.try_consume(TokenType::Ident) {
+            Some(mode) if mode.value.as_deref() == Some("wrap") || mode.value.as_deref() == Some("trap") => {
+                Some(NodeInstruction::NodeInstructionSetArithmeticMode {
+                    value:NodeExpr::NodeExprLabelName{value:mode}
+                })
+            }
+            _ => {
+                println!("Expected `wrap` or `trap` after arith, found:{:?}",self.peek_token());
+                std::process::exit(1);
+            }
+        }
+    }
+
     pub fn parse_compare(&mut self) -> Option<NodeInstruction> {
         if let Some(_cmp_tok) = self.try_consume(TokenType::Compare) {
             if let Some(reg1) = self.try_consume(TokenType::Register) {
@@ -721,6 +1092,40 @@ impl  Parser {
         None
     }
 
+    /// `cmpu`, `divu` and `modu`, which take a register and a register or number.
+    pub fn parse_unsigned(&mut self) -> Option<NodeInstruction> {
+        let op = self.peek_token()?.token_type;
+        use TokenType::{CompareUnsigned,DivUnsigned,ModUnsigned};
+        if ![CompareUnsigned,DivUnsigned,ModUnsigned].contains(&op) {
+            return None;
+        }
+        self.consume_token();
+        let lhs = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected register, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        let rhs = if let Some(int_lit) = self.try_consume(TokenType::IntLit) {
+            NodeExpr::NodeExprIntLit{value:int_lit}
+        }else if let Some(register2_tok) = self.try_consume(TokenType::Register) {
+            NodeExpr::NodeExprRegister{value:register2_tok}
+        }else {
+            println!("Expected either register or number value, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        match op {
+            CompareUnsigned => Some(NodeInstruction::NodeInstructionCompareUnsigned { lhs, rhs }),
+            DivUnsigned => Some(NodeInstruction::NodeInstructionDivUnsigned { lhs, rhs }),
+            _ => Some(NodeInstruction::NodeInstructionModUnsigned { lhs, rhs }),
+        }
+    }
+
+    #[allow(unused_assignments)]
     pub fn parse_getstack(&mut self) -> Option<NodeInstruction> {
         if let Some(_getstack_tok) = self.try_consume(TokenType::GetFromStack) {
             let mut lhs:Option<NodeExpr> = None;
@@ -752,6 +1157,7 @@ impl  Parser {
         None
     }
 
+    #[allow(unused_assignments)]
     pub fn parse_getfromsp(&mut self) -> Option<NodeInstruction> {
         if let Some(_getsp_tok) = self.try_consume(TokenType::GetFromStackPointer) {
             let mut lhs:Option<NodeExpr> = None;
@@ -907,9 +1313,8 @@ impl  Parser {
             let mut lhs:Option<NodeExpr> = None;
             let mut rhs: Option<NodeExpr> = None;
     
-            if let Some(int) = self.try_consume(TokenType::IntLit) {
+            if self.try_consume(TokenType::IntLit).is_some() {
                 todo!("use register instead of integer for extend stack 1st argument");
-                lhs = Some(NodeExpr::NodeExprIntLit{value:int});
             }else if let Some(reg) = self.try_consume(TokenType::Register) {
                 lhs =Some(NodeExpr::NodeExprRegister{value:reg});
             }else {
@@ -974,8 +1379,9 @@ impl  Parser {
     }
 
 
+    #[allow(unused_assignments)]
     pub fn parse_getmem(&mut self) -> Option<NodeInstruction> {
-        if let Some(_getmem_tok) = self.try_consume(TokenType::GetMemory) {
+        if let Some(getmem_tok) = self.try_consume(TokenType::GetMemory) {
             let mut lhs:Option<NodeExpr> = None;
             let mut rhs: Option<NodeExpr> = None;
             let mut offset: Option<NodeExpr> = None;
@@ -1013,14 +1419,15 @@ impl  Parser {
                 std::process::exit(1);
             }
  
-            return Some(NodeInstruction::NodeInstructionGetMemory{lhs:lhs.unwrap(),rhs:rhs.unwrap(),offset:offset.unwrap()})
+            return Some(NodeInstruction::NodeInstructionGetMemory{lhs:lhs.unwrap(),rhs:rhs.unwrap(),offset:offset.unwrap(),width:MemoryWidth::from_suffix(getmem_tok.value.as_deref())})
 
         }
         None
 
     }
+    #[allow(unused_assignments)]
     pub fn parse_setmem(&mut self) -> Option<NodeInstruction> {
-        if let Some(_setmem_tok) = self.try_consume(TokenType::SetMemory) {
+        if let Some(setmem_tok) = self.try_consume(TokenType::SetMemory) {
             let mut lhs:Option<NodeExpr> = None;
             let mut rhs: Option<NodeExpr> = None;
             let mut offset: Option<NodeExpr> = None; 
@@ -1063,15 +1470,80 @@ impl  Parser {
  
  
 
-            return Some(NodeInstruction::NodeInstructionSetMemory{lhs:lhs.unwrap(),rhs:rhs.unwrap(),offset:offset.unwrap()})
+            return Some(NodeInstruction::NodeInstructionSetMemory{lhs:lhs.unwrap(),rhs:rhs.unwrap(),offset:offset.unwrap(),width:MemoryWidth::from_suffix(setmem_tok.value.as_deref())})
 
         }
         None
 
     }
 
+    /// `[bp]`, `[bp - 3]` or `[bp + 2]`.
+    pub fn parse_frame_slot(&mut self) -> Option<NodeExpr> {
+        self.try_consume(TokenType::LBracket)?;
+        if self.try_consume(TokenType::BasePointer).is_none() {
+            println!("Expected bp after [, found {:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        let sign = self.try_consume(TokenType::Minus).or_else(|| self.try_consume(TokenType::Plus));
+        let negative = sign.as_ref().is_some_and(|t| t.token_type == TokenType::Minus);
+        let offset = match self.try_consume(TokenType::IntLit) {
+            Some(int) if sign.is_none() || !int.value.as_ref().unwrap().starts_with('-') => {
+                format!("{}{}",if negative { "-" } else { "" },int.value.unwrap())
+            }
+            None if sign.is_none() => "0".to_string(),
+            _ => {
+                println!("Expected offset from bp in [bp - n] or [bp + n], found {:?}",self.peek_token());
+                std::process::exit(1);
+            }
+        };
+        if self.try_consume(TokenType::RBracket).is_none() {
+            println!("Expected ] after [bp {}, found {:?}",offset,self.peek_token());
+            std::process::exit(1);
+        }
+        Some(NodeExpr::NodeExprFrameSlot{value:Token{token_type:TokenType::IntLit,value:Some(offset)}})
+    }
+
+    /// A parameter or local of the `@proc` being parsed, which is the same as its `[bp + n]`.
+    pub fn parse_named_slot(&mut self) -> Option<NodeExpr> {
+        let token = self.peek_token()?;
+        if token.token_type != TokenType::Ident {
+            return None;
+        }
+        let name = token.value.unwrap();
+        let offset = match self.proc.as_ref().and_then(|proc| proc.slots.get(&name)) {
+            Some(offset) => *offset,
+            None => {
+                println!("{:?} is not a parameter or local of the @proc it is used in.",name);
+                std::process::exit(1);
+            }
+        };
+        self.consume_token();
+        Some(NodeExpr::NodeExprFrameSlot{value:Token{token_type:TokenType::IntLit,value:Some(offset.to_string())}})
+    }
+
+    /// `enter 3` starts a stack frame with 3 locals, `leave` ends it.
+    pub fn parse_frame(&mut self) -> Option<NodeInstruction> {
+        if self.try_consume(TokenType::Leave).is_some() {
+            return Some(NodeInstruction::NodeInstructionLeave);
+        }
+        self.try_consume(TokenType::Enter)?;
+        if let Some(int) = self.try_consume(TokenType::IntLit) {
+            if int.value.as_ref().unwrap().parse::<u32>().is_err() {
+                println!("Expected number of locals after enter, found {:?}",int.value.unwrap());
+                std::process::exit(1);
+            }
+            Some(NodeInstruction::NodeInstructionEnter { value:NodeExpr::NodeExprIntLit{value:int} })
+        }else {
+            println!("Expected number of locals after enter, found {:?}",self.peek_token());
+            std::process::exit(1);
+        }
+    }
+
     pub fn parse_ret(&mut self) -> Option<NodeInstruction> {
         if let Some(_ret) = self.try_consume(TokenType::Return) {
+            if let Some(proc) = &self.proc {
+                return Some(NodeInstruction::NodeInstructionProcReturn { params:proc.params })
+            }
             return Some(NodeInstruction::NodeInstructionReturn)
         }   
         None
@@ -1080,7 +1552,7 @@ impl  Parser {
     pub fn parse_label(&mut self) -> Option<(String,usize)> {
         if let Some(_label_tok) = self.try_consume(TokenType::Label) {
             if let Some(label_name) = self.try_consume(TokenType::Ident) {
-                if let Some(_) = self.labels.get(&label_name.value.clone().unwrap()) {
+                if self.labels.contains_key(&label_name.value.clone().unwrap()) {
                     println!("Cannot defined lable with name `{:?}` as it is already defined.",label_name.value.clone().unwrap())
                 }
                 if let Some(_colon) = self.try_consume(TokenType::Colon) {
@@ -1101,24 +1573,299 @@ impl  Parser {
 
 
     #[allow(unused_assignments)]
+    pub fn parse_compare_float(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::CompareFloat)?;
+        let lhs = if let Some(register_tok) = self.try_consume(TokenType::FloatRegister) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected float register to compare, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        let rhs = if let Some(f_lit) = self.try_consume(TokenType::Float) {
+            NodeExpr::NodeExprFloat{value:f_lit}
+        }else if let Some(register2_tok) = self.try_consume(TokenType::FloatRegister) {
+            NodeExpr::NodeExprRegister{value:register2_tok}
+        }else {
+            println!("Expected either float register or float value to compare with, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        Some(NodeInstruction::NodeInstructionCompareFloat { lhs, rhs })
+    }
+
+    /// `cvtf rax, fa, mode` converts a float register to an integer with a rounding mode.
+    pub fn parse_convert_float(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::ConvertFloat)?;
+        let lhs = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected register to convert into, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        let rhs = if let Some(register_tok) = self.try_consume(TokenType::FloatRegister) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected float register to convert, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        match self.try_consume(TokenType::Ident) {
+            Some(mode) if ["nearest","down","up","zero"].contains(&mode.value.as_deref().unwrap()) => {
+                Some(NodeInstruction::NodeInstructionConvertFloat {
+                    lhs,
+                    rhs,
+                    mode:NodeExpr::NodeExprLabelName{value:mode}
+                })
+            }
+            _ => {
+                println!("Expected `nearest`, `down`, `up` or `zero` after cvtf, found:{:?}",self.peek_token());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// `movbits rax, fa` and `movbits fa, rax` copy bits between integer and float registers.
+    pub fn parse_movbits(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::MovBits)?;
+        let (lhs,float) = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            (NodeExpr::NodeExprRegister{value:register_tok},false)
+        }else if let Some(register_tok) = self.try_consume(TokenType::FloatRegister) {
+            (NodeExpr::NodeExprRegister{value:register_tok},true)
+        }else {
+            println!("Expected register to move bits into, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        let rhs_type = if float { TokenType::Register } else { TokenType::FloatRegister };
+        if let Some(register2_tok) = self.try_consume(rhs_type) {
+            Some(NodeInstruction::NodeInstructionMovBits { lhs, rhs:NodeExpr::NodeExprRegister{value:register2_tok} })
+        }else {
+            println!("movbits moves between an integer and a float register, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+    }
+
+    /// `tailcall label` jumps to a routine that returns straight to the caller of this one.
+    pub fn parse_tail_call(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::TailCall)?;
+        let proc_params = self.proc.as_ref().map(|proc| proc.params);
+        if let Some(label) = self.try_consume(TokenType::Ident) {
+            Some(NodeInstruction::NodeInstructionTailCall { value:NodeExpr::NodeExprLabelName{value:label}, proc_params })
+        }else {
+            println!("Expected label to tailcall, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+    }
+
+    /// `loop rcx, label` subtracts 1 from rcx and jumps to label while it is not 0.
+    pub fn parse_loop(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::Loop)?;
+        let lhs = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected register to count with, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        if let Some(label) = self.try_consume(TokenType::Ident) {
+            Some(NodeInstruction::NodeInstructionLoop { lhs, rhs:NodeExpr::NodeExprLabelName{value:label} })
+        }else {
+            println!("Expected label to loop to, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+    }
+
+    /// `throw rax` or `throw 7` goes to the handler of the last `@try` with the error code.
+    pub fn parse_throw(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::Throw)?;
+        if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            Some(NodeInstruction::NodeInstructionThrow { value:NodeExpr::NodeExprRegister{value:register_tok} })
+        }else if let Some(int_lit) = self.try_consume(TokenType::IntLit) {
+            Some(NodeInstruction::NodeInstructionThrow { value:NodeExpr::NodeExprIntLit{value:int_lit} })
+        }else {
+            println!("Expected register or number to throw, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+    }
+
+    /// `brk` stops in a debugger, or prints the registers, flags and stack when there is none.
+    pub fn parse_breakpoint(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::Breakpoint)?;
+        Some(NodeInstruction::NodeInstructionBreakpoint)
+    }
+
+    /// `assert l, "message"` stops with a runtime error that has the message unless the last
+    /// comparison found less than, the conditions are the ones of the jumps.
+    /// `assert l rax, 10, "message"` compares like `cmp rax, 10` first.
+    pub fn parse_assert(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::Assert)?;
+        let condition = match self.peek_token() {
+            Some(Token{token_type:TokenType::Ident,value:Some(name)}) if CONDITIONS.contains(&name.as_str()) => self.consume_token().unwrap(),
+            token => {
+                println!("Expected a condition such as e, l or nz after assert, found:{:?}",token);
+                std::process::exit(1);
+            }
+        };
+        let mut operands = Vec::new();
+        while operands.len() < 2 {
+            if let Some(register_tok) = self.try_consume(TokenType::Register) {
+                operands.push(NodeExpr::NodeExprRegister{value:register_tok});
+            }else if let Some(int_lit) = self.try_consume(TokenType::IntLit) {
+                operands.push(NodeExpr::NodeExprIntLit{value:int_lit});
+            }else if operands.is_empty() {
+                break;
+            }else {
+                println!("Expected register or number to compare, found:{:?}",self.peek_token());
+                std::process::exit(1);
+            }
+            if self.try_consume(TokenType::Comma).is_none() {
+                println!("Expected Comma, found:{:?}",self.peek_token());
+                std::process::exit(1);
+            }
+        }
+        if operands.is_empty() && self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        let message = match self.try_consume(TokenType::StringLit) {
+            Some(string) => string,
+            None => {
+                println!("Expected the message of the assert in quotes, found:{:?}",self.peek_token());
+                std::process::exit(1);
+            }
+        };
+        let compare = if operands.is_empty() { None } else { Some((operands.remove(0),operands.remove(0))) };
+        Some(NodeInstruction::NodeInstructionAssert {
+            condition:NodeExpr::NodeExprLabelName{value:condition},
+            compare,
+            message:NodeExpr::NodeExprStringLit{value:message},
+        })
+    }
+
+    /// `cmovl rax, rbx` copies rbx into rax if the last comparison found less than, the
+    /// conditions are the ones of the jumps.
+    pub fn parse_conditional_move(&mut self) -> Option<NodeInstruction> {
+        let condition = self.try_consume(TokenType::ConditionalMove)?;
+        let lhs = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected register to move into, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        let rhs = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected register to move from, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        Some(NodeInstruction::NodeInstructionConditionalMove { condition:NodeExpr::NodeExprLabelName{value:condition}, lhs, rhs })
+    }
+
+    /// `setl rax` sets rax to 1 if the last comparison found less than and to 0 otherwise.
+    pub fn parse_set_if(&mut self) -> Option<NodeInstruction> {
+        let condition = self.try_consume(TokenType::SetIf)?;
+        if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            Some(NodeInstruction::NodeInstructionSetIf { condition:NodeExpr::NodeExprLabelName{value:condition}, value:NodeExpr::NodeExprRegister{value:register_tok} })
+        }else {
+            println!("Expected register to set, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+    }
+
+    /// `lea rax, label` loads the address of a label (or an instruction number) into a register
+    /// for `jmp rax` and `call rax`.
+    pub fn parse_load_address(&mut self) -> Option<NodeInstruction> {
+        self.try_consume(TokenType::LoadAddress)?;
+        let lhs = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected register to load the address into, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        };
+        if self.try_consume(TokenType::Comma).is_none() {
+            println!("Expected Comma, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+        if let Some(label) = self.try_consume(TokenType::Ident) {
+            Some(NodeInstruction::NodeInstructionLoadAddress { lhs, rhs:NodeExpr::NodeExprLabelName{value:label} })
+        }else if let Some(ad) = self.try_consume(TokenType::IntLit) {
+            if ad.value.as_ref().unwrap().parse::<u32>().is_err() {
+                println!("Expected label or instruction number to load the address of, found {:?}.",ad.value.unwrap());
+                std::process::exit(1);
+            }
+            Some(NodeInstruction::NodeInstructionLoadAddress { lhs, rhs:NodeExpr::NodeExprIntLit{value:ad} })
+        }else {
+            println!("Expected label or instruction number to load the address of, found:{:?}",self.peek_token());
+            std::process::exit(1);
+        }
+    }
+
+    /// `sqrtf fa` replaces fa with its square root, `sqrtf fa, fb` or `sqrtf fa, 2.0` takes the
+    /// square root of the second operand. `powf`, `atan2f`, `minf` and `maxf` always take two
+    /// operands and compute f(fa, fb).
+    pub fn parse_float_math(&mut self) -> Option<NodeInstruction> {
+        let function = self.try_consume(TokenType::FloatMath)?;
+        let name = function.value.clone().unwrap();
+        let lhs = if let Some(register_tok) = self.try_consume(TokenType::FloatRegister) {
+            NodeExpr::NodeExprRegister{value:register_tok}
+        }else {
+            println!("Expected float register after {}, found:{:?}",name,self.peek_token());
+            std::process::exit(1);
+        };
+        let binary = ["powf","atan2f","minf","maxf"].contains(&name.as_str());
+        if self.try_consume(TokenType::Comma).is_none() {
+            if binary {
+                println!("{} takes two operands, found:{:?}",name,self.peek_token());
+                std::process::exit(1);
+            }
+            return Some(NodeInstruction::NodeInstructionFloatMath { function:NodeExpr::NodeExprLabelName{value:function}, rhs:lhs.clone(), lhs });
+        }
+        let rhs = if let Some(f_lit) = self.try_consume(TokenType::Float) {
+            NodeExpr::NodeExprFloat{value:f_lit}
+        }else if let Some(register2_tok) = self.try_consume(TokenType::FloatRegister) {
+            NodeExpr::NodeExprRegister{value:register2_tok}
+        }else {
+            println!("Expected either float register or float value after {}, found:{:?}",name,self.peek_token());
+            std::process::exit(1);
+        };
+        Some(NodeInstruction::NodeInstructionFloatMath { function:NodeExpr::NodeExprLabelName{value:function}, lhs, rhs })
+    }
+
     pub fn parse_movf(&mut self) -> Option<NodeInstruction> {
         if let Some(_movf_tok) = self.try_consume(TokenType::Movf) {
-            let mut lhs:Option<NodeExpr> = None;
-
-            if let Some(register_tok) = self.try_consume(TokenType::FloatRegister) {
-                lhs = Some(NodeExpr::NodeExprRegister{value:register_tok});
+            let lhs = if let Some(register_tok) = self.try_consume(TokenType::FloatRegister) {
+                NodeExpr::NodeExprRegister{value:register_tok}
             }else if let Some(int_tok) = self.try_consume(TokenType::Float) {
                 let register_tok = get_register_from_number(int_tok.value.clone().unwrap().parse::<i32>().unwrap());
                 if register_tok.is_none() {
                     println!("Invalid Register.");
                     std::process::exit(1);
                 }        
-                lhs = Some(NodeExpr::NodeExprIntLit{value:int_tok})
+                NodeExpr::NodeExprIntLit{value:int_tok}
             }else {
                 println!("Expected either register or register number to move flaot into. \nGot:{:?}",self.peek_token());
                 std::process::exit(1);
-            }
-            let lhs = lhs.unwrap();
+            };
             if self.try_consume(TokenType::Comma).is_none() {
                 println!("Expected Comma, found:{:?}",self.peek_token());
                 std::process::exit(1);
@@ -1135,6 +1882,12 @@ impl  Parser {
                     rhs:NodeExpr::NodeExprRegister{value:register2_tok}
                 });
 
+            }else if let Some(register_tok) = self.try_consume(TokenType::Register) {
+                return Some(NodeInstruction::NodeInstructionMovf {
+                    lhs,
+                    rhs:NodeExpr::NodeExprRegister{value:register_tok}
+                });
+
             }else {
                 println!("Expected either register or number value to move float into.");
                 std::process::exit(1);
@@ -1351,7 +2104,7 @@ impl  Parser {
 
 
     pub fn parse_write(&mut self) -> Option<NodeInstruction> {
-        if self.try_consume(TokenType::Write).is_none() { return None }
+        self.try_consume(TokenType::Write)?;
         let len = {
             if let Some(reg) = self.try_consume(TokenType::Register) {
                 NodeExpr::NodeExprRegister{value:reg}
@@ -1385,7 +2138,7 @@ impl  Parser {
     }
 
     pub fn parse_stkcpybacksp(&mut self) -> Option<NodeInstruction> {
-		if self.try_consume(TokenType::StackCopyBackSp).is_none() { return None; }
+		self.try_consume(TokenType::StackCopyBackSp)?;
 		let start_loc  = {
 			if let Some(reg) = self.try_consume(TokenType::Register) {
 				NodeExpr::NodeExprRegister{value:reg}
@@ -1437,7 +2190,7 @@ impl  Parser {
     }
 
     pub fn parse_inst(&mut self) -> Option<NodeInstruction> {
-        while let Some(_cur_token) = self.peek_token() {
+        if let Some(_cur_token) = self.peek_token() {
 
             if let Some(inst_halt) = self.parse_halt() {
                 return Some(inst_halt);
@@ -1527,9 +2280,84 @@ impl  Parser {
             if let Some(jl) = self.parse_jump_less() {
                 return Some(jl)
             }
+            if let Some(jump) = self.parse_jump_greater_equal() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_less_equal() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_above() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_below() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_above_equal() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_below_equal() {
+                return Some(jump)
+            }
             if let Some(cmp) = self.parse_compare() {
                 return Some(cmp)
             }
+            if let Some(unsigned) = self.parse_unsigned() {
+                return Some(unsigned)
+            }
+            if let Some(cmpf) = self.parse_compare_float() {
+                return Some(cmpf)
+            }
+            if let Some(cvtf) = self.parse_convert_float() {
+                return Some(cvtf)
+            }
+            if let Some(movbits) = self.parse_movbits() {
+                return Some(movbits)
+            }
+            if let Some(float_math) = self.parse_float_math() {
+                return Some(float_math)
+            }
+            if let Some(lea) = self.parse_load_address() {
+                return Some(lea)
+            }
+            if let Some(frame) = self.parse_frame() {
+                return Some(frame)
+            }
+            if let Some(tail_call) = self.parse_tail_call() {
+                return Some(tail_call)
+            }
+            if let Some(lp) = self.parse_loop() {
+                return Some(lp)
+            }
+            if let Some(throw) = self.parse_throw() {
+                return Some(throw)
+            }
+            if let Some(brk) = self.parse_breakpoint() {
+                return Some(brk)
+            }
+            if let Some(assert) = self.parse_assert() {
+                return Some(assert)
+            }
+            if let Some(cmov) = self.parse_conditional_move() {
+                return Some(cmov)
+            }
+            if let Some(set) = self.parse_set_if() {
+                return Some(set)
+            }
+            if let Some(jump) = self.parse_jump_overflow() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_not_overflow() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_carry() {
+                return Some(jump)
+            }
+            if let Some(jump) = self.parse_jump_not_carry() {
+                return Some(jump)
+            }
+            if let Some(mode) = self.parse_arithmetic_mode() {
+                return Some(mode)
+            }
             if let Some(gfs) = self.parse_getstack() {
                 return Some(gfs)
             }
@@ -1580,13 +2408,33 @@ impl  Parser {
 	    if let Some(stkcpybacksp) = self.parse_stkcpybacksp() {
 		    return Some(stkcpybacksp);
 	    }
-            else {
-                break;
-            }
         }
         None
     } 
 
+    /// `(a, b)` after `@proc name` or `locals`.
+    fn parse_name_list(&mut self, what:&str) -> Vec<Token> {
+        if self.try_consume(TokenType::LParen).is_none() {
+            println!("Expected ( before the {} of @proc, found {:?}",what,self.peek_token());
+            std::process::exit(1);
+        }
+        let mut names:Vec<Token> = Vec::new();
+        while self.try_consume(TokenType::RParen).is_none() {
+            if !names.is_empty() && self.try_consume(TokenType::Comma).is_none() {
+                println!("Expected , or ) in the {} of @proc, found {:?}",what,self.peek_token());
+                std::process::exit(1);
+            }
+            match self.try_consume(TokenType::Ident) {
+                Some(name) => names.push(name),
+                None => {
+                    println!("Expected a name in the {} of @proc, found {:?}",what,self.peek_token());
+                    std::process::exit(1);
+                }
+            }
+        }
+        names
+    }
+
   pub fn parse_builtin(&mut self) -> Option<NodeBuiltin> {
         if let Some(_builtin_tok) = self.try_consume(TokenType::BuiltinStart) {
             if let Some(builtin_ident) = self.try_consume(TokenType::Ident) {
@@ -1634,6 +2482,97 @@ impl  Parser {
                             std::process::exit(1);
                         }
                     }
+                    "bits" | "floatbits" => {
+                        if self.try_consume(TokenType::LParen).is_none() {
+                            println!("Expected ( after @{}, found {:?}",builtin_ident,self.peek_token());
+                            std::process::exit(1);
+                        }
+                        if let Some(int) = self.try_consume(TokenType::IntLit) {
+                            if self.try_consume(TokenType::RParen).is_none() {
+                                println!("Expected ) to close @{} function, found {:?}",builtin_ident,self.peek_token());
+                                std::process::exit(1);
+                            }
+                            return Some(NodeBuiltin::NodeBuiltinBits{
+                                value: NodeExpr::NodeExprIntLit{value:int},
+                                float: builtin_ident.as_str() == "floatbits"
+                            })
+                        }else {
+                            println!("Expected integer in @{}, found {:?}",builtin_ident,self.peek_token());
+                            std::process::exit(1);
+                        }
+                    }
+                    // @switch(rax, [zero, one, two], other)
+                    "switch" => {
+                        if self.try_consume(TokenType::LParen).is_none() {
+                            println!("Expected ( after @switch, found {:?}",self.peek_token());
+                            std::process::exit(1);
+                        }
+                        let value = if let Some(register_tok) = self.try_consume(TokenType::Register) {
+                            NodeExpr::NodeExprRegister{value:register_tok}
+                        }else {
+                            println!("Expected register to switch on in @switch, found {:?}",self.peek_token());
+                            std::process::exit(1);
+                        };
+                        if self.try_consume(TokenType::Comma).is_none() || self.try_consume(TokenType::LBracket).is_none() {
+                            println!("Expected , and a [ starting the labels of @switch, found {:?}",self.peek_token());
+                            std::process::exit(1);
+                        }
+                        let mut cases = Vec::new();
+                        while self.try_consume(TokenType::RBracket).is_none() {
+                            if !cases.is_empty() && self.try_consume(TokenType::Comma).is_none() {
+                                println!("Expected , or ] in the labels of @switch, found {:?}",self.peek_token());
+                                std::process::exit(1);
+                            }
+                            if let Some(label) = self.try_consume(TokenType::Ident) {
+                                cases.push(NodeExpr::NodeExprLabelName{value:label});
+                            }else {
+                                println!("Expected label in @switch, found {:?}",self.peek_token());
+                                std::process::exit(1);
+                            }
+                        }
+                        if self.try_consume(TokenType::Comma).is_none() {
+                            println!("Expected , and the default label of @switch, found {:?}",self.peek_token());
+                            std::process::exit(1);
+                        }
+                        let default = if let Some(label) = self.try_consume(TokenType::Ident) {
+                            NodeExpr::NodeExprLabelName{value:label}
+                        }else {
+                            println!("Expected default label in @switch, found {:?}",self.peek_token());
+                            std::process::exit(1);
+                        };
+                        if self.try_consume(TokenType::RParen).is_none() {
+                            println!("Expected ) to close @switch function, found {:?}",self.peek_token());
+                            std::process::exit(1);
+                        }
+                        return Some(NodeBuiltin::NodeBuiltinSwitch { value, cases, default })
+                    }
+                    // @proc name(a, b) locals(x, y)
+                    "proc" => {
+                        let name = match self.try_consume(TokenType::Ident) {
+                            Some(name) => name,
+                            None => {
+                                println!("Expected name after @proc, found {:?}",self.peek_token());
+                                std::process::exit(1);
+                            }
+                        };
+                        let params = self.parse_name_list("parameters");
+                        let mut locals = Vec::new();
+                        if self.peek_token().is_some_and(|t| t.token_type == TokenType::Ident && t.value.as_deref() == Some("locals")) {
+                            self.consume_token();
+                            locals = self.parse_name_list("locals");
+                        }
+                        return Some(NodeBuiltin::NodeBuiltinProc { name:NodeExpr::NodeExprLabelName{value:name}, params, locals })
+                    }
+                    "endproc" => return Some(NodeBuiltin::NodeBuiltinEndProc),
+                    // @try handler
+                    "try" => {
+                        if let Some(label) = self.try_consume(TokenType::Ident) {
+                            return Some(NodeBuiltin::NodeBuiltinTry { value:NodeExpr::NodeExprLabelName{value:label} })
+                        }
+                        println!("Expected handler label after @try, found {:?}",self.peek_token());
+                        std::process::exit(1);
+                    }
+                    "endtry" => return Some(NodeBuiltin::NodeBuiltinEndTry),
                     _ => unreachable!()
                   
                 }
@@ -1649,6 +2588,7 @@ impl  Parser {
     pub fn parse(&mut self)  {
       //-> Option<Vec<NodeBuiltin>,(HashMap<String,NodeLabel>)> 
         while self.peek_token().is_some() {
+            let line = self.token_lines.get(self.index).copied().unwrap_or(0);
             if let Some(builtin) = self.parse_builtin() {
                 match builtin{
                     NodeBuiltin::NodeBuiltinLoadString { value, load_len } => {
@@ -1667,7 +2607,10 @@ impl  Parser {
                                         );
                                     self.instruction_counter +=1;
                                 }
-                                if !load_len { continue }
+                                if !load_len {
+                                    self.instruction_lines.resize(self.instructions.len(),line);
+                                    continue
+                                }
                                 // Push length of string 
                                 self.instructions.push(
                                     NodeInstruction::NodeInstructionPush{
@@ -1684,6 +2627,75 @@ impl  Parser {
                            _ => unreachable!()
                        } 
                     }
+                    // Parameters are pushed before the call, so the last one is just below the caller's
+                    // `bp` at `[bp - 2]`. Locals start at `[bp]`.
+                    NodeBuiltin::NodeBuiltinProc { name, params, locals } => {
+                        if self.proc.is_some() {
+                            println!("Cannot start a @proc inside another one, end it with @endproc first.");
+                            std::process::exit(1);
+                        }
+                        let name = match name {
+                            NodeExpr::NodeExprLabelName { value } => value.value.unwrap(),
+                            _ => unreachable!()
+                        };
+                        if self.labels.contains_key(&name) {
+                            println!("Cannot defined lable with name `{:?}` as it is already defined.",name);
+                            std::process::exit(1);
+                        }
+                        self.labels.insert(name,self.instruction_counter);
+                        let mut slots = HashMap::new();
+                        for (i,param) in params.iter().enumerate() {
+                            let offset = i as iInstructionParamType - params.len() as iInstructionParamType - 1;
+                            if slots.insert(param.value.clone().unwrap(),offset).is_some() {
+                                println!("@proc has two parameters or locals named {:?}.",param.value.clone().unwrap());
+                                std::process::exit(1);
+                            }
+                        }
+                        for (i,local) in locals.iter().enumerate() {
+                            if slots.insert(local.value.clone().unwrap(),i as iInstructionParamType).is_some() {
+                                println!("@proc has two parameters or locals named {:?}.",local.value.clone().unwrap());
+                                std::process::exit(1);
+                            }
+                        }
+                        self.proc = Some(Proc { params:params.len(), slots });
+                        self.instructions.push(NodeInstruction::NodeInstructionProcStart { params:params.len(), locals:locals.len() });
+                        self.instruction_counter += 1;
+                    }
+                    // Returns when the end of the @proc is reached.
+                    NodeBuiltin::NodeBuiltinEndProc => {
+                        let params = match self.proc.take() {
+                            Some(proc) => proc.params,
+                            None => {
+                                println!("Found @endproc without a @proc.");
+                                std::process::exit(1);
+                            }
+                        };
+                        self.instructions.push(NodeInstruction::NodeInstructionProcReturn { params });
+                        self.instruction_counter += 1;
+                    }
+                    NodeBuiltin::NodeBuiltinTry { value } => {
+                        self.try_depth += 1;
+                        self.instructions.push(NodeInstruction::NodeInstructionTry { value });
+                        self.instruction_counter += 1;
+                    }
+                    NodeBuiltin::NodeBuiltinEndTry => {
+                        if self.try_depth == 0 {
+                            println!("Found @endtry without a @try.");
+                            std::process::exit(1);
+                        }
+                        self.try_depth -= 1;
+                        self.instructions.push(NodeInstruction::NodeInstructionEndTry);
+                        self.instruction_counter += 1;
+                    }
+                    // A bounds checked jump into a table of jumps, one per case and one for the default.
+                    NodeBuiltin::NodeBuiltinSwitch { value, cases, default } => {
+                        self.instructions.push(NodeInstruction::NodeInstructionJumpTable { value, count:cases.len() });
+                        self.instruction_counter += 1;
+                        for case in cases.into_iter().chain(std::iter::once(default)) {
+                            self.instructions.push(NodeInstruction::NodeInstructionJump { value:case });
+                            self.instruction_counter += 1;
+                        }
+                    }
                     _ => self.builtins.push(builtin)
                 }
             }else  if let Some((name,labelindex)) = self.parse_label() {
@@ -1696,7 +2708,16 @@ impl  Parser {
                 println!("Undefined instruction: {:?}",self.peek_token());
                 std::process::exit(1);
             }
+            self.instruction_lines.resize(self.instructions.len(),line);
         } 
+        if self.proc.is_some() {
+            println!("Expected @endproc before the end of the file.");
+            std::process::exit(1);
+        }
+        if self.try_depth > 0 {
+            println!("Expected @endtry before the end of the file.");
+            std::process::exit(1);
+        }
     }
 
     pub fn peek_token(&self) -> Option<Token> {
@@ -1720,19 +2741,12 @@ impl  Parser {
 
 }
 
-//Kinda useless funtion
+/// The register a program means by a register number such as the `4` in `mov 4, 1`, which is `r8`.
+/// The reserve registers have no number a program can use.
 pub fn get_register_from_number(num:i32) -> Option<InstructionParamType> {
     if num < 0 {
         println!("Expected register or register number, found negative integer.");
         std::process::exit(1);
     }
-    match num {
-        0 => Some(REGA),
-        1 => Some(REGB),
-        2 => Some(REGC),
-        3 => Some(REGD),
-        4 => Some(RESERVEREGISTER1),
-        5 => Some(RESERVEREGISTER2),
-        _ => None
-    }
+    if (num as usize) < USER_REGISTER_COUNT { Some(num as InstructionParamType) } else { None }
 }
@@ -0,0 +1,198 @@
+use crate::{instruction::Instruction,vm::Tracer};
+
+use std::collections::{BTreeMap,HashMap};
+
+/// Collects execution counts while `VM::eval_traced` runs a program.
+///
+/// Every executed instruction is counted by its position (pc). Calls are tracked through the VM's
+/// `return_addresses`: when it grows a call to the new pc was made and when it shrinks the calls
+/// above its new length returned, several at once when an error goes to a `Try` further out.
+pub struct Profiler {
+    /// Label names by the instruction they start at.
+    label_starts: BTreeMap<usize,String>,
+    instruction_counts: Vec<u64>,
+    total: u64,
+    calls: BTreeMap<usize,CallStats>,
+    frames: Vec<Frame>,
+    /// Instructions executed per call stack, in the folded format (`main;fact;fact 12`).
+    folded: HashMap<String,u64>,
+    /// Instructions executed with the current call stack that are not yet added to `folded`.
+    pending: u64,
+}
+
+#[derive(Default,Clone,Copy)]
+struct CallStats {
+    calls: u64,
+    /// Instructions executed from entering the call until it returned, including nested calls.
+    /// Recursive calls are only counted by their outermost call so nothing is counted twice.
+    inclusive: u64,
+    /// Instructions executed by the call itself, without nested calls.
+    exclusive: u64,
+}
+
+struct Frame {
+    target: usize,
+    entered_at: u64,
+    /// Instructions executed by calls made from this frame.
+    nested: u64,
+}
+
+impl Profiler {
+    /// `labels` are the labels of the generator's VM, which map a name to its start and end.
+    pub fn new(labels:&HashMap<String,(usize,Option<usize>)>) -> Self {
+        let mut label_starts: BTreeMap<usize,String> = BTreeMap::new();
+        for (name,(start,_)) in labels.iter() {
+            // Several labels can start at the same instruction, keep the same one every run.
+            match label_starts.get(start) {
+                Some(existing) if existing <= name => {}
+                _ => { label_starts.insert(*start,name.clone()); }
+            }
+        }
+        Self {
+            label_starts,
+            instruction_counts: Vec::new(),
+            total: 0,
+            calls: BTreeMap::new(),
+            frames: Vec::new(),
+            folded: HashMap::new(),
+            pending: 0,
+        }
+    }
+
+    fn record_instruction(&mut self,pc:usize) {
+        if pc >= self.instruction_counts.len() {
+            self.instruction_counts.resize(pc+1,0);
+        }
+        self.instruction_counts[pc] += 1;
+        self.total += 1;
+        self.pending += 1;
+    }
+
+    fn enter_call(&mut self,target:usize) {
+        self.flush_pending();
+        self.calls.entry(target).or_default().calls += 1;
+        self.frames.push(Frame { target, entered_at: self.total, nested: 0 });
+    }
+
+    fn leave_call(&mut self) {
+        self.flush_pending();
+        let frame = match self.frames.pop() {
+            Some(f) => f,
+            // Returning without a call stops the VM, so there is nothing to record.
+            None => return,
+        };
+        let inclusive = self.total - frame.entered_at;
+        let is_recursive = self.frames.iter().any(|f| f.target == frame.target);
+        let stats = self.calls.entry(frame.target).or_default();
+        if !is_recursive {
+            stats.inclusive += inclusive;
+        }
+        stats.exclusive += inclusive - frame.nested;
+        if let Some(parent) = self.frames.last_mut() {
+            parent.nested += inclusive;
+        }
+    }
+
+    /// Closes the calls that were still running when the program halted.
+    fn finish(&mut self) {
+        while !self.frames.is_empty() {
+            self.leave_call();
+        }
+        self.flush_pending();
+    }
+
+    fn flush_pending(&mut self) {
+        if self.pending == 0 { return; }
+        let mut stack = String::from("main");
+        for frame in self.frames.iter() {
+            stack.push(';');
+            stack.push_str(&self.location_name(frame.target));
+        }
+        *self.folded.entry(stack).or_insert(0) += self.pending;
+        self.pending = 0;
+    }
+
+    /// Label the instruction at `pc` belongs to, which is the closest label starting at or before it.
+    fn label_of(&self,pc:usize) -> Option<(&String,usize)> {
+        self.label_starts.range(..=pc).next_back().map(|(start,name)| (name,*start))
+    }
+
+    fn location_name(&self,pc:usize) -> String {
+        match self.label_of(pc) {
+            Some((name,start)) if start == pc => name.clone(),
+            Some((name,start)) => format!("{}+{}",name,pc-start),
+            None if pc == 0 => "<start>".to_string(),
+            None => format!("<start>+{}",pc),
+        }
+    }
+
+    /// Text report with the labels, calls and instructions sorted by how many instructions they executed.
+    pub fn report(&self,instructions:&[Instruction]) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("Instructions executed: {}\n",self.total));
+
+        let mut label_counts: HashMap<String,u64> = HashMap::new();
+        for (pc,count) in self.instruction_counts.iter().enumerate() {
+            if *count == 0 { continue; }
+            let name = self.label_of(pc).map(|(name,_)| name.clone()).unwrap_or_else(|| self.location_name(0));
+            *label_counts.entry(name).or_insert(0) += count;
+        }
+        let mut label_counts = label_counts.into_iter().collect::<Vec<(String,u64)>>();
+        label_counts.sort_by(|a,b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        out.push_str("\nLabels:\n");
+        out.push_str(&format!("{:>12} {:>8}  {}\n","executed","%","label"));
+        for (name,count) in label_counts.iter() {
+            out.push_str(&format!("{:>12} {:>8.2}  {}\n",count,self.percent(*count),name));
+        }
+
+        let mut calls = self.calls.iter().collect::<Vec<(&usize,&CallStats)>>();
+        calls.sort_by(|a,b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+        out.push_str("\nCalls:\n");
+        out.push_str(&format!("{:>12} {:>12} {:>12}  {}\n","calls","inclusive","exclusive","target"));
+        for (target,stats) in calls.iter() {
+            out.push_str(&format!("{:>12} {:>12} {:>12}  {}\n",stats.calls,stats.inclusive,stats.exclusive,self.location_name(**target)));
+        }
+
+        let mut pcs = self.instruction_counts.iter().enumerate()
+            .filter(|(_,count)| **count > 0)
+            .collect::<Vec<(usize,&u64)>>();
+        pcs.sort_by(|a,b| b.1.cmp(a.1).then(a.0.cmp(&b.0)));
+        out.push_str("\nInstructions:\n");
+        out.push_str(&format!("{:>12} {:>8} {:>6}  {}\n","executed","%","pc","location"));
+        for (pc,count) in pcs.iter() {
+            // The `Halt` a program runs into past its last instruction is not among them.
+            let inst = format!("{:?}",instructions.get(*pc).cloned().unwrap_or(Instruction::Halt));
+            out.push_str(&format!("{:>12} {:>8.2} {:>6}  {}  {}\n",count,self.percent(**count),pc,self.location_name(*pc),inst));
+        }
+        out
+    }
+
+    /// Call stacks in the folded format read by flamegraph tools, one `stack count` per line.
+    pub fn folded_stacks(&self) -> String {
+        let mut stacks = self.folded.iter().collect::<Vec<(&String,&u64)>>();
+        stacks.sort();
+        stacks.into_iter().map(|(stack,count)| format!("{} {}\n",stack,count)).collect()
+    }
+
+    fn percent(&self,count:u64) -> f64 {
+        if self.total == 0 { return 0.0; }
+        count as f64 * 100.0 / self.total as f64
+    }
+}
+
+impl Tracer for Profiler {
+    fn executed(&mut self,pc:usize,next_pc:usize,depth:usize) {
+        self.record_instruction(pc);
+        if depth > self.frames.len() {
+            self.enter_call(next_pc);
+        }
+        while self.frames.len() > depth {
+            self.leave_call();
+        }
+    }
+
+    fn halted(&mut self,pc:usize) {
+        self.record_instruction(pc);
+        self.finish();
+    }
+}
@@ -1,7 +1,12 @@
 
-const REGISTERS:[&str;4] = ["rax","rbx","rcx","rdx"];
-const FLOAT_REGISTERS:[&str;4] = ["fa","fb","fc","fd"];
-const FLAGS:[&str;4] = ["zf","eqf","gf","lf"];
+use crate::constants_and_types::{USER_REGISTER_COUNT,USER_FLOAT_REGISTER_COUNT};
+
+// A register's number is its index here.
+pub const REGISTERS:[&str;USER_REGISTER_COUNT] = ["rax","rbx","rcx","rdx","r8","r9","r10","r11","r12","r13","r14","r15"];
+pub const FLOAT_REGISTERS:[&str;USER_FLOAT_REGISTER_COUNT] = ["fa","fb","fc","fd","f8","f9","f10","f11","f12","f13","f14","f15"];
+/// Conditions of `assert`, the same as the suffixes of the jumps, `cmov` and `set`.
+pub const CONDITIONS:[&str;16] = ["z","nz","e","ne","g","l","ge","le","a","b","ae","be","o","no","c","nc"];
+const FLAGS:[&str;9] = ["zf","eqf","gf","lf","bf","af","of","cf","sf"];
 
 #[derive(Debug,Copy,Clone,PartialEq)]
 pub enum TokenType {
@@ -25,8 +30,22 @@ pub enum TokenType {
     JumpIfNotEqual,
     JumpIfGreater,
     JumpIfLess,
+    JumpIfGreaterOrEqual,
+    JumpIfLessOrEqual,
+    JumpIfAbove,
+    JumpIfBelow,
+    JumpIfAboveOrEqual,
+    JumpIfBelowOrEqual,
+    JumpIfOverflow,
+    JumpIfNotOverflow,
+    JumpIfCarry,
+    JumpIfNotCarry,
+    ArithmeticMode,
 
     Compare,
+    CompareUnsigned,
+    DivUnsigned,
+    ModUnsigned,
 
     GetFromStack,
     GetFromStackPointer,
@@ -43,6 +62,8 @@ pub enum TokenType {
     Ident,
     StringLit,
     Register,
+    /// `bp`, which programs can only read with `mov` and use in `[bp + k]`.
+    BasePointer,
     Float, 
     FloatRegister,
 
@@ -55,6 +76,11 @@ pub enum TokenType {
     Not,
     Xor,
     Nand,
+    Shl,
+    Shr,
+    Sar,
+    Rol,
+    Ror,
 
     TruncateStack,
     SetFromStackPointer,
@@ -68,6 +94,12 @@ pub enum TokenType {
     Displayf,
     PushFloatRegister,
     PopFloat,
+    CompareFloat,
+    ConvertFloat,
+    MovBits,
+    /// The value is the name of the function.
+    FloatMath,
+    LoadAddress,
 
     DisplayChar,
     GetFlag,
@@ -75,6 +107,21 @@ pub enum TokenType {
     BuiltinStart, // '@'
     LParen,
     RParen,
+    LBracket,
+    RBracket,
+    Plus,
+    Minus,
+    Enter,
+    Leave,
+    TailCall,
+    Loop,
+    Throw,
+    Breakpoint,
+    Assert,
+    /// The value is the condition, `ge` for `cmovge`.
+    ConditionalMove,
+    /// The value is the condition, `ge` for `setge`.
+    SetIf,
 
     Flag,
     GetStackPointer,
@@ -112,12 +159,28 @@ impl Token {
             "jg" => Token { token_type: TokenType::JumpIfGreater, value: None },
             "jl" => Token { token_type: TokenType::JumpIfLess, value: None },
             "cmp" => Token { token_type: TokenType::Compare, value: None },
+            "jge" => Token { token_type: TokenType::JumpIfGreaterOrEqual, value: None },
+            "jle" => Token { token_type: TokenType::JumpIfLessOrEqual, value: None },
+            "ja" => Token { token_type: TokenType::JumpIfAbove, value: None },
+            "jb" => Token { token_type: TokenType::JumpIfBelow, value: None },
+            "jae" => Token { token_type: TokenType::JumpIfAboveOrEqual, value: None },
+            "jbe" => Token { token_type: TokenType::JumpIfBelowOrEqual, value: None },
+            "jo" => Token { token_type: TokenType::JumpIfOverflow, value: None },
+            "jno" => Token { token_type: TokenType::JumpIfNotOverflow, value: None },
+            "jc" => Token { token_type: TokenType::JumpIfCarry, value: None },
+            "jnc" => Token { token_type: TokenType::JumpIfNotCarry, value: None },
+            "arith" => Token { token_type: TokenType::ArithmeticMode, value: None },
+            "cmpu" => Token { token_type: TokenType::CompareUnsigned, value: None },
+            "divu" => Token { token_type: TokenType::DivUnsigned, value: None },
+            "modu" => Token { token_type: TokenType::ModUnsigned, value: None },
             "getfromstack" => Token { token_type: TokenType::GetFromStack, value: None },
             "getfromstackpointer" | "getfromsp" => Token { token_type: TokenType::GetFromStackPointer, value: None },
             "malloc" => Token { token_type: TokenType::Malloc, value: None },
             "free" => Token { token_type: TokenType::Free, value: None },
             "getmem" => Token { token_type: TokenType::GetMemory, value: None },
             "setmem" => Token { token_type: TokenType::SetMemory, value: None },
+            "getmem8" | "getmem8s" | "getmem16" | "getmem16s" | "getmem32" | "getmem32s" => Token { token_type: TokenType::GetMemory, value: Some(word["getmem".len()..].to_string()) },
+            "setmem8" | "setmem16" | "setmem32" => Token { token_type: TokenType::SetMemory, value: Some(word["setmem".len()..].to_string()) },
             "label" => Token { token_type: TokenType::Label, value: None },
             "ret" => Token {token_type:TokenType::Return, value:None},
             "mul" => Token {token_type:TokenType::Mul,value:None},
@@ -127,6 +190,11 @@ impl Token {
             "not" => Token {token_type:TokenType::Not,value:None},
             "nand" => Token {token_type:TokenType::Nand,value:None},
             "xor" => Token {token_type:TokenType::Xor,value:None},
+            "shl" => Token {token_type:TokenType::Shl,value:None},
+            "shr" => Token {token_type:TokenType::Shr,value:None},
+            "sar" => Token {token_type:TokenType::Sar,value:None},
+            "rol" => Token {token_type:TokenType::Rol,value:None},
+            "ror" => Token {token_type:TokenType::Ror,value:None},
             "truncstack" => Token {token_type:TokenType::TruncateStack,value:None},
             "truncstackrange" | "truncstackr" => Token {token_type:TokenType::TruncateStackRange,value:None},
             "mod" => Token {token_type:TokenType::Mod,value:None},
@@ -142,6 +210,22 @@ impl Token {
             "displayf" => Token { token_type: TokenType::Displayf, value: None },
             "pushrf" | "pushregf" => Token { token_type: TokenType::PushFloatRegister, value: None },
             "popf" => Token { token_type: TokenType::PopFloat, value: None },
+            "cmpf" => Token { token_type: TokenType::CompareFloat, value: None },
+            "cvtf" => Token { token_type: TokenType::ConvertFloat, value: None },
+            "movbits" => Token { token_type: TokenType::MovBits, value: None },
+            "sqrtf" | "absf" | "floorf" | "ceilf" | "roundf" | "expf" | "lnf" | "sinf" | "cosf" | "tanf" |
+            "powf" | "atan2f" | "minf" | "maxf" => Token { token_type: TokenType::FloatMath, value: Some(word.to_string()) },
+            "lea" => Token { token_type: TokenType::LoadAddress, value: None },
+            "enter" => Token { token_type: TokenType::Enter, value: None },
+            "leave" => Token { token_type: TokenType::Leave, value: None },
+            "tailcall" => Token { token_type: TokenType::TailCall, value: None },
+            "loop" => Token { token_type: TokenType::Loop, value: None },
+            "throw" => Token { token_type: TokenType::Throw, value: None },
+            "brk" => Token { token_type: TokenType::Breakpoint, value: None },
+            "assert" => Token { token_type: TokenType::Assert, value: None },
+            "cmovz" | "cmovnz" | "cmove" | "cmovne" | "cmovg" | "cmovl" | "cmovge" | "cmovle" | "cmova" | "cmovb" | "cmovae" | "cmovbe" | "cmovo" | "cmovno" | "cmovc" | "cmovnc" => Token { token_type: TokenType::ConditionalMove, value: Some(word["cmov".len()..].to_string()) },
+            "setz" | "setnz" | "sete" | "setne" | "setg" | "setl" | "setge" | "setle" | "seta" | "setb" | "setae" | "setbe" | "seto" | "setno" | "setc" | "setnc" => Token { token_type: TokenType::SetIf, value: Some(word["set".len()..].to_string()) },
+            "bp" => Token { token_type: TokenType::BasePointer, value: Some("bp".to_string()) },
             "displaychar" | "displayc" | "putc" => Token {token_type: TokenType::DisplayChar, value:None},
             "getflag" => Token {token_type:TokenType::GetFlag, value:None},
             "getsp" => Token {token_type:TokenType::GetStackPointer, value:None},
@@ -153,7 +237,8 @@ impl Token {
                 let is_flag = FLAGS.iter().find(|x| ***x == *word.to_lowercase().as_str());
                 if let Some(reg) = is_reg {
                     return Token { token_type: TokenType::Register, value: Some(reg.to_string())};
-                } if let Some(freg) = is_freg {
+                }
+                if let Some(freg) = is_freg {
                     return Token { token_type: TokenType::FloatRegister, value: Some(freg.to_string())}
                 }
                 if let Some(flag) = is_flag {
@@ -172,6 +257,9 @@ impl Token {
 pub struct Tokenizer {
     input:String,
     char_index:usize,
+    line:usize,
+    /// Line each token returned by `tokenize` starts on, counting from 1.
+    pub token_lines: Vec<usize>,
 }
 
 impl Tokenizer {
@@ -179,6 +267,8 @@ impl Tokenizer {
         return Self {
             input,
             char_index: 0,
+            line: 1,
+            token_lines: Vec::new(),
         }
     }
 
@@ -194,6 +284,7 @@ impl Tokenizer {
                 self.consume_char();
                 continue;
             }
+            let line = self.line;
             if ch.is_alphabetic() || ch == '_' {
                 buf.push(ch);
                 self.consume_char();
@@ -208,6 +299,10 @@ impl Tokenizer {
 
                 tokens.push(Token::process_word(buf.clone()));
                 buf.clear();
+            } else if ch == '-' && !self.peek_char_offset(1).is_some_and(|c| c.is_numeric() || c == '.') {
+                // `[bp - 3]`
+                self.consume_char();
+                tokens.push(Token { token_type: TokenType::Minus, value: None });
             } else if ch == '-' {
                 buf.push(ch);
                 self.consume_char();
@@ -235,6 +330,7 @@ impl Tokenizer {
                         value: Some(buf.clone()),
                     });
                 } else {
+                    check_int_literal(&buf);
                     tokens.push(Token {
                         token_type: TokenType::IntLit,
                         value: Some(buf.clone()),
@@ -266,6 +362,7 @@ impl Tokenizer {
                         value: Some(buf.clone()),
                     });
                 } else {
+                    check_int_literal(&buf);
                     tokens.push(Token {
                         token_type: TokenType::IntLit,
                         value: Some(buf.clone()),
@@ -292,6 +389,18 @@ impl Tokenizer {
                         self.consume_char();
                         tokens.push(Token { token_type: TokenType::RParen, value: None });
                     }
+                    '+' => {
+                        self.consume_char();
+                        tokens.push(Token { token_type: TokenType::Plus, value: None });
+                    }
+                    '[' => {
+                        self.consume_char();
+                        tokens.push(Token { token_type: TokenType::LBracket, value: None });
+                    }
+                    ']' => {
+                        self.consume_char();
+                        tokens.push(Token { token_type: TokenType::RBracket, value: None });
+                    }
                     ';' => {
                         self.consume_char();
                         let mut is_comment = true;
@@ -342,6 +451,7 @@ impl Tokenizer {
                     _ => panic!("Unrecognized token {:?}", ch),
                 }
             }
+            self.token_lines.resize(tokens.len(),line);
         }
         if is_string {
             println!("Did not find closing \".");
@@ -364,6 +474,17 @@ impl Tokenizer {
         if ch.is_some() {
             self.char_index += 1;
         }
+        if ch == Some('\n') {
+            self.line += 1;
+        }
         ch
     }
 }
+
+/// Integer literals can be as wide as a 64 bit register, signed or unsigned.
+fn check_int_literal(buf:&str) {
+    if buf.parse::<i64>().is_err() && buf.parse::<u64>().is_err() {
+        println!("Tokenization Error.\nInteger {} does not fit in 64 bits.",buf);
+        std::process::exit(1);
+    }
+}
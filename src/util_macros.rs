@@ -73,7 +73,7 @@ macro_rules! create_fcommand_two_arg {
 macro_rules! jump {
     ($a:expr,$labels:expr,$insts:expr,$run_label_raw_inst:expr) =>  {
         {
-               use crate::instruction::StringNumberUnion;
+               use $crate::instruction::StringNumberUnion;
                 let label_address = match $a {
                     StringNumberUnion::String(s) => {
                        if let Some(value) = $labels.get(s.into()) {
@@ -99,7 +99,7 @@ macro_rules! parse_jump {
     ($jump_token_type:expr,$jump_node_inst:expr,$try_consume:expr) => {
         {
             if let Some(_jmp_tok) = $try_consume($jump_token_type) {
-                use crate::parser::NodeInstruction::*;
+                use $crate::parser::NodeInstruction::*;
                 if let Some(label_name) = $try_consume(TokenType::Ident){ 
 
                     match $jump_node_inst {
@@ -109,9 +109,19 @@ macro_rules! parse_jump {
                             NodeInstructionJumpIfEqual{value: ref mut value}|
                             NodeInstructionJumpIfNotEqual{value: ref mut value}|
                             NodeInstructionJumpIfGreater{value: ref mut value}|
-                            NodeInstructionJumpIfLess{value: ref mut value}
+                            NodeInstructionJumpIfLess{value: ref mut value}|
+                            NodeInstructionJumpIfGreaterOrEqual{value: ref mut value}|
+                            NodeInstructionJumpIfLessOrEqual{value: ref mut value}|
+                            NodeInstructionJumpIfAbove{value: ref mut value}|
+                            NodeInstructionJumpIfBelow{value: ref mut value}|
+                            NodeInstructionJumpIfAboveOrEqual{value: ref mut value}|
+                            NodeInstructionJumpIfBelowOrEqual{value: ref mut value}|
+                            NodeInstructionJumpIfOverflow{value: ref mut value}|
+                            NodeInstructionJumpIfNotOverflow{value: ref mut value}|
+                            NodeInstructionJumpIfCarry{value: ref mut value}|
+                            NodeInstructionJumpIfNotCarry{value: ref mut value}
                             =>  {
-                                *value = crate::parser::NodeExpr::NodeExprLabelName {
+                                *value = $crate::parser::NodeExpr::NodeExprLabelName {
                                     value:label_name,
                                 };
                             }, 
@@ -1,58 +1,117 @@
 use crate::{
-instruction::Instruction,
+instruction::{Instruction,ProgramHeader},
     constants_and_types::*,
     memory::*,
+    parser::SourceLocation,
+    float_math,
 };
 
 
 use crate::{
     to_binary_slice,binary_slice_to_number,
-    ones_complement,twos_complement,integer_from_twos_complement,
+    twos_complement,integer_from_twos_complement,
     jump,
     binary_to_float,to_float_repr
 };
 
 
-use std::collections::HashMap;
+use std::collections::{HashMap,HashSet};
+
+/// Gets told about every instruction `VM::eval_traced` runs.
+pub trait Tracer {
+    /// The instruction at `pc` ran and `next_pc` runs next. `depth` is how many return addresses
+    /// there are after it, one more after a call and fewer after a return or a caught error.
+    fn executed(&mut self, pc:usize, next_pc:usize, depth:usize);
+    /// The program reached the `Halt` at `pc`.
+    fn halted(&mut self, pc:usize);
+    /// The instruction at `pc` is about to run.
+    fn before(&mut self, _vm:&VM, _pc:usize) {}
+    /// The program reached the `Breakpoint` at `pc`. Returns whether the tracer is a debugger that
+    /// took over, otherwise the VM prints its state as when nothing is attached.
+    fn breakpoint(&mut self, _vm:&VM, _pc:usize) -> bool {
+        false
+    }
+}
+
 pub struct VM {
-    registers: [RegisterDataType;7],
-    floating_point_registers: [FloatRegisterDataType;5],
+    registers: [RegisterDataType;REGISTER_COUNT],
+    floating_point_registers: [FloatRegisterDataType;FLOAT_REGISTER_COUNT],
     stack:Vec<[u8;STACK_DATA_SIZE]>,
    
     // points to the position where the next element of the stack will be added
     // default value is 0
     sp:usize,
-    instructions:[Instruction;1000],
+    instructions:[Instruction;INSTRUCTION_MEMORY_SIZE],
     command_pointer: usize, 
     last_command:usize,
     flags:[u8;16],
     return_addresses:Vec<usize>,
     labels:HashMap<String,(usize,Option<usize>)>,
     memory: MemoryHandler,
+    /// Whether add, sub, mul, div and mod stop with a runtime error when the result does not fit.
+    trap_overflow: bool,
+    /// Source location of each instruction, used in runtime error messages.
+    source_map: Vec<Option<SourceLocation>>,
+    header: ProgramHeader,
+    /// Handlers of the `Try`s that have not reached their `EndTry`, innermost last.
+    handlers: Vec<Handler>,
+    /// Addresses the `LoadAddress`es of the program load, the only ones `JumpRegister` and
+    /// `CallRegister` can go to. Filled by `collect_lea_targets` once the labels are resolved.
+    lea_targets: HashSet<usize>,
+}
+
+/// Where a runtime error goes after a `Try`, and the state to go back to.
+struct Handler {
+    address: usize,
+    return_addresses: usize,
+    /// A copy of the whole stack, as the protected code can pop or overwrite what was there.
+    stack: Vec<[u8;STACK_DATA_SIZE]>,
+    sp: usize,
+    bp: RegisterDataType,
+}
+
+/// A runtime error an instruction returns, `run_current_inst` hands it to the last handler.
+pub struct Fault {
+    code: RegisterDataType,
+    /// What to print when no handler catches it.
+    report: String,
 }
 
 impl VM {
     pub fn new() -> Self {
         const ARRAY_REPEAT_VALUE:Instruction = Instruction::Halt;
         Self {
-            registers: [0; 7],
-            floating_point_registers: [0.0;5],
+            registers: [0; REGISTER_COUNT],
+            floating_point_registers: [0.0;FLOAT_REGISTER_COUNT],
             stack: Vec::new(),
             sp: 0,
-            instructions:[ARRAY_REPEAT_VALUE;1000],
+            instructions:[ARRAY_REPEAT_VALUE;INSTRUCTION_MEMORY_SIZE],
             command_pointer: 0,
             last_command:1, // 0th element will be a Jump to main label
             flags: [0;16],
             labels:HashMap::new(),
             return_addresses: Vec::new(),
-            memory:MemoryHandler::new()
+            memory:MemoryHandler::new(),
+            trap_overflow: false,
+            source_map: Vec::new(),
+            header: ProgramHeader::default(),
+            handlers: Vec::new(),
+            lea_targets: HashSet::new(),
         }
     }
 
     pub fn from_raw_instructions(s:String) -> Self {
+        if s.len() < HEADER_SIZE || s[..HEADER_SIZE].chars().any(|ch| ch != '0' && ch != '1') {
+            println!("Bytecode Corruption Error: Bytecode does not start with a header.");
+            std::process::exit(1);
+        }
+        let header = ProgramHeader::from_number(binary_slice_to_number!(HeaderBinaryType,s[..HEADER_SIZE]
+            .chars()
+            .map(|x| x.to_digit(2).unwrap() as u8)
+            .collect::<Vec<u8>>()));
         let mut instructions = {
             let mut v:Vec<Instruction> = Vec::new();
-            let mut i = INSTRUCTION_NAME_SIZE; 
+            let mut i = HEADER_SIZE + INSTRUCTION_NAME_SIZE; 
             let step_by = INSTRUCTION_NAME_SIZE;
             while i < s.len(){
                 let ch = s.chars().nth(i);
@@ -77,7 +136,7 @@ impl VM {
                         std::process::exit(1);
                     }
                 };
-                if instruction == Instruction::Halt || instruction == Instruction::Return {
+                if instruction == Instruction::Halt || instruction == Instruction::Return || instruction == Instruction::Leave || instruction == Instruction::EndTry || instruction == Instruction::Breakpoint {
                     v.push(instruction);
                     i += step_by; continue;
                 }
@@ -102,7 +161,15 @@ impl VM {
                         GetFlag(ref mut a, ref mut b) |
                         TruncateStackRange(ref mut a, ref mut b) |
                         Write(ref mut a, ref mut b) |
-                        ExtendStack(ref mut a, ref mut b)
+                        ExtendStack(ref mut a, ref mut b) |
+                        MovRegister(ref mut a, ref mut b) | MovfRegister(ref mut a, ref mut b) |
+                        MovIntToFloat(ref mut a, ref mut b) | MovFloatToInt(ref mut a, ref mut b) |
+                        Shl(ref mut a, ref mut b) | Shr(ref mut a, ref mut b) | Sar(ref mut a, ref mut b) |
+                        Rol(ref mut a, ref mut b) | Ror(ref mut a, ref mut b) |
+                        CompareUnsigned(ref mut a, ref mut b) | DivUnsigned(ref mut a, ref mut b) | ModUnsigned(ref mut a, ref mut b) |
+                        CompareFloat(ref mut a, ref mut b) | MovFloatBitsToInt(ref mut a, ref mut b) | MovIntBitsToFloat(ref mut a, ref mut b) |
+                        JumpTable(ref mut a, ref mut b) |
+                        SetIf(ref mut a, ref mut b)
 
                         => {
 
@@ -131,7 +198,13 @@ impl VM {
                             Not(ref mut a) |
                         GetStackPointer(ref mut a) | 
                         Malloc(ref mut a) |
-                        Free(ref mut a)
+                        Free(ref mut a) |
+                        SetArithmeticMode(ref mut a) |
+                        JumpRegister(ref mut a) |
+                        CallRegister(ref mut a) |
+                        Enter(ref mut a) |
+                        Throw(ref mut a) |
+                        AssertFailed(ref mut a)
                             => {
                                 let size_reg = param_size.0.unwrap();
                             let param = s[i..i+size_reg]
@@ -147,7 +220,15 @@ impl VM {
                             }
 
                         // Register, integer 
-                        Mov(ref mut a,ref mut b) => {
+                        Mov(ref mut a,ref mut b) |
+                            AddImmediate(ref mut a,ref mut b) | SubImmediate(ref mut a,ref mut b) | MulImmediate(ref mut a,ref mut b) |
+                            DivImmediate(ref mut a,ref mut b) | ModImmediate(ref mut a,ref mut b) |
+                            AndImmediate(ref mut a,ref mut b) | OrImmediate(ref mut a,ref mut b) | XorImmediate(ref mut a,ref mut b) |
+                            CompareImmediate(ref mut a,ref mut b) |
+                            CompareUnsignedImmediate(ref mut a,ref mut b) | DivUnsignedImmediate(ref mut a,ref mut b) | ModUnsignedImmediate(ref mut a,ref mut b) |
+                            ShlImmediate(ref mut a,ref mut b) | ShrImmediate(ref mut a,ref mut b) | SarImmediate(ref mut a,ref mut b) |
+                            RolImmediate(ref mut a,ref mut b) | RorImmediate(ref mut a,ref mut b) |
+                            GetFromBasePointer(ref mut a,ref mut b) | SetFromBasePointer(ref mut a,ref mut b) => {
                             let (size_a,size_b) = (param_size.0.unwrap(),param_size.1.unwrap());
                             let param_a = binary_slice_to_number!(InstructionParamType,s[i..i+size_a]
                                 .chars()
@@ -174,11 +255,11 @@ impl VM {
                                 .chars()
                                 .map(|x| x.to_digit(2).unwrap() as u8)
                                 .collect::<Vec<u8>>());
-                            let param_b = binary_slice_to_number!(InstructionParamType,s[i+size_a..i+size_a+size_b]
+                            let param_b = binary_slice_to_number!(u64,s[i+size_a..i+size_a+size_b]
                                 .chars().
                                 map(|x| x.to_digit(2).unwrap() as u8).
                                 collect::<Vec<u8>>());
-                            let param_b_float = binary_to_float!(FloatInstructionParamType,InstructionParamType,param_b);
+                            let param_b_float = binary_to_float!(FloatInstructionParamType,u64,param_b);
                         *a  = param_a;
                             *b = param_b_float;
                         v.push(instruction.clone());
@@ -186,6 +267,49 @@ impl VM {
                             
                             i += step_by; continue;
                         }
+                        MovWide(ref mut a,ref mut b) => {
+                            let (size_a,size_b) = (param_size.0.unwrap(),param_size.1.unwrap());
+                            let param_a = binary_slice_to_number!(InstructionParamType,s[i..i+size_a]
+                                .chars()
+                                .map(|x| x.to_digit(2).unwrap() as u8)
+                                .collect::<Vec<u8>>());
+                            let param_b = binary_slice_to_number!(RegisterDataType,s[i+size_a..i+size_a+size_b]
+                                .chars()
+                                .map(|x| x.to_digit(2).unwrap() as u8)
+                                .collect::<Vec<u8>>());
+                            *a = param_a;
+                            *b = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,param_b);
+                            v.push(instruction.clone());
+                            i += size_a+size_b;
+                            i += step_by; continue;
+                        }
+                        LoadAddress(ref mut a,ref mut b) | Loop(ref mut a,ref mut b) => {
+                            let (size_a,size_b) = (param_size.0.unwrap(),param_size.1.unwrap());
+                            let param_a = binary_slice_to_number!(InstructionParamType,s[i..i+size_a]
+                                .chars()
+                                .map(|x| x.to_digit(2).unwrap() as u8)
+                                .collect::<Vec<u8>>());
+                            let param_b = binary_slice_to_number!(InstructionParamType,s[i+size_a..i+size_a+size_b]
+                                .chars()
+                                .map(|x| x.to_digit(2).unwrap() as u8)
+                                .collect::<Vec<u8>>());
+                            *a = param_a;
+                            *b = crate::instruction::StringNumberUnion::Num(param_b);
+                            v.push(instruction.clone());
+                            i += size_a+size_b;
+                            i += step_by; continue;
+                        }
+                        PushWide(ref mut a) => {
+                            let size_a = param_size.0.unwrap();
+                            let param_a = binary_slice_to_number!(RegisterDataType,s[i..i+size_a]
+                                .chars()
+                                .map(|x| x.to_digit(2).unwrap() as u8)
+                                .collect::<Vec<u8>>());
+                            *a = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,param_a);
+                            v.push(instruction.clone());
+                            i += size_a;
+                            i += step_by; continue;
+                        }
                         Push(ref mut a) => {
                         let size_a = param_size.0.unwrap();
                         let param_a = integer_from_twos_complement!(iInstructionParamType,InstructionParamType,binary_slice_to_number!(InstructionParamType,s[i..i+size_a]
@@ -208,6 +332,18 @@ impl VM {
                             JumpIfNotEqual(ref mut dest) |
                             JumpIfGreater(ref mut dest) |
                             JumpIfLess(ref mut dest)|
+                            JumpIfGreaterOrEqual(ref mut dest)|
+                            JumpIfLessOrEqual(ref mut dest)|
+                            JumpIfAbove(ref mut dest)|
+                            JumpIfBelow(ref mut dest)|
+                            JumpIfAboveOrEqual(ref mut dest)|
+                            JumpIfBelowOrEqual(ref mut dest)|
+                            JumpIfOverflow(ref mut dest)|
+                            JumpIfNotOverflow(ref mut dest)|
+                            JumpIfCarry(ref mut dest)|
+                            JumpIfNotCarry(ref mut dest)|
+                            TailCall(ref mut dest)|
+                            Try(ref mut dest)|
                             Call(ref mut dest)=> {
                                 if param_size.0.is_none() {
                                     println!("Bytecode Error: Argument 0 not found for {:?}",instruction.clone());
@@ -225,7 +361,14 @@ impl VM {
                         }
                         GetMemory(ref mut a, ref mut b, ref mut c) |
                             SetMemory(ref mut a, ref mut b, ref mut c) | 
-                            StackCopyBackSp(ref mut a, ref mut b, ref mut c) => {
+                            GetMemory8(ref mut a, ref mut b, ref mut c) | GetMemory8Signed(ref mut a, ref mut b, ref mut c) |
+                            GetMemory16(ref mut a, ref mut b, ref mut c) | GetMemory16Signed(ref mut a, ref mut b, ref mut c) |
+                            GetMemory32(ref mut a, ref mut b, ref mut c) | GetMemory32Signed(ref mut a, ref mut b, ref mut c) |
+                            SetMemory8(ref mut a, ref mut b, ref mut c) | SetMemory16(ref mut a, ref mut b, ref mut c) | SetMemory32(ref mut a, ref mut b, ref mut c) |
+                            StackCopyBackSp(ref mut a, ref mut b, ref mut c) |
+                            MovFloatToIntRounded(ref mut a, ref mut b, ref mut c) |
+                            FloatMath(ref mut a, ref mut b, ref mut c) |
+                            ConditionalMove(ref mut a, ref mut b, ref mut c) => {
                                 if param_size.0.is_none() {
                                     println!("Bytecode Error: Argument 0 not found for {:?}",instruction.clone());
                                     std::process::exit(1);
@@ -265,19 +408,27 @@ impl VM {
             }
             v
         };
-        let to_fill = 1000-instructions.len();
+        let decoded_count = instructions.len();
+        if decoded_count > INSTRUCTION_MEMORY_SIZE {
+            println!("Bytecode Error: {} instructions do not fit in the {} the VM has.",decoded_count,INSTRUCTION_MEMORY_SIZE);
+            std::process::exit(1);
+        }
+        let to_fill = INSTRUCTION_MEMORY_SIZE-instructions.len();
         for _i in 0..to_fill {
             instructions.push(Instruction::Halt);
         }
         let mut vm = Self::new();
+        vm.header = header;
         vm.instructions = instructions.try_into().unwrap();
+        vm.last_command = decoded_count;
+        vm.collect_lea_targets();
         vm
 
     }
 
         
 
-    pub fn run_instruction(&mut self, inst:&Instruction) {
+    pub fn run_instruction(&mut self, inst:&Instruction) -> Result<(),Fault> {
         use Instruction::*;
         match inst {
           Halt => { std::process::exit(0); }
@@ -287,20 +438,12 @@ impl VM {
                 self.registers[*dest as usize] =  twos_comp as RegisterDataType;
             }
             Add(a,b) => {
-                let reg_a = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*a as usize]);
-                let reg_b = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*b as usize]);
-                let sum = reg_a + reg_b; 
-                let sum_twos_comp = twos_complement!(RegisterDataType,sum);
-                self.registers[*a as usize] = sum_twos_comp;
-                //self.registers[a as usize]+=self.registers[b as usize];
+                let value = self.registers[*b as usize];
+                self.add(*a,value)?;
             }
             Sub(a,b) => {
-                let (a,b) = (*a,*b);
-                let reg_a = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[a as usize]);
-                let reg_b = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[b as usize]);
-                let dif = reg_a - reg_b; 
-                let dif_twos_comp = twos_complement!(RegisterDataType,dif);
-                self.registers[a as usize] = dif_twos_comp;          
+                let value = self.registers[*b as usize];
+                self.sub(*a,value)?;
             }
 
             Display(a) => {
@@ -311,6 +454,14 @@ impl VM {
                 let twos_comp = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,real_num);
                 println!("{:?}",twos_comp);
             }
+            MovWide(dest,val) => {
+                self.registers[*dest as usize] = self.wrap(twos_complement!(RegisterDataType,*val));
+            }
+            PushWide(a) => {
+                let a = to_binary_slice!(RegisterDataType,self.wrap(twos_complement!(RegisterDataType,*a)));
+                self.stack.push(a.as_slice().try_into().unwrap());
+                self.sp += 1;
+            }
             Push(a) => {
 
                 let  a =  to_binary_slice!(RegisterDataType,twos_complement!(RegisterDataType,*a));
@@ -327,26 +478,38 @@ impl VM {
                 self.sp += 1;
             }
             Pop(a) => {
-                if self.stack.len() == 0 {
-                    println!("Runtime Error: Stack cannot be popped from as stack is empty.");
-                    std::process::exit(1);
+                if self.stack.is_empty() {
+                    return Err(Fault { code: ERROR_STACK, report: "Runtime Error: Stack cannot be popped from as stack is empty.".to_string() });
                 }
                 let pop = self.stack.pop().unwrap();
                 
                 let pop_num = binary_slice_to_number!(RegisterDataType,&pop);
-                self.registers[*a as usize] = pop_num;
+                self.registers[*a as usize] = self.wrap(pop_num);
                 self.sp -= 1;
             }
             PushFloatRegister(a) => {
-                let register_data = to_binary_slice!(RegisterDataType,to_float_repr!(FloatRegisterDataType,RegisterDataType,self.floating_point_registers[*a as usize])).try_into().unwrap();
+                let float = self.floating_point_registers[*a as usize];
+                let bits = if self.header.double_floats {
+                    to_float_repr!(FloatRegisterDataType,RegisterDataType,float)
+                }else {
+                    self.wrap(to_float_repr!(f32,u32,float as f32) as RegisterDataType)
+                };
+                let register_data = to_binary_slice!(RegisterDataType,bits).try_into().unwrap();
 
                 self.stack.push(register_data);
                 self.sp += 1;
             }
             PopFloat(a) => {
-
-                let pop = self.stack.pop();
-                self.floating_point_registers[*a as usize] = binary_to_float!(FloatRegisterDataType,RegisterDataType,binary_slice_to_number!(RegisterDataType,&pop.unwrap()));
+                let pop = match self.stack.pop() {
+                    Some(pop) => pop,
+                    None => return Err(Fault { code: ERROR_STACK, report: "Runtime Error: Stack cannot be popped from as stack is empty.".to_string() }),
+                };
+                let bits = binary_slice_to_number!(RegisterDataType,&pop);
+                self.floating_point_registers[*a as usize] = if self.header.double_floats {
+                    binary_to_float!(FloatRegisterDataType,RegisterDataType,bits)
+                }else {
+                    binary_to_float!(f32,u32,bits) as FloatRegisterDataType
+                };
                 self.sp -= 1;
             }
 
@@ -371,9 +534,53 @@ impl VM {
                 //  jump_inst!(dest,labels,rn); 
             }
 
+            TailCall(a) => {
+                // The frame being replaced takes its handlers with it.
+                self.drop_handlers(self.return_addresses.len());
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { 
+                    self.set_command_pointer(ad-1);
+                };
+                jump!(a,labels,insts,s);
+            }
+
+            Loop(a,s) => {
+                let count = self.wrap(self.registers[*a as usize].wrapping_sub(1));
+                self.registers[*a as usize] = count;
+                if count == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut jump_to = |ad| { self.set_command_pointer(ad-1); };
+                jump!(s,labels,insts,jump_to);
+            }
+
+            JumpRegister(a) => {
+                let target = self.indirect_target(*a)?;
+                self.set_command_pointer(target-1);
+            }
+
+            CallRegister(a) => {
+                let target = self.indirect_target(*a)?;
+                self.return_addresses.push(self.command_pointer);
+                self.set_command_pointer(target-1);
+            }
+
+            JumpTable(a,count) => {
+                let index = self.registers[*a as usize].min(*count as RegisterDataType);
+                self.set_command_pointer(self.command_pointer+index as usize);
+            }
+
+            LoadAddress(a,s) => {
+                match self.resolve_label(s) {
+                    crate::instruction::StringNumberUnion::Num(address) => self.registers[*a as usize] = address as RegisterDataType,
+                    crate::instruction::StringNumberUnion::String(name) => return Err(self.trap(ERROR_BAD_ADDRESS,&format!("label {} does not exist",name))),
+                }
+            }
+
             JumpIfZero(a) => {
 
-                if  *self.get_flag(ZERO_FLAG).unwrap() == 0{ return };
+                if  *self.get_flag(ZERO_FLAG).unwrap() == 0{ return Ok(()) };
                 let labels = self.labels.clone();
                 let insts = self.instructions.clone();
                 let mut s = |ad| {self.set_command_pointer(ad-1);};
@@ -381,7 +588,7 @@ impl VM {
 
             }
             JumpIfNotZero(a) => {
-                if  *self.get_flag(ZERO_FLAG).unwrap() !=0 { return };
+                if  *self.get_flag(ZERO_FLAG).unwrap() !=0 { return Ok(()) };
 
                 let labels = self.labels.clone();
                 let insts = self.instructions.clone();
@@ -389,14 +596,14 @@ impl VM {
                 jump!(a,labels,insts,s);            
             }
             JumpIfEqual(a) => {
-                if *self.get_flag(EQUAL_FLAG).unwrap() == 0 { return; }
+                if *self.get_flag(EQUAL_FLAG).unwrap() == 0 { return Ok(()); }
                 let labels = self.labels.clone();
                 let insts = self.instructions.clone();
                 let mut s = |ad| { self.set_command_pointer(ad-1); };
                 jump!(a,labels,insts,s);
             } 
             JumpIfNotEqual(a) => {
-                if *self.get_flag(EQUAL_FLAG).unwrap() == 1 { return; }
+                if *self.get_flag(EQUAL_FLAG).unwrap() == 1 { return Ok(()); }
                 let labels = self.labels.clone();
                 let insts = self.instructions.clone();
                 let mut s = |ad| {self.set_command_pointer(ad-1); };
@@ -405,7 +612,7 @@ impl VM {
             }
 
             JumpIfGreater(a) => {
-                if *self.get_flag(GREATER_THAN_FLAG).unwrap() == 0 { return; }
+                if *self.get_flag(GREATER_THAN_FLAG).unwrap() == 0 { return Ok(()); }
                 let labels = self.labels.clone();
                 let insts = self.instructions.clone();
                 let mut s = |ad| { self.set_command_pointer(ad-1) };
@@ -413,42 +620,111 @@ impl VM {
                 jump!(a,labels,insts,s);   
             }
             JumpIfLess(a) => {
-                if *self.get_flag(LESS_THAN_FLAG).unwrap() == 0 { return; }
+                if *self.get_flag(LESS_THAN_FLAG).unwrap() == 0 { return Ok(()); }
                 let labels = self.labels.clone();
                 let insts = self.instructions.clone();
                 let mut s = |ad| {  self.set_command_pointer(ad-1)  };
                 jump!(a,labels,insts,s);    
             }
 
+            JumpIfGreaterOrEqual(a) => {
+                if *self.get_flag(GREATER_THAN_FLAG).unwrap() == 0 && *self.get_flag(EQUAL_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfLessOrEqual(a) => {
+                if *self.get_flag(LESS_THAN_FLAG).unwrap() == 0 && *self.get_flag(EQUAL_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfAbove(a) => {
+                if *self.get_flag(ABOVE_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfBelow(a) => {
+                if *self.get_flag(BELOW_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfAboveOrEqual(a) => {
+                if *self.get_flag(ABOVE_FLAG).unwrap() == 0 && *self.get_flag(EQUAL_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfBelowOrEqual(a) => {
+                if *self.get_flag(BELOW_FLAG).unwrap() == 0 && *self.get_flag(EQUAL_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+
+            JumpIfOverflow(a) => {
+                if *self.get_flag(OVERFLOW_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfNotOverflow(a) => {
+                if *self.get_flag(OVERFLOW_FLAG).unwrap() == 1 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfCarry(a) => {
+                if *self.get_flag(CARRY_FLAG).unwrap() == 0 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+            JumpIfNotCarry(a) => {
+                if *self.get_flag(CARRY_FLAG).unwrap() == 1 { return Ok(()); }
+                let labels = self.labels.clone();
+                let insts = self.instructions.clone();
+                let mut s = |ad| { self.set_command_pointer(ad-1) };
+                jump!(a,labels,insts,s);
+            }
+
             Compare(a,b) => {
 
                 let (a,b) = (*a,*b);
                 let reg_a = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[a as usize]);
                 let reg_b =  integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[b as usize]);
-                let _ = self.set_flag(ZERO_FLAG, (reg_a ==0 && reg_b == 0) as u8);
-                let _ = self.set_flag(EQUAL_FLAG,(reg_a==reg_b) as u8);
-                let _ =self.set_flag(GREATER_THAN_FLAG,(reg_a>reg_b) as u8);
-                let _ =self.set_flag(LESS_THAN_FLAG,(reg_a<reg_b) as u8);
+                self.compare(reg_a,reg_b);
             }
 
             GetFromStack(sp,reg) => {
                 let (reg,sp) = (*reg,*sp);
                 let regsp = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[sp as usize]) as RegisterDataType;
                 if let Some(content) = self.stack.get(regsp as usize) {
-                    self.registers[reg as usize] = binary_slice_to_number!(RegisterDataType,content);
+                    self.registers[reg as usize] = self.wrap(binary_slice_to_number!(RegisterDataType,content));
                 }else {
-                    panic!("Cannot get element number: {:?} from stack with total items: {:?}",regsp,self.stack.len());
+                    return Err(self.trap(ERROR_STACK,&format!("cannot get element {} of the stack",regsp as iRegisterDataType)));
                 }
             }
             GetFromStackPointer(offset,reg) => {
                 let (offset,reg) = (*offset,*reg);
                 let sp = self.sp;
                 let regoffset = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[offset as usize]);
-                let index = sp - regoffset as usize;
-                if let Some(content) = self.stack.get(index) {
-                    self.registers[reg as usize] = binary_slice_to_number!(RegisterDataType,content);
+                let index = sp as iRegisterDataType - regoffset;
+                if let Some(content) = usize::try_from(index).ok().and_then(|index| self.stack.get(index)) {
+                    self.registers[reg as usize] = self.wrap(binary_slice_to_number!(RegisterDataType,content));
                 }else {
-                    panic!("Cannot get element number: {:?} from stack with total items: {:?}",sp,self.stack.len());
+                    return Err(self.trap(ERROR_STACK,&format!("cannot get element {} of the stack",index)));
                 }
 
             }
@@ -457,8 +733,8 @@ impl VM {
                 let (loc,reg) = (*loc,*reg);
                 let regloc = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[loc as usize]);
                 let index = regloc as usize;
-                  if index >= self.stack.len() {
-                    panic!("Cannot set element number: {:?} from stack with total items: {:?}",index,self.stack.len());
+                  if regloc < 0 || index >= self.stack.len() {
+                    return Err(self.trap(ERROR_STACK,&format!("cannot set element {} of the stack",regloc)));
                 }
                 self.stack[index] =  to_binary_slice!(RegisterDataType,self.registers[reg as usize]).as_slice().try_into().unwrap();
                
@@ -468,15 +744,45 @@ impl VM {
                 let (offset,reg) = (*offset,*reg);
                 let sp = self.sp;
                 let regoffset = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[offset as usize]);
-                let index = sp - regoffset as usize;
-                if index >= self.stack.len() {
-                    panic!("Cannot set element number: {:?}  from stack pointer from stack with total items: {:?}",sp,self.stack.len());
+                let index = sp as iRegisterDataType - regoffset;
+                if index < 0 || index as usize >= self.stack.len() {
+                    return Err(self.trap(ERROR_STACK,&format!("cannot set element {} of the stack",index)));
                 }
+                let index = index as usize;
                 self.stack[index] =  to_binary_slice!(RegisterDataType,self.registers[reg as usize]).as_slice().try_into().unwrap();
                 
 
             }
 
+            Enter(locals) => {
+                let caller_bp = to_binary_slice!(RegisterDataType,self.registers[REGBP as usize]);
+                self.stack.push(caller_bp.as_slice().try_into().unwrap());
+                self.registers[REGBP as usize] = self.wrap(self.stack.len() as RegisterDataType);
+                for _ in 0..*locals {
+                    self.stack.push([0;STACK_DATA_SIZE]);
+                }
+                self.sp += *locals as usize + 1;
+            }
+            Leave => {
+                let bp = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[REGBP as usize]);
+                if bp < 1 || bp as usize > self.stack.len() {
+                    return Err(self.trap(ERROR_STACK,"leave without a matching enter"));
+                }
+                let dropped = self.stack.len() - bp as usize + 1;
+                self.stack.truncate(bp as usize);
+                let caller_bp = self.stack.pop().unwrap();
+                self.registers[REGBP as usize] = self.wrap(binary_slice_to_number!(RegisterDataType,&caller_bp));
+                self.sp -= dropped;
+            }
+            GetFromBasePointer(reg,offset) => {
+                let index = self.frame_slot(*offset)?;
+                self.registers[*reg as usize] = self.wrap(binary_slice_to_number!(RegisterDataType,&self.stack[index]));
+            }
+            SetFromBasePointer(reg,offset) => {
+                let index = self.frame_slot(*offset)?;
+                self.stack[index] = to_binary_slice!(RegisterDataType,self.registers[*reg as usize]).as_slice().try_into().unwrap();
+            }
+
             ExtendStack(extend_by, default_value) => {
                 let extend_by = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*extend_by as usize]);
                 let default_value = to_binary_slice!(RegisterDataType,self.registers[*default_value as usize]).try_into().unwrap();
@@ -501,12 +807,8 @@ impl VM {
 
             Free(locreg) => {
                 let mem_id = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*locreg as usize]);
-            match self.memory.free(mem_id as usize) {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!("Unable to free memory unit {:?}: {}",mem_id,e);
-                    std::process::exit(1);
-                }
+            if self.memory.free(mem_id as usize).is_err() {
+                return Err(self.trap(ERROR_MEMORY,&format!("cannot free memory unit {} as it does not exist",mem_id)));
             }
             }
 
@@ -516,13 +818,13 @@ impl VM {
                 let id = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[id as usize]);
                 let offset = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[offset as usize]);
                 if let Some(mem_unit) = self.memory.get(id as usize) {
-                    if let Some(val) =mem_unit.get(offset as usize) {
+                    if let Some(val) = mem_unit.get(offset as usize).filter(|_| offset >= 0) {
                         self.registers[reg as usize] = val;
                     }else {
-                        panic!("Unable to get location {:?} in memory unit {:?}. Memory is not set",offset,id); 
+                        return Err(self.trap(ERROR_MEMORY,&format!("location {} of memory unit {} is not set",offset,id)));
                     }
                 }else {
-                    panic!("Unable to get memory unit {:?}: Does not exist.",id);
+                    return Err(self.trap(ERROR_MEMORY,&format!("memory unit {} does not exist",id)));
                 }
             }
 
@@ -531,87 +833,74 @@ impl VM {
                 let id = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[id as usize]);
                 let reg = self.registers[reg as usize];
                 let offset = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[offset as usize]);
-                if offset < 0 {
-                    panic!("Unable to set location {:?} in memory unit {:?}: Location must be a positive number ",offset,id);
-                }
-
-                if let Some(mem_unit) = self.memory.get_mut(id as usize) {
-                    match mem_unit.try_set(offset as usize,reg) {
-                        Ok(_) => (),
-                        Err(e) => {
-                            eprintln!("Unable to set location {:?} in memory unit {:?}: {}",offset,id,e);
-                            std::process::exit(1);
-                        }
-                    }
-                }else {
-                    panic!("Unable to set memory unit {:?}: Does not exist.",id);
+                let set = match self.memory.get_mut(id as usize) {
+                    Some(mem_unit) => offset >= 0 && mem_unit.try_set(offset as usize,reg).is_ok(),
+                    None => return Err(self.trap(ERROR_MEMORY,&format!("memory unit {} does not exist",id))),
+                };
+                if !set {
+                    return Err(self.trap(ERROR_MEMORY,&format!("location {} of memory unit {} does not exist",offset,id)));
                 }
             }
 
+            GetMemory8(id,reg,offset) => self.load_memory(*id,*reg,*offset,1,false)?,
+            GetMemory8Signed(id,reg,offset) => self.load_memory(*id,*reg,*offset,1,true)?,
+            GetMemory16(id,reg,offset) => self.load_memory(*id,*reg,*offset,2,false)?,
+            GetMemory16Signed(id,reg,offset) => self.load_memory(*id,*reg,*offset,2,true)?,
+            GetMemory32(id,reg,offset) => self.load_memory(*id,*reg,*offset,4,false)?,
+            GetMemory32Signed(id,reg,offset) => self.load_memory(*id,*reg,*offset,4,true)?,
+            SetMemory8(id,reg,offset) => self.store_memory(*id,*reg,*offset,1)?,
+            SetMemory16(id,reg,offset) => self.store_memory(*id,*reg,*offset,2)?,
+            SetMemory32(id,reg,offset) => self.store_memory(*id,*reg,*offset,4)?,
+
             Return => {
-                let last_address = self.return_addresses.pop();
-                if let Some(la) = last_address {
-                    self.command_pointer  = la;
-                }else {
-                    panic!("Could not return as last address is not set");
+                if self.return_addresses.is_empty() {
+                    return Err(self.trap(ERROR_STACK,"ret without a matching call"));
                 }
+                self.drop_handlers(self.return_addresses.len());
+                self.command_pointer = self.return_addresses.pop().unwrap();
             }
 
             Mul(a,b) => {
-                
-                let (a,b) = (*a,*b);
-                let reg_a = self.registers[a as usize];
-                let reg_b = self.registers[b as usize];
-                let mul = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,reg_a) * integer_from_twos_complement!(iRegisterDataType,RegisterDataType,reg_b);
-                let mul_twos_comp = twos_complement!(RegisterDataType,mul);
-                self.registers[a as usize] = mul_twos_comp;
+                let value = self.registers[*b as usize];
+                self.mul(*a,value)?;
             } 
             Div(a,b) => {
-                
-                let (a,b) = (*a,*b);
-                let reg_a = self.registers[a as usize];
-                let reg_b = self.registers[b as usize];
-                let div = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,reg_a) / integer_from_twos_complement!(iRegisterDataType,RegisterDataType,reg_b);
-                let div_twos_comp = twos_complement!(RegisterDataType,div);
-
-                self.registers[a as usize] = div_twos_comp;
-
+                let value = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*b as usize]);
+                self.div(*a,value,false)?;
             }
             Mod(a,b) => {
-                
-                let (a,b) = (*a,*b);
-                let reg_a = self.registers[a as usize];
-                let reg_b = self.registers[b as usize];
-                let rmod = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,reg_a) % integer_from_twos_complement!(iRegisterDataType,RegisterDataType,reg_b);
-                let mod_twos_comp = twos_complement!(RegisterDataType,rmod);
-                self.registers[a as usize] = mod_twos_comp;
+                let value = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*b as usize]);
+                self.div(*a,value,true)?;
             } 
             Or(a,b) => {
                 let (a,b) = (*a,*b);
                 
-                self.registers[a as usize]|=self.registers[b as usize];
+                self.set_logic_result(a,self.registers[a as usize] | self.registers[b as usize]);
 
             }
             And(a,b) => {
                 
                 let (a,b) = (*a,*b);
-                self.registers[a as usize]&=self.registers[b as usize];
+                self.set_logic_result(a,self.registers[a as usize] & self.registers[b as usize]);
 
             } 
             Not(a) => {
                
-                self.registers[*a as usize] = !self.registers[*a as usize];
+                self.set_logic_result(*a,!self.registers[*a as usize]);
             }
             Xor(a,b) => {
                 
                 let (a,b) = (*a,*b);
-                self.registers[a as usize] ^= self.registers[b as usize];
+                self.set_logic_result(a,self.registers[a as usize] ^ self.registers[b as usize]);
             } 
             Nand(_a,_b) => unimplemented!(), //Idk if i should add it
         
             TruncateStack(a) => {
                 
                 let val = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*a as usize]);
+                if val < 0 || val as usize > self.sp {
+                    return Err(self.trap(ERROR_STACK,&format!("cannot truncate {} elements of the stack",val)));
+                }
                 for _ in 0..val {
                     self.stack.pop();
                 }
@@ -632,16 +921,63 @@ impl VM {
 
            Movf(a,b) => {
                 let (a,b) = (*a,*b);
-                self.floating_point_registers[a as usize] =  b as FloatRegisterDataType;
+                self.floating_point_registers[a as usize] = self.round_float(b);
                
             }
             
+            MovRegister(a,b) => {
+                self.registers[*a as usize] = self.registers[*b as usize];
+            }
+            MovfRegister(a,b) => {
+                self.floating_point_registers[*a as usize] = self.floating_point_registers[*b as usize];
+            }
+            MovIntToFloat(a,b) => {
+                let reg_b = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*b as usize]);
+                // Converting straight to f32 rounds once, going through f64 could round twice.
+                self.floating_point_registers[*a as usize] = if self.header.double_floats { reg_b as FloatRegisterDataType } else { reg_b as f32 as FloatRegisterDataType };
+            }
+            MovFloatToInt(a,b) => {
+                let float = self.floating_point_registers[*b as usize];
+                self.registers[*a as usize] = self.float_to_int(float.trunc());
+            }
+            MovFloatToIntRounded(a,b,mode) => {
+                let float = self.floating_point_registers[*b as usize];
+                let rounded = match *mode {
+                    ROUND_NEAREST => float.round_ties_even(),
+                    ROUND_DOWN => float.floor(),
+                    ROUND_UP => float.ceil(),
+                    ROUND_ZERO => float.trunc(),
+                    mode => panic!("Rounding mode {} does not exist.",mode),
+                };
+                self.registers[*a as usize] = self.float_to_int(rounded);
+            }
+            MovFloatBitsToInt(a,b) => {
+                let float = self.floating_point_registers[*b as usize];
+                let bits = if self.header.double_floats { float.to_bits() } else { (float as f32).to_bits() as RegisterDataType };
+                self.registers[*a as usize] = self.wrap(bits);
+            }
+            MovIntBitsToFloat(a,b) => {
+                let bits = self.unsigned(self.registers[*b as usize]);
+                self.floating_point_registers[*a as usize] = if self.header.double_floats {
+                    FloatRegisterDataType::from_bits(bits)
+                }else {
+                    f32::from_bits(bits as u32) as FloatRegisterDataType
+                };
+            }
+            FloatMath(a,b,function) => {
+                let result = float_math::apply(*function,self.floating_point_registers[*a as usize],self.floating_point_registers[*b as usize]);
+                self.floating_point_registers[*a as usize] = self.round_float(result);
+            }
+            CompareFloat(a,b) => {
+                self.compare_float(self.floating_point_registers[*a as usize],self.floating_point_registers[*b as usize]);
+            }
+
             Addf(a,b) =>{ 
                 let (a,b) = (*a,*b);
                 let reg_a = self.floating_point_registers[a as usize];
                 let reg_b = self.floating_point_registers[b as usize];
                 let sum = reg_a + reg_b;
-                self.floating_point_registers[a as usize] = sum;
+                self.floating_point_registers[a as usize] = self.round_float(sum);
                 //self.registers[a as usize]+=self.registers[b as usize];
             }
             Subf(a,b) => {
@@ -649,13 +985,17 @@ impl VM {
                 let reg_a = self.floating_point_registers[a as usize];
                 let reg_b = self.floating_point_registers[b as usize];
                 let dif = reg_a - reg_b;
-                self.floating_point_registers[a as usize] = dif;
+                self.floating_point_registers[a as usize] = self.round_float(dif);
 
             }
 
             Displayf(a) => {
                 let reg_a = self.floating_point_registers[*a as usize];
-                println!("{:?}",reg_a);
+                if self.header.double_floats {
+                    println!("{:?}",reg_a);
+                }else {
+                    println!("{:?}",reg_a as f32);
+                }
             }
 
             Mulf(a,b) => {
@@ -663,14 +1003,14 @@ impl VM {
                 let reg_a = self.floating_point_registers[a as usize];
                 let reg_b = self.floating_point_registers[b as usize];
                 let prod = reg_a * reg_b;
-                self.floating_point_registers[a as usize] = prod;
+                self.floating_point_registers[a as usize] = self.round_float(prod);
             }
             Divf(a,b) => {
                 let (a,b) = (*a,*b);
                 let reg_a = self.floating_point_registers[a as usize];
                 let reg_b = self.floating_point_registers[b as usize];
                 let q = reg_a / reg_b;
-                self.floating_point_registers[a as usize] = q;
+                self.floating_point_registers[a as usize] = self.round_float(q);
 
 
             }
@@ -685,6 +1025,46 @@ impl VM {
                 }
                 print!("{}",ch.unwrap());
             }
+            Try(s) => {
+                let address = match self.resolve_label(s) {
+                    crate::instruction::StringNumberUnion::Num(address) => address,
+                    crate::instruction::StringNumberUnion::String(name) => return Err(self.trap(ERROR_BAD_ADDRESS,&format!("label {} does not exist",name))),
+                };
+                self.handlers.push(Handler {
+                    address: address as usize,
+                    return_addresses: self.return_addresses.len(),
+                    stack: self.stack.clone(),
+                    sp: self.sp,
+                    bp: self.registers[REGBP as usize],
+                });
+            }
+            EndTry => {
+                if self.handlers.pop().is_none() {
+                    return Err(self.trap(ERROR_BAD_INSTRUCTION,"@endtry without a matching @try"));
+                }
+            }
+            Throw(a) => {
+                let code = self.registers[*a as usize];
+                return Err(self.trap(code,&format!("uncaught error {}",integer_from_twos_complement!(iRegisterDataType,RegisterDataType,code))));
+            }
+            Breakpoint => {
+                eprintln!("Breakpoint {}\n{}",self.location(self.command_pointer),self.dump_state());
+            }
+            AssertFailed(len) => {
+                let len = (*len as usize).min(self.stack.len());
+                let message = self.stack[self.stack.len()-len..].iter()
+                    .map(|slot| char::from_u32(binary_slice_to_number!(RegisterDataType,slot) as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect::<String>();
+                return Err(self.trap(ERROR_ASSERT,&format!("assertion failed: {}",message)));
+            }
+            ConditionalMove(a,b,condition) => {
+                if self.condition_holds(*condition)? {
+                    self.registers[*a as usize] = self.registers[*b as usize];
+                }
+            }
+            SetIf(a,condition) => {
+                self.registers[*a as usize] = self.condition_holds(*condition)? as RegisterDataType;
+            }
             GetFlag(dest,flagregno) => {
                 let (dest,flagregno) = (*dest,*flagregno);
                 let flag = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[flagregno as usize]);
@@ -704,7 +1084,7 @@ impl VM {
                 let len = integer_from_twos_complement!(iInstructionParamType,InstructionParamType,self.registers[*len_reg as usize]) as usize;
                 let str_loc = integer_from_twos_complement!(iInstructionParamType,InstructionParamType,self.registers[*str_loc as usize]) as usize;
                 let chars = self.stack.get(str_loc-len..str_loc).unwrap_or(Vec::new().as_slice())
-                    .into_iter()
+                    .iter()
                     .map(|x|TryInto::<u8>::try_into(
                             integer_from_twos_complement!(iRegisterDataType,RegisterDataType,binary_slice_to_number!(iRegisterDataType,x))).unwrap_or(0) as char)
                     .collect::<Vec<char>>()
@@ -723,37 +1103,153 @@ impl VM {
 		let start_loc_rel = integer_from_twos_complement!(iInstructionParamType,InstructionParamType,self.registers[start_loc_rel as usize]) as usize;
 		let end_loc_rel = integer_from_twos_complement!(iInstructionParamType,InstructionParamType,self.registers[end_loc_rel as usize]) as usize;
 		let dest_loc_rel = integer_from_twos_complement!(iInstructionParamType,InstructionParamType,self.registers[dest_loc_rel as usize]) as usize;
-		let sp = self.sp as usize;
-		if(sp == 0) {
+		let sp = self.sp;
+		if sp == 0 {
 			panic!("Stack is empty");
 		}
-		let start_loc = sp-start_loc_rel as usize;
-		let end_loc = sp-end_loc_rel as usize;
+		let start_loc = sp-start_loc_rel;
+		let end_loc = sp-end_loc_rel;
 		let data_size = end_loc-start_loc;
-		let dest_loc = sp-dest_loc_rel;
+		let _dest_loc = sp-dest_loc_rel;
 
 		
 		// amount of extra stack needed to allocate data ; usefull only when you want to shift data ahead
 		let needed_stack_size = data_size as isize-((self.stack.len()-sp) as isize)-1;
 		if needed_stack_size > 0 {
-			self.stack.resize(self.stack.len()+needed_stack_size as usize,[0;32]);
+			self.stack.resize(self.stack.len()+needed_stack_size as usize,[0;STACK_DATA_SIZE]);
 		}
 		//self.stack[sp-dest_loc_rel..sp-dest_loc_rel+data_size] = self.stack[sp-start_loc_rel..sp-end_loc_rel];	
 		let data_to_move = &self.stack.clone()[sp-start_loc_rel..sp-end_loc_rel];
-		for i in 0..data_size {
-			self.stack[sp-dest_loc_rel+i] = data_to_move[i]; 
-		}
+		self.stack[sp-dest_loc_rel..sp-dest_loc_rel+data_size].copy_from_slice(&data_to_move[..data_size]);
 		
 	    }
+
+            AddImmediate(a,b) => self.add(*a,twos_complement!(RegisterDataType,*b))?,
+            SubImmediate(a,b) => self.sub(*a,twos_complement!(RegisterDataType,*b))?,
+            MulImmediate(a,b) => self.mul(*a,twos_complement!(RegisterDataType,*b))?,
+            DivImmediate(a,b) => self.div(*a,*b as iRegisterDataType,false)?,
+            ModImmediate(a,b) => self.div(*a,*b as iRegisterDataType,true)?,
+            AndImmediate(a,b) => {
+                self.set_logic_result(*a,self.registers[*a as usize] & twos_complement!(RegisterDataType,*b));
+            }
+            OrImmediate(a,b) => {
+                self.set_logic_result(*a,self.registers[*a as usize] | twos_complement!(RegisterDataType,*b));
+            }
+            XorImmediate(a,b) => {
+                self.set_logic_result(*a,self.registers[*a as usize] ^ twos_complement!(RegisterDataType,*b));
+            }
+            CompareImmediate(a,b) => {
+                let reg_a = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[*a as usize]);
+                self.compare(reg_a,*b as iRegisterDataType);
+            }
+            CompareUnsigned(a,b) => {
+                self.compare_unsigned(self.registers[*a as usize],self.registers[*b as usize]);
+            }
+            CompareUnsignedImmediate(a,b) => {
+                self.compare_unsigned(self.registers[*a as usize],twos_complement!(RegisterDataType,*b));
+            }
+            DivUnsigned(a,b) => {
+                let value = self.registers[*b as usize];
+                self.div_unsigned(*a,value,false)?;
+            }
+            ModUnsigned(a,b) => {
+                let value = self.registers[*b as usize];
+                self.div_unsigned(*a,value,true)?;
+            }
+            DivUnsignedImmediate(a,b) => self.div_unsigned(*a,twos_complement!(RegisterDataType,*b),false)?,
+            ModUnsignedImmediate(a,b) => self.div_unsigned(*a,twos_complement!(RegisterDataType,*b),true)?,
+            SetArithmeticMode(a) => {
+                self.trap_overflow = *a == ARITHMETIC_TRAPPING;
+            }
+            Shl(a,b) => {
+                let count = self.registers[*b as usize];
+                self.set_logic_result(*a,self.shift_left(self.registers[*a as usize],count));
+            }
+            Shr(a,b) => {
+                let count = self.registers[*b as usize];
+                self.set_logic_result(*a,self.shift_right(self.registers[*a as usize],count));
+            }
+            Sar(a,b) => {
+                let count = self.registers[*b as usize];
+                self.set_logic_result(*a,self.shift_right_arithmetic(self.registers[*a as usize],count));
+            }
+            Rol(a,b) => {
+                let count = self.registers[*b as usize];
+                self.set_logic_result(*a,self.rotate_left(self.registers[*a as usize],count));
+            }
+            Ror(a,b) => {
+                let count = self.registers[*b as usize];
+                self.set_logic_result(*a,self.rotate_right(self.registers[*a as usize],count));
+            }
+            ShlImmediate(a,b) => {
+                self.set_logic_result(*a,self.shift_left(self.registers[*a as usize],*b as InstructionParamType as RegisterDataType));
+            }
+            ShrImmediate(a,b) => {
+                self.set_logic_result(*a,self.shift_right(self.registers[*a as usize],*b as InstructionParamType as RegisterDataType));
+            }
+            SarImmediate(a,b) => {
+                self.set_logic_result(*a,self.shift_right_arithmetic(self.registers[*a as usize],*b as InstructionParamType as RegisterDataType));
+            }
+            RolImmediate(a,b) => {
+                self.set_logic_result(*a,self.rotate_left(self.registers[*a as usize],*b as InstructionParamType as RegisterDataType));
+            }
+            RorImmediate(a,b) => {
+                self.set_logic_result(*a,self.rotate_right(self.registers[*a as usize],*b as InstructionParamType as RegisterDataType));
+            }
             _ => unimplemented!()
 
         }
+        Ok(())
+    }
+
+    /// Sets the flags `jz`, `je`, `jg` and friends look at from comparing `a` with `b`.
+    /// The zero, sign, overflow and carry flags are the ones `sub` would set for `a - b`, but the
+    /// result is not kept and never traps.
+    /// The below and above flags used by `jb` and `ja` compare the same bits as unsigned numbers.
+    fn compare(&mut self, a:iRegisterDataType, b:iRegisterDataType) {
+        let (unsigned_a,unsigned_b) = (self.unsigned(twos_complement!(RegisterDataType,a)),self.unsigned(twos_complement!(RegisterDataType,b)));
+        let result = self.wrap(unsigned_a.wrapping_sub(unsigned_b));
+        self.set_result_flags(result);
+        let overflow = a.checked_sub(b)
+            .is_none_or(|exact| exact != integer_from_twos_complement!(iRegisterDataType,RegisterDataType,result));
+        let _ = self.set_flag(OVERFLOW_FLAG,overflow as u8);
+        let _ = self.set_flag(CARRY_FLAG,(unsigned_a < unsigned_b) as u8);
+        let _ = self.set_flag(EQUAL_FLAG,(a == b) as u8);
+        let _ = self.set_flag(GREATER_THAN_FLAG,(a > b) as u8);
+        let _ = self.set_flag(LESS_THAN_FLAG,(a < b) as u8);
+        let _ = self.set_flag(ABOVE_FLAG,(unsigned_a > unsigned_b) as u8);
+        let _ = self.set_flag(BELOW_FLAG,(unsigned_a < unsigned_b) as u8);
+    }
+
+    /// Sets the flags as described at `CompareFloat`.
+    fn compare_float(&mut self, a:FloatRegisterDataType, b:FloatRegisterDataType) {
+        let (equal,greater,less) = ((a == b) as u8,(a > b) as u8,(a < b) as u8);
+        for (flag,value) in [(EQUAL_FLAG,equal),(ZERO_FLAG,equal),(GREATER_THAN_FLAG,greater),(ABOVE_FLAG,greater),
+            (LESS_THAN_FLAG,less),(BELOW_FLAG,less),(SIGN_FLAG,less),(CARRY_FLAG,less)] {
+            let _ = self.set_flag(flag,value);
+        }
+        let _ = self.set_flag(OVERFLOW_FLAG,(a.is_nan() || b.is_nan()) as u8);
+    }
+
+    /// Converts an already rounded float to an integer of the program's width, saturating at the
+    /// limits. NaN becomes 0.
+    fn float_to_int(&self, float:FloatRegisterDataType) -> RegisterDataType {
+        let int = if self.header.wide_integers { float as iRegisterDataType } else { float as i32 as iRegisterDataType };
+        twos_complement!(RegisterDataType,int)
+    }
+
+    /// Same as `compare` but the less and greater flags follow the unsigned order too.
+    fn compare_unsigned(&mut self, a:RegisterDataType, b:RegisterDataType) {
+        self.compare(integer_from_twos_complement!(iRegisterDataType,RegisterDataType,a),integer_from_twos_complement!(iRegisterDataType,RegisterDataType,b));
+        let (a,b) = (self.unsigned(a),self.unsigned(b));
+        let _ = self.set_flag(GREATER_THAN_FLAG,(a > b) as u8);
+        let _ = self.set_flag(LESS_THAN_FLAG,(a < b) as u8);
     }
 
     // See eval for more info 
     #[allow(dead_code)]
     pub fn run_label_inst(&mut self, label:String,is_main:bool) {
-        if self.labels.get(&label).is_none() { panic!("Label with name {:?} does not exist.",label)}
+        if !self.labels.contains_key(&label) { panic!("Label with name {:?} does not exist.",label)}
 
         let (label_start,label_end_option) = &self.labels.get(&label).unwrap(); 
         
@@ -770,7 +1266,7 @@ impl VM {
         if is_main {
             self.return_addresses.push(self.last_command);
         }else {
-            self.return_addresses.push(self.command_pointer.into());
+            self.return_addresses.push(self.command_pointer);
         }
         self.command_pointer = *label_start;
         while self.command_pointer < label_end-1 {
@@ -783,7 +1279,9 @@ impl VM {
     pub fn run_current_inst(&mut self) {
         
         let cur_inst = self.instructions[self.command_pointer].clone();
-        self.run_instruction(&cur_inst);
+        if let Err(fault) = self.run_instruction(&cur_inst) {
+            self.raise(fault);
+        }
      }
 
      // This is when you manually use the VM by itself 
@@ -796,6 +1294,7 @@ impl VM {
      // vm.eval();
     #[allow(dead_code)]
      pub fn eval(&mut self) {
+         self.collect_lea_targets();
          self.run_label_inst("main".to_string(),true);
      }
 
@@ -819,13 +1318,35 @@ impl VM {
         }
     }
 
+    /// Same as `eval_raw` but tells `tracer` about every instruction it runs.
+    /// Returns when the program halts instead of exiting so the tracer's results can be written.
+    pub fn eval_traced(&mut self, tracer:&mut dyn Tracer) {
+        loop {
+            let pc = self.command_pointer;
+            if self.instructions[pc] == Instruction::Halt {
+                tracer.halted(pc);
+                return;
+            }
+            tracer.before(self,pc);
+            if self.instructions[pc] != Instruction::Breakpoint || !tracer.breakpoint(self,pc) {
+                self.run_current_inst();
+            }
+            self.command_pointer += 1;
+            tracer.executed(pc,self.command_pointer,self.return_addresses.len());
+        }
+    }
+
     pub fn add_instruction(&mut self, inst:Instruction) {
+        if self.last_command >= INSTRUCTION_MEMORY_SIZE {
+            println!("Generation Error: The program does not fit in the {} instructions the VM has.",INSTRUCTION_MEMORY_SIZE);
+            std::process::exit(1);
+        }
        self.instructions[self.last_command] = inst;
         self.last_command += 1;
    }
 
     pub fn get_raw_byte_code(&mut self) -> String {
-        let mut fin = String::new();
+        let mut fin = self.header.to_binary().iter().map(|&b| b.to_string()).collect::<String>();
         let mut i = 0;
         while i<=self.last_command {
 
@@ -934,6 +1455,21 @@ impl VM {
 
                 }
 
+                JumpIfGreaterOrEqual(s) => JumpIfGreaterOrEqual(self.resolve_label(s)),
+                JumpIfLessOrEqual(s) => JumpIfLessOrEqual(self.resolve_label(s)),
+                JumpIfAbove(s) => JumpIfAbove(self.resolve_label(s)),
+                JumpIfBelow(s) => JumpIfBelow(self.resolve_label(s)),
+                JumpIfAboveOrEqual(s) => JumpIfAboveOrEqual(self.resolve_label(s)),
+                JumpIfBelowOrEqual(s) => JumpIfBelowOrEqual(self.resolve_label(s)),
+                JumpIfOverflow(s) => JumpIfOverflow(self.resolve_label(s)),
+                JumpIfNotOverflow(s) => JumpIfNotOverflow(self.resolve_label(s)),
+                JumpIfCarry(s) => JumpIfCarry(self.resolve_label(s)),
+                JumpIfNotCarry(s) => JumpIfNotCarry(self.resolve_label(s)),
+                LoadAddress(a,s) => LoadAddress(*a,self.resolve_label(s)),
+                TailCall(s) => TailCall(self.resolve_label(s)),
+                Try(s) => Try(self.resolve_label(s)),
+                Loop(a,s) => Loop(*a,self.resolve_label(s)),
+
                 Call(s) => {
                     use crate::instruction::StringNumberUnion::*;
                     match s {
@@ -959,6 +1495,331 @@ impl VM {
         return fin
     }
 
+    pub fn header(&self) -> ProgramHeader {
+        self.header
+    }
+
+    pub fn set_header(&mut self, header:ProgramHeader) {
+        self.header = header;
+    }
+
+    /// `source_map` is the generator's, so runtime errors can tell the line they happened at.
+    pub fn set_source_map(&mut self, source_map:Vec<Option<SourceLocation>>) {
+        self.source_map = source_map;
+    }
+
+    /// Where the instruction at `pc` comes from, as runtime errors tell it.
+    pub fn location(&self, pc:usize) -> String {
+        describe_location(&self.source_map,pc)
+    }
+
+    /// The registers, flags and up to the top 8 values of the stack, one group per line, which
+    /// `Breakpoint` prints.
+    pub fn dump_state(&self) -> String {
+        let signed = |value:RegisterDataType| integer_from_twos_complement!(iRegisterDataType,RegisterDataType,value);
+        let mut registers = crate::tokens::REGISTERS.iter().enumerate()
+            .map(|(i,name)| format!("{}={}",name,signed(self.registers[i])))
+            .collect::<Vec<String>>();
+        registers.push(format!("bp={}",signed(self.registers[REGBP as usize])));
+        let flags = [("zf",ZERO_FLAG),("eqf",EQUAL_FLAG),("gf",GREATER_THAN_FLAG),("lf",LESS_THAN_FLAG),("bf",BELOW_FLAG),
+            ("af",ABOVE_FLAG),("of",OVERFLOW_FLAG),("cf",CARRY_FLAG),("sf",SIGN_FLAG)].iter()
+            .map(|(name,flag)| format!("{}={}",name,self.flags[*flag]))
+            .collect::<Vec<String>>();
+        let shown = self.stack.len().min(8);
+        let mut stack = if shown < self.stack.len() { " ...".to_string() } else { String::new() };
+        for slot in self.stack[self.stack.len()-shown..].iter() {
+            stack.push_str(&format!(" {}",binary_slice_to_number!(RegisterDataType,slot) as i64));
+        }
+        format!("  {}\n  {}\n  stack of {} (top last):{}",registers.join(" "),flags.join(" "),self.stack.len(),stack)
+    }
+
+    /// A runtime error about the instruction that is running, for it to return.
+    fn trap(&self, code:RegisterDataType, message:&str) -> Fault {
+        Fault { code, report: format!("Runtime Error: {} {}",message,self.location(self.command_pointer)) }
+    }
+
+    /// Goes to the handler of the last `Try` with the fault's code, or prints its report and
+    /// stops the program when there is none.
+    fn raise(&mut self, fault:Fault) {
+        if self.handlers.is_empty() {
+            println!("{}",fault.report);
+            std::process::exit(1);
+        }
+        self.catch(fault.code);
+    }
+
+    /// Forgets the handlers of `Try`s run `depth` calls deep or deeper, as their frame is gone.
+    fn drop_handlers(&mut self, depth:usize) {
+        self.handlers.retain(|handler| handler.return_addresses < depth);
+    }
+
+    /// Puts the state back to how it was at the `Try` of the last handler and jumps to it.
+    fn catch(&mut self, code:RegisterDataType) {
+        let handler = self.handlers.pop().unwrap();
+        self.return_addresses.truncate(handler.return_addresses);
+        self.stack = handler.stack;
+        self.sp = handler.sp;
+        self.registers[REGBP as usize] = handler.bp;
+        self.registers[REGA as usize] = self.wrap(code);
+        self.set_command_pointer(handler.address-1);
+    }
+
+    /// The memory unit in register `id` and the byte offset in register `offset`, after checking
+    /// that the `width` bytes from there are in the unit.
+    fn memory_bytes(&self, id:InstructionParamType, offset:InstructionParamType, width:usize) -> Result<(iRegisterDataType,usize),Fault> {
+        let id = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[id as usize]);
+        let offset = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[offset as usize]);
+        let size = match self.memory.get(id as usize) {
+            Some(mem_unit) => mem_unit.size() * self.cell_bytes(),
+            None => return Err(self.trap(ERROR_MEMORY,&format!("memory unit {} does not exist",id))),
+        };
+        if offset < 0 || offset as usize + width > size {
+            let first = if offset < 0 { offset } else { offset.max(size as iRegisterDataType) };
+            return Err(self.trap(ERROR_MEMORY,&format!("byte {} of memory unit {} does not exist",first,id)));
+        }
+        Ok((id,offset as usize))
+    }
+
+    /// Number of bytes in a memory cell, the same as in a register.
+    fn cell_bytes(&self) -> usize {
+        self.integer_bits() as usize / 8
+    }
+
+    /// `GetMemory8` and the like: loads `width` bytes, lowest first, and sign extends them when `signed`.
+    fn load_memory(&mut self, id:InstructionParamType, reg:InstructionParamType, offset:InstructionParamType, width:usize, signed:bool) -> Result<(),Fault> {
+        let (id,offset) = self.memory_bytes(id,offset,width)?;
+        let cell_bytes = self.cell_bytes();
+        let mem_unit = self.memory.get(id as usize).unwrap();
+        let mut value:RegisterDataType = 0;
+        for i in 0..width {
+            let byte = offset + i;
+            let cell = match mem_unit.get(byte / cell_bytes) {
+                Some(cell) => cell,
+                None => return Err(self.trap(ERROR_MEMORY,&format!("byte {} of memory unit {} is not set",byte,id))),
+            };
+            value |= ((cell >> (byte % cell_bytes * 8)) & 0xff) << (8 * i);
+        }
+        if signed && width < 8 {
+            let sign:RegisterDataType = 1 << (8 * width - 1);
+            value = (value ^ sign).wrapping_sub(sign);
+        }
+        self.registers[reg as usize] = self.wrap(value);
+        Ok(())
+    }
+
+    /// `SetMemory8` and the like: stores the low `width` bytes of the register, lowest first.
+    fn store_memory(&mut self, id:InstructionParamType, reg:InstructionParamType, offset:InstructionParamType, width:usize) -> Result<(),Fault> {
+        let (id,offset) = self.memory_bytes(id,offset,width)?;
+        let cell_bytes = self.cell_bytes();
+        let value = self.registers[reg as usize];
+        for i in 0..width {
+            let byte = offset + i;
+            let (cell,shift) = (byte / cell_bytes,byte % cell_bytes * 8);
+            let old = self.memory.get(id as usize).unwrap().get(cell).unwrap_or(0);
+            let new = self.wrap((old & !(0xff << shift)) | (((value >> (8 * i)) & 0xff) << shift));
+            let _ = self.memory.get_mut(id as usize).unwrap().try_set(cell,new);
+        }
+        Ok(())
+    }
+
+    fn condition_holds(&self, condition:InstructionParamType) -> Result<bool,Fault> {
+        match crate::instruction::condition_flags(condition) {
+            Some((flags,negated)) => Ok(flags.iter().any(|flag| self.flags[*flag] != 0) != negated),
+            None => Err(self.trap(ERROR_BAD_INSTRUCTION,&format!("condition {} does not exist",condition))),
+        }
+    }
+
+    /// Number of bits the integer registers of this program hold.
+    fn integer_bits(&self) -> RegisterDataType {
+        if self.header.wide_integers { 64 } else { 32 }
+    }
+
+    /// Cuts `value` down to the bits a register holds. In 32-bit mode the value is sign extended
+    /// from its low 32 bits, so reading it as a signed 64 bit number gives the 32 bit one.
+    fn wrap(&self, value:RegisterDataType) -> RegisterDataType {
+        if self.header.wide_integers {
+            value
+        }else {
+            twos_complement!(RegisterDataType,value as u32 as i32)
+        }
+    }
+
+    /// Rounds `value` to the precision of the float registers.
+    fn round_float(&self, value:FloatRegisterDataType) -> FloatRegisterDataType {
+        if self.header.double_floats { value } else { value as f32 as FloatRegisterDataType }
+    }
+
+    /// `value` read as an unsigned number of the program's width.
+    fn unsigned(&self, value:RegisterDataType) -> RegisterDataType {
+        if self.header.wide_integers { value } else { value as u32 as RegisterDataType }
+    }
+
+    /// Sets the zero and sign flags from the result of an arithmetic or logic instruction.
+    fn set_result_flags(&mut self, result:RegisterDataType) {
+        let _ = self.set_flag(ZERO_FLAG,(result == 0) as u8);
+        let _ = self.set_flag(SIGN_FLAG,(result >> 63) as u8);
+    }
+
+    /// Stores the result of a logic, shift or rotate instruction, which never overflows or carries.
+    fn set_logic_result(&mut self, reg:InstructionParamType, result:RegisterDataType) {
+        self.set_result_flags(result);
+        let _ = self.set_flag(OVERFLOW_FLAG,0);
+        let _ = self.set_flag(CARRY_FLAG,0);
+        self.registers[reg as usize] = result;
+    }
+
+    fn set_arithmetic_flags(&mut self, overflow:bool, carry:bool) -> Result<(),Fault> {
+        let _ = self.set_flag(OVERFLOW_FLAG,overflow as u8);
+        let _ = self.set_flag(CARRY_FLAG,carry as u8);
+        if overflow && self.trap_overflow {
+            return Err(self.trap(ERROR_OVERFLOW,"integer overflow"));
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, reg:InstructionParamType, value:RegisterDataType) -> Result<(),Fault> {
+        let a = self.registers[reg as usize];
+        let result = self.wrap(a.wrapping_add(value));
+        let carry = self.unsigned(result) < self.unsigned(a);
+        let overflow = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,a)
+            .checked_add(integer_from_twos_complement!(iRegisterDataType,RegisterDataType,value))
+            .is_none_or(|exact| exact != integer_from_twos_complement!(iRegisterDataType,RegisterDataType,result));
+        self.set_arithmetic_flags(overflow,carry)?;
+        self.set_result_flags(result);
+        self.registers[reg as usize] = result;
+        Ok(())
+    }
+
+    fn sub(&mut self, reg:InstructionParamType, value:RegisterDataType) -> Result<(),Fault> {
+        let a = self.registers[reg as usize];
+        let result = self.wrap(a.wrapping_sub(value));
+        let carry = self.unsigned(a) < self.unsigned(value);
+        let overflow = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,a)
+            .checked_sub(integer_from_twos_complement!(iRegisterDataType,RegisterDataType,value))
+            .is_none_or(|exact| exact != integer_from_twos_complement!(iRegisterDataType,RegisterDataType,result));
+        self.set_arithmetic_flags(overflow,carry)?;
+        self.set_result_flags(result);
+        self.registers[reg as usize] = result;
+        Ok(())
+    }
+
+    /// Both flags tell whether the signed product fit.
+    fn mul(&mut self, reg:InstructionParamType, value:RegisterDataType) -> Result<(),Fault> {
+        let a = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[reg as usize]);
+        let value = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,value);
+        let result = self.wrap(twos_complement!(RegisterDataType,a.wrapping_mul(value)));
+        let overflow = a.checked_mul(value)
+            .is_none_or(|exact| exact != integer_from_twos_complement!(iRegisterDataType,RegisterDataType,result));
+        self.set_arithmetic_flags(overflow,overflow)?;
+        self.set_result_flags(result);
+        self.registers[reg as usize] = result;
+        Ok(())
+    }
+
+    /// Divides register `reg` by `value`, keeping the remainder instead of the quotient if `remainder`.
+    /// Dividing the smallest integer by -1 overflows, which wraps to the smallest integer and a remainder of 0.
+    fn div(&mut self, reg:InstructionParamType, value:iRegisterDataType, remainder:bool) -> Result<(),Fault> {
+        if value == 0 {
+            return Err(self.trap(ERROR_DIVISION_BY_ZERO,"division by zero"));
+        }
+        let a = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[reg as usize]);
+        let min = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.wrap(1 << (self.integer_bits()-1)));
+        let overflow = a == min && value == -1;
+        self.set_arithmetic_flags(overflow,overflow)?;
+        let result = if remainder { a.wrapping_rem(value) } else { a.wrapping_div(value) };
+        let result = self.wrap(twos_complement!(RegisterDataType,result));
+        self.set_result_flags(result);
+        self.registers[reg as usize] = result;
+        Ok(())
+    }
+
+    fn div_unsigned(&mut self, reg:InstructionParamType, value:RegisterDataType, remainder:bool) -> Result<(),Fault> {
+        if self.unsigned(value) == 0 {
+            return Err(self.trap(ERROR_DIVISION_BY_ZERO,"division by zero"));
+        }
+        let (a,value) = (self.unsigned(self.registers[reg as usize]),self.unsigned(value));
+        self.set_logic_result(reg,self.wrap(if remainder { a % value } else { a / value }));
+        Ok(())
+    }
+
+    /// Shifting by the register width or more moves every bit out.
+    fn shift_left(&self, value:RegisterDataType, count:RegisterDataType) -> RegisterDataType {
+        if count >= self.integer_bits() { 0 } else { self.wrap(value << count) }
+    }
+
+    fn shift_right(&self, value:RegisterDataType, count:RegisterDataType) -> RegisterDataType {
+        if count >= self.integer_bits() { 0 } else { self.wrap(self.unsigned(value) >> count) }
+    }
+
+    /// Shifting by the register width or more leaves only copies of the sign bit.
+    fn shift_right_arithmetic(&self, value:RegisterDataType, count:RegisterDataType) -> RegisterDataType {
+        let value = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,value);
+        twos_complement!(RegisterDataType,value >> count.min(self.integer_bits()-1))
+    }
+
+    /// Rotates use the count modulo the register width.
+    fn rotate_left(&self, value:RegisterDataType, count:RegisterDataType) -> RegisterDataType {
+        if self.header.wide_integers {
+            value.rotate_left((count % 64) as u32)
+        }else {
+            self.wrap((value as u32).rotate_left((count % 32) as u32) as RegisterDataType)
+        }
+    }
+
+    fn rotate_right(&self, value:RegisterDataType, count:RegisterDataType) -> RegisterDataType {
+        if self.header.wide_integers {
+            value.rotate_right((count % 64) as u32)
+        }else {
+            self.wrap((value as u32).rotate_right((count % 32) as u32) as RegisterDataType)
+        }
+    }
+
+    /// Address in register `register` for `JumpRegister` and `CallRegister`. Only addresses
+    /// that a `LoadAddress` in the program loads can be jumped to.
+    fn indirect_target(&self, register:InstructionParamType) -> Result<usize,Fault> {
+        let value = self.registers[register as usize];
+        if !self.lea_targets.contains(&(value as usize)) || value as usize >= self.instructions.len() {
+            return Err(self.trap(ERROR_BAD_ADDRESS,&format!("cannot jump to address {} as no lea loads it",integer_from_twos_complement!(iRegisterDataType,RegisterDataType,value))));
+        }
+        Ok(value as usize)
+    }
+
+    /// Index of the stack slot `bp + offset`, which must be on the stack.
+    fn frame_slot(&self, offset:iInstructionParamType) -> Result<usize,Fault> {
+        let bp = integer_from_twos_complement!(iRegisterDataType,RegisterDataType,self.registers[REGBP as usize]);
+        let index = bp + offset as iRegisterDataType;
+        if index < 0 || index as usize >= self.stack.len() {
+            return Err(self.trap(ERROR_STACK,&format!("{} is outside the stack",describe_frame_slot(offset))));
+        }
+        Ok(index as usize)
+    }
+
+    /// Replaces a label name with the instruction number it starts at.
+    /// Labels that do not exist are left as they are.
+    fn resolve_label(&self, s:&crate::instruction::StringNumberUnion) -> crate::instruction::StringNumberUnion {
+        use crate::instruction::StringNumberUnion::*;
+        match s {
+            String(name) => match self.labels.get(name) {
+                Some(v) => Num(v.0 as u32),
+                None => s.clone(),
+            },
+            Num(n) => Num(*n),
+        }
+    }
+
+    fn collect_lea_targets(&mut self) {
+        use crate::instruction::StringNumberUnion::Num;
+        self.lea_targets = self.instructions[..self.last_command].iter()
+            .filter_map(|inst| match inst {
+                Instruction::LoadAddress(_,s) => match self.resolve_label(s) {
+                    Num(n) => Some(n as usize),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+    }
+
     pub fn get_flag(&self,flag:usize) -> Option<&u8> {
         return self.flags.get(flag);
     }
@@ -974,7 +1835,7 @@ impl VM {
     }
 
     pub fn register_start(&mut self) {
-        if let Some((_start,_)) = self.labels.get(&"main".to_string()) {
+        if let Some((_start,_)) = self.labels.get("main") {
             let lc = self.last_command;
             self.last_command = 0;
             self.add_instruction(Instruction::Jump(crate::instruction::StringNumberUnion::String("main".to_string())));
@@ -994,7 +1855,7 @@ impl VM {
     }
 
     pub fn create_label(&mut self, label_index:usize, label_name:&String) {
-        if self.labels.get(label_name).is_some() {
+        if self.labels.contains_key(label_name) {
             println!("Cannot create label with name `{:?}` as it already exists.",label_name);
             std::process::exit(1);
         }
@@ -1005,11 +1866,330 @@ impl VM {
         &self.labels
     }
 
+    #[allow(dead_code)]
     pub fn labels_mut(&mut self) -> &mut HashMap<String,(usize,Option<usize>)> {
         &mut self.labels
     }
 
+    #[allow(dead_code)]
     pub fn instructions(&self) -> &[Instruction] {
         return &self.instructions;
     }
+
+    /// Instructions that were added or loaded, without the `Halt`s filling the rest of the instruction memory.
+    pub fn generated_instructions(&self) -> &[Instruction] {
+        return &self.instructions[..self.last_command];
+    }
+}
+
+/// How a program writes the stack slot `bp + offset`, for runtime error messages.
+pub fn describe_frame_slot(offset:iInstructionParamType) -> String {
+    match offset {
+        0 => "[bp]".to_string(),
+        o if o < 0 => format!("[bp - {}]",-(o as i64)),
+        o => format!("[bp + {}]",o),
+    }
+}
+
+/// Where the instruction at `pc` comes from, for runtime error messages.
+pub fn describe_location(source_map:&[Option<SourceLocation>], pc:usize) -> String {
+    match source_map.get(pc) {
+        Some(Some(location)) => format!("at line {} of {}",location.line,location.file),
+        _ => format!("at instruction {}",pc),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::StringNumberUnion::Num;
+
+    fn run(vm:&mut VM, instructions:&[Instruction]) {
+        for inst in instructions {
+            assert!(vm.run_instruction(inst).is_ok(),"{:?} trapped",inst);
+        }
+    }
+
+    fn zero_and_sign(vm:&VM) -> (u8,u8) {
+        (*vm.get_flag(ZERO_FLAG).unwrap(),*vm.get_flag(SIGN_FLAG).unwrap())
+    }
+
+    #[test]
+    fn arithmetic_sets_zero_and_sign() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Mov(0,5),Instruction::Mov(1,-5),Instruction::Add(0,1)]);
+        assert_eq!(zero_and_sign(&vm),(1,0));
+        run(&mut vm,&[Instruction::Mov(0,3),Instruction::Mov(1,4),Instruction::Sub(0,1)]);
+        assert_eq!(zero_and_sign(&vm),(0,1));
+        run(&mut vm,&[Instruction::MulImmediate(0,-2)]);
+        assert_eq!(zero_and_sign(&vm),(0,0));
+        run(&mut vm,&[Instruction::SubImmediate(0,2)]);
+        assert_eq!(zero_and_sign(&vm),(1,0));
+    }
+
+    #[test]
+    fn logic_sets_zero_and_sign_and_clears_carry_and_overflow() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Mov(0,i32::MIN as iInstructionParamType),Instruction::SubImmediate(0,1)]);
+        assert_eq!(*vm.get_flag(OVERFLOW_FLAG).unwrap(),1);
+        run(&mut vm,&[Instruction::Mov(0,6),Instruction::AndImmediate(0,1)]);
+        assert_eq!(zero_and_sign(&vm),(1,0));
+        assert_eq!((*vm.get_flag(OVERFLOW_FLAG).unwrap(),*vm.get_flag(CARRY_FLAG).unwrap()),(0,0));
+        run(&mut vm,&[Instruction::Not(0)]);
+        assert_eq!(zero_and_sign(&vm),(0,1));
+        run(&mut vm,&[Instruction::Mov(1,-1),Instruction::Xor(0,1)]);
+        assert_eq!(zero_and_sign(&vm),(1,0));
+        run(&mut vm,&[Instruction::OrImmediate(0,7)]);
+        assert_eq!(zero_and_sign(&vm),(0,0));
+    }
+
+    #[test]
+    fn compare_sets_the_flags_of_sub_without_storing() {
+        let pairs = [(3,3),(2,5),(5,2),(-1,1),(i32::MIN as iInstructionParamType,1),(i32::MAX as iInstructionParamType,-1),(0,i32::MIN as iInstructionParamType)];
+        for (a,b) in pairs {
+            let (mut compared,mut subtracted) = (VM::new(),VM::new());
+            let setup = [Instruction::Mov(0,a),Instruction::Mov(1,b)];
+            run(&mut compared,&setup);
+            run(&mut subtracted,&setup);
+            run(&mut compared,&[Instruction::Compare(0,1)]);
+            run(&mut subtracted,&[Instruction::Sub(0,1)]);
+            for flag in [ZERO_FLAG,SIGN_FLAG,OVERFLOW_FLAG,CARRY_FLAG] {
+                assert_eq!(compared.get_flag(flag),subtracted.get_flag(flag),"flag {} of cmp {} {}",flag,a,b);
+            }
+            assert_eq!(compared.registers[0],twos_complement!(RegisterDataType,a));
+            assert_eq!(compared.registers[1],twos_complement!(RegisterDataType,b));
+        }
+    }
+
+    #[test]
+    fn truncating_past_the_bottom_of_the_stack_is_a_stack_error() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Mov(0,1),Instruction::Push(7),Instruction::Mov(1,2)]);
+        assert_eq!(vm.run_instruction(&Instruction::TruncateStack(1)).unwrap_err().code,ERROR_STACK);
+        run(&mut vm,&[Instruction::Mov(1,-1)]);
+        assert_eq!(vm.run_instruction(&Instruction::TruncateStack(1)).unwrap_err().code,ERROR_STACK);
+        run(&mut vm,&[Instruction::TruncateStack(0)]);
+        assert_eq!(vm.sp,0);
+    }
+
+    /// Runs `inst`, which has to fail, and hands the fault to the handler like `run_current_inst`.
+    fn fail(vm:&mut VM, inst:Instruction) {
+        let fault = vm.run_instruction(&inst).err().unwrap_or_else(|| panic!("{:?} did not trap",inst));
+        vm.raise(fault);
+    }
+
+    #[test]
+    fn popping_a_float_from_an_empty_stack_is_caught() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Try(Num(20))]);
+        fail(&mut vm,Instruction::PopFloat(0));
+        assert_eq!(vm.registers[REGA as usize],ERROR_STACK);
+        assert_eq!(vm.command_pointer,19);
+    }
+
+    #[test]
+    fn catching_puts_back_what_the_try_popped() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Push(1),Instruction::Push(2),Instruction::Try(Num(20)),
+            Instruction::Pop(0),Instruction::Pop(0),Instruction::Mov(1,0)]);
+        fail(&mut vm,Instruction::Div(0,1));
+        assert_eq!((vm.stack.len(),vm.sp),(2,2));
+        run(&mut vm,&[Instruction::Pop(2),Instruction::Pop(3)]);
+        assert_eq!((vm.registers[2],vm.registers[3]),(2,1));
+    }
+
+    #[test]
+    fn jumps_through_a_register_only_go_where_a_lea_points() {
+        let mut vm = VM::new();
+        vm.add_instruction(Instruction::LoadAddress(0,Num(5)));
+        vm.collect_lea_targets();
+        run(&mut vm,&[Instruction::LoadAddress(0,Num(5)),Instruction::JumpRegister(0)]);
+        assert_eq!(vm.command_pointer,4);
+        run(&mut vm,&[Instruction::Mov(1,6)]);
+        assert_eq!(vm.run_instruction(&Instruction::JumpRegister(1)).unwrap_err().code,ERROR_BAD_ADDRESS);
+    }
+
+    #[test]
+    fn lea_of_a_missing_label_is_a_bad_address() {
+        let mut vm = VM::new();
+        let missing = crate::instruction::StringNumberUnion::String("nowhere".to_string());
+        assert_eq!(vm.run_instruction(&Instruction::LoadAddress(0,missing)).unwrap_err().code,ERROR_BAD_ADDRESS);
+    }
+
+    #[test]
+    fn getflag_reads_a_flag_into_a_register() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Mov(0,2),Instruction::Mov(1,7),Instruction::Compare(0,1)]);
+        run(&mut vm,&[Instruction::Mov(2,SIGN_FLAG as iInstructionParamType),Instruction::GetFlag(3,2)]);
+        assert_eq!(vm.registers[3],1);
+        run(&mut vm,&[Instruction::Mov(2,ZERO_FLAG as iInstructionParamType),Instruction::GetFlag(3,2)]);
+        assert_eq!(vm.registers[3],0);
+        run(&mut vm,&[Instruction::Compare(1,1),Instruction::GetFlag(3,2)]);
+        assert_eq!(vm.registers[3],1);
+    }
+
+    fn signed(vm:&VM, register:InstructionParamType) -> iRegisterDataType {
+        integer_from_twos_complement!(iRegisterDataType,RegisterDataType,vm.registers[register as usize])
+    }
+
+    fn wide() -> ProgramHeader {
+        ProgramHeader { wide_integers: true, double_floats: false }
+    }
+
+    #[test]
+    fn shifting_by_the_register_width_or_more() {
+        for (header,bits) in [(ProgramHeader::default(),32),(wide(),64)] {
+            let mut vm = VM::new();
+            vm.set_header(header);
+            for count in [bits,bits+1,1000] {
+                run(&mut vm,&[Instruction::Mov(1,count),Instruction::Mov(0,-8),Instruction::Shl(0,1)]);
+                assert_eq!(signed(&vm,0),0,"shl by {}",count);
+                run(&mut vm,&[Instruction::Mov(0,-8),Instruction::Shr(0,1)]);
+                assert_eq!(signed(&vm,0),0,"shr by {}",count);
+                run(&mut vm,&[Instruction::Mov(0,-8),Instruction::Sar(0,1)]);
+                assert_eq!(signed(&vm,0),-1,"sar by {}",count);
+                run(&mut vm,&[Instruction::Mov(0,8),Instruction::Sar(0,1)]);
+                assert_eq!(signed(&vm,0),0,"sar by {}",count);
+                run(&mut vm,&[Instruction::Mov(0,-8),Instruction::ShlImmediate(0,count),Instruction::Mov(2,-8),Instruction::SarImmediate(2,count)]);
+                assert_eq!((signed(&vm,0),signed(&vm,2)),(0,-1),"immediate shifts by {}",count);
+            }
+            run(&mut vm,&[Instruction::Mov(0,1),Instruction::ShlImmediate(0,bits-1)]);
+            assert_eq!(signed(&vm,0),iRegisterDataType::MIN >> (64-bits));
+            run(&mut vm,&[Instruction::Mov(0,-8),Instruction::Mov(1,bits),Instruction::Rol(0,1)]);
+            assert_eq!(signed(&vm,0),-8,"rol by the width");
+            run(&mut vm,&[Instruction::Mov(0,3),Instruction::Mov(1,bits+1),Instruction::Ror(0,1)]);
+            assert_eq!(signed(&vm,0),iRegisterDataType::MIN >> (64-bits) | 1,"ror by the width plus one");
+        }
+    }
+
+    /// Whether `jump` to instruction 5 is taken, from the start of a program of `Halt`s.
+    fn jumps(vm:&mut VM, jump:fn(crate::instruction::StringNumberUnion) -> Instruction) -> bool {
+        while vm.instructions.len() < 8 {
+            vm.add_instruction(Instruction::Halt);
+        }
+        vm.command_pointer = 0;
+        run(vm,&[jump(Num(5))]);
+        vm.command_pointer == 4
+    }
+
+    #[test]
+    fn unsigned_jumps_read_minus_one_as_the_largest_number() {
+        for compare in [Instruction::Compare(0,1),Instruction::CompareUnsigned(0,1),Instruction::CompareImmediate(0,1),Instruction::CompareUnsignedImmediate(0,1)] {
+            let mut vm = VM::new();
+            run(&mut vm,&[Instruction::Mov(0,-1),Instruction::Mov(1,1),compare.clone()]);
+            assert!(jumps(&mut vm,Instruction::JumpIfAboveOrEqual),"jae after {:?}",compare);
+            assert!(jumps(&mut vm,Instruction::JumpIfAbove),"ja after {:?}",compare);
+            assert!(!jumps(&mut vm,Instruction::JumpIfBelow),"jb after {:?}",compare);
+            assert!(!jumps(&mut vm,Instruction::JumpIfBelowOrEqual),"jbe after {:?}",compare);
+        }
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Mov(0,1),Instruction::Mov(1,-1),Instruction::Compare(0,1)]);
+        assert!(jumps(&mut vm,Instruction::JumpIfBelow));
+        assert!(!jumps(&mut vm,Instruction::JumpIfAboveOrEqual));
+        assert!(jumps(&mut vm,Instruction::JumpIfGreaterOrEqual));
+    }
+
+    fn overflow_and_carry(vm:&VM) -> (u8,u8) {
+        (*vm.get_flag(OVERFLOW_FLAG).unwrap(),*vm.get_flag(CARRY_FLAG).unwrap())
+    }
+
+    #[test]
+    fn overflow_and_carry_follow_the_register_width() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::Mov(0,i32::MAX as iInstructionParamType),Instruction::AddImmediate(0,1)]);
+        assert_eq!((signed(&vm,0),overflow_and_carry(&vm)),(i32::MIN as iRegisterDataType,(1,0)));
+        run(&mut vm,&[Instruction::SubImmediate(0,1)]);
+        assert_eq!((signed(&vm,0),overflow_and_carry(&vm)),(i32::MAX as iRegisterDataType,(1,0)));
+        run(&mut vm,&[Instruction::Mov(0,-1),Instruction::AddImmediate(0,1)]);
+        assert_eq!(overflow_and_carry(&vm),(0,1));
+        run(&mut vm,&[Instruction::Mov(0,i32::MAX as iInstructionParamType),Instruction::MulImmediate(0,2)]);
+        assert_eq!(overflow_and_carry(&vm),(1,1));
+
+        let mut vm = VM::new();
+        vm.set_header(wide());
+        run(&mut vm,&[Instruction::Mov(0,i32::MAX as iInstructionParamType),Instruction::AddImmediate(0,1)]);
+        assert_eq!((signed(&vm,0),overflow_and_carry(&vm)),(1 << 31,(0,0)));
+        run(&mut vm,&[Instruction::MovWide(0,iRegisterDataType::MAX),Instruction::AddImmediate(0,1)]);
+        assert_eq!((signed(&vm,0),overflow_and_carry(&vm)),(iRegisterDataType::MIN,(1,0)));
+        run(&mut vm,&[Instruction::Mov(0,-1),Instruction::AddImmediate(0,1)]);
+        assert_eq!(overflow_and_carry(&vm),(0,1));
+        run(&mut vm,&[Instruction::Mov(0,i32::MAX as iInstructionParamType),Instruction::MulImmediate(0,2)]);
+        assert_eq!(overflow_and_carry(&vm),(0,0));
+    }
+
+    #[test]
+    fn trapping_mode_faults_on_overflow_and_keeps_the_register() {
+        let mut vm = VM::new();
+        run(&mut vm,&[Instruction::SetArithmeticMode(ARITHMETIC_TRAPPING),Instruction::Mov(0,-1),Instruction::AddImmediate(0,1)]);
+        run(&mut vm,&[Instruction::Mov(0,i32::MAX as iInstructionParamType)]);
+        assert_eq!(vm.run_instruction(&Instruction::AddImmediate(0,1)).unwrap_err().code,ERROR_OVERFLOW);
+        assert_eq!(signed(&vm,0),i32::MAX as iRegisterDataType);
+
+        let mut vm = VM::new();
+        vm.set_header(wide());
+        run(&mut vm,&[Instruction::SetArithmeticMode(ARITHMETIC_TRAPPING),Instruction::Mov(0,i32::MAX as iInstructionParamType),Instruction::AddImmediate(0,1)]);
+        run(&mut vm,&[Instruction::MovWide(0,iRegisterDataType::MIN)]);
+        assert_eq!(vm.run_instruction(&Instruction::SubImmediate(0,1)).unwrap_err().code,ERROR_OVERFLOW);
+        assert_eq!(signed(&vm,0),iRegisterDataType::MIN);
+    }
+
+    #[test]
+    fn converting_nan_and_out_of_range_floats_saturates() {
+        let cases = [(f64::NAN,0,0),(f64::INFINITY,i32::MAX as iRegisterDataType,iRegisterDataType::MAX),
+            (f64::NEG_INFINITY,i32::MIN as iRegisterDataType,iRegisterDataType::MIN),(1e20,i32::MAX as iRegisterDataType,iRegisterDataType::MAX),
+            (-1e20,i32::MIN as iRegisterDataType,iRegisterDataType::MIN)];
+        for (header,is_wide) in [(ProgramHeader::default(),false),(wide(),true)] {
+            for (float,narrow,wide) in cases {
+                let mut vm = VM::new();
+                vm.set_header(header);
+                vm.floating_point_registers[0] = float;
+                let expected = if is_wide { wide } else { narrow };
+                run(&mut vm,&[Instruction::MovFloatToInt(0,0)]);
+                assert_eq!(signed(&vm,0),expected,"cvt of {}",float);
+                for mode in [ROUND_NEAREST,ROUND_DOWN,ROUND_UP,ROUND_ZERO] {
+                    run(&mut vm,&[Instruction::MovFloatToIntRounded(1,0,mode)]);
+                    assert_eq!(signed(&vm,1),expected,"cvtf of {} with mode {}",float,mode);
+                }
+            }
+        }
+        let mut vm = VM::new();
+        vm.floating_point_registers[0] = -2.5;
+        for (mode,expected) in [(ROUND_NEAREST,-2),(ROUND_DOWN,-3),(ROUND_UP,-2),(ROUND_ZERO,-2)] {
+            run(&mut vm,&[Instruction::MovFloatToIntRounded(1,0,mode)]);
+            assert_eq!(signed(&vm,1),expected,"cvtf of -2.5 with mode {}",mode);
+        }
+    }
+
+    #[test]
+    fn switch_sends_negative_and_too_large_indices_to_the_default() {
+        for header in [ProgramHeader::default(),wide()] {
+            let mut vm = VM::new();
+            vm.set_header(header);
+            let smallest = if header.wide_integers { iRegisterDataType::MIN } else { i32::MIN as iRegisterDataType };
+            for (index,skipped) in [(0,0),(2,2),(3,3),(4,3),(1000,3),(-1,3),(smallest,3)] {
+                vm.command_pointer = 10;
+                run(&mut vm,&[Instruction::MovWide(0,index),Instruction::JumpTable(0,3)]);
+                assert_eq!(vm.command_pointer,10+skipped,"index {} of {:?}",index,header);
+            }
+        }
+    }
+
+    #[test]
+    fn halfword_loads_past_either_end_of_a_memory_unit_fault() {
+        for header in [ProgramHeader::default(),wide()] {
+            let mut vm = VM::new();
+            vm.set_header(header);
+            let last = if header.wide_integers { 14 } else { 6 };
+            run(&mut vm,&[Instruction::Mov(0,2),Instruction::Malloc(0),Instruction::Pop(1),
+                Instruction::Mov(2,last),Instruction::Mov(3,-2),Instruction::SetMemory16(1,3,2),Instruction::GetMemory16Signed(1,4,2)]);
+            assert_eq!(signed(&vm,4),-2);
+            for offset in [last+1,last+2,-1,-2,i32::MAX as iInstructionParamType] {
+                run(&mut vm,&[Instruction::Mov(2,offset)]);
+                assert_eq!(vm.run_instruction(&Instruction::GetMemory16Signed(1,4,2)).unwrap_err().code,ERROR_MEMORY,"offset {}",offset);
+                assert_eq!(signed(&vm,4),-2);
+            }
+            run(&mut vm,&[Instruction::Mov(2,0),Instruction::Mov(1,7)]);
+            assert_eq!(vm.run_instruction(&Instruction::GetMemory16Signed(1,4,2)).unwrap_err().code,ERROR_MEMORY);
+        }
+    }
 }
@@ -0,0 +1,3605 @@
+use crate::{
+    instruction::{Instruction,StringNumberUnion,ProgramHeader,condition_flags,assert_message},
+    constants_and_types::*,
+    parser::SourceLocation,
+    vm::{describe_location,describe_frame_slot},
+    float_math,
+};
+
+use std::collections::BTreeSet;
+
+/// Number of values the bytecode stack of a native program can hold.
+/// The VM grows its stack without limit, a native program stops with a runtime error instead.
+pub const NATIVE_STACK_CAPACITY:usize = 1 << 20;
+
+/// Number of `@try`s a native program can be in at once.
+pub const NATIVE_HANDLER_CAPACITY:usize = 1 << 16;
+
+/// Translates generated instructions into x86-64 assembly (GNU assembler, AT&T syntax) for Linux.
+///
+/// `rax`-`rdx` live in `%rax`-`%rdx`, `bp` in `%rbp`, the reserve registers in `%r12`-`%r14` and `fa`-`fd` and
+/// the reserve float register in `%xmm8`-`%xmm12`. There are no general purpose registers left for `r8`-`r15`,
+/// so they live in `bc_registers`, while `f8`-`f15` get `%xmm1`-`%xmm7` and `%xmm14`. Integer registers are used
+/// at the width `header` gives them, `%eax` or `%rax`, and float registers hold floats or doubles. The bytecode
+/// stack is a fixed size array of 64 bit slots indexed by `%r15`, `call`/`ret` use the native stack and
+/// `%r8`-`%r11` are scratch registers for a single instruction.
+/// Printing and runtime errors go through the small runtime in `X86_RUNTIME`, which calls into libc,
+/// so the output is linked with `cc <file>.s -o <program>`.
+///
+/// `instructions` must already have its labels resolved to instruction numbers, which is the case
+/// for instructions read back with `VM::from_raw_instructions`. `source_map` gives the source line
+/// of every instruction for runtime error messages.
+pub fn translate(instructions:&[Instruction],header:ProgramHeader,source_map:&[Option<SourceLocation>]) -> Result<String,String> {
+    use Instruction::*;
+
+    let w = Width { wide: header.wide_integers, double: header.double_floats };
+    let reg = |r:InstructionParamType| w.reg(r);
+
+    let mut jump_targets: BTreeSet<usize> = BTreeSet::new();
+    let mut address_targets: BTreeSet<usize> = BTreeSet::new();
+    for inst in instructions.iter() {
+        if let Some(target) = get_jump_destination(inst)? {
+            jump_targets.insert(target);
+        }
+        if let LoadAddress(..) = inst {
+            address_targets.insert(get_jump_destination(inst)?.unwrap());
+        }
+    }
+    for (i,inst) in instructions.iter().enumerate() {
+        if let JumpTable(_,count) = inst {
+            jump_targets.extend(i+1..=i+1+*count as usize);
+        }
+    }
+    let address_table_size = address_targets.iter().next_back().map_or(0,|t| t+1);
+    let has_try = instructions.iter().any(|inst| matches!(inst,Try(_)));
+    let has_gap = instructions.iter().any(|inst| matches!(inst,StackCopyBackSp(..)));
+    // Jumping past the generated code lands on the `Halt`s the VM fills its instruction memory with.
+    let instruction_count = jump_targets.iter()
+        .map(|t| t+1)
+        .fold(instructions.len(),usize::max);
+
+    let mut body = String::new();
+    let mut messages: Vec<String> = Vec::new();
+    let mut jump_tables = String::new();
+    for i in 0..instruction_count {
+        if jump_targets.contains(&i) {
+            body.push_str(&format!(".L{}:\n",i));
+        }
+        let inst = instructions.get(i).unwrap_or(&Halt);
+        let location = describe_location(source_map,i);
+        let mut trap = |message:&str| {
+            messages.push(format!("Runtime Error: {} {}",message,location));
+            format!(".Lmessage{}",messages.len()-1)
+        };
+        let lines = match inst {
+            Halt => vec!["call bc_halt".to_string()],
+            Mov(a,b) => vec![format!("{} ${}, {}",w.int("mov"),b,reg(*a)?)],
+            MovWide(a,b) => {
+                let mut lines = vec![format!("movabsq ${}, %r11",b)];
+                lines.extend(mov(w,&w.r("%r11"),&reg(*a)?));
+                lines
+            }
+            Add(a,b) => arithmetic(w,update(w,&w.int("add"),&reg(*b)?,&reg(*a)?),&reg(*a)?,&trap("integer overflow")),
+            Sub(a,b) => arithmetic(w,update(w,&w.int("sub"),&reg(*b)?,&reg(*a)?),&reg(*a)?,&trap("integer overflow")),
+            Mul(a,b) => arithmetic(w,update(w,&w.int("imul"),&reg(*b)?,&reg(*a)?),&reg(*a)?,&trap("integer overflow")),
+            Div(a,b) => divide(w,&reg(*a)?,&reg(*b)?,"%rax",&trap("division by zero"),&trap("integer overflow")),
+            Mod(a,b) => divide(w,&reg(*a)?,&reg(*b)?,"%rdx",&trap("division by zero"),&trap("integer overflow")),
+            Display(a) => vec![sign_extend(w,&reg(*a)?,"%rdi"),"call bc_display".to_string()],
+            Push(a) => push(w,&format!("${}",a)),
+            PushWide(a) => {
+                let mut lines = vec![format!("movabsq ${}, %r11",a)];
+                lines.extend(push(w,&w.r("%r11")));
+                lines
+            }
+            PushRegister(a) => push(w,&reg(*a)?),
+            Pop(a) => pop(w,&reg(*a)?),
+
+            Jump(_) => vec![format!("jmp .L{}",get_jump_destination(inst)?.unwrap())],
+            Call(_) => vec![format!("call .L{}",get_jump_destination(inst)?.unwrap())],
+            TailCall(_) => {
+                let mut lines = drop_handlers(has_try);
+                lines.push(format!("jmp .L{}",get_jump_destination(inst)?.unwrap()));
+                lines
+            }
+            // The flags live in bc_flags, so dec changing the real ones does not matter.
+            Loop(a,_) => vec![format!("{} {}",w.int("dec"),reg(*a)?),format!("jnz .L{}",get_jump_destination(inst)?.unwrap())],
+            JumpIfZero(_) => jump_if_flag(ZERO_FLAG,0,"jne",inst)?,
+            JumpIfNotZero(_) => jump_if_flag(ZERO_FLAG,0,"je",inst)?,
+            JumpIfEqual(_) => jump_if_flag(EQUAL_FLAG,0,"jne",inst)?,
+            JumpIfNotEqual(_) => jump_if_flag(EQUAL_FLAG,1,"jne",inst)?,
+            JumpIfGreater(_) => jump_if_flag(GREATER_THAN_FLAG,0,"jne",inst)?,
+            JumpIfLess(_) => jump_if_flag(LESS_THAN_FLAG,0,"jne",inst)?,
+            JumpIfGreaterOrEqual(_) => [jump_if_flag(GREATER_THAN_FLAG,0,"jne",inst)?,jump_if_flag(EQUAL_FLAG,0,"jne",inst)?].concat(),
+            JumpIfLessOrEqual(_) => [jump_if_flag(LESS_THAN_FLAG,0,"jne",inst)?,jump_if_flag(EQUAL_FLAG,0,"jne",inst)?].concat(),
+            JumpIfAbove(_) => jump_if_flag(ABOVE_FLAG,0,"jne",inst)?,
+            JumpIfBelow(_) => jump_if_flag(BELOW_FLAG,0,"jne",inst)?,
+            JumpIfAboveOrEqual(_) => [jump_if_flag(ABOVE_FLAG,0,"jne",inst)?,jump_if_flag(EQUAL_FLAG,0,"jne",inst)?].concat(),
+            JumpIfBelowOrEqual(_) => [jump_if_flag(BELOW_FLAG,0,"jne",inst)?,jump_if_flag(EQUAL_FLAG,0,"jne",inst)?].concat(),
+            JumpIfOverflow(_) => jump_if_flag(OVERFLOW_FLAG,0,"jne",inst)?,
+            JumpIfNotOverflow(_) => jump_if_flag(OVERFLOW_FLAG,0,"je",inst)?,
+            JumpIfCarry(_) => jump_if_flag(CARRY_FLAG,0,"jne",inst)?,
+            JumpIfNotCarry(_) => jump_if_flag(CARRY_FLAG,0,"je",inst)?,
+            Return => {
+                let mut lines = vec![
+                    "leaq bc_error_return_without_call(%rip), %r8".to_string(),
+                    "cmpq %r8, (%rsp)".to_string(),
+                    "jne 1f".to_string(),
+                ];
+                lines.extend(raise(ERROR_STACK,&trap("ret without a matching call")));
+                lines.push("1:".to_string());
+                lines.extend(drop_handlers(has_try));
+                lines.push("ret".to_string());
+                lines
+            }
+            // Offsets of the jumps after it from `.Ltable<n>`, the last one is the default.
+            JumpTable(a,count) => {
+                jump_tables.push_str(&format!("    .balign 4\n.Ltable{}:\n",i));
+                for case in 0..=*count as usize {
+                    jump_tables.push_str(&format!("    .long .L{} - .Ltable{}\n",i+1+case,i));
+                }
+                vec![
+                    sign_extend(w,&reg(*a)?,"%rsi"),
+                    format!("cmpq ${}, %rsi",count),
+                    "jb 1f".to_string(),
+                    format!("movl ${}, %esi",count),
+                    "1:".to_string(),
+                    format!("leaq .Ltable{}(%rip), %r9",i),
+                    "movslq (%r9,%rsi,4), %r10".to_string(),
+                    "addq %r9, %r10".to_string(),
+                    "jmp *%r10".to_string(),
+                ]
+            }
+            LoadAddress(a,_) => vec![format!("{} ${}, {}",w.int("mov"),get_jump_destination(inst)?.unwrap(),reg(*a)?)],
+            JumpRegister(a) | CallRegister(a) => {
+                let bad_address = number_message(&mut messages,"cannot jump to address %ld as no lea loads it",&location);
+                let mut lines = vec![
+                    sign_extend(w,&reg(*a)?,"%rsi"),
+                    format!("cmpq ${}, %rsi",address_table_size),
+                    "jae 1f".to_string(),
+                    "leaq bc_address_table(%rip), %r9".to_string(),
+                    "movslq (%r9,%rsi,4), %r10".to_string(),
+                    "testq %r10, %r10".to_string(),
+                    "jz 1f".to_string(),
+                    "addq %r9, %r10".to_string(),
+                ];
+                if let CallRegister(_) = inst {
+                    lines.extend(["call *%r10".to_string(),"jmp 2f".to_string()]);
+                }else {
+                    lines.push("jmp *%r10".to_string());
+                }
+                lines.push("1:".to_string());
+                lines.extend(raise_with_number(ERROR_BAD_ADDRESS,&bad_address));
+                lines.push("2:".to_string());
+                lines
+            }
+
+            Compare(a,b) => compare(w,&reg(*a)?,&reg(*b)?,false),
+            CompareUnsigned(a,b) => compare(w,&reg(*a)?,&reg(*b)?,true),
+            GetFromStack(a,b) | SetStack(a,b) => {
+                let action = if let GetFromStack(..) = inst { "get" } else { "set" };
+                let message = number_message(&mut messages,&format!("cannot {} element %ld of the stack",action),&location);
+                let mut lines = stack_index(vec![sign_extend(w,&reg(*a)?,"%r9")],&message);
+                lines.extend(if action == "get" { mov(w,"(%r10,%r9,8)",&reg(*b)?) } else { to_slot(w,&reg(*b)?,"(%r10,%r9,8)") });
+                lines
+            }
+            GetFromStackPointer(a,b) | SetFromStackPointer(a,b) => {
+                let action = if let GetFromStackPointer(..) = inst { "get" } else { "set" };
+                let message = number_message(&mut messages,&format!("cannot {} element %ld of the stack",action),&location);
+                let mut lines = stack_index(stack_pointer_offset(w,&reg(*a)?,has_gap),&message);
+                lines.extend(if action == "get" { mov(w,"(%r10,%r9,8)",&reg(*b)?) } else { to_slot(w,&reg(*b)?,"(%r10,%r9,8)") });
+                lines
+            }
+            Enter(a) => {
+                let mut lines = push(w,&w.r("%rbp"));
+                lines.extend(mov(w,&w.r("%r15"),&w.r("%rbp")));
+                lines.extend([
+                    format!("leaq {}(%r15), %r9",a),
+                    format!("cmpq ${}, %r9",NATIVE_STACK_CAPACITY),
+                    "ja bc_error_stack_overflow".to_string(),
+                    "leaq bc_stack(%rip), %r10".to_string(),
+                    "1:".to_string(),
+                    "cmpq %r9, %r15".to_string(),
+                    "jae 2f".to_string(),
+                    "movq $0, (%r10,%r15,8)".to_string(),
+                    "incq %r15".to_string(),
+                    "jmp 1b".to_string(),
+                    "2:".to_string(),
+                ]);
+                lines
+            }
+            Leave => {
+                let mut lines = vec![
+                    sign_extend(w,&w.r("%rbp"),"%r8"),
+                    "testq %r8, %r8".to_string(),
+                    "jle 1f".to_string(),
+                    "cmpq %r15, %r8".to_string(),
+                    "jbe 2f".to_string(),
+                    "1:".to_string(),
+                ];
+                lines.extend(raise(ERROR_STACK,&trap("leave without a matching enter")));
+                lines.extend([
+                    "2:".to_string(),
+                    "leaq -1(%r8), %r15".to_string(),
+                    "leaq bc_stack(%rip), %r9".to_string(),
+                ]);
+                lines.extend(mov(w,"(%r9,%r15,8)",&w.r("%rbp")));
+                lines
+            }
+            GetFromBasePointer(a,b) => {
+                let mut lines = frame_slot(w,*b,&trap(&format!("{} is outside the stack",describe_frame_slot(*b))));
+                lines.extend(mov(w,"(%r9,%r8,8)",&reg(*a)?));
+                lines
+            }
+            SetFromBasePointer(a,b) => {
+                let mut lines = frame_slot(w,*b,&trap(&format!("{} is outside the stack",describe_frame_slot(*b))));
+                lines.extend(to_slot(w,&reg(*a)?,"(%r9,%r8,8)"));
+                lines
+            }
+            ExtendStack(a,b) => vec![
+                sign_extend(w,&reg(*a)?,"%r8"),
+                "testq %r8, %r8".to_string(),
+                "js bc_error_extend_stack".to_string(),
+                "leaq (%r15,%r8), %r9".to_string(),
+                format!("cmpq ${}, %r9",NATIVE_STACK_CAPACITY),
+                "ja bc_error_stack_overflow".to_string(),
+                "leaq bc_stack(%rip), %r10".to_string(),
+                sign_extend(w,&reg(*b)?,"%r11"),
+                "1:".to_string(),
+                "cmpq %r9, %r15".to_string(),
+                "jae 2f".to_string(),
+                "movq %r11, (%r10,%r15,8)".to_string(),
+                "incq %r15".to_string(),
+                "jmp 1b".to_string(),
+                "2:".to_string(),
+            ],
+            // A negative count compares above the stack pointer as well.
+            TruncateStack(a) => {
+                let message = number_message(&mut messages,"cannot truncate %ld elements of the stack",&location);
+                let mut lines = stack_pointer(has_gap);
+                lines.extend([
+                    sign_extend(w,&reg(*a)?,"%r8"),
+                    "cmpq %r9, %r8".to_string(),
+                    "jbe 1f".to_string(),
+                    "movq %r8, %rsi".to_string(),
+                ]);
+                lines.extend(raise_with_number(ERROR_STACK,&message));
+                lines.extend(["1:".to_string(),"subq %r8, %r15".to_string()]);
+                lines
+            }
+            TruncateStackRange(a,b) => vec![
+                sign_extend(w,&reg(*a)?,"%r8"),
+                sign_extend(w,&reg(*b)?,"%r9"),
+                "cmpq %r9, %r8".to_string(),
+                "ja bc_error_truncate_stack_range".to_string(),
+                "cmpq %r15, %r9".to_string(),
+                "ja bc_error_truncate_stack_range".to_string(),
+                "leaq bc_stack(%rip), %r10".to_string(),
+                "1:".to_string(),
+                "cmpq %r15, %r9".to_string(),
+                "jae 2f".to_string(),
+                "movq (%r10,%r9,8), %r11".to_string(),
+                "movq %r11, (%r10,%r8,8)".to_string(),
+                "incq %r8".to_string(),
+                "incq %r9".to_string(),
+                "jmp 1b".to_string(),
+                "2:".to_string(),
+                "movq %r8, %r15".to_string(),
+            ],
+            GetStackPointer(a) => {
+                let mut lines = stack_pointer(has_gap);
+                lines.extend(mov(w,&w.r("%r9"),&reg(*a)?));
+                lines
+            }
+            // Counts are read as 32 bit numbers, like the VM does.
+            StackCopyBackSp(a,b,c) => {
+                let narrow = Width { wide: false, ..w };
+                vec![
+                    format!("movslq {}, %r8",narrow.reg(*a)?),
+                    format!("movslq {}, %r9",narrow.reg(*b)?),
+                    format!("movslq {}, %r10",narrow.reg(*c)?),
+                    "call bc_stack_copy_back_sp".to_string(),
+                ]
+            }
+
+            Or(a,b) => logic(w,update(w,&w.int("or"),&reg(*b)?,&reg(*a)?),&reg(*a)?),
+            And(a,b) => logic(w,update(w,&w.int("and"),&reg(*b)?,&reg(*a)?),&reg(*a)?),
+            Xor(a,b) => logic(w,update(w,&w.int("xor"),&reg(*b)?,&reg(*a)?),&reg(*a)?),
+            Not(a) => logic(w,vec![format!("{} {}",w.int("not"),reg(*a)?)],&reg(*a)?),
+
+            AddImmediate(a,b) => arithmetic(w,vec![format!("{} ${}, {}",w.int("add"),b,reg(*a)?)],&reg(*a)?,&trap("integer overflow")),
+            SubImmediate(a,b) => arithmetic(w,vec![format!("{} ${}, {}",w.int("sub"),b,reg(*a)?)],&reg(*a)?,&trap("integer overflow")),
+            MulImmediate(a,b) => arithmetic(w,update(w,&w.int("imul"),&format!("${}",b),&reg(*a)?),&reg(*a)?,&trap("integer overflow")),
+            DivImmediate(a,b) => divide(w,&reg(*a)?,&format!("${}",b),"%rax",&trap("division by zero"),&trap("integer overflow")),
+            ModImmediate(a,b) => divide(w,&reg(*a)?,&format!("${}",b),"%rdx",&trap("division by zero"),&trap("integer overflow")),
+            AndImmediate(a,b) => logic(w,vec![format!("{} ${}, {}",w.int("and"),b,reg(*a)?)],&reg(*a)?),
+            OrImmediate(a,b) => logic(w,vec![format!("{} ${}, {}",w.int("or"),b,reg(*a)?)],&reg(*a)?),
+            XorImmediate(a,b) => logic(w,vec![format!("{} ${}, {}",w.int("xor"),b,reg(*a)?)],&reg(*a)?),
+            CompareImmediate(a,b) => compare(w,&reg(*a)?,&format!("${}",b),false),
+            CompareUnsignedImmediate(a,b) => compare(w,&reg(*a)?,&format!("${}",b),true),
+            DivUnsigned(a,b) => logic(w,divide_unsigned(w,&reg(*a)?,&reg(*b)?,"%rax",&trap("division by zero")),&reg(*a)?),
+            ModUnsigned(a,b) => logic(w,divide_unsigned(w,&reg(*a)?,&reg(*b)?,"%rdx",&trap("division by zero")),&reg(*a)?),
+            DivUnsignedImmediate(a,b) => logic(w,divide_unsigned(w,&reg(*a)?,&format!("${}",b),"%rax",&trap("division by zero")),&reg(*a)?),
+            ModUnsignedImmediate(a,b) => logic(w,divide_unsigned(w,&reg(*a)?,&format!("${}",b),"%rdx",&trap("division by zero")),&reg(*a)?),
+
+            Movf(a,b) if w.double => vec![format!("movabsq ${:#x}, %r8",b.to_bits()),format!("movq %r8, {}",float_reg(*a)?)],
+            Movf(a,b) => vec![format!("movl ${:#x}, %r8d",(*b as f32).to_bits()),format!("movd %r8d, {}",float_reg(*a)?)],
+            MovRegister(a,b) => mov(w,&reg(*b)?,&reg(*a)?),
+            MovfRegister(a,b) => vec![format!("movaps {}, {}",float_reg(*b)?,float_reg(*a)?)],
+            MovIntToFloat(a,b) => vec![format!("{} {}, {}",w.int(&w.float("cvtsi2s")),reg(*b)?,float_reg(*a)?)],
+            MovFloatToInt(a,b) => float_to_int(w,&reg(*a)?,float_reg(*b)?),
+            // The rounding modes are the ones `roundss` takes.
+            MovFloatToIntRounded(a,b,mode) => {
+                if *mode > ROUND_ZERO {
+                    return Err(format!("Rounding mode {} does not exist.",mode));
+                }
+                let mut lines = vec![format!("{} ${}, {}, %xmm13",w.float("rounds"),mode,float_reg(*b)?)];
+                lines.extend(float_to_int(w,&reg(*a)?,"%xmm13"));
+                lines
+            }
+            // SSE rounds these exactly, the others go through the copy of float_math.rs in X86_MATH.
+            FloatMath(a,b,function) => match *function {
+                MATH_SQRT => vec![format!("{} {}, {}",w.float("sqrts"),float_reg(*b)?,float_reg(*a)?)],
+                MATH_ABS if w.double => vec![
+                    format!("movq {}, %r8",float_reg(*b)?),
+                    "btrq $63, %r8".to_string(),
+                    format!("movq %r8, {}",float_reg(*a)?),
+                ],
+                MATH_ABS => vec![
+                    format!("movd {}, %r8d",float_reg(*b)?),
+                    "andl $0x7fffffff, %r8d".to_string(),
+                    format!("movd %r8d, {}",float_reg(*a)?),
+                ],
+                MATH_FLOOR => vec![format!("{} ${}, {}, {}",w.float("rounds"),ROUND_DOWN,float_reg(*b)?,float_reg(*a)?)],
+                MATH_CEIL => vec![format!("{} ${}, {}, {}",w.float("rounds"),ROUND_UP,float_reg(*b)?,float_reg(*a)?)],
+                function => {
+                    let mut lines = if float_math::is_binary(function) {
+                        vec![
+                            format!("{} {}, %xmm0",w.float_to_double(),float_reg(*a)?),
+                            format!("{} {}, %xmm13",w.float_to_double(),float_reg(*b)?),
+                        ]
+                    }else {
+                        vec![format!("{} {}, %xmm0",w.float_to_double(),float_reg(*b)?)]
+                    };
+                    lines.extend([
+                        format!("leaq {}(%rip), %rdi",x86_math_function(function)?),
+                        "call bc_float_math".to_string(),
+                        format!("{} %xmm0, {}",w.double_to_float(),float_reg(*a)?),
+                    ]);
+                    lines
+                }
+            },
+            // With 64 bit registers and floats the bits of a float are zero extended, with 32 bit registers
+            // and doubles only the low half of them fits.
+            MovFloatBitsToInt(a,b) => {
+                let mut lines = vec![format!("{} {}, {}",w.bits_mov(),float_reg(*b)?,w.bits("%r11"))];
+                lines.extend(mov(w,&w.r("%r11"),&reg(*a)?));
+                lines
+            }
+            MovIntBitsToFloat(a,b) => {
+                let mut lines = mov(w,&reg(*b)?,&w.r("%r11"));
+                lines.push(format!("{} {}, {}",w.bits_mov(),w.bits("%r11"),float_reg(*a)?));
+                lines
+            }
+            CompareFloat(a,b) => {
+                // `ucomiss` sets the zero, parity and carry flags for NaN, so equal and less are
+                // only kept when the parity flag says the floats are ordered.
+                let mut lines = vec![
+                    format!("{} {}, {}",w.float("ucomis"),float_reg(*b)?,float_reg(*a)?),
+                    "setnp %r8b".to_string(),
+                    "sete %r9b".to_string(),
+                    "setb %r10b".to_string(),
+                    format!("seta bc_flags+{}(%rip)",GREATER_THAN_FLAG),
+                    format!("seta bc_flags+{}(%rip)",ABOVE_FLAG),
+                    format!("setp bc_flags+{}(%rip)",OVERFLOW_FLAG),
+                    "andb %r8b, %r9b".to_string(),
+                    "andb %r8b, %r10b".to_string(),
+                ];
+                for flag in [EQUAL_FLAG,ZERO_FLAG] {
+                    lines.push(format!("movb %r9b, bc_flags+{}(%rip)",flag));
+                }
+                for flag in [LESS_THAN_FLAG,BELOW_FLAG,SIGN_FLAG,CARRY_FLAG] {
+                    lines.push(format!("movb %r10b, bc_flags+{}(%rip)",flag));
+                }
+                lines
+            }
+            Shl(a,b) => logic(w,shift_by_register(w,&reg(*a)?,&reg(*b)?,"shl"),&reg(*a)?),
+            Shr(a,b) => logic(w,shift_by_register(w,&reg(*a)?,&reg(*b)?,"shr"),&reg(*a)?),
+            Sar(a,b) => logic(w,shift_by_register(w,&reg(*a)?,&reg(*b)?,"sar"),&reg(*a)?),
+            Rol(a,b) => logic(w,shift_by_register(w,&reg(*a)?,&reg(*b)?,"rol"),&reg(*a)?),
+            Ror(a,b) => logic(w,shift_by_register(w,&reg(*a)?,&reg(*b)?,"ror"),&reg(*a)?),
+            ShlImmediate(a,b) => logic(w,shift_by_immediate(w,&reg(*a)?,*b,"shl"),&reg(*a)?),
+            ShrImmediate(a,b) => logic(w,shift_by_immediate(w,&reg(*a)?,*b,"shr"),&reg(*a)?),
+            SarImmediate(a,b) => logic(w,shift_by_immediate(w,&reg(*a)?,*b,"sar"),&reg(*a)?),
+            RolImmediate(a,b) => logic(w,shift_by_immediate(w,&reg(*a)?,*b,"rol"),&reg(*a)?),
+            RorImmediate(a,b) => logic(w,shift_by_immediate(w,&reg(*a)?,*b,"ror"),&reg(*a)?),
+
+            Addf(a,b) => vec![format!("{} {}, {}",w.float("adds"),float_reg(*b)?,float_reg(*a)?)],
+            Subf(a,b) => vec![format!("{} {}, {}",w.float("subs"),float_reg(*b)?,float_reg(*a)?)],
+            Mulf(a,b) => vec![format!("{} {}, {}",w.float("muls"),float_reg(*b)?,float_reg(*a)?)],
+            Divf(a,b) => vec![format!("{} {}, {}",w.float("divs"),float_reg(*b)?,float_reg(*a)?)],
+            Displayf(a) => vec![
+                format!("movaps {}, %xmm0",float_reg(*a)?),
+                format!("call {}",if w.double { "bc_display_double" } else { "bc_display_float" }),
+            ],
+            // A float's bits are kept like a 32 bit integer, sign extended unless registers are 64 bits.
+            PushFloatRegister(a) => push_slot(if w.double {
+                vec![format!("movq {}, (%r8,%r15,8)",float_reg(*a)?)]
+            }else {
+                let mut lines = vec![format!("movd {}, %r11d",float_reg(*a)?)];
+                lines.extend(to_slot(w,&w.r("%r11"),"(%r8,%r15,8)"));
+                lines
+            }),
+            PopFloat(a) => pop_slot(vec![format!("{} (%r8,%r15,8), {}",w.bits_mov(),float_reg(*a)?)]),
+
+            DisplayChar(a) => vec![sign_extend(w,&reg(*a)?,"%rdi"),"call bc_display_char".to_string()],
+            GetFlag(a,b) => {
+                let mut lines = vec![
+                    sign_extend(w,&reg(*b)?,"%rsi"),
+                    "cmpq $16, %rsi".to_string(),
+                    "jae bc_error_get_flag".to_string(),
+                    "leaq bc_flags(%rip), %r8".to_string(),
+                ];
+                lines.extend(update(w,&w.int("movzb"),"(%r8,%rsi)",&reg(*a)?));
+                lines
+            }
+            // %esi is not 0 when the condition holds, unless it is negated.
+            ConditionalMove(a,b,condition) => {
+                let (mut lines,negated) = x86_condition(*condition)?;
+                lines.extend(update(w,&w.int(if negated { "cmovz" } else { "cmovnz" }),&reg(*b)?,&reg(*a)?));
+                lines
+            }
+            SetIf(a,condition) => {
+                let (mut lines,negated) = x86_condition(*condition)?;
+                lines.push(format!("{} %sil",if negated { "setz" } else { "setnz" }));
+                lines.extend(update(w,&w.int("movzb"),"%sil",&reg(*a)?));
+                lines
+            }
+            Write(a,b) => [mov(w,&reg(*a)?,&w.r("%rdi")),mov(w,&reg(*b)?,&w.r("%rsi")),vec!["call bc_write".to_string()]].concat(),
+
+            // The interpreter does not implement these either.
+            Nand(..) | Modf(..) => {
+                messages.push(format!("not implemented: {:?}",inst));
+                vec![format!("leaq .Lmessage{}(%rip), %rdi",messages.len()-1),"jmp bc_panic".to_string()]
+            }
+            SetArithmeticMode(a) => vec![format!("movb ${}, bc_trap_overflow(%rip)",(*a == ARITHMETIC_TRAPPING) as u8)],
+
+            AssertFailed(_) => {
+                let message = assert_message(instructions,i).ok_or(format!("Instruction {} is not preceded by the message it asserts with.",i))?;
+                raise(ERROR_ASSERT,&trap(&format!("assertion failed: {}",message)))
+            }
+
+            Try(_) => vec![format!("leaq .L{}(%rip), %rdi",get_jump_destination(inst)?.unwrap()),"call bc_try".to_string()],
+            EndTry => {
+                let mut lines = vec!["cmpq $0, bc_handler_count(%rip)".to_string(),"jne 1f".to_string()];
+                lines.extend(raise(ERROR_BAD_INSTRUCTION,&trap("@endtry without a matching @try")));
+                lines.extend(["1:".to_string(),"call bc_pop_handler".to_string()]);
+                lines
+            }
+            Malloc(a) => {
+                let mut lines = vec![sign_extend(w,&reg(*a)?,"%rdi"),"call bc_malloc".to_string()];
+                lines.extend(push(w,&w.r("%r9")));
+                lines
+            }
+            Free(a) => {
+                let mut lines = memory_unit(w,&reg(*a)?,&number_message(&mut messages,"cannot free memory unit %ld as it does not exist",&location));
+                lines.push("call bc_free".to_string());
+                lines
+            }
+            GetMemory(a,b,c) | SetMemory(a,b,c) => {
+                let cell_bytes = if w.wide { 8 } else { 4 };
+                let mut lines = memory_unit(w,&reg(*a)?,&number_message(&mut messages,"memory unit %ld does not exist",&location));
+                lines.extend([
+                    sign_extend(w,&reg(*c)?,"%r9"),
+                    "movq (%r8), %r10".to_string(),
+                    "cmpq %r10, %r9".to_string(),
+                    "jae 1f".to_string(),
+                    format!("leaq 8(%r8,%r10,{}), %r10",cell_bytes),
+                ]);
+                let cell = format!("8(%r8,%r9,{})",cell_bytes);
+                if let GetMemory(..) = inst {
+                    lines.extend(["cmpb $0, (%r10,%r9)".to_string(),"jne 2f".to_string(),"1:".to_string()]);
+                    lines.extend(memory_error(&number_message(&mut messages,"location %ld of memory unit %ld is not set",&location)));
+                    lines.push("2:".to_string());
+                    lines.extend(mov(w,&cell,&reg(*b)?));
+                }else {
+                    lines.extend(["jmp 2f".to_string(),"1:".to_string()]);
+                    lines.extend(memory_error(&number_message(&mut messages,"location %ld of memory unit %ld does not exist",&location)));
+                    lines.push("2:".to_string());
+                    lines.extend(mov(w,&reg(*b)?,&cell));
+                    lines.push("movb $1, (%r10,%r9)".to_string());
+                }
+                lines
+            }
+            GetMemory8(a,b,c) | GetMemory8Signed(a,b,c) | GetMemory16(a,b,c) | GetMemory16Signed(a,b,c) |
+                GetMemory32(a,b,c) | GetMemory32Signed(a,b,c) => {
+                let (width,signed) = match inst {
+                    GetMemory8(..) => (1,false),
+                    GetMemory8Signed(..) => (1,true),
+                    GetMemory16(..) => (2,false),
+                    GetMemory16Signed(..) => (2,true),
+                    GetMemory32(..) => (4,false),
+                    _ => (4,true),
+                };
+                let mut lines = memory_bytes(w,&reg(*a)?,&reg(*c)?,width,&number_message(&mut messages,"memory unit %ld does not exist",&location),&number_message(&mut messages,"byte %ld of memory unit %ld does not exist",&location));
+                // The first byte of an unset cell is the one the VM reports.
+                let shift = if w.wide { 3 } else { 2 };
+                lines.extend([
+                    "movq %r9, %rsi".to_string(),
+                    format!("shrq ${}, %rsi",shift),
+                    "cmpb $0, (%r10,%rsi)".to_string(),
+                    "jne 1f".to_string(),
+                    "movq %r9, %rsi".to_string(),
+                    "jmp 2f".to_string(),
+                    "1:".to_string(),
+                    format!("leaq {}(%r9), %rsi",width-1),
+                    format!("shrq ${}, %rsi",shift),
+                    "cmpb $0, (%r10,%rsi)".to_string(),
+                    "jne 3f".to_string(),
+                    format!("shlq ${}, %rsi",shift),
+                    "2:".to_string(),
+                    "movq %rdi, %r9".to_string(),
+                ]);
+                lines.extend(raise_with_number(ERROR_MEMORY,&number_message(&mut messages,"byte %ld of memory unit %ld is not set",&location)));
+                lines.push("3:".to_string());
+                lines.push(match (width,signed) {
+                    (4,false) => "movl 8(%r8,%r9), %r11d".to_string(),
+                    (4,true) if w.wide => "movslq 8(%r8,%r9), %r11".to_string(),
+                    (4,true) => "movl 8(%r8,%r9), %r11d".to_string(),
+                    _ => format!("{} 8(%r8,%r9), {}",w.int(&format!("mov{}{}",if signed { 's' } else { 'z' },if width == 1 { 'b' } else { 'w' })),w.r("%r11")),
+                });
+                lines.extend(mov(w,&w.r("%r11"),&reg(*b)?));
+                lines
+            }
+            // Cells the bytes fall in that were never set are set to 0 first.
+            SetMemory8(a,b,c) | SetMemory16(a,b,c) | SetMemory32(a,b,c) => {
+                let width = match inst {
+                    SetMemory8(..) => 1,
+                    SetMemory16(..) => 2,
+                    _ => 4,
+                };
+                let mut lines = memory_bytes(w,&reg(*a)?,&reg(*c)?,width,&number_message(&mut messages,"memory unit %ld does not exist",&location),&number_message(&mut messages,"byte %ld of memory unit %ld does not exist",&location));
+                let (cell_bytes,shift) = if w.wide { (8,3) } else { (4,2) };
+                for (byte,label) in [(0,1),(width-1,2)] {
+                    lines.extend([
+                        format!("leaq {}(%r9), %rsi",byte),
+                        format!("shrq ${}, %rsi",shift),
+                        "cmpb $0, (%r10,%rsi)".to_string(),
+                        format!("jne {}f",label),
+                        "movb $1, (%r10,%rsi)".to_string(),
+                        format!("{} $0, 8(%r8,%rsi,{})",w.int("mov"),cell_bytes),
+                        format!("{}:",label),
+                    ]);
+                }
+                lines.extend(mov(w,&reg(*b)?,&w.r("%r11")));
+                lines.push(match width {
+                    1 => "movb %r11b, 8(%r8,%r9)",
+                    2 => "movw %r11w, 8(%r8,%r9)",
+                    _ => "movl %r11d, 8(%r8,%r9)",
+                }.to_string());
+                lines
+            }
+
+            Throw(a) => {
+                let message = number_message(&mut messages,"uncaught error %ld",&location);
+                vec![
+                    sign_extend(w,&reg(*a)?,"%rsi"),
+                    "movq %rsi, %r11".to_string(),
+                    format!("leaq {}(%rip), %rdi",message),
+                    "jmp bc_raise_with_number".to_string(),
+                ]
+            }
+
+            Breakpoint => {
+                messages.push(format!("Breakpoint {}",location));
+                vec![format!("leaq .Lmessage{}(%rip), %rdi",messages.len()-1),"call bc_breakpoint".to_string()]
+            }
+        };
+        for line in lines {
+            if !line.ends_with(':') {
+                body.push_str("    ");
+            }
+            body.push_str(&line);
+            body.push('\n');
+        }
+    }
+    body.push_str("    call bc_halt\n");
+
+    let mut data = String::new();
+    for (label,handler,message) in RUNTIME_ERRORS.iter() {
+        body.push_str(&format!("{}:\n    leaq .L{}_message(%rip), %rdi\n    jmp {}\n",label,label,handler));
+        data.push_str(&format!(".L{}_message:\n    .asciz {:?}\n",label,message));
+    }
+    for (label,code,message) in CATCHABLE_ERRORS.iter() {
+        body.push_str(&format!("{}:\n    movl ${}, %r11d\n    leaq .L{}_message(%rip), %rdi\n    jmp bc_raise\n",label,code,label));
+        data.push_str(&format!(".L{}_message:\n    .asciz {:?}\n",label,message));
+    }
+    for (i,message) in messages.iter().enumerate() {
+        data.push_str(&format!(".Lmessage{}:\n    .asciz {:?}\n",i,message));
+    }
+    data.push_str(&jump_tables);
+    // Offsets of every address a `lea` loads from the table itself, 0 for the others.
+    // `jmp` and `call` through a register look their target up here.
+    if instructions.iter().any(|inst| matches!(inst,JumpRegister(_) | CallRegister(_))) {
+        data.push_str("    .balign 4\nbc_address_table:\n");
+        for address in 0..address_table_size {
+            if address_targets.contains(&address) {
+                data.push_str(&format!("    .long .L{} - bc_address_table\n",address));
+            }else {
+                data.push_str("    .long 0\n");
+            }
+        }
+    }
+
+    let breakpoint = if instructions.contains(&Breakpoint) { X86_BREAKPOINT } else { "" };
+    let math = if instructions.iter().any(|inst| matches!(inst,FloatMath(..))) { X86_MATH } else { "" };
+    Ok(format!(
+        "{}\n    .equ BC_STACK_CAPACITY, {}\n    .equ BC_HANDLER_CAPACITY, {}\n    .equ BC_CELL_BYTES, {}\n    .text\n    .globl main\nmain:\n{}{}{}{}{}\n    .section .rodata\n{}{}\n    .bss\n    .balign 16\nbc_stack:\n    .zero {}\nbc_flags:\n    .zero 16\nbc_trap_overflow:\n    .zero 1\n    .balign 8\nbc_registers:\n    .zero 64\nbc_sp_gap:\n    .zero 8\nbc_units:\n    .zero 8\nbc_unit_count:\n    .zero 8\nbc_unit_capacity:\n    .zero 8\nbc_handler_count:\n    .zero 8\nbc_handlers:\n    .zero {}\n\n    .section .note.GNU-stack,\"\",@progbits\n",
+        X86_HEADER,NATIVE_STACK_CAPACITY,NATIVE_HANDLER_CAPACITY,if w.wide { 8 } else { 4 },
+        X86_START,body,X86_RUNTIME,breakpoint,math,X86_RODATA,data,NATIVE_STACK_CAPACITY*8,NATIVE_HANDLER_CAPACITY*48
+    ))
+}
+
+/// Operand sizes of a program, from its header.
+#[derive(Clone,Copy)]
+struct Width {
+    /// Integer registers are 64 bits instead of 32.
+    wide: bool,
+    /// Float registers hold doubles instead of floats.
+    double: bool,
+}
+
+impl Width {
+    /// `mnemonic` with the suffix for the integer size: `add` becomes `addl` or `addq`.
+    fn int(self,mnemonic:&str) -> String {
+        format!("{}{}",mnemonic,if self.wide { 'q' } else { 'l' })
+    }
+
+    /// `mnemonic` with the suffix for the float size: `adds` becomes `addss` or `addsd`.
+    fn float(self,mnemonic:&str) -> String {
+        format!("{}{}",mnemonic,if self.double { 'd' } else { 's' })
+    }
+
+    /// The instruction turning a float of the float size into a double.
+    fn float_to_double(self) -> &'static str {
+        if self.double { "movapd" } else { "cvtss2sd" }
+    }
+
+    /// The instruction turning a double into a float of the float size.
+    fn double_to_float(self) -> &'static str {
+        if self.double { "movapd" } else { "cvtsd2ss" }
+    }
+
+    /// The general purpose register `name`, given by its 64 bit name, at the integer size:
+    /// `%rax` becomes `%eax` and `%r8` becomes `%r8d` with 32 bit integers. Other operands stay as they are.
+    fn r(self,name:&str) -> String {
+        sized(name,self.wide)
+    }
+
+    /// Same as `r` at the float size, for moving the bits of a float.
+    fn bits(self,name:&str) -> String {
+        sized(name,self.double)
+    }
+
+    /// The instruction moving the bits of a float between SSE and general purpose registers.
+    fn bits_mov(self) -> &'static str {
+        if self.double { "movq" } else { "movd" }
+    }
+
+    fn reg(self,r:InstructionParamType) -> Result<String,String> {
+        Ok(self.r(match r {
+            REGA => "%rax",
+            REGB => "%rbx",
+            REGC => "%rcx",
+            REGD => "%rdx",
+            RESERVEREGISTER1 => "%r12",
+            RESERVEREGISTER2 => "%r13",
+            RESERVEREGISTER3 => "%r14",
+            REGBP => "%rbp",
+            _ if (r as usize) < USER_REGISTER_COUNT => MEMORY_REGISTERS[r as usize - 4],
+            _ => return Err(format!("Register number {} does not exist.",r)),
+        }))
+    }
+}
+
+/// `name` as a 64 bit register, or its low 32 bits unless `wide`.
+fn sized(name:&str,wide:bool) -> String {
+    match name.strip_prefix("%r") {
+        Some(_) if wide => name.to_string(),
+        Some(number) if number.starts_with(|c:char| c.is_ascii_digit()) => format!("{}d",name),
+        Some(name) => format!("%e{}",name),
+        None => name.to_string(),
+    }
+}
+
+/// Routine of `X86_MATH` that computes the `MATH_` function `function`.
+fn x86_math_function(function:InstructionParamType) -> Result<&'static str,String> {
+    Ok(match function {
+        MATH_ROUND => "bc_round",
+        MATH_EXP => "bc_exp",
+        MATH_LN => "bc_ln",
+        MATH_SIN => "bc_sin",
+        MATH_COS => "bc_cos",
+        MATH_TAN => "bc_tan",
+        MATH_POW => "bc_pow",
+        MATH_ATAN2 => "bc_atan2",
+        MATH_MIN => "bc_min",
+        MATH_MAX => "bc_max",
+        function => return Err(format!("Math function {} does not exist.",function)),
+    })
+}
+
+fn float_reg(r:InstructionParamType) -> Result<&'static str,String> {
+    match r {
+        0 => Ok("%xmm8"),
+        1 => Ok("%xmm9"),
+        2 => Ok("%xmm10"),
+        3 => Ok("%xmm11"),
+        RESERVEFLOATREGISTER => Ok("%xmm12"),
+        _ if (r as usize) < USER_FLOAT_REGISTER_COUNT => Ok(["%xmm1","%xmm2","%xmm3","%xmm4","%xmm5","%xmm6","%xmm7","%xmm14"][r as usize - 4]),
+        _ => Err(format!("Float register number {} does not exist.",r)),
+    }
+}
+
+/// Where `r8`-`r15` live, 8 bytes apart.
+const MEMORY_REGISTERS: [&str;8] = [
+    "bc_registers(%rip)","bc_registers+8(%rip)","bc_registers+16(%rip)","bc_registers+24(%rip)",
+    "bc_registers+32(%rip)","bc_registers+40(%rip)","bc_registers+48(%rip)","bc_registers+56(%rip)",
+];
+
+/// Leaves the index of the stack slot `bp + offset` in `%r8` and the start of the stack in `%r9`,
+/// stopping with the message at `outside` when it is not on the stack.
+fn frame_slot(w:Width,offset:iInstructionParamType,outside:&str) -> Vec<String> {
+    let mut lines = vec![
+        sign_extend(w,&w.r("%rbp"),"%r8"),
+        format!("addq ${}, %r8",offset),
+        "cmpq %r15, %r8".to_string(),
+        "jb 1f".to_string(),
+    ];
+    lines.extend(raise(ERROR_STACK,outside));
+    lines.extend(["1:".to_string(),"leaq bc_stack(%rip), %r9".to_string()]);
+    lines
+}
+
+fn push(w:Width,value:&str) -> Vec<String> {
+    push_slot(to_slot(w,value,"(%r8,%r15,8)"))
+}
+
+/// Pushes a slot onto the bytecode stack, which `store` fills in at `(%r8,%r15,8)`.
+fn push_slot(store:Vec<String>) -> Vec<String> {
+    let mut lines = vec![
+        format!("cmpq ${}, %r15",NATIVE_STACK_CAPACITY),
+        "jae bc_error_stack_overflow".to_string(),
+        "leaq bc_stack(%rip), %r8".to_string(),
+    ];
+    lines.extend(store);
+    lines.push("incq %r15".to_string());
+    lines
+}
+
+fn pop(w:Width,dest:&str) -> Vec<String> {
+    pop_slot(mov(w,"(%r8,%r15,8)",dest))
+}
+
+/// Pops a slot off the bytecode stack, which `load` reads at `(%r8,%r15,8)`.
+fn pop_slot(load:Vec<String>) -> Vec<String> {
+    let mut lines = vec![
+        "testq %r15, %r15".to_string(),
+        "jz bc_error_pop_empty_stack".to_string(),
+        "decq %r15".to_string(),
+        "leaq bc_stack(%rip), %r8".to_string(),
+    ];
+    lines.extend(load);
+    lines
+}
+
+/// Stores `value`, an integer register or immediate, in the 64 bit stack `slot`. 32 bit integers
+/// are sign extended, like the VM does.
+fn to_slot(w:Width,value:&str,slot:&str) -> Vec<String> {
+    if value.starts_with('$') {
+        vec![format!("movq {}, {}",value,slot)]
+    }else if w.wide {
+        mov(w,value,slot)
+    }else {
+        vec![sign_extend(w,value,"%r11"),format!("movq %r11, {}",slot)]
+    }
+}
+
+/// Loads the integer register `src` into the 64 bit register `dest`, sign extending 32 bit integers.
+fn sign_extend(w:Width,src:&str,dest:&str) -> String {
+    format!("{} {}, {}",if w.wide { "movq" } else { "movslq" },src,dest)
+}
+
+/// Whether `operand` is in memory, like `r8`-`r15` and stack slots. x86 instructions take at most one.
+fn is_memory(operand:&str) -> bool {
+    operand.contains('(')
+}
+
+/// `mov src, dest`, through `%r11` when both are in memory.
+fn mov(w:Width,src:&str,dest:&str) -> Vec<String> {
+    let op = w.int("mov");
+    if is_memory(src) && is_memory(dest) {
+        let scratch = w.r("%r11");
+        vec![format!("{} {}, {}",op,src,scratch),format!("{} {}, {}",op,scratch,dest)]
+    }else {
+        vec![format!("{} {}, {}",op,src,dest)]
+    }
+}
+
+/// `op src, dest`, through `%r11` when `dest` is in memory, as `src` can be too and `imul`,
+/// `cmov` and `movzb` only write registers. The `mov` back leaves the flags `op` set alone.
+fn update(w:Width,op:&str,src:&str,dest:&str) -> Vec<String> {
+    if is_memory(dest) {
+        let (mov,scratch) = (w.int("mov"),w.r("%r11"));
+        vec![format!("{} {}, {}",mov,dest,scratch),format!("{} {}, {}",op,src,scratch),format!("{} {}, {}",mov,scratch,dest)]
+    }else {
+        vec![format!("{} {}, {}",op,src,dest)]
+    }
+}
+
+/// Runs `op`, which leaves its result in `dest`, and sets the flags from it, stopping with the message
+/// at `overflow` when the program asked for trapping arithmetic. `imul` sets both overflow and carry on
+/// signed overflow, like the VM, but leaves the zero and sign flags undefined, so those come from a `cmp`.
+fn arithmetic(w:Width,mut lines:Vec<String>,dest:&str,overflow:&str) -> Vec<String> {
+    lines.extend([
+        format!("seto bc_flags+{}(%rip)",OVERFLOW_FLAG),
+        format!("setc bc_flags+{}(%rip)",CARRY_FLAG),
+    ]);
+    lines.extend(result_flags(w,dest));
+    lines.extend([
+        format!("cmpb $0, bc_flags+{}(%rip)",OVERFLOW_FLAG),
+        "je 1f".to_string(),
+        "cmpb $0, bc_trap_overflow(%rip)".to_string(),
+        "je 1f".to_string(),
+    ]);
+    lines.extend(raise(ERROR_OVERFLOW,overflow));
+    lines.push("1:".to_string());
+    lines
+}
+
+/// Stops with error `code` and the message at `message`, or goes to the handler of the last `@try`.
+fn raise(code:RegisterDataType,message:&str) -> Vec<String> {
+    vec![format!("movl ${}, %r11d",code),format!("leaq {}(%rip), %rdi",message),"jmp bc_raise".to_string()]
+}
+
+/// Same as `raise` with a `%ld` format at `message` and the numbers in `%rsi` and `%r9`.
+fn raise_with_number(code:RegisterDataType,message:&str) -> Vec<String> {
+    vec![format!("movl ${}, %r11d",code),format!("leaq {}(%rip), %rdi",message),"jmp bc_raise_with_number".to_string()]
+}
+
+/// Forgets the handlers of the `@try`s run in the routine a `ret` or `tailcall` leaves, which saw
+/// the `%rsp` it has now.
+fn drop_handlers(has_try:bool) -> Vec<String> {
+    if has_try {
+        vec!["movq %rsp, %rdi".to_string(),"call bc_drop_handlers".to_string()]
+    }else {
+        Vec::new()
+    }
+}
+
+/// Sets the zero and sign flags from `dest`.
+fn result_flags(w:Width,dest:&str) -> Vec<String> {
+    vec![
+        format!("{} $0, {}",w.int("cmp"),dest),
+        format!("setz bc_flags+{}(%rip)",ZERO_FLAG),
+        format!("sets bc_flags+{}(%rip)",SIGN_FLAG),
+    ]
+}
+
+/// Sets the flags after `lines`, a logic, shift or rotate instruction leaving its result in `dest`.
+/// These never overflow or carry.
+fn logic(w:Width,mut lines:Vec<String>,dest:&str) -> Vec<String> {
+    lines.extend(result_flags(w,dest));
+    lines.push(format!("movb $0, bc_flags+{}(%rip)",OVERFLOW_FLAG));
+    lines.push(format!("movb $0, bc_flags+{}(%rip)",CARRY_FLAG));
+    lines
+}
+
+/// `idiv` works on `%rdx:%rax`, which hold `rax` and `rdx`, so both are kept in scratch registers meanwhile.
+/// `divisor` is a register or an immediate. `result` is `%rax` for the quotient and `%rdx` for the remainder.
+/// `by_zero` and `overflow` are the messages to stop with. `INT_MIN / -1`, the one dividend `neg` overflows
+/// on, only stops in trapping mode and wraps to `INT_MIN` with a remainder of 0 otherwise, which `idiv`
+/// cannot do by itself.
+fn divide(w:Width,a:&str,divisor:&str,result:&str,by_zero:&str,overflow:&str) -> Vec<String> {
+    let (mov,r8,r9,r10) = (w.int("mov"),w.r("%r8"),w.r("%r9"),w.r("%r10"));
+    let mut lines = vec![
+        format!("{} {}, {}",mov,divisor,r9),
+        format!("{} {}, {}",w.int("test"),r9,r9),
+        "jnz 1f".to_string(),
+    ];
+    lines.extend(raise(ERROR_DIVISION_BY_ZERO,by_zero));
+    lines.extend([
+        "1:".to_string(),
+        format!("{} {}, {}",mov,a,r8),
+        format!("{} $-1, {}",w.int("cmp"),r9),
+        "jne 2f".to_string(),
+        format!("{} {}, {}",mov,r8,r10),
+        format!("{} {}",w.int("neg"),r10),
+        "jno 2f".to_string(),
+        "cmpb $0, bc_trap_overflow(%rip)".to_string(),
+        "je 3f".to_string(),
+    ]);
+    lines.extend(raise(ERROR_OVERFLOW,overflow));
+    lines.push("3:".to_string());
+    if result == "%rdx" {
+        lines.push("xorl %r8d, %r8d".to_string());
+    }
+    lines.extend([
+        "movb $1, %r9b".to_string(),
+        "jmp 4f".to_string(),
+        "2:".to_string(),
+        "movq %rax, %r10".to_string(),
+        "movq %rdx, %r11".to_string(),
+        format!("{} {}, {}",mov,r8,w.r("%rax")),
+        if w.wide { "cqto" } else { "cltd" }.to_string(),
+        format!("{} {}",w.int("idiv"),r9),
+        format!("{} {}, {}",mov,w.r(result),r8),
+        "movq %r10, %rax".to_string(),
+        "movq %r11, %rdx".to_string(),
+        "xorl %r9d, %r9d".to_string(),
+        "4:".to_string(),
+        format!("movb %r9b, bc_flags+{}(%rip)",OVERFLOW_FLAG),
+        format!("movb %r9b, bc_flags+{}(%rip)",CARRY_FLAG),
+        format!("{} {}, {}",mov,r8,a),
+        format!("{} {}, {}",w.int("test"),r8,r8),
+        format!("setz bc_flags+{}(%rip)",ZERO_FLAG),
+        format!("sets bc_flags+{}(%rip)",SIGN_FLAG),
+    ]);
+    lines
+}
+
+/// x86 only looks at the low 5 (or 6) bits of a shift count, which is what rotates need, while the VM
+/// shifts every bit out for counts of the register width or more.
+/// Register counts have to be in `%cl`, which belongs to `rcx`, so `%rcx` is kept in `%r10` meanwhile.
+fn shift_by_register(w:Width,a:&str,b:&str,mnemonic:&str) -> Vec<String> {
+    let bits = if w.wide { 64 } else { 32 };
+    let (mov,r8,r9,r11) = (w.int("mov"),w.r("%r8"),w.r("%r9"),w.r("%r11"));
+    let mut lines = vec![
+        format!("{} {}, {}",mov,b,r9),
+        format!("{} {}, {}",mov,a,r8),
+    ];
+    if mnemonic == "sar" {
+        lines.push(format!("{} ${}, {}",mov,bits-1,r11));
+        lines.push(format!("{} ${}, {}",w.int("cmp"),bits,r9));
+        lines.push(format!("{} {}, {}",w.int("cmovae"),r11,r9));
+    }
+    lines.push("movq %rcx, %r10".to_string());
+    lines.push("movq %r9, %rcx".to_string());
+    lines.push(format!("{} %cl, {}",w.int(mnemonic),r8));
+    lines.push("movq %r10, %rcx".to_string());
+    if mnemonic == "shl" || mnemonic == "shr" {
+        lines.push("xorl %r11d, %r11d".to_string());
+        lines.push(format!("{} ${}, {}",w.int("cmp"),bits,r9));
+        lines.push(format!("{} {}, {}",w.int("cmovae"),r11,r8));
+    }
+    lines.push(format!("{} {}, {}",mov,r8,a));
+    lines
+}
+
+fn shift_by_immediate(w:Width,a:&str,count:iInstructionParamType,mnemonic:&str) -> Vec<String> {
+    let bits = if w.wide { 64 } else { 32 };
+    let count = count as u32;
+    match mnemonic {
+        "shl" | "shr" if count >= bits => vec![format!("{} $0, {}",w.int("mov"),a)],
+        "sar" if count >= bits => vec![format!("{} ${}, {}",w.int("sar"),bits-1,a)],
+        _ => vec![format!("{} ${}, {}",w.int(mnemonic),count % bits,a)],
+    }
+}
+
+/// Sets the flags like the VM's `Compare`. `b` is a register or an immediate.
+/// With `unsigned` the less and greater flags follow the unsigned order, like `CompareUnsigned`.
+fn compare(w:Width,a:&str,b:&str,unsigned:bool) -> Vec<String> {
+    let (less,greater) = if unsigned { ("setb","seta") } else { ("setl","setg") };
+    let scratch = w.r("%r11");
+    let (mut lines,a) = if is_memory(a) && is_memory(b) { (mov(w,a,&scratch),scratch.as_str()) } else { (Vec::new(),a) };
+    lines.extend([
+        format!("{} {}, {}",w.int("cmp"),b,a),
+        format!("setz bc_flags+{}(%rip)",ZERO_FLAG),
+        format!("sets bc_flags+{}(%rip)",SIGN_FLAG),
+        format!("seto bc_flags+{}(%rip)",OVERFLOW_FLAG),
+        format!("setc bc_flags+{}(%rip)",CARRY_FLAG),
+        format!("sete bc_flags+{}(%rip)",EQUAL_FLAG),
+        format!("{} bc_flags+{}(%rip)",less,LESS_THAN_FLAG),
+        format!("{} bc_flags+{}(%rip)",greater,GREATER_THAN_FLAG),
+        format!("setb bc_flags+{}(%rip)",BELOW_FLAG),
+        format!("seta bc_flags+{}(%rip)",ABOVE_FLAG),
+    ]);
+    lines
+}
+
+/// Converts the float in `src` to an integer in `dest`, rounding towards zero.
+/// `cvttss2si` gives `INT_MIN` for NaN and out of range values, while the VM saturates and
+/// turns NaN into 0. `INT_MIN` is the one integer `neg` overflows on and `not` turns into `INT_MAX`.
+fn float_to_int(w:Width,dest:&str,src:&str) -> Vec<String> {
+    let (mov,r8,r9) = (w.int("mov"),w.r("%r8"),w.r("%r9"));
+    vec![
+        format!("{}2si {}, {}",w.float("cvtts"),src,r8),
+        format!("{} {}, {}",mov,r8,r9),
+        format!("{} {}",w.int("neg"),r9),
+        "jno 1f".to_string(),
+        format!("{} {}, {}",w.float("ucomis"),src,src),
+        "jp 2f".to_string(),
+        format!("{} {}, %r9d",w.float("movmskp"),src),
+        "testl $1, %r9d".to_string(),
+        "jnz 1f".to_string(),
+        format!("{} {}",w.int("not"),r8),
+        "jmp 1f".to_string(),
+        "2:".to_string(),
+        "xorl %r8d, %r8d".to_string(),
+        "1:".to_string(),
+        format!("{} {}, {}",mov,r8,dest),
+    ]
+}
+
+/// Same as `divide` with `div`, which cannot overflow.
+fn divide_unsigned(w:Width,a:&str,divisor:&str,result:&str,by_zero:&str) -> Vec<String> {
+    let (mov,r8,r9) = (w.int("mov"),w.r("%r8"),w.r("%r9"));
+    let mut lines = vec![
+        format!("{} {}, {}",mov,divisor,r9),
+        format!("{} {}, {}",w.int("test"),r9,r9),
+        "jnz 1f".to_string(),
+    ];
+    lines.extend(raise(ERROR_DIVISION_BY_ZERO,by_zero));
+    lines.extend([
+        "1:".to_string(),
+        format!("{} {}, {}",mov,a,r8),
+        "movq %rax, %r10".to_string(),
+        "movq %rdx, %r11".to_string(),
+        format!("{} {}, {}",mov,r8,w.r("%rax")),
+        "xorl %edx, %edx".to_string(),
+        format!("{} {}",w.int("div"),r9),
+        format!("{} {}, {}",mov,w.r(result),r8),
+        "movq %r10, %rax".to_string(),
+        "movq %r11, %rdx".to_string(),
+        format!("{} {}, {}",mov,r8,a),
+    ]);
+    lines
+}
+
+/// Leaves the VM's stack pointer in `%r9`. It is `%r15`, the number of slots on the stack, less the
+/// slots `stkcpybacksp` added past it, which only programs using `stkcpybacksp` keep count of.
+fn stack_pointer(has_gap:bool) -> Vec<String> {
+    let mut lines = vec!["movq %r15, %r9".to_string()];
+    if has_gap {
+        lines.push("subq bc_sp_gap(%rip), %r9".to_string());
+    }
+    lines
+}
+
+/// Leaves the stack index `sp - offset` in `%r9`.
+fn stack_pointer_offset(w:Width,offset:&str,has_gap:bool) -> Vec<String> {
+    let mut lines = vec![sign_extend(w,offset,"%r8")];
+    lines.extend(stack_pointer(has_gap));
+    lines.push("subq %r8, %r9".to_string());
+    lines
+}
+
+/// Checks that the stack index `index` leaves in %r9 is on the stack, or stops with the `%ld`
+/// format at `message`. Leaves the stack's address in %r10.
+fn stack_index(mut index:Vec<String>,message:&str) -> Vec<String> {
+    index.extend([
+        "cmpq %r15, %r9".to_string(),
+        "jb 1f".to_string(),
+        "movq %r9, %rsi".to_string(),
+    ]);
+    index.extend(raise_with_number(ERROR_STACK,message));
+    index.extend(["1:".to_string(),"leaq bc_stack(%rip), %r10".to_string()]);
+    index
+}
+
+/// Adds the `%ld` format `message` for `raise_with_number` to `messages` and returns its label.
+fn number_message(messages:&mut Vec<String>,message:&str,location:&str) -> String {
+    messages.push(format!("Runtime Error: {} {}\n",message,location.replace('%',"%%")));
+    format!(".Lmessage{}",messages.len()-1)
+}
+
+/// Leaves the memory unit whose id is in the register `id` in `%r8` and the id in `%rdi`, or stops
+/// with the format at `missing` when there is no such unit.
+fn memory_unit(w:Width,id:&str,missing:&str) -> Vec<String> {
+    let mut lines = vec![
+        sign_extend(w,id,"%rdi"),
+        "call bc_memory_unit".to_string(),
+        "testq %r8, %r8".to_string(),
+        "jnz 5f".to_string(),
+        "movq %rdi, %rsi".to_string(),
+    ];
+    lines.extend(raise_with_number(ERROR_MEMORY,missing));
+    lines.push("5:".to_string());
+    lines
+}
+
+/// Stops with the format at `message`, which takes the offset in `%r9` and then the id in `%rdi`.
+fn memory_error(message:&str) -> Vec<String> {
+    let mut lines = vec!["movq %r9, %rsi".to_string(),"movq %rdi, %r9".to_string()];
+    lines.extend(raise_with_number(ERROR_MEMORY,message));
+    lines
+}
+
+/// Same as `memory_unit` for the `width` bytes at the offset in the register `offset`, stopping with the
+/// format at `outside` when they are not all in the unit. Also leaves the offset in `%r9` and the
+/// address of the unit's set flags in `%r10`. Bytes of a unit follow each other, so byte `n` is at `8 + n`.
+fn memory_bytes(w:Width,id:&str,offset:&str,width:usize,missing:&str,outside:&str) -> Vec<String> {
+    let cell_bytes = if w.wide { 8 } else { 4 };
+    let mut lines = memory_unit(w,id,missing);
+    lines.extend([
+        sign_extend(w,offset,"%r9"),
+        "movq (%r8), %r10".to_string(),
+        format!("leaq 8(%r8,%r10,{}), %r11",cell_bytes),
+        format!("imulq ${}, %r10",cell_bytes),
+        "testq %r9, %r9".to_string(),
+        "js 6f".to_string(),
+        format!("leaq {}(%r9), %rsi",width),
+        "cmpq %r10, %rsi".to_string(),
+        "jbe 7f".to_string(),
+        // The VM reports the first byte past the unit.
+        "movq %r9, %rsi".to_string(),
+        "cmpq %r10, %rsi".to_string(),
+        "cmovlq %r10, %rsi".to_string(),
+        "jmp 8f".to_string(),
+        "6:".to_string(),
+        "movq %r9, %rsi".to_string(),
+        "8:".to_string(),
+        "movq %rdi, %r9".to_string(),
+    ]);
+    lines.extend(raise_with_number(ERROR_MEMORY,outside));
+    lines.extend(["7:".to_string(),"movq %r11, %r10".to_string()]);
+    lines
+}
+
+/// ORs the flags a condition tests into %esi and tests it. Also returns whether the condition
+/// is negated, so holds when %esi is 0.
+fn x86_condition(condition:InstructionParamType) -> Result<(Vec<String>,bool),String> {
+    let (flags,negated) = condition_flags(condition).ok_or(format!("Condition {} does not exist.",condition))?;
+    let mut lines = vec![format!("movzbl bc_flags+{}(%rip), %esi",flags[0])];
+    for flag in flags[1..].iter() {
+        lines.push(format!("orb bc_flags+{}(%rip), %sil",flag));
+    }
+    lines.push("testl %esi, %esi".to_string());
+    Ok((lines,negated))
+}
+
+fn jump_if_flag(flag:usize,value:u8,jump:&str,inst:&Instruction) -> Result<Vec<String>,String> {
+    Ok(vec![
+        format!("cmpb ${}, bc_flags+{}(%rip)",value,flag),
+        format!("{} .L{}",jump,get_jump_destination(inst)?.unwrap()),
+    ])
+}
+
+fn get_jump_destination(inst:&Instruction) -> Result<Option<usize>,String> {
+    use Instruction::*;
+    match inst {
+        Jump(s) | Call(s) | TailCall(s) | Loop(_,s) |
+            JumpIfZero(s) | JumpIfNotZero(s) |
+            JumpIfEqual(s) | JumpIfNotEqual(s) |
+            JumpIfGreater(s) | JumpIfLess(s) |
+            JumpIfGreaterOrEqual(s) | JumpIfLessOrEqual(s) |
+            JumpIfAbove(s) | JumpIfBelow(s) | JumpIfAboveOrEqual(s) | JumpIfBelowOrEqual(s) |
+            JumpIfOverflow(s) | JumpIfNotOverflow(s) | JumpIfCarry(s) | JumpIfNotCarry(s) |
+            LoadAddress(_,s) | Try(s) => {
+                match s {
+                    StringNumberUnion::Num(n) => Ok(Some(*n as usize)),
+                    StringNumberUnion::String(s) => Err(format!("Label {:?} has not been resolved to an instruction number.",s)),
+                }
+            }
+        _ => Ok(None)
+    }
+}
+
+/// Runtime errors as (label, handler, message). The messages follow the ones the VM prints.
+const RUNTIME_ERRORS: &[(&str,&str,&str)] = &[
+    ("bc_error_stack_overflow","bc_panic","the bytecode stack is full"),
+    ("bc_error_too_many_handlers","bc_panic","too many nested @try"),
+    ("bc_error_out_of_memory","bc_panic","out of memory"),
+    ("bc_error_return_without_call","bc_panic","Could not return as last address is not set"),
+    ("bc_error_extend_stack","bc_panic","Cannot extend stack by negative number"),
+    ("bc_error_truncate_stack_range","bc_panic","range out of bounds of the stack"),
+    ("bc_error_character_range","bc_panic","character code out of range"),
+    ("bc_error_write_range","bc_panic","attempt to subtract with overflow"),
+    ("bc_error_stack_empty","bc_panic","Stack is empty"),
+    ("bc_error_stack_location","bc_panic","stack location out of bounds"),
+    ("bc_error_get_flag","bc_fail_with_number","Runtime Error: Could not get flag number %ld as it does not exist.\n"),
+    ("bc_error_invalid_character","bc_fail_with_number","Run Time Error: Cannot get character from number %ld\n"),
+];
+
+/// Runtime errors a `@try` can catch, as (label, error code, message).
+const CATCHABLE_ERRORS: &[(&str,RegisterDataType,&str)] = &[
+    ("bc_error_pop_empty_stack",ERROR_STACK,"Runtime Error: Stack cannot be popped from as stack is empty."),
+];
+
+const X86_HEADER: &str = "# Generated by bytecode. Assemble and link with: cc <file>.s -o <program>";
+
+/// Sets up the VM's initial state. A return with no call left lands on `bc_error_return_without_call`.
+const X86_START: &str = r#"    xorl %eax, %eax
+    xorl %ebx, %ebx
+    xorl %ecx, %ecx
+    xorl %edx, %edx
+    xorl %r12d, %r12d
+    xorl %r13d, %r13d
+    xorl %r14d, %r14d
+    xorl %r15d, %r15d
+    xorl %ebp, %ebp
+    xorps %xmm8, %xmm8
+    xorps %xmm9, %xmm9
+    xorps %xmm10, %xmm10
+    xorps %xmm11, %xmm11
+    xorps %xmm12, %xmm12
+    xorps %xmm1, %xmm1
+    xorps %xmm2, %xmm2
+    xorps %xmm3, %xmm3
+    xorps %xmm4, %xmm4
+    xorps %xmm5, %xmm5
+    xorps %xmm6, %xmm6
+    xorps %xmm7, %xmm7
+    xorps %xmm14, %xmm14
+    leaq bc_error_return_without_call(%rip), %r8
+    pushq %r8
+"#;
+
+/// Runtime shared by every translated program.
+///
+/// Functions called from generated code keep every register holding VM state intact. They save the
+/// caller saved ones with `BC_SAVE` (which also aligns the stack for libc) and restore them with `BC_RESTORE`.
+/// Arguments are passed in `%rdi`, `%rsi` and `%xmm0`.
+const X86_RUNTIME: &str = r#"
+    .macro BC_SAVE
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $320, %rsp
+    andq $-16, %rsp
+    movq %rax, -8(%rbp)
+    movq %rcx, -16(%rbp)
+    movq %rdx, -24(%rbp)
+    movq %xmm8, -32(%rbp)
+    movq %xmm9, -40(%rbp)
+    movq %xmm10, -48(%rbp)
+    movq %xmm11, -56(%rbp)
+    movq %xmm12, -64(%rbp)
+    movq %xmm1, -264(%rbp)
+    movq %xmm2, -272(%rbp)
+    movq %xmm3, -280(%rbp)
+    movq %xmm4, -288(%rbp)
+    movq %xmm5, -296(%rbp)
+    movq %xmm6, -304(%rbp)
+    movq %xmm7, -312(%rbp)
+    movq %xmm14, -320(%rbp)
+    .endm
+
+    .macro BC_RESTORE
+    movq -8(%rbp), %rax
+    movq -16(%rbp), %rcx
+    movq -24(%rbp), %rdx
+    movq -32(%rbp), %xmm8
+    movq -40(%rbp), %xmm9
+    movq -48(%rbp), %xmm10
+    movq -56(%rbp), %xmm11
+    movq -64(%rbp), %xmm12
+    movq -264(%rbp), %xmm1
+    movq -272(%rbp), %xmm2
+    movq -280(%rbp), %xmm3
+    movq -288(%rbp), %xmm4
+    movq -296(%rbp), %xmm5
+    movq -304(%rbp), %xmm6
+    movq -312(%rbp), %xmm7
+    movq -320(%rbp), %xmm14
+    leave
+    .endm
+
+bc_halt:
+    andq $-16, %rsp
+    xorl %edi, %edi
+    call exit@PLT
+
+# Prints the message in %rdi to stderr and exits with 101, like a panic in the VM.
+bc_panic:
+    andq $-16, %rsp
+    pushq %rdi
+    pushq %rdi
+    xorl %edi, %edi
+    call fflush@PLT
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rdi
+    leaq bc_panic_format(%rip), %rsi
+    movq (%rsp), %rdx
+    xorl %eax, %eax
+    call fprintf@PLT
+    movl $101, %edi
+    call exit@PLT
+
+# Prints the message in %rdi to stdout and exits with 1.
+bc_fail:
+    andq $-16, %rsp
+    call puts@PLT
+    movl $1, %edi
+    call exit@PLT
+
+# Prints the format in %rdi with the numbers in %rsi and %r9 to stdout and exits with 1.
+bc_fail_with_number:
+    andq $-16, %rsp
+    movq %r9, %rdx
+    xorl %eax, %eax
+    call printf@PLT
+    movl $1, %edi
+    call exit@PLT
+
+# Raises error %r11 with the message in %rdi: goes to the handler of the last `@try`, or prints
+# the message and exits with 1 when there is none.
+bc_raise:
+    cmpq $0, bc_handler_count(%rip)
+    jne bc_catch
+    jmp bc_fail
+
+# Same as bc_raise with the format in %rdi and the numbers in %rsi and %r9.
+bc_raise_with_number:
+    cmpq $0, bc_handler_count(%rip)
+    jne bc_catch
+    jmp bc_fail_with_number
+
+# Handlers of the `@try`s that have not reached their `@endtry`, innermost last, are 48 bytes in
+# bc_handlers: the address of the handler, %rsp at the `@try`, a copy of the stack, %r15, %rbp and bc_sp_gap.
+# A native stack below the saved %rsp holds the return addresses of calls made since.
+
+# Pushes a handler for the code at %rdi.
+bc_try:
+    movq bc_handler_count(%rip), %r8
+    cmpq $BC_HANDLER_CAPACITY, %r8
+    jae bc_error_too_many_handlers
+    imulq $48, %r8, %r8
+    leaq bc_handlers(%rip), %r9
+    addq %r9, %r8
+    movq %rdi, (%r8)
+    leaq 8(%rsp), %r9
+    movq %r9, 8(%r8)
+    movq %r15, 24(%r8)
+    movq %rbp, 32(%r8)
+    movq bc_sp_gap(%rip), %r9
+    movq %r9, 40(%r8)
+    BC_SAVE
+    movq %r8, -72(%rbp)
+    leaq 8(,%r15,8), %rdi
+    call malloc@PLT
+    testq %rax, %rax
+    jz bc_error_out_of_memory
+    movq -72(%rbp), %r8
+    movq %rax, 16(%r8)
+    movq %rax, %rdi
+    leaq bc_stack(%rip), %rsi
+    leaq (,%r15,8), %rdx
+    call memcpy@PLT
+    incq bc_handler_count(%rip)
+    BC_RESTORE
+    ret
+
+# Drops the last handler and its copy of the stack.
+bc_pop_handler:
+    BC_SAVE
+    decq bc_handler_count(%rip)
+    movq bc_handler_count(%rip), %r8
+    imulq $48, %r8, %r8
+    leaq bc_handlers(%rip), %r9
+    movq 16(%r9,%r8), %rdi
+    call free@PLT
+    BC_RESTORE
+    ret
+
+# Drops the handlers of the `@try`s run with %rsp at or below %rdi.
+bc_drop_handlers:
+    movq bc_handler_count(%rip), %r8
+    testq %r8, %r8
+    jz 1f
+    decq %r8
+    imulq $48, %r8, %r8
+    leaq bc_handlers(%rip), %r9
+    cmpq %rdi, 8(%r9,%r8)
+    ja 1f
+    pushq %rdi
+    call bc_pop_handler
+    popq %rdi
+    jmp bc_drop_handlers
+1:
+    ret
+
+# Puts the stack, %r15, %rbp, bc_sp_gap and %rsp back to how they were at the last `@try` and goes on at
+# its handler with the error code from %r11 in %rax. The `ret` at the end jumps to the handler.
+bc_catch:
+    movq bc_handler_count(%rip), %r8
+    decq %r8
+    imulq $48, %r8, %r8
+    leaq bc_handlers(%rip), %r9
+    addq %r9, %r8
+    movq 8(%r8), %rsp
+    movq 24(%r8), %r15
+    movq 32(%r8), %rbp
+    movq 40(%r8), %r9
+    movq %r9, bc_sp_gap(%rip)
+    movq %r11, %rax
+    pushq (%r8)
+    BC_SAVE
+    leaq bc_stack(%rip), %rdi
+    movq 16(%r8), %rsi
+    leaq (,%r15,8), %rdx
+    call memcpy@PLT
+    call bc_pop_handler
+    BC_RESTORE
+    ret
+
+# Memory units are blocks of the number of cells, the cells of BC_CELL_BYTES bytes and a byte per cell
+# that is 1 once the cell is set. Ids are never reused, so bc_units holds a unit for every id given out,
+# or 0 once it is freed.
+
+# Leaves the unit with the id in %rdi in %r8, or 0 when there is none.
+bc_memory_unit:
+    xorl %r8d, %r8d
+    cmpq bc_unit_count(%rip), %rdi
+    jae 1f
+    movq bc_units(%rip), %r8
+    movq (%r8,%rdi,8), %r8
+1:
+    ret
+
+# Creates a unit of %rdi cells and leaves its id in %r9. The VM cannot allocate more than a
+# negative number of cells either.
+bc_malloc:
+    movabsq $0x10000000000, %r8
+    cmpq %r8, %rdi
+    jae bc_error_out_of_memory
+    BC_SAVE
+    movq %rdi, -72(%rbp)
+    movq bc_unit_count(%rip), %rax
+    cmpq bc_unit_capacity(%rip), %rax
+    jb 1f
+    leaq 16(%rax,%rax), %rsi
+    movq %rsi, bc_unit_capacity(%rip)
+    shlq $3, %rsi
+    movq bc_units(%rip), %rdi
+    call realloc@PLT
+    testq %rax, %rax
+    jz bc_error_out_of_memory
+    movq %rax, bc_units(%rip)
+1:
+    movq -72(%rbp), %rdi
+    imulq $BC_CELL_BYTES+1, %rdi, %rdi
+    addq $8, %rdi
+    movl $1, %esi
+    call calloc@PLT
+    testq %rax, %rax
+    jz bc_error_out_of_memory
+    movq -72(%rbp), %rcx
+    movq %rcx, (%rax)
+    movq bc_unit_count(%rip), %r9
+    movq bc_units(%rip), %rcx
+    movq %rax, (%rcx,%r9,8)
+    incq bc_unit_count(%rip)
+    BC_RESTORE
+    ret
+
+# Frees the unit with the id in %rdi, which exists.
+bc_free:
+    BC_SAVE
+    movq bc_units(%rip), %rcx
+    leaq (%rcx,%rdi,8), %rcx
+    movq (%rcx), %rdi
+    movq $0, (%rcx)
+    call free@PLT
+    BC_RESTORE
+    ret
+
+# Copies the stack slots [sp - %r8, sp - %r9) to sp - %r10 onwards, first adding zeroed slots past the
+# end of the stack when the copy does not fit, like the VM. Those count in %r15 but not in sp.
+bc_stack_copy_back_sp:
+    movq %r15, %r11
+    subq bc_sp_gap(%rip), %r11
+    testq %r11, %r11
+    jz bc_error_stack_empty
+    cmpq %r11, %r8
+    ja bc_error_stack_location
+    cmpq %r11, %r9
+    ja bc_error_stack_location
+    cmpq %r11, %r10
+    ja bc_error_stack_location
+    negq %r8
+    addq %r11, %r8
+    negq %r9
+    addq %r11, %r9
+    negq %r10
+    addq %r11, %r10
+    subq %r8, %r9
+    jb bc_error_stack_location
+    movq %r9, %rdi
+    subq bc_sp_gap(%rip), %rdi
+    decq %rdi
+    jle 2f
+    leaq (%r15,%rdi), %rsi
+    cmpq $BC_STACK_CAPACITY, %rsi
+    ja bc_error_stack_overflow
+    addq %rdi, bc_sp_gap(%rip)
+    leaq bc_stack(%rip), %rsi
+1:
+    movq $0, (%rsi,%r15,8)
+    incq %r15
+    decq %rdi
+    jnz 1b
+2:
+    leaq (%r10,%r9), %rsi
+    cmpq %r15, %rsi
+    ja bc_error_stack_location
+    BC_SAVE
+    leaq bc_stack(%rip), %rsi
+    leaq (%rsi,%r10,8), %rdi
+    leaq (%rsi,%r8,8), %rsi
+    leaq (,%r9,8), %rdx
+    call memmove@PLT
+    BC_RESTORE
+    ret
+
+# Calls the function of X86_MATH at %rdi with the doubles in %xmm0 and %xmm13, leaving its result in %xmm0.
+bc_float_math:
+    BC_SAVE
+    movapd %xmm13, %xmm1
+    call *%rdi
+    BC_RESTORE
+    ret
+
+bc_display:
+    BC_SAVE
+    movq %rdi, %rsi
+    leaq bc_display_format(%rip), %rdi
+    xorl %eax, %eax
+    call printf@PLT
+    BC_RESTORE
+    ret
+
+bc_display_char:
+    movq %rdi, %r8
+    shrq $32, %r8
+    jnz bc_error_character_range
+    movq %rdi, %rsi
+    cmpl $0x10ffff, %edi
+    ja bc_error_invalid_character
+    movl %edi, %r8d
+    andl $0xfffff800, %r8d
+    cmpl $0xd800, %r8d
+    je bc_error_invalid_character
+    BC_SAVE
+    call bc_put_utf8
+    BC_RESTORE
+    ret
+
+# Prints the stack values in [%esi - %edi, %esi) as characters, values that do not fit in a byte become 0.
+bc_write:
+    BC_SAVE
+    movslq %edi, %rdi
+    movslq %esi, %rsi
+    cmpq %rsi, %rdi
+    ja bc_error_write_range
+    cmpq %r15, %rsi
+    ja 2f
+    movq %rsi, -80(%rbp)
+    subq %rdi, %rsi
+    movq %rsi, -72(%rbp)
+1:
+    movq -72(%rbp), %rax
+    cmpq -80(%rbp), %rax
+    jae 2f
+    leaq bc_stack(%rip), %rcx
+    movq (%rcx,%rax,8), %rdi
+    cmpq $255, %rdi
+    jbe 3f
+    xorl %edi, %edi
+3:
+    call bc_put_utf8
+    incq -72(%rbp)
+    jmp 1b
+2:
+    xorl %edi, %edi
+    call fflush@PLT
+    BC_RESTORE
+    ret
+
+# Writes the code point in %edi to stdout as UTF-8. Follows the C calling convention.
+bc_put_utf8:
+    subq $24, %rsp
+    cmpl $0x80, %edi
+    jae 1f
+    movb %dil, (%rsp)
+    movl $1, %edx
+    jmp 4f
+1:
+    cmpl $0x800, %edi
+    jae 2f
+    movl %edi, %eax
+    shrl $6, %eax
+    orl $0xc0, %eax
+    movb %al, (%rsp)
+    movl $2, %edx
+    jmp 5f
+2:
+    cmpl $0x10000, %edi
+    jae 3f
+    movl %edi, %eax
+    shrl $12, %eax
+    orl $0xe0, %eax
+    movb %al, (%rsp)
+    movl %edi, %eax
+    shrl $6, %eax
+    andl $0x3f, %eax
+    orl $0x80, %eax
+    movb %al, 1(%rsp)
+    movl $3, %edx
+    jmp 5f
+3:
+    movl %edi, %eax
+    shrl $18, %eax
+    orl $0xf0, %eax
+    movb %al, (%rsp)
+    movl %edi, %eax
+    shrl $12, %eax
+    andl $0x3f, %eax
+    orl $0x80, %eax
+    movb %al, 1(%rsp)
+    movl %edi, %eax
+    shrl $6, %eax
+    andl $0x3f, %eax
+    orl $0x80, %eax
+    movb %al, 2(%rsp)
+    movl $4, %edx
+5:
+    movl %edi, %eax
+    andl $0x3f, %eax
+    orl $0x80, %eax
+    movb %al, -1(%rsp,%rdx)
+4:
+    movq %rsp, %rdi
+    movl $1, %esi
+    movq stdout@GOTPCREL(%rip), %rcx
+    movq (%rcx), %rcx
+    call fwrite@PLT
+    addq $24, %rsp
+    ret
+
+# Prints the float in %xmm0 the way Rust's `{:?}` does: the shortest digits that read back as the
+# same float, in scientific notation only for very small or very large numbers. bc_display_double
+# does the same for the double in %xmm0.
+# Frame: -72 the number as a double, -76 precision, -80 sign, -84 digit count, -88 exponent,
+#        -92 a precision that is always enough, -96 1 for doubles,
+#        -160 snprintf output, -192 digits, -256 text to print.
+bc_display_float:
+    BC_SAVE
+    cvtss2sd %xmm0, %xmm0
+    movl $9, -92(%rbp)
+    movl $0, -96(%rbp)
+    jmp .Lbc_display_number
+bc_display_double:
+    BC_SAVE
+    movl $17, -92(%rbp)
+    movl $1, -96(%rbp)
+.Lbc_display_number:
+    movsd %xmm0, -72(%rbp)
+    movq %xmm0, %rax
+    movq %rax, %rcx
+    btrq $63, %rcx
+    movabsq $0x7ff0000000000000, %rdx
+    cmpq %rdx, %rcx
+    ja .Lbc_float_nan
+    je .Lbc_float_inf
+    testq %rcx, %rcx
+    jz .Lbc_float_zero
+    movl $0, -76(%rbp)
+1:
+    leaq -160(%rbp), %rdi
+    movl $64, %esi
+    leaq bc_exponent_format(%rip), %rdx
+    movl -76(%rbp), %ecx
+    movsd -72(%rbp), %xmm0
+    movl $1, %eax
+    call snprintf@PLT
+    leaq -160(%rbp), %rdi
+    xorl %esi, %esi
+    cmpl $0, -96(%rbp)
+    jne 10f
+    call strtof@PLT
+    cvtss2sd %xmm0, %xmm0
+    jmp 11f
+10:
+    call strtod@PLT
+11:
+    ucomisd -72(%rbp), %xmm0
+    je 2f
+    incl -76(%rbp)
+    movl -76(%rbp), %eax
+    cmpl -92(%rbp), %eax
+    jb 1b
+2:
+    leaq -160(%rbp), %rsi
+    xorl %r8d, %r8d
+    cmpb $45, (%rsi)
+    jne 3f
+    movl $1, %r8d
+    incq %rsi
+3:
+    leaq -192(%rbp), %rdi
+    xorl %ecx, %ecx
+4:
+    movzbl (%rsi), %eax
+    testb %al, %al
+    jz 6f
+    cmpb $101, %al
+    je 6f
+    cmpb $48, %al
+    jb 5f
+    cmpb $57, %al
+    ja 5f
+    movb %al, (%rdi,%rcx)
+    incl %ecx
+5:
+    incq %rsi
+    jmp 4b
+6:
+    movl %r8d, -80(%rbp)
+    movl %ecx, -84(%rbp)
+    leaq 1(%rsi), %rdi
+    call atoi@PLT
+    movl %eax, -88(%rbp)
+
+    movl -84(%rbp), %ecx
+    leaq -192(%rbp), %rsi
+7:
+    cmpl $1, %ecx
+    jbe 8f
+    cmpb $48, -1(%rsi,%rcx)
+    jne 8f
+    decl %ecx
+    jmp 7b
+8:
+    leaq -256(%rbp), %rdi
+    cmpl $0, -80(%rbp)
+    je 9f
+    movb $45, (%rdi)
+    incq %rdi
+9:
+    movl -88(%rbp), %edx
+    cmpl $-4, %edx
+    jl .Lbc_float_scientific
+    cmpl $16, %edx
+    jge .Lbc_float_scientific
+    testl %edx, %edx
+    js .Lbc_float_small
+
+    # Digits before the point, padded with zeros, then the rest or a single 0.
+    xorl %eax, %eax
+1:
+    cmpl %edx, %eax
+    jg 3f
+    movl $48, %r8d
+    cmpl %ecx, %eax
+    jge 2f
+    movzbl (%rsi,%rax), %r8d
+2:
+    movb %r8b, (%rdi)
+    incq %rdi
+    incl %eax
+    jmp 1b
+3:
+    movb $46, (%rdi)
+    incq %rdi
+    cmpl %ecx, %eax
+    jl 4f
+    movb $48, (%rdi)
+    incq %rdi
+    jmp .Lbc_float_print
+4:
+    cmpl %ecx, %eax
+    jge .Lbc_float_print
+    movzbl (%rsi,%rax), %r8d
+    movb %r8b, (%rdi)
+    incq %rdi
+    incl %eax
+    jmp 4b
+
+.Lbc_float_small:
+    movb $48, (%rdi)
+    movb $46, 1(%rdi)
+    addq $2, %rdi
+    movl %edx, %eax
+    negl %eax
+    decl %eax
+1:
+    testl %eax, %eax
+    jle 2f
+    movb $48, (%rdi)
+    incq %rdi
+    decl %eax
+    jmp 1b
+2:
+    cmpl %ecx, %eax
+    jge .Lbc_float_print
+    movzbl (%rsi,%rax), %r8d
+    movb %r8b, (%rdi)
+    incq %rdi
+    incl %eax
+    jmp 2b
+
+.Lbc_float_scientific:
+    movzbl (%rsi), %r8d
+    movb %r8b, (%rdi)
+    incq %rdi
+    cmpl $1, %ecx
+    jle 2f
+    movb $46, (%rdi)
+    incq %rdi
+    movl $1, %eax
+1:
+    cmpl %ecx, %eax
+    jge 2f
+    movzbl (%rsi,%rax), %r8d
+    movb %r8b, (%rdi)
+    incq %rdi
+    incl %eax
+    jmp 1b
+2:
+    movb $0, (%rdi)
+    leaq bc_scientific_format(%rip), %rdi
+    leaq -256(%rbp), %rsi
+    xorl %eax, %eax
+    call printf@PLT
+    jmp .Lbc_float_done
+
+.Lbc_float_print:
+    movb $0, (%rdi)
+    leaq -256(%rbp), %rdi
+    call puts@PLT
+    jmp .Lbc_float_done
+.Lbc_float_nan:
+    leaq bc_nan(%rip), %rdi
+    call puts@PLT
+    jmp .Lbc_float_done
+.Lbc_float_inf:
+    leaq bc_infinity(%rip), %rdi
+    testq %rax, %rax
+    jns 1f
+    leaq bc_negative_infinity(%rip), %rdi
+1:
+    call puts@PLT
+    jmp .Lbc_float_done
+.Lbc_float_zero:
+    leaq bc_zero(%rip), %rdi
+    testq %rax, %rax
+    jns 1f
+    leaq bc_negative_zero(%rip), %rdi
+1:
+    call puts@PLT
+.Lbc_float_done:
+    BC_RESTORE
+    ret
+"#;
+
+/// Only added to programs that use `Breakpoint`. Prints the state the way `VM::dump_state` does.
+const X86_BREAKPOINT: &str = r#"
+    # Loads the register value at \from into \to sign extended from the integer size.
+    .macro BC_LOAD_SIGNED from, to
+    .if BC_CELL_BYTES == 4
+    movslq \from, \to
+    .else
+    movq \from, \to
+    .endif
+    .endm
+
+# Stops in the debugger when one is attached, which is when TracerPid in /proc/self/status is not 0,
+# and prints the state to stderr with the location in %rdi otherwise.
+bc_breakpoint:
+    BC_SAVE
+    movq %rdi, -72(%rbp)
+    xorl %edi, %edi
+    call fflush@PLT
+    leaq bc_status_path(%rip), %rdi
+    leaq bc_status_mode(%rip), %rsi
+    call fopen@PLT
+    testq %rax, %rax
+    jz 3f
+    movq %rax, -80(%rbp)
+    movq $0, -88(%rbp)
+1:
+    leaq -224(%rbp), %rdi
+    movl $128, %esi
+    movq -80(%rbp), %rdx
+    call fgets@PLT
+    testq %rax, %rax
+    jz 2f
+    leaq -224(%rbp), %rdi
+    leaq bc_tracer_pid(%rip), %rsi
+    movl $10, %edx
+    call strncmp@PLT
+    testl %eax, %eax
+    jnz 1b
+    leaq -214(%rbp), %rdi
+    call atoi@PLT
+    movl %eax, -88(%rbp)
+2:
+    movq -80(%rbp), %rdi
+    call fclose@PLT
+    cmpq $0, -88(%rbp)
+    je 3f
+    movl $5, %edi
+    call raise@PLT
+    BC_RESTORE
+    ret
+3:
+    # rax-rdx, r8-r15 and bp at -200(%rbp) onwards.
+    movq -8(%rbp), %rax
+    movq %rax, -200(%rbp)
+    movq %rbx, -192(%rbp)
+    movq -16(%rbp), %rax
+    movq %rax, -184(%rbp)
+    movq -24(%rbp), %rax
+    movq %rax, -176(%rbp)
+    xorl %ecx, %ecx
+    leaq bc_registers(%rip), %rdx
+4:
+    movq (%rdx,%rcx,8), %rax
+    movq %rax, -168(%rbp,%rcx,8)
+    incq %rcx
+    cmpq $8, %rcx
+    jb 4b
+    movq (%rbp), %rax
+    movq %rax, -104(%rbp)
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rdi
+    leaq bc_breakpoint_format(%rip), %rsi
+    movq -72(%rbp), %rdx
+    xorl %eax, %eax
+    call fprintf@PLT
+    movq $0, -88(%rbp)
+5:
+    movq -88(%rbp), %r8
+    leaq -200(%rbp,%r8,8), %rcx
+    BC_LOAD_SIGNED (%rcx), %rcx
+    leaq bc_register_names(%rip), %rdx
+    leaq (%rdx,%r8,4), %rdx
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rdi
+    leaq bc_breakpoint_register(%rip), %rsi
+    xorl %eax, %eax
+    call fprintf@PLT
+    incq -88(%rbp)
+    cmpq $13, -88(%rbp)
+    jb 5b
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rdi
+    leaq bc_breakpoint_flags(%rip), %rsi
+    xorl %eax, %eax
+    call fprintf@PLT
+    movq $0, -88(%rbp)
+6:
+    movq -88(%rbp), %r8
+    leaq bc_flag_numbers(%rip), %rcx
+    movzbl (%rcx,%r8), %ecx
+    leaq bc_flags(%rip), %rdx
+    movzbl (%rdx,%rcx), %ecx
+    leaq bc_flag_names(%rip), %rdx
+    leaq (%rdx,%r8,4), %rdx
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rdi
+    leaq bc_breakpoint_flag(%rip), %rsi
+    xorl %eax, %eax
+    call fprintf@PLT
+    incq -88(%rbp)
+    cmpq $9, -88(%rbp)
+    jb 6b
+    movq %r15, %rax
+    subq $8, %rax
+    leaq bc_breakpoint_more(%rip), %rcx
+    leaq bc_breakpoint_end(%rip), %r8
+    cmovbeq %r8, %rcx
+    movl $0, %r8d
+    cmovbeq %r8, %rax
+    movq %rax, -88(%rbp)
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rdi
+    leaq bc_breakpoint_stack(%rip), %rsi
+    movq %r15, %rdx
+    xorl %eax, %eax
+    call fprintf@PLT
+    jmp 8f
+7:
+    leaq bc_stack(%rip), %rdx
+    movq (%rdx,%rcx,8), %rdx
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rdi
+    leaq bc_breakpoint_slot(%rip), %rsi
+    xorl %eax, %eax
+    call fprintf@PLT
+    incq -88(%rbp)
+8:
+    movq -88(%rbp), %rcx
+    cmpq %r15, %rcx
+    jb 7b
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rsi
+    movl $10, %edi
+    call fputc@PLT
+    BC_RESTORE
+    ret
+
+    .section .rodata
+bc_status_path:
+    .asciz "/proc/self/status"
+bc_status_mode:
+    .asciz "r"
+bc_tracer_pid:
+    .asciz "TracerPid:"
+bc_breakpoint_format:
+    .asciz "%s\n "
+bc_breakpoint_register:
+    .asciz " %s=%ld"
+bc_breakpoint_flags:
+    .asciz "\n "
+bc_breakpoint_flag:
+    .asciz " %s=%d"
+bc_breakpoint_stack:
+    .asciz "\n  stack of %ld (top last):%s"
+bc_breakpoint_more:
+    .asciz " ..."
+bc_breakpoint_end:
+    .asciz ""
+bc_breakpoint_slot:
+    .asciz " %ld"
+# Names of 4 bytes each, in the order they are printed.
+bc_register_names:
+    .ascii "rax\0rbx\0rcx\0rdx\0r8\0\0r9\0\0r10\0r11\0r12\0r13\0r14\0r15\0bp\0\0"
+bc_flag_names:
+    .ascii "zf\0\0eqf\0gf\0\0lf\0\0bf\0\0af\0\0of\0\0cf\0\0sf\0\0"
+bc_flag_numbers:
+    .byte 0, 1, 3, 2, 4, 5, 6, 7, 8
+    .text
+"#;
+
+/// Copy of float_math.rs, only added to programs that use `FloatMath`.
+const X86_MATH: &str = r#"
+# Copy of float_math.rs. The functions take doubles in %xmm0 and %xmm1 and return one in %xmm0 like C
+# functions, and may change every SSE register and the caller saved general purpose ones. Helpers pass
+# pairs of doubles in %xmm0 and %xmm1, and only change the registers they say they do.
+
+# round(%xmm0) rounding halves away from zero.
+bc_round:
+    roundsd $3, %xmm0, %xmm1
+    movapd %xmm0, %xmm2
+    subsd %xmm1, %xmm2
+    andpd bc_math_abs_mask(%rip), %xmm2
+    ucomisd bc_math_half(%rip), %xmm2
+    jb 1f
+    movsd bc_math_one(%rip), %xmm2
+    movmskpd %xmm0, %eax
+    testl $1, %eax
+    jz 2f
+    xorpd bc_math_sign_mask(%rip), %xmm2
+2:
+    addsd %xmm2, %xmm1
+1:
+    movapd %xmm1, %xmm0
+    ret
+
+# NaN is ignored if the other operand is a number and -0.0 is less than 0.0.
+bc_min:
+    ucomisd %xmm0, %xmm0
+    jp 2f
+    ucomisd %xmm1, %xmm1
+    jp 1f
+    ucomisd %xmm1, %xmm0
+    jb 1f
+    jne 2f
+    movmskpd %xmm0, %eax
+    testl $1, %eax
+    jnz 1f
+2:
+    movapd %xmm1, %xmm0
+1:
+    ret
+
+bc_max:
+    ucomisd %xmm0, %xmm0
+    jp 2f
+    ucomisd %xmm1, %xmm1
+    jp 1f
+    ucomisd %xmm1, %xmm0
+    ja 1f
+    jne 2f
+    movmskpd %xmm0, %eax
+    testl $1, %eax
+    jz 1f
+2:
+    movapd %xmm1, %xmm0
+1:
+    ret
+
+# %xmm0 * 2^%eax without rounding twice. Changes %rax and %xmm1.
+bc_scale:
+    cmpl $1023, %eax
+    jle 1f
+    mulsd bc_math_two_1023(%rip), %xmm0
+    subl $1023, %eax
+    cmpl $1023, %eax
+    jle 2f
+    mulsd bc_math_two_1023(%rip), %xmm0
+    subl $1023, %eax
+    cmpl $1023, %eax
+    jle 2f
+    movl $1023, %eax
+    jmp 2f
+1:
+    cmpl $-1022, %eax
+    jge 2f
+    # 2^-1022 * 2^53, so the result is rounded only once when it ends up subnormal.
+    mulsd bc_math_two_minus_969(%rip), %xmm0
+    addl $969, %eax
+    cmpl $-1022, %eax
+    jge 2f
+    mulsd bc_math_two_minus_969(%rip), %xmm0
+    addl $969, %eax
+    cmpl $-1022, %eax
+    jge 2f
+    movl $-1022, %eax
+2:
+    addl $0x3ff, %eax
+    shlq $52, %rax
+    movq %rax, %xmm1
+    mulsd %xmm1, %xmm0
+    ret
+
+bc_exp:
+    ucomisd %xmm0, %xmm0
+    jp 9f
+    ucomisd bc_math_exp_max(%rip), %xmm0
+    ja 7f
+    ucomisd bc_math_exp_min(%rip), %xmm0
+    jb 8f
+    # x = k*ln2 + r with |r| <= ln2/2, hi - lo in %xmm2 and %xmm3.
+    movapd %xmm0, %xmm1
+    andpd bc_math_abs_mask(%rip), %xmm1
+    ucomisd bc_math_half_ln2(%rip), %xmm1
+    jbe 3f
+    ucomisd bc_math_three_halves_ln2(%rip), %xmm1
+    jae 1f
+    movl $1, %eax
+    movmskpd %xmm0, %ecx
+    testl $1, %ecx
+    jz 2f
+    movl $-1, %eax
+    jmp 2f
+1:
+    movsd bc_math_half(%rip), %xmm2
+    movmskpd %xmm0, %ecx
+    testl $1, %ecx
+    jz 1f
+    xorpd bc_math_sign_mask(%rip), %xmm2
+1:
+    movsd bc_math_inv_ln2(%rip), %xmm1
+    mulsd %xmm0, %xmm1
+    addsd %xmm2, %xmm1
+    cvttsd2si %xmm1, %eax
+2:
+    cvtsi2sdl %eax, %xmm1
+    movapd %xmm1, %xmm3
+    mulsd bc_math_ln2_hi(%rip), %xmm1
+    movapd %xmm0, %xmm2
+    subsd %xmm1, %xmm2
+    mulsd bc_math_ln2_lo(%rip), %xmm3
+    jmp 4f
+3:
+    ucomisd bc_math_two_minus_28(%rip), %xmm1
+    jbe 6f
+    xorl %eax, %eax
+    movapd %xmm0, %xmm2
+    xorpd %xmm3, %xmm3
+4:
+    # r in %xmm4, r*r in %xmm5, c in %xmm6.
+    movapd %xmm2, %xmm4
+    subsd %xmm3, %xmm4
+    movapd %xmm4, %xmm5
+    mulsd %xmm4, %xmm5
+    movsd bc_math_exp_p5(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    addsd bc_math_exp_p4(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    addsd bc_math_exp_p3(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    addsd bc_math_exp_p2(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    addsd bc_math_exp_p1(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    movapd %xmm4, %xmm7
+    subsd %xmm6, %xmm7
+    # 1 + (r*c/(2 - c) - lo + hi)
+    movapd %xmm4, %xmm0
+    mulsd %xmm7, %xmm0
+    movsd bc_math_two(%rip), %xmm1
+    subsd %xmm7, %xmm1
+    divsd %xmm1, %xmm0
+    subsd %xmm3, %xmm0
+    addsd %xmm2, %xmm0
+    addsd bc_math_one(%rip), %xmm0
+    testl %eax, %eax
+    jnz bc_scale
+    ret
+6:
+    addsd bc_math_one(%rip), %xmm0
+    ret
+7:
+    movsd bc_math_infinity(%rip), %xmm0
+    ret
+8:
+    xorpd %xmm0, %xmm0
+9:
+    ret
+
+bc_ln:
+    movq %xmm0, %rax
+    movq %rax, %rcx
+    shrq $32, %rcx
+    xorl %edx, %edx
+    cmpl $0x00100000, %ecx
+    jb 1f
+    testl %ecx, %ecx
+    js 1f
+    cmpl $0x7ff00000, %ecx
+    jae 9f
+    ucomisd bc_math_one(%rip), %xmm0
+    jne 2f
+    jp 2f
+    xorpd %xmm0, %xmm0
+    ret
+1:
+    xorpd %xmm1, %xmm1
+    ucomisd %xmm1, %xmm0
+    jp 8f
+    jb 8f
+    je 7f
+    # Subnormal, scale it up.
+    movl $-54, %edx
+    mulsd bc_math_two_54(%rip), %xmm0
+    movq %xmm0, %rax
+    movq %rax, %rcx
+    shrq $32, %rcx
+2:
+    # x = 2^k * m with sqrt(2)/2 < m < sqrt(2), k in %edx.
+    addl $0x3ff00000 - 0x3fe6a09e, %ecx
+    movl %ecx, %esi
+    shrl $20, %esi
+    leal -0x3ff(%rdx,%rsi), %edx
+    andl $0x000fffff, %ecx
+    addl $0x3fe6a09e, %ecx
+    shlq $32, %rcx
+    movl %eax, %eax
+    orq %rcx, %rax
+    movq %rax, %xmm0
+    # f in %xmm1, hfsq in %xmm2, s in %xmm3, z in %xmm4, w in %xmm5.
+    movapd %xmm0, %xmm1
+    subsd bc_math_one(%rip), %xmm1
+    movsd bc_math_half(%rip), %xmm2
+    mulsd %xmm1, %xmm2
+    mulsd %xmm1, %xmm2
+    movsd bc_math_two(%rip), %xmm6
+    addsd %xmm1, %xmm6
+    movapd %xmm1, %xmm3
+    divsd %xmm6, %xmm3
+    movapd %xmm3, %xmm4
+    mulsd %xmm3, %xmm4
+    movapd %xmm4, %xmm5
+    mulsd %xmm4, %xmm5
+    # t1 in %xmm6, t2 in %xmm7.
+    movsd bc_math_lg6(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    addsd bc_math_lg4(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    addsd bc_math_lg2(%rip), %xmm6
+    mulsd %xmm5, %xmm6
+    movsd bc_math_lg7(%rip), %xmm7
+    mulsd %xmm5, %xmm7
+    addsd bc_math_lg5(%rip), %xmm7
+    mulsd %xmm5, %xmm7
+    addsd bc_math_lg3(%rip), %xmm7
+    mulsd %xmm5, %xmm7
+    addsd bc_math_lg1(%rip), %xmm7
+    mulsd %xmm4, %xmm7
+    addsd %xmm6, %xmm7
+    # s*(hfsq + r) + dk*LN2_LO - hfsq + f + dk*LN2_HI
+    addsd %xmm2, %xmm7
+    movapd %xmm3, %xmm0
+    mulsd %xmm7, %xmm0
+    cvtsi2sdl %edx, %xmm6
+    movapd %xmm6, %xmm7
+    mulsd bc_math_ln2_lo(%rip), %xmm7
+    addsd %xmm7, %xmm0
+    subsd %xmm2, %xmm0
+    addsd %xmm1, %xmm0
+    mulsd bc_math_ln2_hi(%rip), %xmm6
+    addsd %xmm6, %xmm0
+    ret
+7:
+    movsd bc_math_negative_infinity(%rip), %xmm0
+    ret
+8:
+    movsd bc_math_nan(%rip), %xmm0
+9:
+    ret
+
+# sin on [-pi/4, pi/4] of %xmm0 + %xmm1, the tail of a reduced argument in %xmm1.
+# Changes %xmm0-%xmm7.
+bc_sin_kernel:
+    # z in %xmm2, w in %xmm3, r in %xmm4, v in %xmm5.
+    movapd %xmm0, %xmm2
+    mulsd %xmm0, %xmm2
+    movapd %xmm2, %xmm3
+    mulsd %xmm2, %xmm3
+    movsd bc_math_s4(%rip), %xmm4
+    mulsd %xmm2, %xmm4
+    addsd bc_math_s3(%rip), %xmm4
+    mulsd %xmm2, %xmm4
+    addsd bc_math_s2(%rip), %xmm4
+    movsd bc_math_s6(%rip), %xmm5
+    mulsd %xmm2, %xmm5
+    addsd bc_math_s5(%rip), %xmm5
+    movapd %xmm2, %xmm6
+    mulsd %xmm3, %xmm6
+    mulsd %xmm6, %xmm5
+    addsd %xmm5, %xmm4
+    movapd %xmm2, %xmm5
+    mulsd %xmm0, %xmm5
+    # x - ((z*(0.5*y - v*r) - y) - v*S1)
+    movsd bc_math_half(%rip), %xmm6
+    mulsd %xmm1, %xmm6
+    movapd %xmm5, %xmm7
+    mulsd %xmm4, %xmm7
+    subsd %xmm7, %xmm6
+    mulsd %xmm2, %xmm6
+    subsd %xmm1, %xmm6
+    mulsd bc_math_s1(%rip), %xmm5
+    subsd %xmm5, %xmm6
+    subsd %xmm6, %xmm0
+    ret
+
+# cos on [-pi/4, pi/4] like bc_sin_kernel.
+bc_cos_kernel:
+    # z in %xmm2, w in %xmm3, r in %xmm4.
+    movapd %xmm0, %xmm2
+    mulsd %xmm0, %xmm2
+    movapd %xmm2, %xmm3
+    mulsd %xmm2, %xmm3
+    movsd bc_math_c3(%rip), %xmm4
+    mulsd %xmm2, %xmm4
+    addsd bc_math_c2(%rip), %xmm4
+    mulsd %xmm2, %xmm4
+    addsd bc_math_c1(%rip), %xmm4
+    mulsd %xmm2, %xmm4
+    movsd bc_math_c6(%rip), %xmm5
+    mulsd %xmm2, %xmm5
+    addsd bc_math_c5(%rip), %xmm5
+    mulsd %xmm2, %xmm5
+    addsd bc_math_c4(%rip), %xmm5
+    movapd %xmm3, %xmm6
+    mulsd %xmm3, %xmm6
+    mulsd %xmm6, %xmm5
+    addsd %xmm5, %xmm4
+    # hz in %xmm5, w = 1 - hz in %xmm6, w + (((1 - w) - hz) + (z*r - x*y))
+    movsd bc_math_half(%rip), %xmm5
+    mulsd %xmm2, %xmm5
+    movsd bc_math_one(%rip), %xmm6
+    subsd %xmm5, %xmm6
+    movsd bc_math_one(%rip), %xmm7
+    subsd %xmm6, %xmm7
+    subsd %xmm5, %xmm7
+    mulsd %xmm4, %xmm2
+    mulsd %xmm1, %xmm0
+    subsd %xmm0, %xmm2
+    addsd %xmm2, %xmm7
+    addsd %xmm6, %xmm7
+    movapd %xmm7, %xmm0
+    ret
+
+# %xmm0 * %xmm1 as the sum of %xmm0 and %xmm1 without rounding, by splitting both into halves.
+# Changes %xmm0-%xmm6.
+bc_exact_mul:
+    # The halves of a in %xmm3 and %xmm4, of b in %xmm2 and %xmm5.
+    movsd bc_math_split(%rip), %xmm2
+    movapd %xmm2, %xmm3
+    mulsd %xmm0, %xmm3
+    movapd %xmm3, %xmm4
+    subsd %xmm0, %xmm4
+    subsd %xmm4, %xmm3
+    movapd %xmm0, %xmm4
+    subsd %xmm3, %xmm4
+    mulsd %xmm1, %xmm2
+    movapd %xmm2, %xmm5
+    subsd %xmm1, %xmm5
+    subsd %xmm5, %xmm2
+    movapd %xmm1, %xmm5
+    subsd %xmm2, %xmm5
+    mulsd %xmm1, %xmm0
+    # ((ah*bh - p) + ah*bl + al*bh) + al*bl
+    movapd %xmm3, %xmm1
+    mulsd %xmm2, %xmm1
+    subsd %xmm0, %xmm1
+    movapd %xmm3, %xmm6
+    mulsd %xmm5, %xmm6
+    addsd %xmm6, %xmm1
+    movapd %xmm4, %xmm6
+    mulsd %xmm2, %xmm6
+    addsd %xmm6, %xmm1
+    mulsd %xmm5, %xmm4
+    addsd %xmm4, %xmm1
+    ret
+
+# %xmm0 + %xmm1 as the sum of %xmm0 and %xmm1 without rounding. Changes %xmm0-%xmm4.
+bc_exact_add:
+    movapd %xmm0, %xmm2
+    addsd %xmm1, %xmm2
+    movapd %xmm2, %xmm3
+    subsd %xmm0, %xmm3
+    movapd %xmm2, %xmm4
+    subsd %xmm3, %xmm4
+    subsd %xmm4, %xmm0
+    subsd %xmm3, %xmm1
+    addsd %xmm0, %xmm1
+    movapd %xmm2, %xmm0
+    ret
+
+# Product of %xmm0 + %xmm1 and %xmm2 + %xmm3, each the sum of two floats. Changes %xmm0-%xmm7.
+bc_extended_mul:
+    movapd %xmm0, %xmm7
+    mulsd %xmm3, %xmm7
+    mulsd %xmm2, %xmm1
+    addsd %xmm1, %xmm7
+    movapd %xmm2, %xmm1
+    call bc_exact_mul
+    addsd %xmm7, %xmm1
+    movapd %xmm0, %xmm2
+    addsd %xmm1, %xmm2
+    movapd %xmm2, %xmm3
+    subsd %xmm0, %xmm3
+    subsd %xmm3, %xmm1
+    movapd %xmm2, %xmm0
+    ret
+
+# bc_reduce for arguments where n*pi/2 needs more bits of pi than a few floats hold.
+# x*2/pi mod 4 is computed exactly with 32 bit pieces of the mantissa and of bc_two_over_pi,
+# bits of 2/pi that only add multiples of 4 are skipped.
+bc_reduce_large:
+    # The window of 2/pi at (%rsp), three zero words and the product at 72(%rsp), x at 120(%rsp).
+    subq $128, %rsp
+    movsd %xmm0, 120(%rsp)
+    movq %xmm0, %rax
+    btrq $63, %rax
+    movq %rax, %rcx
+    shrq $52, %rcx
+    movabsq $0x000fffffffffffff, %rdx
+    andq %rdx, %rax
+    btsq $52, %rax
+    movq %rax, %r11
+    # 192 bits of 2/pi starting with the one that makes the product 2^1, from bit e - 2 + 64 on.
+    leal -1075+62(%rcx), %r10d
+    leaq bc_two_over_pi(%rip), %rsi
+    xorl %edi, %edi
+1:
+    movl $5, %eax
+    subl %edi, %eax
+    shll $5, %eax
+    addl %r10d, %eax
+    movl %eax, %edx
+    shrl $5, %edx
+    andl $31, %eax
+    movl (%rsi,%rdx,4), %r8d
+    shlq $32, %r8
+    movl 4(%rsi,%rdx,4), %r9d
+    orq %r9, %r8
+    movl $32, %ecx
+    subl %eax, %ecx
+    shrq %cl, %r8
+    movl %r8d, %r8d
+    movq %r8, (%rsp,%rdi,8)
+    incl %edi
+    cmpl $6, %edi
+    jb 1b
+    xorl %eax, %eax
+    movl $9, %ecx
+    leaq 48(%rsp), %rdi
+    rep stosq
+    # product mod 2^192, the top two bits are x*2/pi mod 4 and the rest its fraction.
+    xorl %edi, %edi
+2:
+    movq %r11, %r8
+    movl %edi, %ecx
+    shll $5, %ecx
+    shrq %cl, %r8
+    movl %r8d, %r8d
+    xorl %r9d, %r9d
+    xorl %esi, %esi
+3:
+    leal (%rsi,%rdi), %r10d
+    movq (%rsp,%rsi,8), %rax
+    imulq %r8, %rax
+    addq 72(%rsp,%r10,8), %rax
+    addq %r9, %rax
+    movl %eax, %edx
+    movq %rdx, 72(%rsp,%r10,8)
+    movq %rax, %r9
+    shrq $32, %r9
+    incl %esi
+    movl $6, %eax
+    subl %edi, %eax
+    cmpl %eax, %esi
+    jb 3b
+    incl %edi
+    cmpl $2, %edi
+    jb 2b
+    # n in %r8, whether the fraction is negative in %r9.
+    movq 112(%rsp), %rax
+    movq %rax, %r8
+    shrq $30, %r8
+    movq %rax, %rcx
+    shrq $29, %rcx
+    andl $1, %ecx
+    addq %rcx, %r8
+    movq %r8, %rcx
+    shlq $30, %rcx
+    subq %rcx, %rax
+    movl %eax, %eax
+    movq %rax, 112(%rsp)
+    shrq $31, %rax
+    movq %rax, %r9
+    testq %r9, %r9
+    jz 5f
+    movl $1, %ecx
+    xorl %esi, %esi
+4:
+    movq 72(%rsp,%rsi,8), %rax
+    notq %rax
+    movl %eax, %eax
+    addq %rcx, %rax
+    movl %eax, %edx
+    movq %rdx, 72(%rsp,%rsi,8)
+    shrq $32, %rax
+    movq %rax, %rcx
+    incl %esi
+    cmpl $6, %esi
+    jb 4b
+5:
+    # The fraction to two floats, from its highest set bit on.
+    movl $5, %esi
+6:
+    cmpq $0, 72(%rsp,%rsi,8)
+    jne 7f
+    decl %esi
+    jns 6b
+    movl %r8d, %eax
+    andl $3, %eax
+    xorpd %xmm0, %xmm0
+    xorpd %xmm1, %xmm1
+    addq $128, %rsp
+    ret
+7:
+    movq 72(%rsp,%rsi,8), %rax
+    bsrl %eax, %edx
+    movl $31, %ecx
+    subl %edx, %ecx
+    shlq $32, %rax
+    orq 64(%rsp,%rsi,8), %rax
+    shlq %cl, %rax
+    movq 56(%rsp,%rsi,8), %rdx
+    shlq %cl, %rdx
+    movq %rdx, %r10
+    shrq $32, %rdx
+    orq %rdx, %rax
+    movq 48(%rsp,%rsi,8), %rdx
+    shlq %cl, %rdx
+    shrq $32, %rdx
+    orq %r10, %rdx
+    movl %edx, %edx
+    # The exponent, 32*(top - 2) - zeros - 190, in %r10d, t_hi in %xmm0 and t_lo in %xmm7.
+    leal -2(%rsi), %r10d
+    shll $5, %r10d
+    subl %ecx, %r10d
+    subl $190, %r10d
+    movq %rax, %r11
+    shrq $11, %r11
+    cvtsi2sdq %r11, %xmm0
+    leal 0x3ff+43(%r10), %r11d
+    shlq $52, %r11
+    movq %r11, %xmm1
+    mulsd %xmm1, %xmm0
+    andl $0x7ff, %eax
+    shlq $32, %rax
+    orq %rdx, %rax
+    cvtsi2sdq %rax, %xmm7
+    leal 0x3ff(%r10), %r11d
+    shlq $52, %r11
+    movq %r11, %xmm1
+    mulsd %xmm1, %xmm7
+    movapd %xmm0, %xmm8
+    movsd bc_math_pio2_hi(%rip), %xmm1
+    call bc_exact_mul
+    mulsd bc_math_pio2_lo(%rip), %xmm8
+    mulsd bc_math_pio2_hi(%rip), %xmm7
+    addsd %xmm7, %xmm8
+    addsd %xmm8, %xmm1
+    movapd %xmm0, %xmm2
+    addsd %xmm1, %xmm2
+    movapd %xmm2, %xmm3
+    subsd %xmm0, %xmm3
+    subsd %xmm3, %xmm1
+    movapd %xmm2, %xmm0
+    movq 120(%rsp), %rax
+    shrq $63, %rax
+    cmpq %rax, %r9
+    je 8f
+    movsd bc_math_sign_mask(%rip), %xmm2
+    xorpd %xmm2, %xmm0
+    xorpd %xmm2, %xmm1
+8:
+    testq %rax, %rax
+    jz 9f
+    negq %r8
+9:
+    movl %r8d, %eax
+    andl $3, %eax
+    addq $128, %rsp
+    ret
+
+# Leaves n in %eax and y0 + y1 in %xmm0 and %xmm1 with %xmm0 = n*pi/2 + y0 + y1 and |y0 + y1| <= pi/4.
+bc_reduce:
+    movapd %xmm0, %xmm1
+    andpd bc_math_abs_mask(%rip), %xmm1
+    ucomisd bc_math_pio4(%rip), %xmm1
+    ja 1f
+    xorl %eax, %eax
+    xorpd %xmm1, %xmm1
+    ret
+1:
+    # Past 2^20*pi/2 the three parts of pi/2 below are not enough.
+    ucomisd bc_math_reduce_max(%rip), %xmm1
+    ja bc_reduce_large
+    # n in %xmm2, r in %xmm3, w in %xmm4, y0 in %xmm5 and the exponent of x in %ecx.
+    movapd %xmm0, %xmm2
+    mulsd bc_math_inv_pio2(%rip), %xmm2
+    addsd bc_math_to_int(%rip), %xmm2
+    subsd bc_math_to_int(%rip), %xmm2
+    movapd %xmm2, %xmm1
+    mulsd bc_math_pio2_1(%rip), %xmm1
+    movapd %xmm0, %xmm3
+    subsd %xmm1, %xmm3
+    movapd %xmm2, %xmm4
+    mulsd bc_math_pio2_1t(%rip), %xmm4
+    movapd %xmm3, %xmm5
+    subsd %xmm4, %xmm5
+    movq %xmm0, %rcx
+    shrq $52, %rcx
+    andl $0x7ff, %ecx
+    # Take more bits of pi/2 when the first subtraction cancelled a lot.
+    movq %xmm5, %rax
+    shrq $52, %rax
+    andl $0x7ff, %eax
+    movl %ecx, %edx
+    subl %eax, %edx
+    cmpl $16, %edx
+    jle 2f
+    movapd %xmm3, %xmm6
+    movapd %xmm2, %xmm4
+    mulsd bc_math_pio2_2(%rip), %xmm4
+    subsd %xmm4, %xmm3
+    movapd %xmm6, %xmm7
+    subsd %xmm3, %xmm7
+    subsd %xmm4, %xmm7
+    movapd %xmm2, %xmm4
+    mulsd bc_math_pio2_2t(%rip), %xmm4
+    subsd %xmm7, %xmm4
+    movapd %xmm3, %xmm5
+    subsd %xmm4, %xmm5
+    movq %xmm5, %rax
+    shrq $52, %rax
+    andl $0x7ff, %eax
+    movl %ecx, %edx
+    subl %eax, %edx
+    cmpl $49, %edx
+    jle 2f
+    movapd %xmm3, %xmm6
+    movapd %xmm2, %xmm4
+    mulsd bc_math_pio2_3(%rip), %xmm4
+    subsd %xmm4, %xmm3
+    movapd %xmm6, %xmm7
+    subsd %xmm3, %xmm7
+    subsd %xmm4, %xmm7
+    movapd %xmm2, %xmm4
+    mulsd bc_math_pio2_3t(%rip), %xmm4
+    subsd %xmm7, %xmm4
+    movapd %xmm3, %xmm5
+    subsd %xmm4, %xmm5
+2:
+    movapd %xmm3, %xmm1
+    subsd %xmm5, %xmm1
+    subsd %xmm4, %xmm1
+    movapd %xmm5, %xmm0
+    cvttsd2si %xmm2, %eax
+    andl $3, %eax
+    ret
+
+bc_sin:
+    movapd %xmm0, %xmm1
+    andpd bc_math_abs_mask(%rip), %xmm1
+    ucomisd bc_math_infinity(%rip), %xmm1
+    jae 8f
+    jp 8f
+    ucomisd bc_math_two_minus_26(%rip), %xmm1
+    jb 9f
+    call bc_reduce
+    testl $1, %eax
+    jnz 1f
+    call bc_sin_kernel
+    jmp 2f
+1:
+    call bc_cos_kernel
+2:
+    testl $2, %eax
+    jz 9f
+    xorpd bc_math_sign_mask(%rip), %xmm0
+    ret
+8:
+    movsd bc_math_nan(%rip), %xmm0
+9:
+    ret
+
+bc_cos:
+    movapd %xmm0, %xmm1
+    andpd bc_math_abs_mask(%rip), %xmm1
+    ucomisd bc_math_infinity(%rip), %xmm1
+    jae 8f
+    jp 8f
+    ucomisd bc_math_cos_tiny(%rip), %xmm1
+    jb 7f
+    call bc_reduce
+    testl $1, %eax
+    jnz 1f
+    call bc_cos_kernel
+    jmp 2f
+1:
+    call bc_sin_kernel
+2:
+    # Negative for n = 1 and n = 2.
+    incl %eax
+    testl $2, %eax
+    jz 9f
+    xorpd bc_math_sign_mask(%rip), %xmm0
+    ret
+7:
+    movsd bc_math_one(%rip), %xmm0
+    ret
+8:
+    movsd bc_math_nan(%rip), %xmm0
+9:
+    ret
+
+bc_tan:
+    movapd %xmm0, %xmm1
+    andpd bc_math_abs_mask(%rip), %xmm1
+    ucomisd bc_math_infinity(%rip), %xmm1
+    jae 8f
+    jp 8f
+    ucomisd bc_math_two_minus_27(%rip), %xmm1
+    jb 9f
+    call bc_reduce
+    movapd %xmm0, %xmm8
+    movapd %xmm1, %xmm9
+    call bc_sin_kernel
+    movapd %xmm0, %xmm10
+    movapd %xmm8, %xmm0
+    movapd %xmm9, %xmm1
+    call bc_cos_kernel
+    testl $1, %eax
+    jnz 1f
+    divsd %xmm0, %xmm10
+    movapd %xmm10, %xmm0
+    ret
+1:
+    xorpd bc_math_sign_mask(%rip), %xmm0
+    divsd %xmm10, %xmm0
+    ret
+8:
+    movsd bc_math_nan(%rip), %xmm0
+9:
+    ret
+
+# atan(%xmm0), which only changes %rax, %rcx, %rdx and %xmm0-%xmm5.
+bc_atan:
+    ucomisd %xmm0, %xmm0
+    jp 9f
+    # Whether x is negative in %ecx, |x| in %xmm1.
+    xorl %ecx, %ecx
+    xorpd %xmm1, %xmm1
+    ucomisd %xmm0, %xmm1
+    seta %cl
+    movapd %xmm0, %xmm1
+    andpd bc_math_abs_mask(%rip), %xmm1
+    ucomisd bc_math_two_66(%rip), %xmm1
+    jae 7f
+    ucomisd bc_math_two_minus_27(%rip), %xmm1
+    jb 9f
+    # Moves the argument near one of the points in bc_math_atan_hi, whose index goes in %edx.
+    ucomisd bc_math_atan_bound0(%rip), %xmm1
+    jae 1f
+    movl $-1, %edx
+    testl %ecx, %ecx
+    jz 5f
+    xorpd bc_math_sign_mask(%rip), %xmm1
+    jmp 5f
+1:
+    ucomisd bc_math_atan_bound1(%rip), %xmm1
+    jae 2f
+    xorl %edx, %edx
+    movapd %xmm1, %xmm2
+    addsd %xmm1, %xmm2
+    subsd bc_math_one(%rip), %xmm2
+    addsd bc_math_two(%rip), %xmm1
+    divsd %xmm1, %xmm2
+    movapd %xmm2, %xmm1
+    jmp 5f
+2:
+    ucomisd bc_math_atan_bound2(%rip), %xmm1
+    jae 3f
+    movl $1, %edx
+    movapd %xmm1, %xmm2
+    subsd bc_math_one(%rip), %xmm2
+    addsd bc_math_one(%rip), %xmm1
+    divsd %xmm1, %xmm2
+    movapd %xmm2, %xmm1
+    jmp 5f
+3:
+    ucomisd bc_math_atan_bound3(%rip), %xmm1
+    jae 4f
+    movl $2, %edx
+    movapd %xmm1, %xmm2
+    subsd bc_math_three_halves(%rip), %xmm2
+    mulsd bc_math_three_halves(%rip), %xmm1
+    addsd bc_math_one(%rip), %xmm1
+    divsd %xmm1, %xmm2
+    movapd %xmm2, %xmm1
+    jmp 5f
+4:
+    movl $3, %edx
+    movsd bc_math_minus_one(%rip), %xmm2
+    divsd %xmm1, %xmm2
+    movapd %xmm2, %xmm1
+5:
+    # z in %xmm2, w in %xmm3, s1 + s2 in %xmm4.
+    leaq bc_math_atan_coefficients(%rip), %rax
+    movapd %xmm1, %xmm2
+    mulsd %xmm1, %xmm2
+    movapd %xmm2, %xmm3
+    mulsd %xmm2, %xmm3
+    movsd 80(%rax), %xmm4
+    mulsd %xmm3, %xmm4
+    addsd 64(%rax), %xmm4
+    mulsd %xmm3, %xmm4
+    addsd 48(%rax), %xmm4
+    mulsd %xmm3, %xmm4
+    addsd 32(%rax), %xmm4
+    mulsd %xmm3, %xmm4
+    addsd 16(%rax), %xmm4
+    mulsd %xmm3, %xmm4
+    addsd (%rax), %xmm4
+    mulsd %xmm2, %xmm4
+    movsd 72(%rax), %xmm5
+    mulsd %xmm3, %xmm5
+    addsd 56(%rax), %xmm5
+    mulsd %xmm3, %xmm5
+    addsd 40(%rax), %xmm5
+    mulsd %xmm3, %xmm5
+    addsd 24(%rax), %xmm5
+    mulsd %xmm3, %xmm5
+    addsd 8(%rax), %xmm5
+    mulsd %xmm3, %xmm5
+    addsd %xmm5, %xmm4
+    mulsd %xmm1, %xmm4
+    testl %edx, %edx
+    jns 6f
+    movapd %xmm1, %xmm0
+    subsd %xmm4, %xmm0
+    ret
+6:
+    # atan_hi - ((x*(s1 + s2) - atan_lo) - x)
+    leaq bc_math_atan_lo(%rip), %rax
+    subsd (%rax,%rdx,8), %xmm4
+    subsd %xmm1, %xmm4
+    leaq bc_math_atan_hi(%rip), %rax
+    movsd (%rax,%rdx,8), %xmm0
+    subsd %xmm4, %xmm0
+    jmp 8f
+7:
+    movsd bc_math_atan_hi+24(%rip), %xmm0
+    addsd bc_math_atan_lo+24(%rip), %xmm0
+8:
+    testl %ecx, %ecx
+    jz 9f
+    xorpd bc_math_sign_mask(%rip), %xmm0
+9:
+    ret
+
+# Angle of the point (%xmm1, %xmm0), the arguments are in the same order as in C.
+bc_atan2:
+    ucomisd %xmm1, %xmm0
+    jp 9f
+    ucomisd bc_math_one(%rip), %xmm1
+    jne 1f
+    jp 1f
+    jmp bc_atan
+1:
+    # The sign of y in bit 0 of %r8d and the sign of x in bit 1.
+    movapd %xmm0, %xmm2
+    unpcklpd %xmm1, %xmm2
+    movmskpd %xmm2, %r8d
+    xorpd %xmm3, %xmm3
+    ucomisd %xmm3, %xmm0
+    jne 2f
+    testl $2, %r8d
+    jz 8f
+    movsd bc_math_pi(%rip), %xmm0
+    jmp 6f
+2:
+    ucomisd %xmm3, %xmm1
+    je 5f
+    # |x| and |y| in %xmm2 and %xmm3.
+    movapd %xmm1, %xmm2
+    andpd bc_math_abs_mask(%rip), %xmm2
+    movapd %xmm0, %xmm3
+    andpd bc_math_abs_mask(%rip), %xmm3
+    ucomisd bc_math_infinity(%rip), %xmm2
+    jne 4f
+    ucomisd bc_math_infinity(%rip), %xmm3
+    jne 3f
+    movsd bc_math_pio4(%rip), %xmm0
+    testl $2, %r8d
+    jz 6f
+    movsd bc_math_three_pio4(%rip), %xmm0
+    jmp 6f
+3:
+    xorpd %xmm0, %xmm0
+    testl $2, %r8d
+    jz 6f
+    movsd bc_math_pi(%rip), %xmm0
+    jmp 6f
+4:
+    # The ratio |y/x| in %xmm0.
+    movapd %xmm0, %xmm4
+    divsd %xmm1, %xmm4
+    andpd bc_math_abs_mask(%rip), %xmm4
+    ucomisd bc_math_infinity(%rip), %xmm3
+    je 5f
+    ucomisd bc_math_two_64(%rip), %xmm4
+    ja 5f
+    xorpd %xmm0, %xmm0
+    testl $2, %r8d
+    jz 1f
+    ucomisd bc_math_two_minus_64(%rip), %xmm4
+    jb 2f
+1:
+    movapd %xmm4, %xmm0
+    call bc_atan
+2:
+    testl $2, %r8d
+    jz 6f
+    subsd bc_math_pi_lo(%rip), %xmm0
+    testl $1, %r8d
+    jnz 3f
+    movsd bc_math_pi(%rip), %xmm1
+    subsd %xmm0, %xmm1
+    movapd %xmm1, %xmm0
+    ret
+3:
+    subsd bc_math_pi(%rip), %xmm0
+    ret
+5:
+    movsd bc_math_pio2(%rip), %xmm0
+6:
+    testl $1, %r8d
+    jz 8f
+    xorpd bc_math_sign_mask(%rip), %xmm0
+8:
+    ret
+9:
+    addsd %xmm0, %xmm1
+    movapd %xmm1, %xmm0
+    ret
+
+# ln(%xmm0) for a positive finite x as the sum of %xmm0 and %xmm1, good for about 70 bits, which
+# bc_pow needs as it multiplies the error by y. Changes %rax, %rcx, %rdx, %rdi and every SSE register.
+bc_ln_extended:
+    xorl %edi, %edi
+    ucomisd bc_math_min_positive(%rip), %xmm0
+    jae 1f
+    movl $-54, %edi
+    mulsd bc_math_two_54(%rip), %xmm0
+1:
+    # x = 2^k * m, k in %edi.
+    movq %xmm0, %rax
+    movq %rax, %rcx
+    shrq $32, %rcx
+    addl $0x3ff00000 - 0x3fe6a09e, %ecx
+    movl %ecx, %edx
+    shrl $20, %edx
+    leal -0x3ff(%rdi,%rdx), %edi
+    andl $0x000fffff, %ecx
+    addl $0x3fe6a09e, %ecx
+    shlq $32, %rcx
+    movl %eax, %eax
+    orq %rcx, %rax
+    movq %rax, %xmm1
+    # ln(m) = 2*atanh(s) = 2s + 2s^3/3 + 2s^5/5 + ... with s = (m-1)/(m+1), |s| < 0.172.
+    # u in %xmm9, s in %xmm12 and %xmm13, z in %xmm14 and %xmm15.
+    movapd %xmm1, %xmm9
+    subsd bc_math_one(%rip), %xmm9
+    movsd bc_math_one(%rip), %xmm0
+    call bc_exact_add
+    movapd %xmm0, %xmm10
+    movapd %xmm1, %xmm11
+    movapd %xmm9, %xmm12
+    divsd %xmm10, %xmm12
+    movapd %xmm12, %xmm0
+    movapd %xmm10, %xmm1
+    call bc_exact_mul
+    movapd %xmm9, %xmm13
+    subsd %xmm0, %xmm13
+    subsd %xmm1, %xmm13
+    movapd %xmm12, %xmm2
+    mulsd %xmm11, %xmm2
+    subsd %xmm2, %xmm13
+    divsd %xmm10, %xmm13
+    movapd %xmm12, %xmm0
+    movapd %xmm12, %xmm1
+    call bc_exact_mul
+    movapd %xmm0, %xmm14
+    movapd %xmm1, %xmm15
+    # The s^3 term is still large enough to need the extra bits, its cube goes in %xmm9 and %xmm10.
+    movapd %xmm12, %xmm0
+    xorpd %xmm1, %xmm1
+    movapd %xmm14, %xmm2
+    movapd %xmm15, %xmm3
+    call bc_extended_mul
+    movapd %xmm0, %xmm9
+    movapd %xmm1, %xmm10
+    movsd bc_math_two(%rip), %xmm11
+    divsd bc_math_series_divisors(%rip), %xmm11
+    leaq bc_math_series_divisors+8(%rip), %rax
+    movl $10, %ecx
+2:
+    movsd bc_math_two(%rip), %xmm2
+    divsd (%rax), %xmm2
+    mulsd %xmm14, %xmm11
+    addsd %xmm2, %xmm11
+    addq $8, %rax
+    decl %ecx
+    jnz 2b
+    movapd %xmm14, %xmm3
+    mulsd %xmm11, %xmm3
+    addsd bc_math_two_thirds_lo(%rip), %xmm3
+    movsd bc_math_two_thirds_hi(%rip), %xmm2
+    movapd %xmm9, %xmm0
+    movapd %xmm10, %xmm1
+    call bc_extended_mul
+    # The tail's low half in %xmm15, lo1 in %xmm9 and k in %xmm10.
+    movapd %xmm1, %xmm15
+    movapd %xmm0, %xmm1
+    movsd bc_math_two(%rip), %xmm0
+    mulsd %xmm12, %xmm0
+    call bc_exact_add
+    movapd %xmm1, %xmm9
+    movapd %xmm0, %xmm1
+    cvtsi2sdl %edi, %xmm10
+    movapd %xmm10, %xmm0
+    mulsd bc_math_ln2_hi(%rip), %xmm0
+    call bc_exact_add
+    # lo1 + lo2 + (tail_lo + 2*s_lo/(1 - z) + k*LN2_LO)
+    addsd %xmm1, %xmm9
+    movsd bc_math_two(%rip), %xmm2
+    mulsd %xmm13, %xmm2
+    movsd bc_math_one(%rip), %xmm3
+    subsd %xmm14, %xmm3
+    divsd %xmm3, %xmm2
+    addsd %xmm15, %xmm2
+    mulsd bc_math_ln2_lo(%rip), %xmm10
+    addsd %xmm10, %xmm2
+    addsd %xmm2, %xmm9
+    movapd %xmm0, %xmm2
+    addsd %xmm9, %xmm0
+    movapd %xmm0, %xmm3
+    subsd %xmm2, %xmm3
+    movapd %xmm9, %xmm1
+    subsd %xmm3, %xmm1
+    ret
+
+# %xmm0 to the power of the integer %rsi by squaring, 1 over that when %ecx is not 0, carrying the
+# rounding errors along so exact results stay exact. NaN if anything on the way overflows or gets
+# so small that the low halves lose bits. Changes %rsi and every SSE register.
+bc_powi:
+    # The base in %xmm8 and %xmm9, the result in %xmm10 and %xmm11.
+    movapd %xmm0, %xmm8
+    xorpd %xmm9, %xmm9
+    movsd bc_math_one(%rip), %xmm10
+    xorpd %xmm11, %xmm11
+    testq %rsi, %rsi
+    jz 3f
+1:
+    testl $1, %esi
+    jz 2f
+    movapd %xmm10, %xmm0
+    movapd %xmm11, %xmm1
+    movapd %xmm8, %xmm2
+    movapd %xmm9, %xmm3
+    call bc_extended_mul
+    movapd %xmm0, %xmm10
+    movapd %xmm1, %xmm11
+2:
+    shrq $1, %rsi
+    jz 3f
+    movapd %xmm8, %xmm0
+    movapd %xmm9, %xmm1
+    movapd %xmm8, %xmm2
+    movapd %xmm9, %xmm3
+    call bc_extended_mul
+    movapd %xmm0, %xmm8
+    movapd %xmm1, %xmm9
+    jmp 1b
+3:
+    movapd %xmm10, %xmm0
+    andpd bc_math_abs_mask(%rip), %xmm0
+    ucomisd bc_math_infinity(%rip), %xmm0
+    jae 8f
+    ucomisd bc_math_powi_min(%rip), %xmm10
+    jb 8f
+    movapd %xmm10, %xmm0
+    testl %ecx, %ecx
+    jz 9f
+    # q + (((1 - p) - e) - result_lo*q)*q with q = 1/result in %xmm8.
+    movsd bc_math_one(%rip), %xmm8
+    divsd %xmm10, %xmm8
+    movapd %xmm8, %xmm0
+    movapd %xmm10, %xmm1
+    call bc_exact_mul
+    movsd bc_math_one(%rip), %xmm2
+    subsd %xmm0, %xmm2
+    subsd %xmm1, %xmm2
+    mulsd %xmm8, %xmm11
+    subsd %xmm11, %xmm2
+    mulsd %xmm8, %xmm2
+    addsd %xmm2, %xmm8
+    movapd %xmm8, %xmm0
+    ret
+8:
+    movsd bc_math_nan(%rip), %xmm0
+9:
+    ret
+
+bc_pow:
+    # x at (%rsp), y at 8(%rsp), whether the result is negated in %r10d.
+    subq $24, %rsp
+    movsd %xmm0, (%rsp)
+    movsd %xmm1, 8(%rsp)
+    xorpd %xmm2, %xmm2
+    ucomisd %xmm2, %xmm1
+    jne 1f
+    jnp 7f
+1:
+    ucomisd bc_math_one(%rip), %xmm0
+    jne 1f
+    jnp 7f
+1:
+    ucomisd %xmm1, %xmm0
+    jp 8f
+    # Whether y is an integer in %r8d and whether it is odd in %r9d.
+    xorl %r8d, %r8d
+    xorl %r9d, %r9d
+    roundsd $1, %xmm1, %xmm3
+    ucomisd %xmm1, %xmm3
+    jne 2f
+    jp 2f
+    movl $1, %r8d
+    movapd %xmm1, %xmm3
+    andpd bc_math_abs_mask(%rip), %xmm3
+    ucomisd bc_math_two_53(%rip), %xmm3
+    jae 2f
+    movsd bc_math_half(%rip), %xmm3
+    mulsd %xmm1, %xmm3
+    roundsd $1, %xmm3, %xmm4
+    ucomisd %xmm3, %xmm4
+    je 2f
+    movl $1, %r9d
+2:
+    # |x| in %xmm3.
+    movapd %xmm0, %xmm3
+    andpd bc_math_abs_mask(%rip), %xmm3
+    movapd %xmm1, %xmm4
+    andpd bc_math_abs_mask(%rip), %xmm4
+    ucomisd bc_math_infinity(%rip), %xmm4
+    jne 3f
+    ucomisd bc_math_minus_one(%rip), %xmm0
+    je 7f
+    # Infinity when |x| < 1 and y < 0 agree.
+    xorl %eax, %eax
+    ucomisd bc_math_one(%rip), %xmm3
+    setb %al
+    xorl %ecx, %ecx
+    ucomisd %xmm1, %xmm2
+    seta %cl
+    cmpl %eax, %ecx
+    je 6f
+    jmp 5f
+3:
+    ucomisd %xmm2, %xmm0
+    je 4f
+    ucomisd bc_math_infinity(%rip), %xmm3
+    jne 10f
+4:
+    # Infinity when x == 0 and y < 0 agree, negative when x is negative and y odd.
+    xorl %eax, %eax
+    ucomisd %xmm2, %xmm0
+    sete %al
+    xorl %ecx, %ecx
+    ucomisd %xmm1, %xmm2
+    seta %cl
+    movmskpd %xmm0, %r10d
+    andl %r9d, %r10d
+    cmpl %eax, %ecx
+    jne 11f
+    movsd bc_math_infinity(%rip), %xmm0
+    jmp 12f
+11:
+    xorpd %xmm0, %xmm0
+    jmp 12f
+10:
+    ucomisd %xmm0, %xmm2
+    jbe 10f
+    testl %r8d, %r8d
+    jz 8f
+10:
+    # Negative when x < 0 and y is odd.
+    xorl %r10d, %r10d
+    ucomisd %xmm0, %xmm2
+    seta %r10b
+    andl %r9d, %r10d
+    movsd %xmm3, 16(%rsp)
+    testl %r8d, %r8d
+    jz 13f
+    ucomisd bc_math_1024(%rip), %xmm4
+    ja 13f
+    cvttsd2si %xmm4, %rsi
+    xorl %ecx, %ecx
+    xorpd %xmm2, %xmm2
+    ucomisd %xmm1, %xmm2
+    seta %cl
+    movapd %xmm3, %xmm0
+    call bc_powi
+    ucomisd %xmm0, %xmm0
+    jnp 12f
+13:
+    movsd 16(%rsp), %xmm0
+    call bc_ln_extended
+    # p = y*l_hi, the low half of ln in 16(%rsp).
+    movsd %xmm1, 16(%rsp)
+    movsd 8(%rsp), %xmm1
+    movapd %xmm1, %xmm2
+    mulsd %xmm0, %xmm2
+    ucomisd bc_math_pow_max(%rip), %xmm2
+    ja 14f
+    ucomisd bc_math_pow_min(%rip), %xmm2
+    jb 15f
+    # exp(w_hi + w_lo) = exp(w_hi) * (1 + w_lo) as w_lo is tiny.
+    movapd %xmm1, %xmm2
+    movapd %xmm0, %xmm1
+    movapd %xmm2, %xmm0
+    call bc_exact_mul
+    movsd 8(%rsp), %xmm2
+    mulsd 16(%rsp), %xmm2
+    addsd %xmm2, %xmm1
+    movapd %xmm0, %xmm2
+    addsd %xmm1, %xmm2
+    movapd %xmm2, %xmm3
+    subsd %xmm0, %xmm3
+    subsd %xmm3, %xmm1
+    movsd %xmm1, 16(%rsp)
+    movapd %xmm2, %xmm0
+    call bc_exp
+    movapd %xmm0, %xmm1
+    andpd bc_math_abs_mask(%rip), %xmm1
+    ucomisd bc_math_infinity(%rip), %xmm1
+    jae 12f
+    movsd 16(%rsp), %xmm1
+    mulsd %xmm0, %xmm1
+    addsd %xmm1, %xmm0
+    jmp 12f
+14:
+    movsd bc_math_infinity(%rip), %xmm0
+    jmp 12f
+15:
+    xorpd %xmm0, %xmm0
+12:
+    testl %r10d, %r10d
+    jz 9f
+    xorpd bc_math_sign_mask(%rip), %xmm0
+    jmp 9f
+5:
+    xorpd %xmm0, %xmm0
+    jmp 9f
+6:
+    movsd bc_math_infinity(%rip), %xmm0
+    jmp 9f
+7:
+    movsd bc_math_one(%rip), %xmm0
+    jmp 9f
+8:
+    movsd bc_math_nan(%rip), %xmm0
+9:
+    addq $24, %rsp
+    ret
+
+    .section .rodata
+    .balign 16
+bc_math_sign_mask:
+    .quad 0x8000000000000000, 0
+bc_math_abs_mask:
+    .quad 0x7fffffffffffffff, 0
+bc_math_one:
+    .quad 0x3ff0000000000000
+bc_math_minus_one:
+    .quad 0xbff0000000000000
+bc_math_half:
+    .quad 0x3fe0000000000000
+bc_math_two:
+    .quad 0x4000000000000000
+bc_math_three_halves:
+    .quad 0x3ff8000000000000
+bc_math_1024:
+    .quad 0x4090000000000000
+bc_math_infinity:
+    .quad 0x7ff0000000000000
+bc_math_negative_infinity:
+    .quad 0xfff0000000000000
+bc_math_nan:
+    .quad 0x7ff8000000000000
+bc_math_min_positive:
+    .quad 0x0010000000000000
+bc_math_two_1023:
+    .quad 0x7fe0000000000000
+bc_math_two_minus_969:
+    .quad 0x0360000000000000
+bc_math_two_54:
+    .quad 0x4350000000000000
+bc_math_two_53:
+    .quad 0x4340000000000000
+bc_math_two_64:
+    .quad 0x43f0000000000000
+bc_math_two_66:
+    .quad 0x4410000000000000
+bc_math_two_minus_26:
+    .quad 0x3e50000000000000
+bc_math_two_minus_27:
+    .quad 0x3e40000000000000
+bc_math_two_minus_28:
+    .quad 0x3e30000000000000
+bc_math_two_minus_64:
+    .quad 0x3bf0000000000000
+bc_math_powi_min:
+    .quad 0x06b0000000000000
+bc_math_pow_max:
+    .quad 0x4086300000000000
+bc_math_pow_min:
+    .quad 0xc087500000000000
+bc_math_ln2_hi:
+    .quad 0x3fe62e42fee00000
+bc_math_ln2_lo:
+    .quad 0x3dea39ef35793c76
+bc_math_half_ln2:
+    .quad 0x3fd62e42fefa39ef
+bc_math_three_halves_ln2:
+    .quad 0x3ff0a2b23f3bab73
+bc_math_inv_ln2:
+    .quad 0x3ff71547652b82fe
+bc_math_exp_max:
+    .quad 0x40862e42fefa39ef
+bc_math_exp_min:
+    .quad 0xc0874910d52d3051
+bc_math_exp_p1:
+    .quad 0x3fc555555555553e
+bc_math_exp_p2:
+    .quad 0xbf66c16c16bebd93
+bc_math_exp_p3:
+    .quad 0x3f11566aaf25de2c
+bc_math_exp_p4:
+    .quad 0xbebbbd41c5d26bf1
+bc_math_exp_p5:
+    .quad 0x3e66376972bea4d0
+bc_math_lg1:
+    .quad 0x3fe5555555555593
+bc_math_lg2:
+    .quad 0x3fd999999997fa04
+bc_math_lg3:
+    .quad 0x3fd2492494229359
+bc_math_lg4:
+    .quad 0x3fcc71c51d8e78af
+bc_math_lg5:
+    .quad 0x3fc7466496cb03de
+bc_math_lg6:
+    .quad 0x3fc39a09d078c69f
+bc_math_lg7:
+    .quad 0x3fc2f112df3e5244
+bc_math_s1:
+    .quad 0xbfc5555555555549
+bc_math_s2:
+    .quad 0x3f8111111110f8a6
+bc_math_s3:
+    .quad 0xbf2a01a019c161d5
+bc_math_s4:
+    .quad 0x3ec71de357b1fe7d
+bc_math_s5:
+    .quad 0xbe5ae5e68a2b9ceb
+bc_math_s6:
+    .quad 0x3de5d93a5acfd57c
+bc_math_c1:
+    .quad 0x3fa555555555554c
+bc_math_c2:
+    .quad 0xbf56c16c16c15177
+bc_math_c3:
+    .quad 0x3efa01a019cb1590
+bc_math_c4:
+    .quad 0xbe927e4f809c52ad
+bc_math_c5:
+    .quad 0x3e21ee9ebdb4b1c4
+bc_math_c6:
+    .quad 0xbda8fae9be8838d4
+bc_math_cos_tiny:
+    .quad 0x3e46a09e667f3bcd
+bc_math_split:
+    .quad 0x41a0000002000000
+bc_math_pi:
+    .quad 0x400921fb54442d18
+bc_math_pi_lo:
+    .quad 0x3ca1a62633145c07
+bc_math_pio2:
+    .quad 0x3ff921fb54442d18
+bc_math_pio4:
+    .quad 0x3fe921fb54442d18
+bc_math_three_pio4:
+    .quad 0x4002d97c7f3321d2
+bc_math_pio2_hi:
+    .quad 0x3ff921fb54442d18
+bc_math_pio2_lo:
+    .quad 0x3c91a62633145c07
+bc_math_inv_pio2:
+    .quad 0x3fe45f306dc9c883
+bc_math_to_int:
+    .quad 0x4338000000000000
+bc_math_reduce_max:
+    .quad 0x413921fb00000000
+bc_math_pio2_1:
+    .quad 0x3ff921fb54400000
+bc_math_pio2_1t:
+    .quad 0x3dd0b4611a626331
+bc_math_pio2_2:
+    .quad 0x3dd0b4611a600000
+bc_math_pio2_2t:
+    .quad 0x3ba3198a2e037073
+bc_math_pio2_3:
+    .quad 0x3ba3198a2e000000
+bc_math_pio2_3t:
+    .quad 0x397b839a252049c1
+bc_math_atan_bound0:
+    .quad 0x3fdc000000000000
+bc_math_atan_bound1:
+    .quad 0x3fe6000000000000
+bc_math_atan_bound2:
+    .quad 0x3ff3000000000000
+bc_math_atan_bound3:
+    .quad 0x4003800000000000
+bc_math_atan_hi:
+    .quad 0x3fddac670561bb4f, 0x3fe921fb54442d18, 0x3fef730bd281f69b, 0x3ff921fb54442d18
+bc_math_atan_lo:
+    .quad 0x3c7a2b7f222f65e2, 0x3c81a62633145c07, 0x3c7007887af0cbbd, 0x3c91a62633145c07
+bc_math_atan_coefficients:
+    .quad 0x3fd555555555550d, 0xbfc999999998ebc4, 0x3fc24924920083ff, 0xbfbc71c6fe231671
+    .quad 0x3fb745cdc54c206e, 0xbfb3b0f2af749a6d, 0x3fb10d66a0d03d51, 0xbfadde2d52defd9a
+    .quad 0x3fa97b4b24760deb, 0xbfa2b4442c6a6c2f, 0x3f90ad3ae322da11
+bc_math_two_thirds_hi:
+    .quad 0x3fe5555555555555
+bc_math_two_thirds_lo:
+    .quad 0x3c85555555555555
+# 25, then the divisors of the terms of the series from the last one on.
+bc_math_series_divisors:
+    .quad 0x4039000000000000, 0x4037000000000000, 0x4035000000000000, 0x4033000000000000
+    .quad 0x4031000000000000, 0x402e000000000000, 0x402a000000000000, 0x4026000000000000
+    .quad 0x4022000000000000, 0x401c000000000000, 0x4014000000000000
+# The bits of 2/pi after the point behind two zero words, enough for the largest f64.
+bc_two_over_pi:
+    .long 0, 0
+    .long 0xa2f9836e, 0x4e441529, 0xfc2757d1, 0xf534ddc0, 0xdb629599, 0x3c439041, 0xfe5163ab, 0xdebbc561
+    .long 0xb7246e3a, 0x424dd2e0, 0x06492eea, 0x09d1921c, 0xfe1deb1c, 0xb129a73e, 0xe88235f5, 0x2ebb4484
+    .long 0xe99c7026, 0xb45f7e41, 0x3991d639, 0x835339f4, 0x9c845f8b, 0xbdf9283b, 0x1ff897ff, 0xde05980f
+    .long 0xef2f118b, 0x5a0a6d1f, 0x6d367ecf, 0x27cb09b7, 0x4f463f66, 0x9e5fea2d, 0x7527bac7, 0xebe5f17b
+    .long 0x3d0739f7, 0x8a5292ea, 0x6bfb5fb1, 0x1f8d5d08, 0x56033046, 0xfc7b6bab, 0xf0cfbc20, 0x9af4361d
+    .text
+"#;
+
+const X86_RODATA: &str = r#"bc_panic_format:
+    .asciz "Runtime Error: %s\n"
+bc_display_format:
+    .asciz "%ld\n"
+bc_exponent_format:
+    .asciz "%.*e"
+bc_scientific_format:
+    .asciz "%se%d\n"
+bc_nan:
+    .asciz "NaN"
+bc_infinity:
+    .asciz "inf"
+bc_negative_infinity:
+    .asciz "-inf"
+bc_zero:
+    .asciz "0.0"
+bc_negative_zero:
+    .asciz "-0.0"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs every routine of `X86_MATH` on pairs of `float_math::test_inputs` from a C program and
+    /// checks the bits of each result against `float_math`.
+    #[test]
+    #[cfg(all(target_arch = "x86_64",target_os = "linux"))]
+    fn x86_math_matches_float_math() {
+        let inputs = float_math::test_inputs();
+        let mut assembly = String::from("    .text\n");
+        let mut program = String::from("#include <stdint.h>\n#include <stdio.h>\n#include <string.h>\n");
+        for function in MATH_ROUND..=MATH_MAX {
+            let name = x86_math_function(function).unwrap();
+            assembly.push_str(&format!("    .globl {}\n",name));
+            program.push_str(&format!("double {}(double{});\n",name,if float_math::is_binary(function) { ", double" } else { "" }));
+        }
+        assembly.push_str(X86_MATH);
+        assembly.push_str("    .section .note.GNU-stack,\"\",@progbits\n");
+        program.push_str("static const uint64_t inputs[] = {");
+        for input in inputs.iter() {
+            program.push_str(&format!("0x{:016x}ull,",input.to_bits()));
+        }
+        program.push_str("};\nstatic double from_bits(uint64_t bits) { double x; memcpy(&x, &bits, sizeof x); return x; }\n");
+        program.push_str("static void print(double x) { uint64_t bits; memcpy(&bits, &x, sizeof bits); printf(\"%016llx\\n\", (unsigned long long)bits); }\n");
+        program.push_str("int main(void) {\n    size_t count = sizeof inputs / sizeof *inputs;\n");
+        let mut expected = Vec::new();
+        for function in MATH_ROUND..=MATH_MAX {
+            let name = x86_math_function(function).unwrap();
+            if float_math::is_binary(function) {
+                program.push_str(&format!("    for (size_t i = 0; i < count; i++) for (size_t j = 0; j < count; j++) print({}(from_bits(inputs[i]), from_bits(inputs[j])));\n",name));
+                for a in inputs.iter() {
+                    for b in inputs.iter() {
+                        expected.push((function,*a,*b,float_math::apply(function,*a,*b)));
+                    }
+                }
+            }else {
+                program.push_str(&format!("    for (size_t i = 0; i < count; i++) print({}(from_bits(inputs[i])));\n",name));
+                for b in inputs.iter() {
+                    expected.push((function,0.0,*b,float_math::apply(function,0.0,*b)));
+                }
+            }
+        }
+        program.push_str("    return 0;\n}\n");
+
+        let dir = std::env::temp_dir().join(format!("bytecode-x86-math-{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source,math,binary) = (dir.join("main.c"),dir.join("math.s"),dir.join("math"));
+        std::fs::write(&source,program).unwrap();
+        std::fs::write(&math,assembly).unwrap();
+        let compile = std::process::Command::new("cc").args(["-O2","-Wall"]).arg(&source).arg(&math).arg("-o").arg(&binary).output().unwrap();
+        assert!(compile.status.success(),"{}",String::from_utf8_lossy(&compile.stderr));
+        let output = std::process::Command::new(&binary).output().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let lines = String::from_utf8(output.stdout).unwrap();
+        let lines = lines.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(),expected.len());
+        for (line,(function,a,b,result)) in lines.iter().zip(expected.iter()) {
+            assert_eq!(*line,format!("{:016x}",result.to_bits()),"MATH_ function {} of {:e} and {:e}",function,a,b);
+        }
+    }
+}
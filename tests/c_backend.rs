@@ -0,0 +1,30 @@
+mod common;
+
+use common::{run_built,run_vm};
+
+/// Every example with a `main`, `factorial.basm` is only imported by `importing.basm`.
+const EXAMPLES: &[&str] = &[
+    "basic.basm",
+    "branchless.basm",
+    "bytes.basm",
+    "callbacks.basm",
+    "countdown.basm",
+    "debugging.basm",
+    "errors.basm",
+    "factorial64.basm",
+    "frames.basm",
+    "hello_world.basm",
+    "importing.basm",
+    "loops.basm",
+    "precision.basm",
+    "procs.basm",
+    "switch.basm",
+    "trig.basm",
+];
+
+#[test]
+fn examples_print_the_same_as_the_vm() {
+    for name in EXAMPLES {
+        assert_eq!(run_built(name,"c","c"),run_vm(name),"{} differs from the VM",name);
+    }
+}
@@ -0,0 +1,125 @@
+//! Runs the `bytecode` binary on small programs written out by each test.
+
+use std::fs;
+use std::path::{Path,PathBuf};
+use std::process::{Command,Output,Stdio};
+
+/// Directory the programs are written to and run in, one per test so they do not clash.
+fn directory(test:&str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes `source` to `<dir>/<name>` and runs `bytecode` with `args` in front of the name.
+fn run(dir:&Path, name:&str, source:&str, args:&[&str]) -> Output {
+    fs::write(dir.join(name),source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_bytecode")).args(args).arg(name)
+        .current_dir(dir).stdin(Stdio::null()).output().unwrap()
+}
+
+/// Counts down in a loop, calls a label and leaves one label unused.
+const COUNTDOWN:&str = "label main:
+    mov rax, 3
+label again:
+    sub rax, 1
+    jnz again
+    call twice
+    display rax
+    halt
+label twice:
+    add rax, rax
+    ret
+label unused:
+    display rbx
+    ret
+";
+
+fn stdout(output:&Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn calling_a_proc_with_the_wrong_number_of_arguments_does_not_build() {
+    let dir = directory("proc_arguments");
+    let source = "label main:\n    display rax\n    call total(1)\n    halt\n@proc total(a, b)\n    mov rax, a\n@endproc\n";
+    for args in [&[][..],&["build","--emit","c"][..],&["build","--emit","asm"][..]] {
+        let output = run(&dir,"mismatch.basm",source,args);
+        assert_eq!(output.status.code(),Some(1),"{:?}",args);
+        assert_eq!(stdout(&output),"Generation Error: total takes 2 arguments but 1 were given at mismatch.basm:3\n","{:?}",args);
+    }
+    let output = run(&dir,"not_a_proc.basm","label main:\n    call helper(1)\n    halt\nlabel helper:\n    ret\n",&[]);
+    assert_eq!(output.status.code(),Some(1));
+    assert_eq!(stdout(&output),"Generation Error: helper is not a @proc at not_a_proc.basm:2\n");
+}
+
+#[test]
+fn profiling_writes_the_counts_per_label_call_and_instruction() {
+    let dir = directory("profile");
+    let output = run(&dir,"countdown.basm",COUNTDOWN,&["--profile"]);
+    assert_eq!((output.status.code(),stdout(&output)),(Some(0),"0\n".to_string()));
+    assert_eq!(fs::read_to_string(dir.join("countdown.basm.profile")).unwrap(),"\
+Profile of countdown.basm
+Instructions executed: 13
+
+Labels:
+    executed        %  label
+           9    69.23  again
+           2    15.38  twice
+           1     7.69  <start>
+           1     7.69  main
+
+Calls:
+       calls    inclusive    exclusive  target
+           1            2            2  twice
+
+Instructions:
+    executed        %     pc  location
+           3    23.08      2  again  SubImmediate(0, 1)
+           3    23.08      3  again+1  JumpIfNotZero(Num(2))
+           1     7.69      0  <start>  Jump(Num(1))
+           1     7.69      1  main  Mov(0, 3)
+           1     7.69      4  again+2  Call(Num(7))
+           1     7.69      5  again+3  Display(0)
+           1     7.69      6  again+4  Halt
+           1     7.69      7  twice  Add(0, 0)
+           1     7.69      8  twice+1  Return
+");
+    assert_eq!(fs::read_to_string(dir.join("countdown.basm.folded")).unwrap(),"main 11\nmain;twice 2\n");
+}
+
+#[test]
+fn coverage_reports_the_lines_and_branches_that_ran() {
+    let dir = directory("coverage");
+    let output = run(&dir,"countdown.basm",COUNTDOWN,&["--coverage"]);
+    assert_eq!(output.status.code(),Some(0));
+    assert_eq!(stdout(&output),"\
+0
+
+Coverage (written to countdown.basm.lcov):
+File                             Lines                Branches
+countdown.basm           8/10 (80.00%)           2/2 (100.00%)
+
+");
+    assert_eq!(fs::read_to_string(dir.join("countdown.basm.lcov")).unwrap(),"\
+TN:
+SF:countdown.basm
+BRDA:5,3,0,2
+BRDA:5,3,1,1
+BRF:2
+BRH:2
+DA:2,1
+DA:4,3
+DA:5,3
+DA:6,1
+DA:7,1
+DA:8,1
+DA:10,1
+DA:11,1
+DA:13,0
+DA:14,0
+LF:10
+LH:8
+end_of_record
+");
+}
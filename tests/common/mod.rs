@@ -0,0 +1,40 @@
+//! Runs the examples on the VM and as the programs a backend builds from them, so the tests can
+//! check that both print the same.
+
+use std::path::{Path,PathBuf};
+use std::process::{Command,Stdio};
+
+/// What a program printed to stdout and the code it exited with.
+#[derive(Debug,PartialEq)]
+pub struct Run {
+    pub stdout: String,
+    pub code: Option<i32>,
+}
+
+fn examples() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("examples")
+}
+
+fn run(command:&mut Command) -> Run {
+    let output = command.current_dir(examples()).stdin(Stdio::null()).output().unwrap();
+    Run { stdout: String::from_utf8_lossy(&output.stdout).into_owned(), code: output.status.code() }
+}
+
+/// Runs `examples/<name>` on the VM.
+pub fn run_vm(name:&str) -> Run {
+    run(Command::new(env!("CARGO_BIN_EXE_bytecode")).arg(name))
+}
+
+/// Builds `examples/<name>` with `--emit emit` into a file ending in `extension`, compiles that
+/// with `cc` and runs it.
+pub fn run_built(name:&str, emit:&str, extension:&str) -> Run {
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}-{}",name.trim_end_matches(".basm"),emit));
+    let source = out.with_extension(extension);
+    let build = Command::new(env!("CARGO_BIN_EXE_bytecode"))
+        .args(["build","--emit",emit,name,"-o"]).arg(&source)
+        .current_dir(examples()).output().unwrap();
+    assert!(build.status.success(),"building {} failed: {}",name,String::from_utf8_lossy(&build.stdout));
+    let compile = Command::new("cc").arg(&source).arg("-o").arg(&out).arg("-lm").output().unwrap();
+    assert!(compile.status.success(),"compiling {} failed: {}",source.display(),String::from_utf8_lossy(&compile.stderr));
+    run(&mut Command::new(&out))
+}